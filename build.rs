@@ -3,6 +3,27 @@
 
 use std::env;
 
+#[allow(dead_code)]
+#[path = "src/breadcrumbs.rs"]
+mod breadcrumbs;
+#[allow(dead_code)]
+#[path = "src/conflict_style.rs"]
+mod conflict_style;
+#[allow(dead_code)]
+#[path = "src/diff_algorithm.rs"]
+mod diff_algorithm;
+#[allow(dead_code)]
+#[path = "src/modify_delete.rs"]
+mod modify_delete;
+#[allow(dead_code)]
+#[path = "src/output_format.rs"]
+mod output_format;
+
+use breadcrumbs::Breadcrumbs;
+use conflict_style::ConflictStyle;
+use modify_delete::ModifyDelete;
+use output_format::OutputFormat;
+
 include!("src/bench_args.rs");
 include!("src/main_args.rs");
 
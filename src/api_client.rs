@@ -2,19 +2,23 @@
 // Copyright (C) 2025-2026  Red Hat, Inc.
 
 use crate::config::{
-    EndpointConfig, EndpointContextElement, EndpointContextLayout, EndpointJson,
+    ApiKeyStyle, EndpointConfig, EndpointContextElement, EndpointContextLayout, EndpointJson,
     EndpointTypeConfig, EndpointVariants,
 };
-use crate::conflict_resolver::ConflictResolver;
+use crate::conflict_resolver::{Budget, ConflictResolver};
+use crate::error::SynthmergeError;
 use crate::lmdb_cache::{ApiCache, LmdbCacheImpl};
+use crate::logger;
 use crate::prob;
+use crate::stream;
 use anyhow::{Context, Result, bail};
 use reqwest::Certificate;
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{OnceCell, OwnedSemaphorePermit, Semaphore};
 
 #[derive(Debug)]
 pub struct ApiRequest {
@@ -24,6 +28,10 @@ pub struct ApiRequest {
     pub code: String,
     pub git_diff: Option<String>,
     pub training: String,
+    /// The functions or classes enclosing the CODE
+    pub enclosing_symbol: Option<String>,
+    /// The whole files of the CODE
+    pub file_context: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -31,7 +39,39 @@ pub struct ApiResponseEntry {
     pub response: String,
     pub logprob: Option<f64>,
     pub total_tokens: Option<u64>,
+    pub input_tokens: Option<u64>,
     pub duration: f64,
+    /// The model of the endpoint models list that answered
+    pub model: Option<String>,
+}
+
+/// The tokens used by a response, added to the budget
+trait TotalTokens {
+    fn total_tokens(&self) -> u64;
+}
+
+impl TotalTokens for ApiResponseEntry {
+    fn total_tokens(&self) -> u64 {
+        self.total_tokens.unwrap_or(0)
+    }
+}
+
+impl<T: TotalTokens> TotalTokens for Vec<T> {
+    fn total_tokens(&self) -> u64 {
+        self.iter().map(TotalTokens::total_tokens).sum()
+    }
+}
+
+impl<T: TotalTokens> TotalTokens for Result<T> {
+    fn total_tokens(&self) -> u64 {
+        self.as_ref().map_or(0, TotalTokens::total_tokens)
+    }
+}
+
+impl TotalTokens for () {
+    fn total_tokens(&self) -> u64 {
+        0
+    }
 }
 
 #[derive(Debug)]
@@ -40,6 +80,9 @@ pub enum ApiRequestError {
     UsageLimitExceeded,
     ContentFilterRecitation,
     IncompleteGeneration,
+    /// The stream was cancelled, the confidence of the patched code
+    /// fell below the threshold
+    LowConfidence,
 }
 
 impl fmt::Display for ApiRequestError {
@@ -49,6 +92,7 @@ impl fmt::Display for ApiRequestError {
             ApiRequestError::UsageLimitExceeded => write!(f, "Usage limit exceeded"),
             ApiRequestError::ContentFilterRecitation => write!(f, "Content filter: recitation"),
             ApiRequestError::IncompleteGeneration => write!(f, "Incomplete generation"),
+            ApiRequestError::LowConfidence => write!(f, "Abandoned below the confidence threshold"),
         }
     }
 }
@@ -65,23 +109,62 @@ macro_rules! get_context_field {
 
 pub type ApiResponse = Vec<Vec<Result<ApiResponseEntry>>>;
 
+/// The output of the api_key_command and x_api_key_command of an
+/// endpoint, shared by its clients so each command runs once per run
+pub type CommandApiKeys = Arc<[OnceCell<String>; 2]>;
+
 pub struct ApiClient {
     endpoint: EndpointConfig,
     client: reqwest::Client,
     lmdb_cache: Option<Arc<LmdbCacheImpl>>,
+    /// Each request in flight holds a permit of all the semaphores
+    semaphores: Vec<Arc<Semaphore>>,
+    /// The output of the api_key_command and x_api_key_command
+    command_api_keys: CommandApiKeys,
+    /// Cancel the streamed responses once the aggregated logprob of
+    /// their patched code fell below it
+    abandon_below: Option<f64>,
+    /// The tokens of the responses are added to it and no request is
+    /// sent once it's used up
+    budget: Budget,
 }
 
 impl ApiClient {
-    pub fn new(endpoint: EndpointConfig, lmdb_cache: Option<Arc<LmdbCacheImpl>>) -> Self {
+    pub fn new(
+        endpoint: EndpointConfig,
+        lmdb_cache: Option<Arc<LmdbCacheImpl>>,
+        semaphores: Vec<Arc<Semaphore>>,
+    ) -> Self {
         let client = Self::create_client(&endpoint);
 
         ApiClient {
             endpoint,
             client: client.expect("Failed to create client"),
             lmdb_cache,
+            semaphores,
+            command_api_keys: Default::default(),
+            abandon_below: None,
+            budget: Budget::default(),
         }
     }
 
+    /// Count the tokens of the responses against the budget of the run
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = budget;
+    }
+
+    /// Share the output of the API key commands with the other clients
+    /// of the endpoint
+    pub fn set_command_api_keys(&mut self, command_api_keys: CommandApiKeys) {
+        self.command_api_keys = command_api_keys;
+    }
+
+    /// Abandon the streamed responses whose patched code can't reach
+    /// the logprob anymore
+    pub fn set_abandon_below(&mut self, logprob: f64) {
+        self.abandon_below = Some(logprob);
+    }
+
     pub fn create_client(endpoint: &EndpointConfig) -> Result<reqwest::Client> {
         let mut builder = reqwest::Client::builder()
             .timeout(Duration::from_millis(endpoint.timeout))
@@ -103,6 +186,17 @@ impl ApiClient {
             log::trace!("Root certificate loaded successfully from {}", cert_path);
         }
 
+        // By default reqwest honors the HTTPS_PROXY, HTTP_PROXY,
+        // ALL_PROXY and NO_PROXY environment variables
+        if endpoint.no_proxy {
+            builder = builder.no_proxy();
+        } else if let Some(proxy) = &endpoint.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| anyhow::anyhow!("Invalid proxy {}: {}", proxy, e))?
+                .no_proxy(reqwest::NoProxy::from_env());
+            builder = builder.proxy(proxy);
+        }
+
         builder
             .build()
             .map_err(|e| anyhow::anyhow!("Failed to build client: {}", e))
@@ -114,18 +208,100 @@ impl ApiClient {
             EndpointTypeConfig::OpenAI { .. } => self.query_openai(api_request).await,
             EndpointTypeConfig::Patchpal { .. } => self.query_patchpal(api_request).await,
             EndpointTypeConfig::Anthropic { .. } => self.query_anthropic(api_request).await,
+            EndpointTypeConfig::Ollama { .. } => self.query_ollama(api_request).await,
         }?;
 
         Ok(response)
     }
 
+    /// Check the url, the API key files and the headers of the endpoint,
+    /// with ping also send a tiny request to confirm the authentication
+    pub async fn check(&self, ping: bool) -> Result<()> {
+        let url = reqwest::Url::parse(&self.endpoint.url)
+            .map_err(|e| anyhow::anyhow!("Invalid url {}: {}", self.endpoint.url, e))?;
+        if !["http", "https"].contains(&url.scheme()) {
+            bail!("Invalid url {}: not http or https", self.endpoint.url);
+        }
+        self.create_headers().await?;
+        if !ping {
+            return Ok(());
+        }
+
+        let api_request = ApiRequest {
+            prompt: "Answer with OK.".to_string(),
+            message: "Is the endpoint reachable?".to_string(),
+            patch: String::new(),
+            code: "OK\n".to_string(),
+            git_diff: None,
+            training: "This request only checks the endpoint.".to_string(),
+            enclosing_symbol: None,
+            file_context: None,
+        };
+        // Any successful completion confirms the endpoint works
+        let mut error = None;
+        for entry in self.query(&api_request).await?.into_iter().flatten() {
+            match entry {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        Err(error.unwrap_or_else(|| anyhow::anyhow!("No response")))
+    }
+
     async fn read_api_key(&self, api_key_file: &String) -> Result<String> {
-        Ok(
-            std::fs::read_to_string(shellexpand::full(api_key_file)?.as_ref())
-                .context("Failed to read API key file")?
-                .trim()
-                .to_string(),
-        )
+        let api_key = std::fs::read_to_string(shellexpand::full(api_key_file)?.as_ref())
+            .context("Failed to read API key file")?
+            .trim()
+            .to_string();
+        logger::add_secret(&api_key);
+        Ok(api_key)
+    }
+
+    async fn run_api_key_command(command: &str) -> Result<String> {
+        // stderr is left to the terminal for the password prompts
+        let output = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .await
+            .with_context(|| format!("Failed to run API key command: {}", command))?;
+        if !output.status.success() {
+            bail!("API key command failed with {}: {}", output.status, command);
+        }
+        let api_key = String::from_utf8(output.stdout)
+            .context("Invalid UTF-8 in the output of the API key command")?
+            .trim()
+            .to_string();
+        if api_key.is_empty() {
+            bail!("API key command printed nothing: {}", command);
+        }
+        logger::add_secret(&api_key);
+        Ok(api_key)
+    }
+
+    /// The API key of the file, the command or the config, only one of
+    /// them is set
+    async fn get_api_key(
+        &self,
+        api_key_file: &Option<String>,
+        api_key_command: &Option<String>,
+        api_key: &Option<String>,
+        command_api_key: &OnceCell<String>,
+    ) -> Result<Option<String>> {
+        Ok(match (api_key_file, api_key_command) {
+            (Some(api_key_file), _) => Some(self.read_api_key(api_key_file).await?),
+            (None, Some(command)) => Some(
+                command_api_key
+                    .get_or_try_init(|| Self::run_api_key_command(command))
+                    .await?
+                    .clone(),
+            ),
+            (None, None) => api_key.clone(),
+        })
     }
 
     async fn create_headers(&self) -> Result<reqwest::header::HeaderMap> {
@@ -134,18 +310,38 @@ impl ApiClient {
             reqwest::header::CONTENT_TYPE,
             reqwest::header::HeaderValue::from_static("application/json"),
         );
-        if let Some(api_key_file) = &*self.endpoint.api_key_file {
-            // Only add the Authorization header if an API key file is specified
-            let api_key = self.read_api_key(api_key_file).await?;
-            headers.insert(
-                reqwest::header::AUTHORIZATION,
-                reqwest::header::HeaderValue::from_str(&format!("Bearer {}", api_key))
-                    .context("Invalid API key")?,
-            );
+        let api_key = self
+            .get_api_key(
+                &self.endpoint.api_key_file,
+                &self.endpoint.api_key_command,
+                &self.endpoint.api_key,
+                &self.command_api_keys[0],
+            )
+            .await?;
+        if let Some(api_key) = api_key {
+            // Only add the Authorization header if an API key is specified
+            match self.api_key_style() {
+                ApiKeyStyle::Bearer => headers.insert(
+                    reqwest::header::AUTHORIZATION,
+                    reqwest::header::HeaderValue::from_str(&format!("Bearer {}", api_key))
+                        .context("Invalid API key")?,
+                ),
+                ApiKeyStyle::ApiKey => headers.insert(
+                    reqwest::header::HeaderName::from_static("api-key"),
+                    reqwest::header::HeaderValue::from_str(&api_key).context("Invalid API key")?,
+                ),
+            };
         }
-        if let Some(x_api_key_file) = &*self.endpoint.x_api_key_file {
-            // Only add the X-API-Key header if an API key file is specified
-            let api_key = self.read_api_key(x_api_key_file).await?;
+        let x_api_key = self
+            .get_api_key(
+                &self.endpoint.x_api_key_file,
+                &self.endpoint.x_api_key_command,
+                &self.endpoint.x_api_key,
+                &self.command_api_keys[1],
+            )
+            .await?;
+        if let Some(api_key) = x_api_key {
+            // Only add the X-API-Key header if an API key is specified
             headers.insert(
                 reqwest::header::HeaderName::from_static("x-api-key"),
                 reqwest::header::HeaderValue::from_str(&api_key).context("Invalid X-API-Key")?,
@@ -162,12 +358,52 @@ impl ApiClient {
         Ok(headers)
     }
 
+    fn api_key_style(&self) -> ApiKeyStyle {
+        match &self.endpoint.config {
+            EndpointTypeConfig::OpenAI {
+                api_key_style: Some(api_key_style),
+                ..
+            } => *api_key_style,
+            EndpointTypeConfig::OpenAI {
+                deployment: Some(_),
+                ..
+            } => ApiKeyStyle::ApiKey,
+            _ => ApiKeyStyle::Bearer,
+        }
+    }
+
+    /// The url of an OpenAI endpoint, Azure OpenAI deployments are
+    /// addressed below the resource endpoint
+    fn openai_url(&self, no_chat: bool) -> String {
+        match &self.endpoint.config {
+            EndpointTypeConfig::OpenAI {
+                deployment: Some(deployment),
+                api_version,
+                ..
+            } => format!(
+                "{}/openai/deployments/{}/{}?api-version={}",
+                self.endpoint.url.trim_end_matches('/'),
+                deployment,
+                if no_chat {
+                    "completions"
+                } else {
+                    "chat/completions"
+                },
+                api_version.as_deref().unwrap_or_default()
+            ),
+            _ => self.endpoint.url.clone(),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn query_openai_variant(
         &self,
         request: &ApiRequest,
         variant: &EndpointVariants,
+        model: Option<&str>,
         no_chat: &bool,
         gbnf: &bool,
+        n_beams: usize,
         perplexity: &mut Vec<String>,
     ) -> Result<ApiResponseEntry> {
         let mut chat = self.create_chat(request, variant);
@@ -178,41 +414,31 @@ impl ApiClient {
             chat.push(perplexity_search.clone());
         }
         let mut payload = if !*no_chat {
-            let mut payload = serde_json::json!({
-                        "messages": [],
-            });
-            let messages = payload["messages"].as_array_mut().unwrap();
-            for (i, msg) in chat.iter().enumerate().filter(|(_, s)| s.is_some()) {
-                let role = if i == 0 {
-                    "system"
-                } else if i % 2 == 1 {
-                    "user"
-                } else {
-                    "assistant"
-                };
-                messages.push(serde_json::json!({
-                    "role": role,
-                    "content": msg
-                }));
-            }
-            payload
+            let messages: Vec<_> = Self::chat_messages(&chat)
+                .map(|(role, msg)| {
+                    serde_json::json!({
+                        "role": role,
+                        "content": msg
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "messages": messages,
+            })
         } else {
-            let prompt = chat
-                .iter()
-                .enumerate()
-                .filter(|(_, s)| s.is_some())
-                //.filter(|(i, s)| s.is_some() && (*i == 0 || i % 2 == 1))
-                .map(|(_, s)| s.as_ref().unwrap().clone())
-                .collect::<Vec<_>>()
-                .join("\n\n")
-                + "\n\n";
             serde_json::json!({
-                "prompt": prompt
+                "prompt": Self::completion_prompt(&chat)
             })
         };
 
         self.apply_parameters(&mut payload, &self.endpoint.json)?;
         self.apply_parameters(&mut payload, &variant.json)?;
+        self.apply_model(&mut payload, model);
+        self.apply_sampling_parameters(&mut payload)?;
+        if self.endpoint.stream {
+            payload["stream"] = serde_json::json!(true);
+            payload["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
         if perplexity_search.is_some() {
             payload.as_object_mut().unwrap().remove("n_probs");
         } else if *gbnf {
@@ -231,7 +457,7 @@ impl ApiClient {
         }
 
         self.retry_request_perplexity_search(
-            &self.endpoint.url,
+            &self.openai_url(*no_chat),
             &payload,
             perplexity,
             |response_text: &str,
@@ -332,19 +558,30 @@ impl ApiClient {
                 let logprob = if perplexity_search.is_some() {
                     None
                 } else {
-                    prob::logprob(&json_response, perplexity)
+                    prob::logprob(
+                        &json_response,
+                        perplexity,
+                        n_beams,
+                        self.endpoint.logprob_aggregation,
+                    )
                 };
 
                 let total_tokens = json_response
                     .get("usage")
                     .and_then(|usage| usage.get("total_tokens"))
                     .and_then(|tokens| tokens.as_u64());
+                let input_tokens = json_response
+                    .get("usage")
+                    .and_then(|usage| usage.get("prompt_tokens"))
+                    .and_then(|tokens| tokens.as_u64());
 
                 let mut response_entry = ApiResponseEntry {
                     response: content.to_string(),
                     logprob,
                     total_tokens,
+                    input_tokens,
                     duration,
+                    model: None,
                 };
                 if let Some(prefix) = &perplexity_search {
                     response_entry.response = format!("{}{}", prefix, response_entry.response);
@@ -353,16 +590,21 @@ impl ApiClient {
             },
         )
         .await
+        .map(|entry| ApiResponseEntry {
+            model: model.map(str::to_string),
+            ..entry
+        })
     }
 
     async fn query_openai(&self, request: &ApiRequest) -> Result<ApiResponse> {
-        let (variants, no_chat, gbnf) = match &self.endpoint.config {
+        let (variants, no_chat, gbnf, n_beams) = match &self.endpoint.config {
             EndpointTypeConfig::OpenAI {
                 variants,
                 no_chat,
                 gbnf,
+                n_beams,
                 ..
-            } => (variants, no_chat, gbnf),
+            } => (variants, no_chat, gbnf, *n_beams as usize),
             _ => panic!("cannot happen"),
         };
 
@@ -374,14 +616,34 @@ impl ApiClient {
 
         let mut responses = Vec::new();
 
+        let models = self.failover_models();
         for variant in variants_list {
             let mut perplexity = Vec::<String>::new();
             let mut variant_responses = Vec::new();
+            let mut model = 0;
             loop {
-                variant_responses.push(
-                    self.query_openai_variant(request, variant, no_chat, gbnf, &mut perplexity)
-                        .await,
-                );
+                let response = self
+                    .query_openai_variant(
+                        request,
+                        variant,
+                        models[model],
+                        no_chat,
+                        gbnf,
+                        n_beams,
+                        &mut perplexity,
+                    )
+                    .await;
+                // The perplexity search continues with the model that
+                // answered the first request
+                if variant_responses.is_empty()
+                    && let Err(e) = &response
+                    && model + 1 < models.len()
+                {
+                    self.log_failover(models[model], models[model + 1], e);
+                    model += 1;
+                    continue;
+                }
+                variant_responses.push(response);
                 if perplexity.is_empty() {
                     break;
                 }
@@ -396,24 +658,51 @@ impl ApiClient {
         &self,
         request: &ApiRequest,
         variant: &EndpointVariants,
+        model: Option<&str>,
     ) -> Result<ApiResponseEntry> {
-        let chat = self.create_chat(request, variant);
+        let prompt_caching = matches!(
+            self.endpoint.config,
+            EndpointTypeConfig::Anthropic {
+                prompt_caching: true,
+                ..
+            }
+        );
+        let (chat, breakpoints) = self.create_cacheable_chat(request, variant);
 
+        let system = match &chat[0] {
+            Some(system) if prompt_caching && !breakpoints[0].is_empty() => {
+                Self::cacheable_content(system, &breakpoints[0])
+            }
+            system => serde_json::json!(system),
+        };
         let mut payload = serde_json::json!({
-            "system": chat[0],
+            "system": system,
             "messages": [],
         });
         let messages = payload["messages"].as_array_mut().unwrap();
-        for (i, msg) in chat[1..].iter().enumerate().filter(|(_, s)| s.is_some()) {
+        for (i, msg) in chat[1..].iter().enumerate() {
+            let Some(msg) = msg else {
+                continue;
+            };
             let role = if i % 2 == 0 { "user" } else { "assistant" };
+            let content = if prompt_caching && i == 0 {
+                Self::cacheable_content(msg, &breakpoints[1])
+            } else {
+                serde_json::json!([{"type": "text", "text": msg}])
+            };
             messages.push(serde_json::json!({
                 "role": role,
-                "content": [{"type": "text", "text": msg}]
+                "content": content
             }));
         }
 
         self.apply_parameters(&mut payload, &self.endpoint.json)?;
         self.apply_parameters(&mut payload, &variant.json)?;
+        self.apply_model(&mut payload, model);
+        self.apply_sampling_parameters(&mut payload)?;
+        if self.endpoint.stream {
+            payload["stream"] = serde_json::json!(true);
+        }
 
         self.retry_request(
             &self.endpoint.url,
@@ -482,13 +771,33 @@ impl ApiClient {
                     })?;
 
                 let mut perplexity = Vec::<String>::new();
-                let logprob = prob::logprob(&json_response, &mut perplexity);
+                let logprob = prob::logprob(
+                    &json_response,
+                    &mut perplexity,
+                    1,
+                    self.endpoint.logprob_aggregation,
+                );
 
-                let total_tokens = json_response
+                let input_tokens = json_response
                     .get("usage")
                     .and_then(|usage| usage.get("input_tokens"))
-                    .and_then(|tokens| tokens.as_u64())
-                    .map(|input_tokens| {
+                    .and_then(|tokens| tokens.as_u64());
+                if prompt_caching {
+                    let cache_tokens = |field: &str| {
+                        json_response
+                            .pointer(&format!("/usage/{}", field))
+                            .and_then(|tokens| tokens.as_u64())
+                            .unwrap_or(0)
+                    };
+                    log::info!(
+                        "Prompt cache of {}: {} input tokens read from the cache, {} written to it, {} uncached",
+                        self.endpoint.name,
+                        cache_tokens("cache_read_input_tokens"),
+                        cache_tokens("cache_creation_input_tokens"),
+                        input_tokens.unwrap_or(0)
+                    );
+                }
+                let total_tokens = input_tokens.map(|input_tokens| {
                         input_tokens
                             + json_response
                                 .get("usage")
@@ -501,7 +810,9 @@ impl ApiClient {
                     response: content.to_string(),
                     logprob,
                     total_tokens,
+                    input_tokens,
                     duration,
+                    model: model.map(str::to_string),
                 })
             },
         )
@@ -522,14 +833,247 @@ impl ApiClient {
 
         let mut responses = Vec::new();
 
+        let models = self.failover_models();
         for variant in variants_list {
-            responses.push(vec![self.query_anthropic_variant(request, variant).await]);
+            let mut response = self
+                .query_anthropic_variant(request, variant, models[0])
+                .await;
+            for next in 1..models.len() {
+                let Err(e) = &response else {
+                    break;
+                };
+                self.log_failover(models[next - 1], models[next], e);
+                response = self
+                    .query_anthropic_variant(request, variant, models[next])
+                    .await;
+            }
+            responses.push(vec![response]);
         }
 
         Ok(responses)
     }
 
+    async fn query_ollama_variant(
+        &self,
+        request: &ApiRequest,
+        variant: &EndpointVariants,
+        model: &str,
+        keep_alive: &Option<String>,
+    ) -> Result<ApiResponseEntry> {
+        let chat = self.create_chat(request, variant);
+
+        let mut payload = serde_json::json!({
+            "model": model,
+            "messages": [],
+            "stream": false,
+        });
+        if let Some(keep_alive) = keep_alive {
+            payload["keep_alive"] = serde_json::json!(keep_alive);
+        }
+        let messages = payload["messages"].as_array_mut().unwrap();
+        for (i, msg) in chat.iter().enumerate().filter(|(_, s)| s.is_some()) {
+            let role = if i == 0 {
+                "system"
+            } else if i % 2 == 1 {
+                "user"
+            } else {
+                "assistant"
+            };
+            messages.push(serde_json::json!({
+                "role": role,
+                "content": msg
+            }));
+        }
+
+        self.apply_parameters(&mut payload, &self.endpoint.json)?;
+        self.apply_parameters(&mut payload, &variant.json)?;
+
+        // The url is the base url of the Ollama server
+        let url = if self.endpoint.url.ends_with("/api/chat") {
+            self.endpoint.url.clone()
+        } else {
+            format!("{}/api/chat", self.endpoint.url.trim_end_matches('/'))
+        };
+
+        self.retry_request(
+            &url,
+            &payload,
+            |response_text: &str, _: &mut Vec<String>, duration: f64| -> Result<ApiResponseEntry> {
+                // A streamed response is one JSON object per line, a
+                // non streamed response is a single JSON object
+                let mut content = String::new();
+                let mut last_response = serde_json::Value::Null;
+                for line in response_text.lines().filter(|line| !line.trim().is_empty()) {
+                    let json_response: serde_json::Value = serde_json::from_str(line)
+                        .map_err(|e| {
+                            log::warn!("Failed to parse JSON response:\n{}", response_text);
+                            anyhow::anyhow!("Failed to parse JSON response: {}", e)
+                        })?;
+
+                    if let Some(error) = json_response.get("error").and_then(|v| v.as_str()) {
+                        bail!("Ollama error: {}", error);
+                    }
+
+                    let chunk = json_response
+                        .get("message")
+                        .and_then(|message| message.get("content"))
+                        .and_then(|content| content.as_str())
+                        .with_context(|| {
+                            log::warn!(
+                                "Failed to extract content from response:\n{}",
+                                serde_json::to_string_pretty(&json_response).unwrap()
+                            );
+                            "Failed to extract content from response"
+                        })?;
+                    content.push_str(chunk);
+                    last_response = json_response;
+                }
+
+                // Check for incomplete generation in Ollama responses
+                if last_response.get("done").and_then(|v| v.as_bool()) != Some(true) {
+                    log::warn!(
+                        "Incomplete generation error for endpoint {}. Not retrying. Response not done",
+                        self.endpoint.name
+                    );
+                    bail!(ApiRequestError::IncompleteGeneration);
+                }
+                if let Some(done_reason) = last_response.get("done_reason").and_then(|v| v.as_str())
+                    && done_reason != "stop"
+                {
+                    log::warn!(
+                        "Incomplete generation error for endpoint {}. Not retrying. Done reason: {}",
+                        self.endpoint.name,
+                        done_reason
+                    );
+                    bail!(ApiRequestError::IncompleteGeneration);
+                }
+
+                let input_tokens = last_response
+                    .get("prompt_eval_count")
+                    .and_then(|tokens| tokens.as_u64());
+                let total_tokens = input_tokens.map(|prompt_tokens| {
+                        prompt_tokens
+                            + last_response
+                                .get("eval_count")
+                                .and_then(|tokens| tokens.as_u64())
+                                .unwrap_or(0)
+                    });
+
+                Ok(ApiResponseEntry {
+                    response: content,
+                    logprob: None,
+                    total_tokens,
+                    input_tokens,
+                    duration,
+                    model: None,
+                })
+            },
+        )
+        .await
+    }
+
+    async fn query_ollama(&self, request: &ApiRequest) -> Result<ApiResponse> {
+        let (model, keep_alive, variants) = match &self.endpoint.config {
+            EndpointTypeConfig::Ollama {
+                model,
+                keep_alive,
+                variants,
+            } => (model, keep_alive, variants),
+            _ => panic!("cannot happen"),
+        };
+
+        // Handle EndpointVariants - if None, create a single entry with no parameters
+        let variants_list = match variants {
+            Some(variants) => variants,
+            None => &vec![EndpointVariants::default()],
+        };
+
+        let mut responses = Vec::new();
+
+        for variant in variants_list {
+            responses.push(vec![
+                self.query_ollama_variant(request, variant, model, keep_alive)
+                    .await,
+            ]);
+        }
+
+        Ok(responses)
+    }
+
+    /// The prompts of the request as sent to the endpoint, one for each
+    /// variant with the name of the variant
+    pub fn prompts(&self, request: &ApiRequest) -> Vec<(Option<String>, String)> {
+        let (variants, no_chat) = match &self.endpoint.config {
+            EndpointTypeConfig::OpenAI {
+                variants, no_chat, ..
+            } => (variants, *no_chat),
+            EndpointTypeConfig::Anthropic { variants, .. }
+            | EndpointTypeConfig::Ollama { variants, .. } => (variants, false),
+            EndpointTypeConfig::Patchpal { .. } => {
+                return vec![(
+                    None,
+                    format!("[patch]\n{}\n[code]\n{}", request.patch, request.code),
+                )];
+            }
+        };
+        let default_variants = vec![EndpointVariants::default()];
+        variants
+            .as_ref()
+            .unwrap_or(&default_variants)
+            .iter()
+            .map(|variant| {
+                let chat = self.create_chat(request, variant);
+                let prompt = if no_chat {
+                    Self::completion_prompt(&chat)
+                } else {
+                    Self::chat_messages(&chat)
+                        .map(|(role, msg)| format!("[{}]\n{}", role, msg))
+                        .collect::<Vec<_>>()
+                        .join("\n\n")
+                };
+                ((*variant.name).clone(), prompt)
+            })
+            .collect()
+    }
+
+    /// The messages of the chat with their role: the system message,
+    /// then the user and the assistant messages in turn
+    fn chat_messages(chat: &[Option<String>]) -> impl Iterator<Item = (&'static str, &String)> {
+        chat.iter().enumerate().filter_map(|(i, msg)| {
+            let role = if i == 0 {
+                "system"
+            } else if i % 2 == 1 {
+                "user"
+            } else {
+                "assistant"
+            };
+            msg.as_ref().map(|msg| (role, msg))
+        })
+    }
+
+    /// The prompt of the endpoints without the chat API, the messages
+    /// of the chat one after the other
+    fn completion_prompt(chat: &[Option<String>]) -> String {
+        chat.iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n\n")
+            + "\n\n"
+    }
+
     fn create_chat(&self, request: &ApiRequest, variant: &EndpointVariants) -> Vec<Option<String>> {
+        self.create_cacheable_chat(request, variant).0
+    }
+
+    /// Create the chat and the offsets of the system and of the user
+    /// message where the context shared by the conflicts ends: after the
+    /// training, the diff and the file context
+    fn create_cacheable_chat(
+        &self,
+        request: &ApiRequest,
+        variant: &EndpointVariants,
+    ) -> (Vec<Option<String>>, [Vec<usize>; 2]) {
         let mut chat = Vec::new();
 
         let layout = get_context_field!(
@@ -541,6 +1085,18 @@ impl ApiClient {
         let no_diff = get_context_field!(&self.endpoint.context, &variant.context, no_diff, false);
         let no_training =
             get_context_field!(&self.endpoint.context, &variant.context, no_training, false);
+        let enclosing_symbol = get_context_field!(
+            &self.endpoint.context,
+            &variant.context,
+            enclosing_symbol,
+            false
+        );
+        let system_prompt = get_context_field!(
+            &self.endpoint.context,
+            &variant.context,
+            system_prompt,
+            String::new()
+        );
         let mut system_message = String::new();
         let mut user_message = String::new();
 
@@ -553,10 +1109,14 @@ impl ApiClient {
             s.push_str(text);
         };
 
-        for (message, context) in [
+        let mut breakpoints: [Vec<usize>; 2] = Default::default();
+        for ((message, context), breakpoints) in [
             (&mut system_message, layout.system_message),
             (&mut user_message, layout.user_message),
-        ] {
+        ]
+        .into_iter()
+        .zip(breakpoints.iter_mut())
+        {
             let mut need_newline = false;
             for element in context.into_iter() {
                 match element {
@@ -565,12 +1125,14 @@ impl ApiClient {
                     }
                     EndpointContextElement::Training => {
                         if !no_training {
-                            push(message, &request.training, &mut need_newline)
+                            push(message, &request.training, &mut need_newline);
+                            breakpoints.push(message.len());
                         }
                     }
                     EndpointContextElement::Diff => {
                         if !no_diff && let Some(git_diff) = &request.git_diff {
-                            push(message, git_diff, &mut need_newline)
+                            push(message, git_diff, &mut need_newline);
+                            breakpoints.push(message.len());
                         }
                     }
                 }
@@ -579,6 +1141,36 @@ impl ApiClient {
             assert!(!message.chars().last().is_some_and(char::is_whitespace));
         }
 
+        let system_prompt = system_prompt.trim();
+        if !system_prompt.is_empty() {
+            system_message = if system_message.is_empty() {
+                system_prompt.to_string()
+            } else {
+                let shift = system_prompt.len() + 2;
+                breakpoints[0].iter_mut().for_each(|b| *b += shift);
+                format!("{}\n\n{}", system_prompt, system_message)
+            };
+        }
+
+        if let Some(file_context) = &request.file_context {
+            if !user_message.is_empty() {
+                user_message.push_str("\n\n");
+            }
+            user_message.push_str(file_context);
+            breakpoints[1].push(user_message.len());
+        }
+        // Anthropic allows up to 4 cache breakpoints, the last ones
+        // cache the longest prefixes
+        while breakpoints[0].len() + breakpoints[1].len() > 4 {
+            let message = if breakpoints[0].is_empty() { 1 } else { 0 };
+            breakpoints[message].remove(0);
+        }
+        if enclosing_symbol && let Some(symbol) = &request.enclosing_symbol {
+            if !user_message.is_empty() {
+                user_message.push_str("\n\n");
+            }
+            user_message.push_str(symbol);
+        }
         if !user_message.is_empty() {
             user_message.push_str("\n\n");
         }
@@ -605,7 +1197,61 @@ impl ApiClient {
                 .collect::<Vec<_>>()
                 .join("\n")
         );
-        chat
+        (chat, breakpoints)
+    }
+
+    /// The Anthropic content blocks of the text split at the breakpoints,
+    /// each block ending at a breakpoint is the end of a cacheable prefix
+    fn cacheable_content(text: &str, breakpoints: &[usize]) -> serde_json::Value {
+        let mut blocks = Vec::new();
+        let mut start = 0;
+        for &breakpoint in breakpoints {
+            if breakpoint <= start || breakpoint > text.len() {
+                continue;
+            }
+            blocks.push(serde_json::json!({
+                "type": "text",
+                "text": &text[start..breakpoint],
+                "cache_control": {"type": "ephemeral"},
+            }));
+            start = breakpoint;
+        }
+        if start < text.len() || blocks.is_empty() {
+            blocks.push(serde_json::json!({"type": "text", "text": &text[start..]}));
+        }
+        serde_json::Value::Array(blocks)
+    }
+
+    /// The models of the endpoint in failover order, a single None
+    /// leaves the model to the json
+    fn failover_models(&self) -> Vec<Option<&str>> {
+        match &self.endpoint.config {
+            EndpointTypeConfig::OpenAI {
+                models: Some(models),
+                ..
+            }
+            | EndpointTypeConfig::Anthropic {
+                models: Some(models),
+                ..
+            } => models.iter().map(|model| Some(model.as_str())).collect(),
+            _ => vec![None],
+        }
+    }
+
+    fn log_failover(&self, model: Option<&str>, next: Option<&str>, e: &anyhow::Error) {
+        log::warn!(
+            "Model {} of endpoint {} failed, trying {}: {:#}",
+            model.unwrap_or_default(),
+            self.endpoint.name,
+            next.unwrap_or_default(),
+            e
+        );
+    }
+
+    fn apply_model(&self, payload: &mut serde_json::Value, model: Option<&str>) {
+        if let Some(model) = model {
+            payload["model"] = serde_json::json!(model);
+        }
     }
 
     fn apply_parameters(
@@ -625,6 +1271,27 @@ impl ApiClient {
         Ok(())
     }
 
+    /// Add the temperature and top_p of the endpoint to the payload,
+    /// the ones not set are left to the provider defaults
+    fn apply_sampling_parameters(&self, payload: &mut serde_json::Value) -> Result<()> {
+        let (temperature, top_p) = match &self.endpoint.config {
+            EndpointTypeConfig::OpenAI {
+                temperature, top_p, ..
+            }
+            | EndpointTypeConfig::Anthropic {
+                temperature, top_p, ..
+            } => (*temperature, *top_p),
+            _ => return Ok(()),
+        };
+        let json = [("temperature", temperature), ("top_p", top_p)]
+            .into_iter()
+            .filter_map(|(key, value)| {
+                value.map(|value| (key.to_string(), serde_json::json!(value)))
+            })
+            .collect();
+        self.apply_parameters(payload, &Some(EndpointJson { json }))
+    }
+
     async fn query_patchpal(&self, request: &ApiRequest) -> Result<ApiResponse> {
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert(
@@ -711,7 +1378,9 @@ impl ApiClient {
                             ),
                             logprob: s.1.ok(),
                             total_tokens: None,
+                            input_tokens: None,
                             duration,
+                            model: None,
                         })
                     })
                     .collect();
@@ -729,7 +1398,7 @@ impl ApiClient {
             .await
     }
 
-    async fn retry_request_perplexity_search<F, R>(
+    async fn retry_request_perplexity_search<F, R: TotalTokens>(
         &self,
         url: &str,
         payload: &serde_json::Value,
@@ -741,6 +1410,7 @@ impl ApiClient {
     {
         let start = std::time::Instant::now();
         let mut last_error = None;
+        let mut last_status = None;
         let mut delay = Duration::from_millis(self.endpoint.delay);
         let max_delay = Duration::from_millis(self.endpoint.max_delay);
 
@@ -777,42 +1447,83 @@ impl ApiClient {
         log::trace!(
             "Request JSON ({}):\n{}",
             self.endpoint.name,
-            serde_json::to_string_pretty(payload).unwrap()
+            logger::redact(&serde_json::to_string_pretty(payload).unwrap())
         );
 
         for _ in 0..self.endpoint.retries {
             let headers = self.create_headers().await?;
-            let response = self
+            // Only the requests in flight count against the concurrency
+            // limits, the permits are released before any backoff sleep
+            let permits = self.acquire_permits().await?;
+            // Checked after waiting for the permits, the requests before
+            // may have used up the tokens meanwhile
+            if self.budget.out_of_tokens() {
+                bail!("Token budget exceeded, not querying {}", self.endpoint.name);
+            }
+            let response = match self
                 .client
                 .post(url.to_string())
                 .headers(headers)
                 .json(payload)
                 .send()
-                .await;
+                .await
+            {
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = Self::retry_after(&response);
+                    if self.endpoint.stream && status.is_success() {
+                        self.read_stream(response).await
+                    } else {
+                        response.text().await.map(Some)
+                    }
+                    .map(|text| (status, retry_after, text))
+                }
+                Err(e) => Err(e),
+            };
+            drop(permits);
 
             match response {
-                Ok(response) => {
-                    let response_text = match response.text().await {
-                        Ok(text) => text,
-                        Err(e) => {
-                            self.apply_delay(&mut delay, max_delay, &e).await;
-                            last_error = Some(e.into());
-                            continue;
-                        }
+                Ok((_, _, None)) => {
+                    // Not cached nor retried, the same request would
+                    // likely be abandoned again
+                    self.apply_wait().await;
+                    bail!(ApiRequestError::LowConfidence);
+                }
+                Ok((status, retry_after, Some(response_text))) => {
+                    let response_text = if self.endpoint.stream && status.is_success() {
+                        self.assemble_stream(response_text)
+                    } else {
+                        response_text
                     };
                     let duration = start.elapsed().as_secs_f64();
                     log::trace!(
                         "Response JSON ({}):\n{}",
                         self.endpoint.name,
-                        serde_json::to_string_pretty(
-                            &serde_json::from_str(&response_text)
-                                .unwrap_or(serde_json::Value::String(response_text.clone()))
+                        logger::redact(
+                            &serde_json::to_string_pretty(
+                                &serde_json::from_str(&response_text)
+                                    .unwrap_or(serde_json::Value::String(response_text.clone()))
+                            )
+                            .unwrap_or(response_text.clone())
                         )
-                        .unwrap_or(response_text.clone())
                     );
 
+                    if Self::is_retryable_status(status) {
+                        let e = anyhow::anyhow!(
+                            "HTTP status {} from endpoint {}",
+                            status,
+                            self.endpoint.name
+                        );
+                        self.apply_delay(&mut delay, max_delay, retry_after, &e)
+                            .await;
+                        last_status = Some(status.as_u16());
+                        last_error = Some(e);
+                        continue;
+                    }
+
                     match response_handler(&response_text, perplexity, duration) {
                         Ok(api_response) => {
+                            self.budget.add_tokens(api_response.total_tokens());
                             if let (Some(cache), Some(key)) = (&self.lmdb_cache, &cache_key) {
                                 cache.cache_response(key, response_text)?;
                             }
@@ -834,33 +1545,59 @@ impl ApiClient {
                                         self.apply_wait().await;
                                         return Err(e);
                                     }
-                                    ApiRequestError::UsageLimitExceeded => {}
+                                    ApiRequestError::UsageLimitExceeded
+                                    | ApiRequestError::LowConfidence => {}
                                 }
                             }
-                            self.apply_delay(&mut delay, max_delay, &e).await;
+                            if status.is_client_error() {
+                                // A bad or unauthorized request fails the
+                                // same way when retried
+                                log::warn!(
+                                    "HTTP status {} from endpoint {}. Not retrying.",
+                                    status,
+                                    self.endpoint.name
+                                );
+                                self.apply_wait().await;
+                                return Err(e.context(SynthmergeError::Api {
+                                    endpoint: self.endpoint.name.clone(),
+                                    status: Some(status.as_u16()),
+                                    message: format!("HTTP status {}", status),
+                                }));
+                            }
+                            self.apply_delay(&mut delay, max_delay, None, &e).await;
+                            last_status = Some(status.as_u16());
                             last_error = Some(e);
                         }
                     }
                 }
                 Err(e) => {
                     if e.is_timeout() {
-                        // Don't retry on timeout errors or it may waste energy
                         log::warn!(
                             "Timeout error for endpoint {}. Consider increasing the timeout.",
                             self.endpoint.name
                         );
-                        self.apply_wait().await;
-                        return Err(e.into());
                     }
-                    self.apply_delay(&mut delay, max_delay, &e).await;
+                    self.apply_delay(&mut delay, max_delay, None, &e).await;
+                    last_status = None;
                     last_error = Some(e.into());
                 }
             }
         }
-        Err(last_error.context("Failed to send request after retries")?)
+        let error = SynthmergeError::Api {
+            endpoint: self.endpoint.name.clone(),
+            status: last_status,
+            message: format!(
+                "failed to send request after {} retries",
+                self.endpoint.retries
+            ),
+        };
+        Err(match last_error {
+            Some(e) => e.context(error),
+            None => error.into(),
+        })
     }
 
-    async fn retry_request<F, R>(
+    async fn retry_request<F, R: TotalTokens>(
         &self,
         url: &str,
         payload: &serde_json::Value,
@@ -874,6 +1611,143 @@ impl ApiClient {
             .await
     }
 
+    /// Read the server-sent events of a streamed response, printing
+    /// the progress of the long responses. Returns None if the
+    /// response was abandoned below the confidence threshold.
+    async fn read_stream(
+        &self,
+        mut response: reqwest::Response,
+    ) -> reqwest::Result<Option<String>> {
+        const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+        let start = std::time::Instant::now();
+        let mut last_progress = start;
+        let mut sse = Vec::new();
+        let mut running = prob::RunningLogprob::new(self.endpoint.logprob_aggregation);
+        // The end of the complete event lines already parsed
+        let mut parsed = 0;
+        while let Some(chunk) = response.chunk().await? {
+            sse.extend_from_slice(&chunk);
+            if let Some(threshold) = self.abandon_below {
+                while let Some(newline) = sse[parsed..].iter().position(|&b| b == b'\n') {
+                    let line = String::from_utf8_lossy(&sse[parsed..parsed + newline]).into_owned();
+                    parsed += newline + 1;
+                    for (token, logprob) in stream::token_logprobs(&line) {
+                        if let Some(running_logprob) = running.push(&token, logprob)
+                            && running_logprob < threshold
+                        {
+                            log::info!(
+                                "Abandoned the response of {}, the logprob {:.4} of the patched code is below {:.4}",
+                                self.endpoint.name,
+                                running_logprob,
+                                threshold
+                            );
+                            return Ok(None);
+                        }
+                    }
+                }
+            }
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = std::time::Instant::now();
+                logger::status!(
+                    " ~ {} streamed {} KiB in {:.0}s",
+                    self.endpoint.name,
+                    sse.len() / 1024,
+                    start.elapsed().as_secs_f64()
+                );
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&sse).into_owned()))
+    }
+
+    /// The JSON of the non streamed response, for the response handlers
+    /// and the cache
+    fn assemble_stream(&self, sse: String) -> String {
+        let assembled = match &self.endpoint.config {
+            EndpointTypeConfig::OpenAI { .. } => stream::assemble_openai(&sse),
+            EndpointTypeConfig::Anthropic { .. } => stream::assemble_anthropic(&sse),
+            _ => None,
+        };
+        assembled.unwrap_or(sse)
+    }
+
+    async fn acquire_permits(&self) -> Result<Vec<OwnedSemaphorePermit>> {
+        let mut permits = Vec::new();
+        for semaphore in &self.semaphores {
+            permits.push(semaphore.clone().acquire_owned().await?);
+        }
+        Ok(permits)
+    }
+
+    /// Rate limits and transient server errors are worth retrying
+    fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+        matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+    }
+
+    /// The delay requested by the Retry-After header, in seconds or as
+    /// an HTTP-date
+    fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+        let value = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)?
+            .to_str()
+            .ok()?;
+        Self::parse_retry_after(value, SystemTime::now())
+    }
+
+    fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+        let value = value.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        // A date already past allows to retry right away
+        Some(
+            Self::parse_http_date(value)?
+                .duration_since(now)
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    /// Parse the IMF-fixdate format of the HTTP-date, like
+    /// "Sun, 06 Nov 1994 08:49:37 GMT"
+    fn parse_http_date(value: &str) -> Option<SystemTime> {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let fields: Vec<&str> = value.split_ascii_whitespace().collect();
+        let [_, day, month, year, time, "GMT"] = fields[..] else {
+            return None;
+        };
+        let day: u64 = day.parse().ok()?;
+        let month = MONTHS.iter().position(|m| *m == month)? as u64;
+        let year: u64 = year.parse().ok()?;
+        let time = time
+            .split(':')
+            .map(|field| field.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        let [hours, minutes, seconds] = time[..] else {
+            return None;
+        };
+        if !(1..=31).contains(&day) || year < 1970 || hours > 23 || minutes > 59 || seconds > 60 {
+            return None;
+        }
+        // The days since the epoch, with the years starting in March
+        // so the leap day is the last day of the year
+        let (year, month) = if month < 2 {
+            (year - 1, month + 10)
+        } else {
+            (year, month - 2)
+        };
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let day_of_year = (153 * month + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+        Some(
+            SystemTime::UNIX_EPOCH
+                + Duration::from_secs(days * 86400 + hours * 3600 + minutes * 60 + seconds),
+        )
+    }
+
     async fn apply_wait(&self) {
         if self.endpoint.wait != 0 {
             log::trace!("Waiting {}ms before next request", self.endpoint.wait);
@@ -881,20 +1755,287 @@ impl ApiClient {
         }
     }
 
-    async fn apply_delay<E>(&self, delay: &mut Duration, max_delay: Duration, error: &E)
-    where
+    async fn apply_delay<E>(
+        &self,
+        delay: &mut Duration,
+        max_delay: Duration,
+        retry_after: Option<Duration>,
+        error: &E,
+    ) where
         E: std::fmt::Display + 'static,
     {
+        let jitter = if self.endpoint.jitter != 0 {
+            use std::hash::{BuildHasher, Hasher};
+            let random = std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish();
+            Duration::from_millis(random % (self.endpoint.jitter + 1))
+        } else {
+            Duration::ZERO
+        };
+        // The server knows better when it will accept the next request,
+        // up to the max_delay
+        let sleep = retry_after.map_or(*delay, |retry_after| retry_after.min(max_delay)) + jitter;
         log::warn!(
-            "Retrying endpoint {} after error: {}",
+            "Retrying endpoint {} in {}ms after error: {}",
             self.endpoint.name,
+            sleep.as_millis(),
             error
         );
-        tokio::time::sleep(*delay).await;
+        tokio::time::sleep(sleep).await;
         *delay = std::cmp::min(*delay * 2, max_delay);
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_abandon_below() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Streams a patched code with an unlikely token, then stalls
+        // without ever completing the response
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 65536];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let mut reply =
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n"
+                    .to_string();
+            for (token, logprob) in [
+                ("<|patched_code|>\n", -0.01),
+                ("d-merged\n", -3.0),
+                ("e\nf\ng\nh\ni\nj\nk\nl\nm\nn\no\np\nq\nr\n", -0.01),
+            ] {
+                let event = serde_json::json!({
+                    "choices": [{
+                        "delta": { "content": token },
+                        "logprobs": { "content": [{ "token": token, "logprob": logprob }] },
+                    }],
+                });
+                reply.push_str(&format!("data: {}\n\n", event));
+            }
+            socket.write_all(reply.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+
+        let endpoint: EndpointConfig = serde_yaml::from_str(&format!(
+            "name: stream\nurl: {}\ntype: openai\nstream: true\nretries: 1\ndelay: 0\n",
+            url
+        ))
+        .unwrap();
+        let mut client = ApiClient::new(endpoint, None, Vec::new());
+        client.set_abandon_below(-1.0);
+        let request = ApiRequest {
+            prompt: "prompt".to_string(),
+            training: "training".to_string(),
+            message: "message".to_string(),
+            patch: "patch".to_string(),
+            code: "code".to_string(),
+            git_diff: None,
+            enclosing_symbol: None,
+            file_context: None,
+        };
+        let response = tokio::time::timeout(Duration::from_secs(30), client.query(&request))
+            .await
+            .expect("the stream wasn't abandoned")
+            .unwrap();
+        assert!(matches!(
+            response[0][0]
+                .as_ref()
+                .unwrap_err()
+                .downcast_ref::<ApiRequestError>(),
+            Some(ApiRequestError::LowConfidence)
+        ));
+    }
+
+    #[test]
+    fn test_cacheable_content() {
+        let content = ApiClient::cacheable_content("diff\n\nfile\n\ncode", &[4, 10]);
+        assert_eq!(
+            content,
+            serde_json::json!([
+                {"type": "text", "text": "diff", "cache_control": {"type": "ephemeral"}},
+                {"type": "text", "text": "\n\nfile", "cache_control": {"type": "ephemeral"}},
+                {"type": "text", "text": "\n\ncode"},
+            ])
+        );
+        // Without a shared context nothing is cacheable
+        assert_eq!(
+            ApiClient::cacheable_content("code", &[]),
+            serde_json::json!([{"type": "text", "text": "code"}])
+        );
+        assert_eq!(
+            ApiClient::cacheable_content("diff", &[4]),
+            serde_json::json!([
+                {"type": "text", "text": "diff", "cache_control": {"type": "ephemeral"}},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_prompts() {
+        let request = ApiRequest {
+            prompt: "prompt".to_string(),
+            training: "training".to_string(),
+            message: "message".to_string(),
+            patch: "patch".to_string(),
+            code: "code".to_string(),
+            git_diff: None,
+            enclosing_symbol: None,
+            file_context: None,
+        };
+        let client = |yaml: &str| {
+            let endpoint: EndpointConfig = serde_yaml::from_str(yaml).unwrap();
+            ApiClient::new(endpoint, None, Vec::new())
+        };
+
+        let prompts = client(
+            "name: chat\nurl: http://localhost\ntype: openai\nvariants:\n  - name: a\n  - name: b\n",
+        )
+        .prompts(&request);
+        assert_eq!(prompts.len(), 2);
+        assert_eq!(prompts[1].0.as_deref(), Some("b"));
+        assert_eq!(
+            prompts[1].1,
+            "[system]\ntraining\n\nprompt\n\n[user]\nmessage"
+        );
+
+        let prompts =
+            client("name: completion\nurl: http://localhost\ntype: openai\nno_chat: true\n")
+                .prompts(&request);
+        assert_eq!(
+            prompts,
+            [(None, "training\n\nprompt\n\nmessage\n\n".to_string())]
+        );
+
+        let prompts =
+            client("name: patchpal\nurl: http://localhost\ntype: patchpal\n").prompts(&request);
+        assert_eq!(
+            prompts,
+            [(None, "[patch]\npatch\n[code]\ncode".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_is_retryable_status() {
+        for status in [429, 500, 502, 503, 504] {
+            let status = reqwest::StatusCode::from_u16(status).unwrap();
+            assert!(ApiClient::is_retryable_status(status), "{}", status);
+        }
+        // A bad or unauthorized request fails the same way when retried
+        for status in [200, 400, 401, 403, 404, 405, 413, 422] {
+            let status = reqwest::StatusCode::from_u16(status).unwrap();
+            assert!(!ApiClient::is_retryable_status(status), "{}", status);
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(
+            ApiClient::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(date)
+        );
+        assert_eq!(
+            ApiClient::parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT"),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1709164800))
+        );
+
+        let now = date - Duration::from_secs(30);
+        assert_eq!(
+            ApiClient::parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            ApiClient::parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        // Already past
+        assert_eq!(
+            ApiClient::parse_retry_after(
+                "Sun, 06 Nov 1994 08:49:37 GMT",
+                date + Duration::from_secs(1)
+            ),
+            Some(Duration::ZERO)
+        );
+        for invalid in [
+            "",
+            "-1",
+            "soon",
+            "Sun, 06 Nov 1994 08:49:37 PST",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:49:37 GMT",
+        ] {
+            assert_eq!(
+                ApiClient::parse_retry_after(invalid, now),
+                None,
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_timeout() {
+        // Accepts the connections without ever answering
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        let connections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        tokio::spawn({
+            let connections = connections.clone();
+            async move {
+                let mut sockets = Vec::new();
+                loop {
+                    let (socket, _) = listener.accept().await.unwrap();
+                    connections.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    sockets.push(socket);
+                }
+            }
+        });
+
+        let endpoint: EndpointConfig = serde_yaml::from_str(&format!(
+            "name: slow\nurl: {}\ntype: openai\ntimeout: 200\nretries: 3\ndelay: 10\n",
+            url
+        ))
+        .unwrap();
+        let client = ApiClient::new(endpoint, None, Vec::new());
+        let error = client
+            .retry_request(&url, &serde_json::json!({}), |_, _, _| Ok(()))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<SynthmergeError>(),
+            Some(SynthmergeError::Api { status: None, .. })
+        ));
+        assert_eq!(connections.load(std::sync::atomic::Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_api_key_command() {
+        assert_eq!(
+            ApiClient::run_api_key_command("printf ' sk-command-1234\\n'")
+                .await
+                .unwrap(),
+            "sk-command-1234"
+        );
+        assert_eq!(logger::redact("key sk-command-1234"), "key ***");
+        assert!(ApiClient::run_api_key_command("true").await.is_err());
+        assert!(ApiClient::run_api_key_command("exit 1").await.is_err());
+    }
+}
+
 // Local Variables:
 // rust-format-on-save: t
 // End:
@@ -3,14 +3,15 @@
 
 use crate::bench_args::BenchArgs;
 use crate::config::{Config, EndpointTypeConfig};
-use crate::conflict_resolver::{Conflict, ConflictResolver};
-use crate::git_utils::{ContextLines, GitUtils, ResolutionMode};
-use crate::prob::logprob_to_prob;
+use crate::conflict_resolver::{Conflict, ConflictResolver, ResolvedConflict};
+use crate::git_utils::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+use crate::prob::{DEFAULT_CONFIDENCE_BASE, logprob_to_prob};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct TestEntry {
@@ -22,6 +23,14 @@ pub struct TestEntry {
     filename: String,
 }
 
+/// A file with conflict markers paired with its golden resolution
+#[derive(Debug)]
+pub struct Fixture {
+    name: String,
+    content: String,
+    resolved: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct TestResult {
     entry_index: usize,
@@ -72,6 +81,8 @@ pub struct Bench {
     model_stats: HashMap<String, ModelStats>,
     git_diffs: HashMap<String, String>,
     line_number_re: regex::Regex,
+    /// The confidence_base of the config of the run
+    confidence_base: f64,
 }
 
 impl Default for Bench {
@@ -90,6 +101,7 @@ impl Bench {
             model_stats: HashMap::new(),
             git_diffs: HashMap::new(),
             line_number_re: Self::line_number_regex(),
+            confidence_base: DEFAULT_CONFIDENCE_BASE,
         }
     }
 
@@ -130,8 +142,47 @@ impl Bench {
         Ok(entries)
     }
 
+    /// Load the fixtures of a directory: each file with conflict
+    /// markers is paired with the golden resolution in the file of the
+    /// same name with the .resolved extension
+    pub fn load_fixtures<P: AsRef<Path>>(dir: P) -> Result<Vec<Fixture>> {
+        let dir = dir.as_ref();
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read fixtures directory: {}", dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.sort();
+
+        let mut fixtures = Vec::new();
+        for path in paths {
+            if !path.is_file() || path.extension().is_some_and(|ext| ext == "resolved") {
+                continue;
+            }
+            let mut resolved_path = path.clone().into_os_string();
+            resolved_path.push(".resolved");
+            let resolved_path = std::path::PathBuf::from(resolved_path);
+            if !resolved_path.exists() {
+                log::warn!(
+                    "Skipping fixture without golden resolution: {}",
+                    path.display()
+                );
+                continue;
+            }
+            let read = |path: &Path| {
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read fixture: {}", path.display()))
+            };
+            fixtures.push(Fixture {
+                name: path.file_name().unwrap().to_string_lossy().to_string(),
+                content: read(&path)?,
+                resolved: read(&resolved_path)?,
+            });
+        }
+        Ok(fixtures)
+    }
+
     fn save_checkpoint(&mut self, args: &BenchArgs) -> Result<()> {
-        let file = File::create(&args.checkpoint_path)?;
+        let file = File::create(args.get_checkpoint_path())?;
         let mut writer = csv::Writer::from_writer(file);
         for result in &self.results {
             writer.serialize(result)?;
@@ -142,11 +193,11 @@ impl Bench {
     }
 
     fn load_checkpoint(&mut self, args: &BenchArgs) -> Result<()> {
-        if !Path::new(&args.checkpoint_path).exists() {
+        if !Path::new(args.get_checkpoint_path()).exists() {
             return Ok(());
         }
 
-        let file = File::open(&args.checkpoint_path)?;
+        let file = File::open(args.get_checkpoint_path())?;
         let mut reader = csv::Reader::from_reader(file);
 
         for result in reader.deserialize() {
@@ -298,11 +349,14 @@ impl Bench {
                 model_logprob[LogprobType::Global as usize]
                     .get(&model)
                     .map(|logprob| {
-                        let avg = logprob.iter().map(|x| logprob_to_prob(*x)).sum::<f64>()
+                        let avg = logprob
+                            .iter()
+                            .map(|x| logprob_to_prob(*x, self.confidence_base))
+                            .sum::<f64>()
                             / logprob.len() as f64;
                         let variance = logprob
                             .iter()
-                            .map(|x| (logprob_to_prob(*x) - avg).powi(2))
+                            .map(|x| (logprob_to_prob(*x, self.confidence_base) - avg).powi(2))
                             .sum::<f64>()
                             / logprob.len() as f64;
                         variance.sqrt()
@@ -311,11 +365,14 @@ impl Bench {
                 model_logprob[LogprobType::Errors as usize]
                     .get(&model)
                     .map(|logprob| {
-                        let avg = logprob.iter().map(|x| logprob_to_prob(*x)).sum::<f64>()
+                        let avg = logprob
+                            .iter()
+                            .map(|x| logprob_to_prob(*x, self.confidence_base))
+                            .sum::<f64>()
                             / logprob.len() as f64;
                         let variance = logprob
                             .iter()
-                            .map(|x| (logprob_to_prob(*x) - avg).powi(2))
+                            .map(|x| (logprob_to_prob(*x, self.confidence_base) - avg).powi(2))
                             .sum::<f64>()
                             / logprob.len() as f64;
                         variance.sqrt()
@@ -324,11 +381,14 @@ impl Bench {
                 model_logprob[LogprobType::Incorrect as usize]
                     .get(&model)
                     .map(|logprob| {
-                        let avg = logprob.iter().map(|x| logprob_to_prob(*x)).sum::<f64>()
+                        let avg = logprob
+                            .iter()
+                            .map(|x| logprob_to_prob(*x, self.confidence_base))
+                            .sum::<f64>()
                             / logprob.len() as f64;
                         let variance = logprob
                             .iter()
-                            .map(|x| (logprob_to_prob(*x) - avg).powi(2))
+                            .map(|x| (logprob_to_prob(*x, self.confidence_base) - avg).powi(2))
                             .sum::<f64>()
                             / logprob.len() as f64;
                         variance.sqrt()
@@ -337,11 +397,14 @@ impl Bench {
                 model_logprob[LogprobType::CorrectStripped as usize]
                     .get(&model)
                     .map(|logprob| {
-                        let avg = logprob.iter().map(|x| logprob_to_prob(*x)).sum::<f64>()
+                        let avg = logprob
+                            .iter()
+                            .map(|x| logprob_to_prob(*x, self.confidence_base))
+                            .sum::<f64>()
                             / logprob.len() as f64;
                         let variance = logprob
                             .iter()
-                            .map(|x| (logprob_to_prob(*x) - avg).powi(2))
+                            .map(|x| (logprob_to_prob(*x, self.confidence_base) - avg).powi(2))
                             .sum::<f64>()
                             / logprob.len() as f64;
                         variance.sqrt()
@@ -350,11 +413,14 @@ impl Bench {
                 model_logprob[LogprobType::CorrectAligned as usize]
                     .get(&model)
                     .map(|logprob| {
-                        let avg = logprob.iter().map(|x| logprob_to_prob(*x)).sum::<f64>()
+                        let avg = logprob
+                            .iter()
+                            .map(|x| logprob_to_prob(*x, self.confidence_base))
+                            .sum::<f64>()
                             / logprob.len() as f64;
                         let variance = logprob
                             .iter()
-                            .map(|x| (logprob_to_prob(*x) - avg).powi(2))
+                            .map(|x| (logprob_to_prob(*x, self.confidence_base) - avg).powi(2))
                             .sum::<f64>()
                             / logprob.len() as f64;
                         variance.sqrt()
@@ -363,11 +429,14 @@ impl Bench {
                 model_logprob[LogprobType::Correct as usize]
                     .get(&model)
                     .map(|logprob| {
-                        let avg = logprob.iter().map(|x| logprob_to_prob(*x)).sum::<f64>()
+                        let avg = logprob
+                            .iter()
+                            .map(|x| logprob_to_prob(*x, self.confidence_base))
+                            .sum::<f64>()
                             / logprob.len() as f64;
                         let variance = logprob
                             .iter()
-                            .map(|x| (logprob_to_prob(*x) - avg).powi(2))
+                            .map(|x| (logprob_to_prob(*x, self.confidence_base) - avg).powi(2))
                             .sum::<f64>()
                             / logprob.len() as f64;
                         variance.sqrt()
@@ -463,7 +532,7 @@ impl Bench {
                     println!(
                         "  {}: {:.1}% (+- {:.1})",
                         logprob_type_names[i],
-                        logprob_to_prob(value),
+                        logprob_to_prob(value, self.confidence_base),
                         stats.std_logprob[i]
                     );
                 }
@@ -483,12 +552,111 @@ impl Bench {
         }
     }
 
+    /// Time the conflict detection on a synthetic repository with a
+    /// cherry-pick conflicting in the given number of files, parsing
+    /// the files serially and in parallel
+    pub fn bench_find_conflicts(args: &BenchArgs, files: usize) -> Result<()> {
+        const LINES: usize = 1000;
+        const CONFLICTS: usize = 10;
+
+        let repo = tempfile::tempdir()?;
+        let git = |args: &[&str]| -> Result<bool> {
+            Ok(std::process::Command::new("git")
+                .arg("-C")
+                .arg(repo.path())
+                .args(args)
+                .output()
+                .context("Failed to execute git")?
+                .status
+                .success())
+        };
+        let write_files = |suffix: &str| -> Result<()> {
+            for file in 0..files {
+                let content: String = (0..LINES)
+                    .map(|line| {
+                        if line % (LINES / CONFLICTS) == LINES / CONFLICTS / 2 {
+                            format!("line {} {}\n", line, suffix)
+                        } else {
+                            format!("line {}\n", line)
+                        }
+                    })
+                    .collect();
+                std::fs::write(repo.path().join(format!("file{}.txt", file)), content)?;
+            }
+            Ok(())
+        };
+
+        git(&["init", "-q", "-b", "main"])?;
+        for (key, value) in [
+            ("user.name", "bench"),
+            ("user.email", "bench@example.com"),
+            ("merge.conflictStyle", "diff3"),
+            ("commit.gpgsign", "false"),
+        ] {
+            git(&["config", key, value])?;
+        }
+        write_files("")?;
+        git(&["add", "."])?;
+        git(&["commit", "-q", "-m", "base"])?;
+        git(&["checkout", "-q", "-b", "topic"])?;
+        write_files("topic")?;
+        git(&["commit", "-q", "-am", "topic"])?;
+        git(&["checkout", "-q", "main"])?;
+        write_files("main")?;
+        git(&["commit", "-q", "-am", "main"])?;
+        if git(&["cherry-pick", "topic"])? {
+            anyhow::bail!("The cherry-pick of the synthetic repository didn't conflict");
+        }
+
+        // GitUtils works on the repository of the current directory
+        std::env::set_current_dir(repo.path())?;
+        let mut git_utils = GitUtils::new(
+            ContextLines {
+                code_context_lines: args.code_context_lines,
+                diff_context_lines: args.diff_context_lines,
+                patch_context_lines: args.patch_context_lines,
+                extra_conflict_lines: 0,
+                diff_algorithm: args.diff_algorithm,
+            },
+            None,
+            false,
+            ResolutionMode::Interactive,
+            0,
+            ApplyOptions::default(),
+        );
+        // Fill the blob and gitattributes caches before timing
+        let conflicts = git_utils.find_conflicts(args.max_context_size, &[])?;
+
+        let mut durations = Vec::new();
+        for threads in [1, 0] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()?;
+            let start = std::time::Instant::now();
+            pool.install(|| git_utils.find_conflicts(args.max_context_size, &[]))?;
+            durations.push((pool.current_num_threads(), start.elapsed().as_secs_f64()));
+        }
+
+        println!(
+            "Found {} conflicts in {} files with {} lines",
+            conflicts.len(),
+            files,
+            LINES
+        );
+        for (threads, duration) in &durations {
+            println!("  {} threads: {:.3}s", threads, duration);
+        }
+        println!("  Speedup: {:.2}x", durations[0].1 / durations[1].1);
+        Ok(())
+    }
+
     pub async fn run_test(
         &mut self,
         config: &Config,
         entries: &[TestEntry],
         args: BenchArgs,
     ) -> Result<()> {
+        self.confidence_base = config.get_confidence_base();
         println!("Running statistics test on {} entries", entries.len());
 
         let context_lines = ContextLines {
@@ -496,6 +664,7 @@ impl Bench {
             diff_context_lines: args.diff_context_lines,
             patch_context_lines: args.patch_context_lines,
             extra_conflict_lines: 0,
+            diff_algorithm: args.diff_algorithm,
         };
 
         // Create a new GitUtils instance to find the commit hash
@@ -505,6 +674,7 @@ impl Bench {
             args.cache_overwrite,
             ResolutionMode::Interactive,
             0,
+            ApplyOptions::default(),
         );
 
         // Load existing checkpoint
@@ -587,19 +757,15 @@ impl Bench {
                 panic!("Git diff for commit {} not found", commit_hash);
             });
 
-            let conflict = self.create_conflict_from_entry(
+            let mut conflict = self.create_conflict_from_entry(
                 entry,
                 args.create_patch,
                 args.patch_context_lines,
             )?;
+            conflict.commit_context = git_diff.map(Arc::new);
 
-            let resolver = ConflictResolver::new(
-                config,
-                git_diff,
-                true,
-                args.get_cache_path(),
-                args.cache_overwrite,
-            );
+            let resolver =
+                ConflictResolver::new(config, true, args.get_cache_path(), args.cache_overwrite);
 
             let resolved_conflicts = resolver.resolve_conflicts(&[conflict], &Vec::new()).await;
             let resolved_conflicts = match resolved_conflicts {
@@ -651,7 +817,8 @@ impl Bench {
                 Err(e) => anyhow::bail!("Failed to resolve conflicts: {}", e),
             };
 
-            let deduplicated_conflicts = GitUtils::deduplicate_conflicts_vibe(&resolved_conflicts);
+            let deduplicated_conflicts =
+                GitUtils::deduplicate_conflicts_vibe(&resolved_conflicts, &config.get_agreement());
 
             let ai_consensus_model = "AI consensus".to_string();
             let ai_consensus_result = if deduplicated_conflicts.is_empty() {
@@ -731,7 +898,7 @@ impl Bench {
             }
         }
 
-        sorted_model_names.sort_by(|a, b| a.0.cmp(&b.0));
+        sorted_model_names.sort_by_key(|a| a.0);
         let ai_consensus_model = sorted_model_names
             .iter()
             .map(|(_, model)| model.as_str())
@@ -742,6 +909,168 @@ impl Bench {
         Ok(())
     }
 
+    /// Resolve the conflicts of each fixture with all the endpoints and
+    /// score the resolved files against the golden resolutions
+    pub async fn run_fixtures(
+        &mut self,
+        config: &Config,
+        fixtures: &[Fixture],
+        args: BenchArgs,
+    ) -> Result<()> {
+        self.confidence_base = config.get_confidence_base();
+        println!("Running statistics test on {} fixtures", fixtures.len());
+
+        let mut git_utils = GitUtils::new(
+            ContextLines {
+                code_context_lines: args.code_context_lines,
+                diff_context_lines: args.diff_context_lines,
+                patch_context_lines: args.patch_context_lines,
+                extra_conflict_lines: 0,
+                diff_algorithm: args.diff_algorithm,
+            },
+            args.get_cache_path(),
+            args.cache_overwrite,
+            ResolutionMode::VibeWithMarkers,
+            0,
+            ApplyOptions {
+                agreement: config.get_agreement(),
+                ..Default::default()
+            },
+        );
+        for (i, fixture) in fixtures
+            .iter()
+            .enumerate()
+            .take(args.max_entries.unwrap_or(usize::MAX))
+        {
+            println!(
+                "Processing fixture {} of {}: {}",
+                i + 1,
+                fixtures.len(),
+                fixture.name
+            );
+            let mut content = fixture.content.clone();
+            let eof_newline = content.ends_with('\n');
+            if !eof_newline && !content.is_empty() {
+                content.push('\n');
+            }
+            let marker_size =
+                GitUtils::detect_marker_size(&content)?.unwrap_or(GitUtils::DEFAULT_MARKER_SIZE);
+            let conflicts = git_utils
+                .parse_file_conflicts(&fixture.name, &content, marker_size)
+                .with_context(|| format!("Failed to parse fixture: {}", fixture.name))?;
+            let resolver =
+                ConflictResolver::new(config, false, args.get_cache_path(), args.cache_overwrite);
+            let (resolved_conflicts, resolver_errors) = resolver
+                .resolve_conflicts(&conflicts, &[])
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to resolve conflicts: {}", e))?;
+            self.score_fixture(
+                i,
+                fixture,
+                &conflicts,
+                &resolved_conflicts,
+                &resolver_errors.errors,
+                eof_newline,
+                &mut git_utils,
+            );
+        }
+
+        self.calculate_stats(&args);
+        Ok(())
+    }
+
+    /// Score the resolved file of each model, and of the AI consensus
+    /// of the models that agree, against the golden resolution
+    #[allow(clippy::too_many_arguments)]
+    fn score_fixture(
+        &mut self,
+        entry_index: usize,
+        fixture: &Fixture,
+        conflicts: &[Conflict],
+        resolved_conflicts: &[ResolvedConflict],
+        errors: &HashMap<String, usize>,
+        eof_newline: bool,
+        git_utils: &mut GitUtils,
+    ) {
+        let score = |model: &str, content: Option<String>, resolved: &[ResolvedConflict]| {
+            let tokens: Vec<u64> = resolved.iter().filter_map(|r| r.total_tokens).collect();
+            let logprobs: Vec<f64> = resolved.iter().filter_map(|r| r.logprob).collect();
+            TestResult {
+                entry_index,
+                model: model.to_string(),
+                correct: content.as_deref() == Some(fixture.resolved.as_str()),
+                correct_aligned: content
+                    .as_deref()
+                    .is_some_and(|content| self.aligned(content, &fixture.resolved)),
+                correct_stripped: content
+                    .as_deref()
+                    .is_some_and(|content| self.stripped(content, &fixture.resolved)),
+                duration: resolved.iter().map(|r| r.duration).sum(),
+                tokens: (!tokens.is_empty()).then(|| tokens.iter().sum()),
+                logprob: (!logprobs.is_empty())
+                    .then(|| logprobs.iter().sum::<f64>() / logprobs.len() as f64),
+                failed_patched_code: content.as_deref().and_then(|content| {
+                    Self::generate_failed_patched_code(content, &fixture.resolved)
+                }),
+                error: false,
+                patch_commit_hash: String::new(),
+                code_commit_hash: fixture.name.clone(),
+            }
+        };
+
+        let mut results = Vec::new();
+        for (model, error_count) in errors {
+            for _ in 0..*error_count {
+                results.push(TestResult {
+                    error: true,
+                    ..score(model, None, &[])
+                });
+            }
+        }
+        // The samples of the multi and beam search are not scored
+        let resolved_conflicts: Vec<ResolvedConflict> = resolved_conflicts
+            .iter()
+            .filter(|r| r.multi == Some(0) && r.beam == Some(0))
+            .cloned()
+            .collect();
+        let mut models: Vec<&str> = Vec::new();
+        for resolved in &resolved_conflicts {
+            if !models.contains(&resolved.model.as_str()) {
+                models.push(&resolved.model);
+            }
+        }
+        for model in models {
+            let resolved: Vec<ResolvedConflict> = resolved_conflicts
+                .iter()
+                .filter(|r| r.model == model)
+                .cloned()
+                .collect();
+            let content = git_utils
+                .resolve_file_content(conflicts, &resolved, eof_newline)
+                .ok();
+            results.push(score(model, content, &resolved));
+        }
+
+        // The consensus applies to each conflict the resolution the
+        // most models agree on
+        let content = git_utils
+            .resolve_file_content(conflicts, &resolved_conflicts, eof_newline)
+            .ok();
+        let deduplicated =
+            GitUtils::deduplicate_conflicts_vibe(&resolved_conflicts, git_utils.agreement());
+        let consensus: Vec<ResolvedConflict> = conflicts
+            .iter()
+            .filter_map(|conflict| {
+                deduplicated
+                    .iter()
+                    .find(|r| r.conflict.local_start == conflict.local_start)
+                    .cloned()
+            })
+            .collect();
+        results.push(score("AI consensus", content, &consensus));
+        self.results.extend(results);
+    }
+
     fn git_show_dirs(
         &self,
         git_utils: &GitUtils,
@@ -763,7 +1092,8 @@ impl Bench {
         for endpoint in config.get_all_endpoints() {
             match &endpoint.config {
                 EndpointTypeConfig::OpenAI { variants, .. }
-                | EndpointTypeConfig::Anthropic { variants, .. } => {
+                | EndpointTypeConfig::Anthropic { variants, .. }
+                | EndpointTypeConfig::Ollama { variants, .. } => {
                     if let Some(variants) = variants {
                         for variant in variants.iter() {
                             let variant_name = if let Some(variant) = &*variant.name {
@@ -897,6 +1227,76 @@ impl Bench {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_score_fixture() {
+        let dir = tempfile::tempdir().unwrap();
+        let content =
+            "a\n<<<<<<< HEAD\nb-local\n||||||| base\nb\n=======\nb-remote\n>>>>>>> topic\nc\n";
+        std::fs::write(dir.path().join("f.c"), content).unwrap();
+        std::fs::write(dir.path().join("f.c.resolved"), "a\nb-merged\nc\n").unwrap();
+        std::fs::write(dir.path().join("orphan.c"), content).unwrap();
+        let fixtures = Bench::load_fixtures(dir.path()).unwrap();
+        assert_eq!(fixtures.len(), 1);
+        assert_eq!(fixtures[0].name, "f.c");
+
+        let mut git_utils = GitUtils::new(
+            ContextLines::default(),
+            None,
+            false,
+            ResolutionMode::VibeWithMarkers,
+            0,
+            ApplyOptions::default(),
+        );
+        let conflicts = git_utils
+            .parse_file_conflicts("f.c", &fixtures[0].content, 7)
+            .unwrap();
+        let resolved = |model: &str, resolved_version: &str, endpoint| ResolvedConflict {
+            conflict: conflicts[0].clone(),
+            resolved_version: resolved_version.to_string(),
+            model: model.to_string(),
+            duration: 1.0,
+            total_tokens: Some(10),
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint,
+            multi: Some(0),
+            beam: Some(0),
+        };
+        let mut bench = Bench::new();
+        bench.score_fixture(
+            0,
+            &fixtures[0],
+            &conflicts,
+            &[
+                resolved("x", "b-local\n", 0),
+                resolved("y", "b-merged\n", 1),
+                resolved("z", "b-merged\n", 2),
+            ],
+            &HashMap::from([("w".to_string(), 1)]),
+            true,
+            &mut git_utils,
+        );
+        let results: Vec<_> = bench
+            .results
+            .iter()
+            .map(|r| (r.model.as_str(), r.correct, r.error))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                ("w", false, true),
+                ("x", false, false),
+                ("y", true, false),
+                ("z", true, false),
+                ("AI consensus", true, false),
+            ]
+        );
+        assert_eq!(bench.results[4].duration, 1.0);
+        // The consensus spends the tokens of both agreeing models
+        assert_eq!(bench.results[4].tokens, Some(20));
+    }
+
     #[test]
     fn test_test_result_serialization() {
         let result = TestResult {
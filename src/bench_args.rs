@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
 // Copyright (C) 2025-2026  Red Hat, Inc.
 
+use crate::diff_algorithm::DiffAlgorithm;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
@@ -15,12 +16,20 @@ pub struct BenchArgs {
     pub config_path: String,
 
     /// Path to test data file (CSV lines format)
-    #[arg(short = 'd', long = "test-data")]
-    pub test_data_path: String,
+    #[arg(
+        short = 'd',
+        long = "test-data",
+        required_unless_present_any = ["find_conflicts_files", "fixtures_dir"]
+    )]
+    pub test_data_path: Option<String>,
 
     /// Path to checkpoint file
-    #[arg(short = 'k', long = "checkpoint")]
-    pub checkpoint_path: String,
+    #[arg(
+        short = 'k',
+        long = "checkpoint",
+        required_unless_present_any = ["find_conflicts_files", "fixtures_dir"]
+    )]
+    pub checkpoint_path: Option<String>,
 
     /// Checkpoint interval (number of entries between saves)
     #[arg(long = "checkpoint-interval", default_value = "100")]
@@ -31,7 +40,11 @@ pub struct BenchArgs {
     pub max_entries: Option<usize>,
 
     /// Git directories to search for diffs
-    #[arg(long = "git-dirs", value_delimiter = ',', required = true)]
+    #[arg(
+        long = "git-dirs",
+        value_delimiter = ',',
+        required_unless_present_any = ["find_conflicts_files", "fixtures_dir"]
+    )]
     pub git_directories: Vec<String>,
 
     /// Number of context lines to include around conflict markers
@@ -42,6 +55,10 @@ pub struct BenchArgs {
     #[arg(long = "diff-context-lines", default_value = "3", value_parser = clap::value_parser!(u32).range(0..))]
     pub diff_context_lines: u32,
 
+    /// The diff algorithm of the git_diff provided as context
+    #[arg(long = "diff-algorithm", value_enum, default_value_t = DiffAlgorithm::Histogram)]
+    pub diff_algorithm: DiffAlgorithm,
+
     /// Number of context lines of the patch
     #[arg(long = "patch-context-lines", default_value = "3", value_parser = clap::value_parser!(u32).range(0..))]
     pub patch_context_lines: u32,
@@ -69,6 +86,17 @@ pub struct BenchArgs {
     /// regenerate the patch with create_patch
     #[arg(long = "create-patch", default_value = "true")]
     pub create_patch: bool,
+
+    /// Directory of files with conflict markers, each paired with its
+    /// golden resolution in the file of the same name with the
+    /// .resolved extension, instead of the test data
+    #[arg(long = "fixtures", value_name = "DIR", conflicts_with_all = ["test_data_path", "checkpoint_path"])]
+    pub fixtures_dir: Option<String>,
+
+    /// Benchmark the conflict detection on a synthetic repository with
+    /// this number of conflicted files, instead of querying the models
+    #[arg(long = "find-conflicts", value_name = "FILES")]
+    pub find_conflicts_files: Option<usize>,
 }
 
 impl BenchArgs {
@@ -82,6 +110,14 @@ impl BenchArgs {
             Some(self.cache_path.clone())
         }
     }
+
+    /// Returns the checkpoint path, required unless benchmarking the
+    /// conflict detection.
+    pub fn get_checkpoint_path(&self) -> &str {
+        self.checkpoint_path
+            .as_deref()
+            .expect("--checkpoint is required")
+    }
 }
 
 // Local Variables:
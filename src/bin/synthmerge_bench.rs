@@ -11,17 +11,33 @@ use synthmerge::logger::log_init;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    log_init();
+    log_init(None);
     let args = BenchArgs::parse();
 
+    if let Some(files) = args.find_conflicts_files {
+        return Bench::bench_find_conflicts(&args, files);
+    }
+
     // Load configuration
     let config_path = shellexpand::full(&args.config_path)?;
     let config_path = std::path::Path::new(config_path.as_ref());
     let config = Config::load(config_path)?;
 
+    if let Some(fixtures_dir) = &args.fixtures_dir {
+        let fixtures_dir = shellexpand::full(fixtures_dir)
+            .with_context(|| format!("Failed to expand fixtures path: {}", fixtures_dir))?;
+        let fixtures = Bench::load_fixtures(fixtures_dir.as_ref())?;
+        println!("Loaded {} fixtures", fixtures.len());
+        return Bench::new().run_fixtures(&config, &fixtures, args).await;
+    }
+
     // Load test database
-    let db_path = shellexpand::full(&args.test_data_path)
-        .with_context(|| format!("Failed to expand test data path: {}", args.test_data_path))?;
+    let test_data_path = args
+        .test_data_path
+        .as_deref()
+        .expect("--test-data is required");
+    let db_path = shellexpand::full(test_data_path)
+        .with_context(|| format!("Failed to expand test data path: {}", test_data_path))?;
     let db_path = db_path.as_ref();
     let entries = Bench::load_database(db_path)
         .with_context(|| format!("Failed to load test data from: {}", db_path))?;
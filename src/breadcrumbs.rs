@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+//! The `--breadcrumbs` choices, in a module of its own so build.rs can
+//! include it with the command line arguments
+
+/// Where vibe mode keeps a copy of the conflicts it replaced
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Breadcrumbs {
+    /// Append the conflicts as a comment at the end of the file
+    Comment,
+    /// Write the conflicts to a sidecar file next to the file
+    Sidecar,
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
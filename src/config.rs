@@ -1,37 +1,503 @@
 // SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
 // Copyright (C) 2025-2026  Red Hat, Inc.
 
+use crate::git_utils::GitUtils;
+use crate::logger;
+use crate::prob;
 use anyhow::{Context, Result};
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    /// The endpoints shared by all the profiles. Once loaded they're
+    /// followed by the endpoints of the selected profile: the one of
+    /// --profile, else of SYNTHMERGE_PROFILE, else of the repository
+    /// config, else `default` if it exists.
+    #[serde(default)]
+    endpoints: Vec<EndpointConfig>,
+    /// Named sets of endpoints, selected with --profile or
+    /// SYNTHMERGE_PROFILE and falling back to `default`
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+    /// The endpoints of the config file before the profile selection
+    #[serde(skip)]
+    common_endpoints: Vec<EndpointConfig>,
+    #[serde(skip)]
+    profile: Option<String>,
+    /// The profile was chosen by the user and the repository config
+    /// can't change it
+    #[serde(skip)]
+    explicit_profile: bool,
+    #[serde(skip)]
+    path: PathBuf,
+    /// Minimum confidence percentage required to apply a vibe resolution
+    #[serde(default)]
+    min_confidence: Option<f64>,
+    /// Per-path minimum confidence, the first matching glob wins
+    #[serde(default)]
+    confidence_thresholds: Vec<ConfidenceThreshold>,
+    /// Whether the resolutions without logprobs pass the thresholds
+    #[serde(default)]
+    missing_logprob: MissingLogprob,
+    /// Cancel the streamed responses once the confidence of their
+    /// patched code can't reach the confidence threshold anymore
+    #[serde(default)]
+    early_abandon: bool,
+    /// Per-path side the models are told to favor, the first matching
+    /// glob wins
+    #[serde(default)]
+    side_preferences: Vec<SidePreference>,
+    /// Globs of files whose conflicts are resolved together in one prompt
+    #[serde(default)]
+    conflict_groups: Vec<Vec<String>>,
+    /// Groups whose code and patches exceed this size in bytes are
+    /// resolved one conflict at a time
+    #[serde(default = "default_max_conflict_group_size")]
+    max_conflict_group_size: usize,
+    /// Whitespace normalization of the prompt inputs
+    #[serde(default)]
+    normalize: Option<NormalizeConfig>,
+    /// Never send the diff of the commit being applied to the endpoints
+    #[serde(default)]
+    no_diff: bool,
+    /// Whitespace differences ignored when comparing the resolutions
+    /// of the models
+    #[serde(default)]
+    agreement: AgreementConfig,
+    #[serde(default, alias = "eof-newline")]
+    eof_newline: EofNewline,
+    #[serde(default, alias = "assisted-by")]
+    assisted_by: AssistedBy,
+    /// Maximum number of requests in flight across all endpoints
+    #[serde(default)]
+    max_concurrency: Option<usize>,
+    /// Maximum number of files whose conflicts are resolved at the
+    /// same time, the resolutions are applied in order regardless
+    #[serde(default)]
+    max_concurrent_files: Option<usize>,
+    /// Send the whole conflicted file as context, if its size in bytes
+    /// is within this budget
+    #[serde(default)]
+    full_file_context: Option<usize>,
+    /// Warn about the conflicts whose resolution took longer than this
+    /// many seconds, 0 disables the warning
+    #[serde(default = "default_slow_conflict")]
+    slow_conflict: f64,
+    /// Names of the endpoints queried in order, one at a time, only for
+    /// the conflicts the other endpoints produced no resolution for
+    #[serde(default)]
+    fallback: Vec<String>,
+    /// Size of the conflict markers of all the files, instead of the
+    /// conflict-marker-size gitattribute and the detection
+    #[serde(default)]
+    marker_size: Option<usize>,
+    /// The base of the confidence percentage of a logprob, the
+    /// percentage is base^logprob
+    #[serde(default = "default_confidence_base")]
+    confidence_base: f64,
+    /// Show the logprobs instead of the confidence percentages
+    #[serde(skip)]
+    raw_logprob: bool,
+    /// Allow the repository config to set the system_prompt of the
+    /// endpoints
+    #[serde(default)]
+    repo_system_prompt: bool,
+    /// Print the prompts sent to the endpoints
+    #[serde(skip)]
+    print_prompt: bool,
+    /// Print the prompts without querying the endpoints
+    #[serde(skip)]
+    prompt_only: bool,
+}
+
+/// Repository policy loaded from the `.synthmerge.yaml` file in the
+/// repo root and layered over the user config. New endpoints, their
+/// urls and their API keys are deliberately not allowed, so a
+/// repository can't redirect the code or the credentials of the user
+/// elsewhere.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RepoConfig {
+    #[serde(default)]
+    min_confidence: Option<f64>,
+    #[serde(default)]
+    confidence_thresholds: Option<Vec<ConfidenceThreshold>>,
+    #[serde(default)]
+    missing_logprob: Option<MissingLogprob>,
+    #[serde(default)]
+    side_preferences: Option<Vec<SidePreference>>,
+    #[serde(default)]
+    conflict_groups: Option<Vec<Vec<String>>>,
+    #[serde(default)]
+    max_conflict_group_size: Option<usize>,
+    #[serde(default)]
+    normalize: Option<NormalizeConfig>,
+    #[serde(default)]
+    no_diff: Option<bool>,
+    #[serde(default)]
+    agreement: Option<AgreementConfig>,
+    #[serde(default, alias = "eof-newline")]
+    eof_newline: Option<EofNewline>,
+    #[serde(default, alias = "assisted-by")]
+    assisted_by: Option<AssistedBy>,
+    #[serde(default)]
+    marker_size: Option<usize>,
+    /// The profile used unless one is chosen with --profile or
+    /// SYNTHMERGE_PROFILE
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default)]
+    endpoints: Vec<RepoEndpointConfig>,
+}
+
+/// Overrides of an endpoint of the user config, matched by name. Only
+/// what is sent to the endpoint can change, not where it's sent nor the
+/// credentials.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RepoEndpointConfig {
+    name: String,
+    /// Merged field by field over the context of the endpoint
+    #[serde(default)]
+    context: Option<EndpointContext>,
+    /// Merged key by key over the json of the endpoint, only the
+    /// REPO_JSON_KEYS
+    #[serde(default)]
+    json: Option<EndpointJson>,
+}
+
+impl RepoEndpointConfig {
+    /// The keys of the json a repository can override, anything else
+    /// could change the behavior of the endpoint beyond the model choice
+    const REPO_JSON_KEYS: [&str; 3] = ["model", "temperature", "reasoning_effort"];
+
+    fn validate(&self, path: &Path) -> Result<()> {
+        for key in self.json.iter().flat_map(|json| json.json.keys()) {
+            if !Self::REPO_JSON_KEYS.contains(&key.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "json key {} of endpoint {} is not allowed in {}, only {} are",
+                    key,
+                    self.name,
+                    path.display(),
+                    Self::REPO_JSON_KEYS.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A named set of endpoints added to the common ones
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+struct Profile {
+    #[serde(default)]
     endpoints: Vec<EndpointConfig>,
 }
 
+/// How the token logprobs of the patched code become the logprob of
+/// the resolution
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogprobAggregation {
+    /// The least likely token
+    #[default]
+    Min,
+    /// The mean of the tokens, the log of the inverse of the
+    /// length-normalized perplexity
+    #[serde(alias = "perplexity")]
+    Mean,
+    /// The logprob of the whole patched code
+    Sum,
+}
+
+/// The id identifying the runs in the telemetry
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryId {
+    /// A new random id for each run, runs can't be correlated
+    Random,
+    /// A hash of the environment, stable across the runs
+    #[default]
+    Stable,
+    /// No id at all
+    None,
+}
+
+/// Newline at end of file policy of the resolved files
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EofNewline {
+    /// Keep the newline at end of file of the local side
+    #[default]
+    Preserve,
+    Ensure,
+    Forbid,
+}
+
+impl EofNewline {
+    /// Apply the policy to the resolved content of a file, given if the
+    /// local side of the file was newline terminated
+    pub fn apply(&self, content: &mut String, local_eof_newline: bool) {
+        let eof_newline = match self {
+            EofNewline::Preserve => local_eof_newline,
+            EofNewline::Ensure => true,
+            EofNewline::Forbid => false,
+        };
+        if eof_newline {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+        } else {
+            content.truncate(content.trim_end_matches(['\n', '\r']).len());
+        }
+    }
+}
+
+/// How much the Assisted-by trailer of the commit message tells
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssistedBy {
+    /// No Assisted-by trailer
+    None,
+    /// Only the name of the tool
+    #[default]
+    ToolOnly,
+    /// The name of the tool followed by the models that contributed
+    WithModels,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NormalizeConfig {
+    /// Expand tabs to spaces with this tab width
+    #[serde(default)]
+    pub tab_width: Option<usize>,
+    #[serde(default)]
+    pub trim_trailing_whitespace: bool,
+}
+
+/// The whitespace differences ignored when grouping the identical
+/// resolutions of the models. The resolution applied is still the
+/// verbatim text of one of the models.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AgreementConfig {
+    #[serde(default)]
+    pub ignore_trailing_whitespace: bool,
+    #[serde(default)]
+    pub ignore_eof_newline: bool,
+    #[serde(default)]
+    pub ignore_indentation: bool,
+}
+
+impl AgreementConfig {
+    /// The form of a resolution compared to find the models that agree
+    pub fn key(&self, resolved_version: &str) -> String {
+        if !self.ignore_trailing_whitespace && !self.ignore_eof_newline && !self.ignore_indentation
+        {
+            return resolved_version.to_string();
+        }
+        let mut key = String::with_capacity(resolved_version.len());
+        for line in resolved_version.split_inclusive('\n') {
+            let (mut content, line_ending) = match line.strip_suffix('\n') {
+                Some(content) => (content, "\n"),
+                None => (line, ""),
+            };
+            if self.ignore_trailing_whitespace {
+                content = content.trim_end();
+            }
+            if self.ignore_indentation {
+                content = content.trim_start();
+            }
+            key.push_str(content);
+            key.push_str(line_ending);
+        }
+        if self.ignore_eof_newline {
+            key.truncate(key.trim_end_matches(['\n', '\r']).len());
+        }
+        key
+    }
+}
+
+fn default_max_conflict_group_size() -> usize {
+    65536
+}
+
+fn default_slow_conflict() -> f64 {
+    120.0
+}
+
+fn default_confidence_base() -> f64 {
+    prob::DEFAULT_CONFIDENCE_BASE
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfidenceThreshold {
+    pub glob: String,
+    pub min_confidence: f64,
+}
+
+/// How the resolutions without logprobs are gated by a confidence
+/// threshold
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingLogprob {
+    /// Always applied
+    #[default]
+    Pass,
+    /// Always rejected
+    Fail,
+}
+
+/// Compiled confidence thresholds, looked up by file path
+#[derive(Debug, Clone)]
+pub struct ConfidenceThresholds {
+    global: Option<f64>,
+    scoped: Vec<(GlobMatcher, f64)>,
+    pub missing_logprob: MissingLogprob,
+    /// The base of the confidence percentages compared with the
+    /// thresholds
+    pub confidence_base: f64,
+}
+
+impl Default for ConfidenceThresholds {
+    fn default() -> Self {
+        ConfidenceThresholds {
+            global: None,
+            scoped: Vec::new(),
+            missing_logprob: MissingLogprob::default(),
+            confidence_base: prob::DEFAULT_CONFIDENCE_BASE,
+        }
+    }
+}
+
+impl ConfidenceThresholds {
+    /// Returns the threshold of the first glob matching the path,
+    /// falling back to the global threshold
+    pub fn threshold_for(&self, file_path: &str) -> Option<f64> {
+        self.scoped
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(file_path))
+            .map(|(_, threshold)| *threshold)
+            .or(self.global)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SidePreference {
+    pub glob: String,
+    pub prefer: Side,
+}
+
+/// The side of a conflict the models are told to favor where the two
+/// sides disagree
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    /// The code of HEAD
+    Local,
+    /// The change being applied
+    Remote,
+    /// The code before the change being applied
+    Base,
+}
+
+/// Compiled side preferences, looked up by file path
+#[derive(Debug, Clone, Default)]
+pub struct SidePreferences {
+    scoped: Vec<(GlobMatcher, Side)>,
+}
+
+impl SidePreferences {
+    /// Returns the side of the first glob matching the path
+    pub fn side_for(&self, file_path: &str) -> Option<Side> {
+        self.scoped
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(file_path))
+            .map(|(_, side)| *side)
+    }
+}
+
+/// Compiled conflict groups, looked up by file path
+#[derive(Debug, Clone, Default)]
+pub struct ConflictGroups {
+    groups: Vec<GlobSet>,
+    pub max_size: usize,
+}
+
+impl ConflictGroups {
+    /// Returns the index of the first group matching the path
+    pub fn group_of(&self, file_path: &str) -> Option<usize> {
+        self.groups
+            .iter()
+            .position(|group| group.is_match(file_path))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EndpointConfig {
     pub name: String,
     pub url: String,
     #[serde(default = "default_timeout")]
     pub timeout: u64,
-    #[serde(default = "default_retries")]
+    #[serde(default = "default_retries", alias = "max_retries")]
     pub retries: u32,
-    #[serde(default = "default_delay")]
+    /// Base delay in ms of the exponential backoff between retries
+    #[serde(default = "default_delay", alias = "retry_base_ms")]
     pub delay: u64,
     #[serde(default = "default_max_delay")]
     pub max_delay: u64,
+    /// Maximum number of requests in flight to this endpoint
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /// Random delay in ms added to each backoff
+    #[serde(default)]
+    pub jitter: u64,
     #[serde(default)]
     pub wait: u64,
     pub root_certificate_pem: Option<String>,
+    /// The `http://`, `https://`, `socks5://` or `socks5h://` proxy,
+    /// overriding the HTTPS_PROXY, HTTP_PROXY and ALL_PROXY environment
+    /// variables. NO_PROXY is still honored.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Connect directly ignoring the proxy environment variables
+    #[serde(default)]
+    pub no_proxy: bool,
     pub api_key_file: Box<Option<String>>,
     pub x_api_key_file: Box<Option<String>>,
+    /// The API key itself, usually as `${VAR}` to take it from the
+    /// environment. Only one of api_key, api_key_file and
+    /// api_key_command can be set.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub x_api_key: Option<String>,
+    /// A shell command printing the API key on stdout, like a git
+    /// credential helper. It runs once, on the first request.
+    #[serde(default)]
+    pub api_key_command: Option<String>,
+    #[serde(default)]
+    pub x_api_key_command: Option<String>,
     pub context: Option<EndpointContext>,
     pub json: Option<EndpointJson>,
     pub headers: Option<EndpointHeaders>,
     #[serde(default)]
+    pub logprob_aggregation: LogprobAggregation,
+    /// Stream the responses of the OpenAI and Anthropic endpoints
+    #[serde(default)]
+    pub stream: bool,
+    /// Price per 1K input tokens, for the estimated cost of the run
+    #[serde(default)]
+    pub input_price: Option<f64>,
+    /// Price per 1K output tokens
+    #[serde(default)]
+    pub output_price: Option<f64>,
+    #[serde(default)]
     pub primary: bool,
     #[serde(default = "default_use_backticks")]
     pub use_backticks: bool,
@@ -82,24 +548,85 @@ pub enum EndpointTypeConfig {
     #[serde(rename = "openai")]
     OpenAI {
         variants: Option<Vec<EndpointVariants>>,
+        /// The models tried in order until one answers, instead of the
+        /// model of the json
+        #[serde(default)]
+        models: Option<Vec<String>>,
         #[serde(default)]
         no_chat: bool, // false: /v1/chat/completions true /v1/completions
         #[serde(default)]
         gbnf: bool,
+        /// Azure OpenAI deployment, the url is the resource endpoint
+        #[serde(default)]
+        deployment: Option<String>,
+        /// Azure OpenAI api-version query parameter
+        #[serde(default)]
+        api_version: Option<String>,
+        /// How the api_key_file is sent, defaults to `api_key` with a
+        /// deployment and to `bearer` otherwise
+        #[serde(default)]
+        api_key_style: Option<ApiKeyStyle>,
+        /// Completions per variant including the perplexity search ones,
+        /// 1 disables the perplexity search
+        #[serde(default = "default_n_beams")]
+        n_beams: u32,
+        /// Sampling temperature, the provider default if not set
+        #[serde(default)]
+        temperature: Option<f64>,
+        /// Nucleus sampling probability mass, the provider default if
+        /// not set
+        #[serde(default)]
+        top_p: Option<f64>,
     },
     #[serde(rename = "anthropic")]
     Anthropic {
         variants: Option<Vec<EndpointVariants>>,
+        /// The models tried in order until one answers, instead of the
+        /// model of the json
+        #[serde(default)]
+        models: Option<Vec<String>>,
+        /// Sampling temperature, the provider default if not set
+        #[serde(default)]
+        temperature: Option<f64>,
+        /// Nucleus sampling probability mass, the provider default if
+        /// not set
+        #[serde(default)]
+        top_p: Option<f64>,
+        /// Mark the system message and the context shared by the
+        /// conflicts, the diff and the file, as cacheable
+        #[serde(default)]
+        prompt_caching: bool,
+    },
+    #[serde(rename = "ollama")]
+    Ollama {
+        /// Model name, the endpoint url is the base url of the server
+        model: String,
+        /// How long the model stays loaded after the request, e.g. "10m"
+        #[serde(default)]
+        keep_alive: Option<String>,
+        variants: Option<Vec<EndpointVariants>>,
     },
     #[serde(rename = "patchpal")]
     Patchpal {
         #[serde(default)]
         telemetry: bool,
+        #[serde(default)]
+        telemetry_id: TelemetryId,
         #[serde(default = "default_n_beams")]
         n_beams: u32,
     },
 }
 
+/// The header carrying the API key
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyStyle {
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// `api-key: <key>` as used by Azure OpenAI
+    ApiKey,
+}
+
 fn default_n_beams() -> u32 {
     3
 }
@@ -111,7 +638,7 @@ pub struct EndpointVariants {
     pub json: Option<EndpointJson>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct EndpointContext {
     #[serde(default)]
     pub layout: Option<EndpointContextLayout>,
@@ -119,6 +646,35 @@ pub struct EndpointContext {
     pub no_diff: Option<bool>,
     #[serde(default)]
     pub no_training: Option<bool>,
+    /// Tell the model the signature of the function or class enclosing
+    /// the conflict
+    #[serde(default)]
+    pub enclosing_symbol: Option<bool>,
+    /// Instructions prepended to the system message, the generated
+    /// prompt with the PATCHED CODE format still follows them
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+impl EndpointContext {
+    /// Override the fields set in the other context
+    fn merge(&mut self, other: EndpointContext) {
+        if other.layout.is_some() {
+            self.layout = other.layout;
+        }
+        if other.no_diff.is_some() {
+            self.no_diff = other.no_diff;
+        }
+        if other.no_training.is_some() {
+            self.no_training = other.no_training;
+        }
+        if other.enclosing_symbol.is_some() {
+            self.enclosing_symbol = other.enclosing_symbol;
+        }
+        if other.system_prompt.is_some() {
+            self.system_prompt = other.system_prompt;
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -174,15 +730,83 @@ pub struct EndpointHeaders {
 
 impl Config {
     const FORBIDDEN_CHARS: &str = "()|,#$";
+    const PROFILE_ENV: &str = "SYNTHMERGE_PROFILE";
+    const DEFAULT_PROFILE: &str = "default";
 
+    /// Load the config like [`Config::load_profile`] without --profile,
+    /// for the library users, the binary always passes --profile
+    #[allow(dead_code)]
     pub fn load(path: &Path) -> Result<Self> {
+        Self::load_profile(path, None)
+    }
+
+    /// Load the config with the endpoints of the profile, by default the
+    /// one named by SYNTHMERGE_PROFILE or else `default` if it exists
+    pub fn load_profile(path: &Path, profile: Option<&str>) -> Result<Self> {
+        let profile = profile.map(str::to_string).or_else(|| {
+            std::env::var(Self::PROFILE_ENV)
+                .ok()
+                .filter(|profile| !profile.is_empty())
+        });
+        Self::load_selected_profile(path, profile.as_deref())
+    }
+
+    /// Load the config with the endpoints of the profile, `default` if
+    /// None and it exists
+    fn load_selected_profile(path: &Path, profile: Option<&str>) -> Result<Self> {
         let contents = fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path.display()))?;
 
         let mut config: Config = serde_yaml::from_str(&contents)
             .with_context(|| format!("Failed to parse config file as YAML: {}", path.display()))?;
 
-        if config.endpoints.is_empty() {
+        config.explicit_profile = profile.is_some();
+        config.path = path.to_path_buf();
+        config.common_endpoints = std::mem::take(&mut config.endpoints);
+        config.select_profile(profile)?;
+
+        config.validate(path)?;
+
+        log::debug!("{}", logger::redact(&format!("{:?}", config)));
+
+        Ok(config)
+    }
+
+    /// Replace the endpoints with the common ones followed by the ones
+    /// of the profile
+    fn select_profile(&mut self, profile: Option<&str>) -> Result<()> {
+        let profile = match profile {
+            Some(profile) if !self.profiles.contains_key(profile) => {
+                let profiles = self.profiles.keys().map(String::as_str).collect::<Vec<_>>();
+                return Err(anyhow::anyhow!(
+                    "Profile '{}' not found in config file {}, {}",
+                    profile,
+                    self.path.display(),
+                    if profiles.is_empty() {
+                        "it has no profiles".to_string()
+                    } else {
+                        format!("the profiles are: {}", profiles.join(", "))
+                    }
+                ));
+            }
+            Some(profile) => Some(profile),
+            None => self
+                .profiles
+                .contains_key(Self::DEFAULT_PROFILE)
+                .then_some(Self::DEFAULT_PROFILE),
+        };
+        let mut endpoints = self.common_endpoints.clone();
+        if let Some(profile) = profile {
+            endpoints.extend(self.profiles[profile].endpoints.iter().cloned());
+        }
+        Self::load_endpoints(&mut endpoints, &self.path)?;
+        self.endpoints = endpoints;
+        self.profile = profile.map(str::to_string);
+        Ok(())
+    }
+
+    fn load_endpoints(endpoints: &mut [EndpointConfig], path: &Path) -> Result<()> {
+        if endpoints.is_empty() {
             return Err(anyhow::anyhow!(
                 "No endpoints configured in config file: {}",
                 path.display()
@@ -190,10 +814,14 @@ impl Config {
         }
 
         // Trim whitespace from endpoint
-        Self::trim_endpoint_whitespace(&mut config.endpoints);
+        Self::trim_endpoint_whitespace(endpoints);
+
+        Self::interpolate_endpoint_env(endpoints)
+            .with_context(|| format!("Failed to load config file: {}", path.display()))?;
+        Self::add_endpoint_secrets(endpoints);
 
         // Check that each endpoint has required fields
-        for (i, endpoint) in config.endpoints.iter().enumerate() {
+        for (i, endpoint) in endpoints.iter().enumerate() {
             if endpoint.name.is_empty() {
                 return Err(anyhow::anyhow!(
                     "Endpoint {} in config file {} has empty name",
@@ -227,7 +855,7 @@ impl Config {
         }
 
         let mut seen_names = std::collections::HashSet::new();
-        for (i, endpoint) in config.endpoints.iter().enumerate() {
+        for (i, endpoint) in endpoints.iter().enumerate() {
             if !seen_names.insert(&endpoint.name) {
                 return Err(anyhow::anyhow!(
                     "Endpoint {} in config file {} has duplicate name '{}'",
@@ -238,11 +866,163 @@ impl Config {
             }
         }
 
-        Self::validate_primary(&mut config.endpoints);
+        Self::validate_primary(endpoints);
+        Ok(())
+    }
 
-        log::debug!("{:?}", config);
+    fn validate(&self, path: &Path) -> Result<()> {
+        self.get_confidence_thresholds()
+            .with_context(|| format!("Invalid confidence thresholds in {}", path.display()))?;
+        self.get_conflict_groups()
+            .with_context(|| format!("Invalid conflict groups in {}", path.display()))?;
+        self.get_side_preferences()
+            .with_context(|| format!("Invalid side preferences in {}", path.display()))?;
+        if self.max_concurrency == Some(0) {
+            return Err(anyhow::anyhow!(
+                "max_concurrency must be at least 1 in {}",
+                path.display()
+            ));
+        }
+        if self.max_concurrent_files == Some(0) {
+            return Err(anyhow::anyhow!(
+                "max_concurrent_files must be at least 1 in {}",
+                path.display()
+            ));
+        }
+        self.get_fallback()
+            .with_context(|| format!("Invalid fallback in {}", path.display()))?;
+        if self
+            .marker_size
+            .is_some_and(|size| size < GitUtils::DEFAULT_MARKER_SIZE)
+        {
+            return Err(anyhow::anyhow!(
+                "marker_size must be at least {} in {}",
+                GitUtils::DEFAULT_MARKER_SIZE,
+                path.display()
+            ));
+        }
+        if !self.confidence_base.is_finite() || self.confidence_base <= 1.0 {
+            return Err(anyhow::anyhow!(
+                "confidence_base must be a number greater than 1 in {}",
+                path.display()
+            ));
+        }
+        if !self.slow_conflict.is_finite() || self.slow_conflict < 0.0 {
+            return Err(anyhow::anyhow!(
+                "slow_conflict must be a number of seconds in {}",
+                path.display()
+            ));
+        }
+        Ok(())
+    }
 
-        Ok(config)
+    /// Layer the repository config of the git root, if any, over this
+    /// config. The fields set in the repository config take precedence.
+    pub fn load_repo_config(&mut self, git_root: &Path) -> Result<()> {
+        let path = git_root.join(concat!(".", env!("CARGO_PKG_NAME"), ".yaml"));
+        if !path.exists() {
+            return Ok(());
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read repo config file: {}", path.display()))?;
+        let repo_config: RepoConfig = serde_yaml::from_str(&contents).with_context(|| {
+            format!(
+                "Failed to parse repo config file as YAML: {}",
+                path.display()
+            )
+        })?;
+        for repo_endpoint in &repo_config.endpoints {
+            repo_endpoint.validate(&path)?;
+        }
+        if let Some(profile) = &repo_config.profile
+            && !self.explicit_profile
+        {
+            self.select_profile(Some(profile))
+                .with_context(|| format!("Invalid profile in {}", path.display()))?;
+        }
+        self.layer(repo_config);
+        for (i, endpoint) in self.endpoints.iter().enumerate() {
+            Self::validate_endpoint(endpoint, i, &path)?;
+        }
+        self.validate(&path)?;
+
+        log::info!("Using repo config file: {}", path.display());
+        log::debug!("{}", logger::redact(&format!("{:?}", self)));
+
+        Ok(())
+    }
+
+    fn layer(&mut self, repo_config: RepoConfig) {
+        if repo_config.min_confidence.is_some() {
+            self.min_confidence = repo_config.min_confidence;
+        }
+        if let Some(confidence_thresholds) = repo_config.confidence_thresholds {
+            self.confidence_thresholds = confidence_thresholds;
+        }
+        if let Some(missing_logprob) = repo_config.missing_logprob {
+            self.missing_logprob = missing_logprob;
+        }
+        if let Some(side_preferences) = repo_config.side_preferences {
+            self.side_preferences = side_preferences;
+        }
+        if let Some(conflict_groups) = repo_config.conflict_groups {
+            self.conflict_groups = conflict_groups;
+        }
+        if let Some(max_conflict_group_size) = repo_config.max_conflict_group_size {
+            self.max_conflict_group_size = max_conflict_group_size;
+        }
+        if repo_config.normalize.is_some() {
+            self.normalize = repo_config.normalize;
+        }
+        if let Some(no_diff) = repo_config.no_diff {
+            self.no_diff = no_diff;
+        }
+        if let Some(agreement) = repo_config.agreement {
+            self.agreement = agreement;
+        }
+        if let Some(eof_newline) = repo_config.eof_newline {
+            self.eof_newline = eof_newline;
+        }
+        if let Some(assisted_by) = repo_config.assisted_by {
+            self.assisted_by = assisted_by;
+        }
+        if repo_config.marker_size.is_some() {
+            self.marker_size = repo_config.marker_size;
+        }
+        for repo_endpoint in repo_config.endpoints {
+            // The endpoint names differ between the users of the
+            // repository, the missing ones are not an error
+            let Some(endpoint) = self
+                .endpoints
+                .iter_mut()
+                .find(|endpoint| endpoint.name == repo_endpoint.name)
+            else {
+                log::warn!(
+                    "Endpoint {} of the repo config is not configured, ignoring it",
+                    repo_endpoint.name
+                );
+                continue;
+            };
+            if let Some(mut context) = repo_endpoint.context {
+                if context.system_prompt.is_some() && !self.repo_system_prompt {
+                    log::warn!(
+                        "Ignoring the system_prompt of endpoint {} in the repo config, set repo_system_prompt to allow it",
+                        repo_endpoint.name
+                    );
+                    context.system_prompt = None;
+                }
+                endpoint.context.get_or_insert_default().merge(context);
+            }
+            if let Some(json) = repo_endpoint.json {
+                endpoint
+                    .json
+                    .get_or_insert_with(|| EndpointJson {
+                        json: Default::default(),
+                    })
+                    .json
+                    .extend(json.json);
+            }
+        }
     }
 
     fn validate_primary(endpoints: &mut [EndpointConfig]) {
@@ -259,9 +1039,90 @@ impl Config {
         }
     }
 
+    /// Register the API keys and the authorization headers of the
+    /// endpoints to be redacted from the logs
+    fn add_endpoint_secrets(endpoints: &[EndpointConfig]) {
+        for endpoint in endpoints {
+            for api_key in [&endpoint.api_key, &endpoint.x_api_key]
+                .into_iter()
+                .flatten()
+            {
+                logger::add_secret(api_key);
+            }
+            if let Some(headers) = &endpoint.headers {
+                for (name, value) in &headers.headers {
+                    if let serde_json::Value::String(value) = value
+                        && ["authorization", "api-key", "x-api-key"]
+                            .contains(&name.to_ascii_lowercase().as_str())
+                    {
+                        logger::add_secret(value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Expand the `${VAR}` and `${VAR:-default}` references to
+    /// environment variables in the string fields of the endpoints
+    fn interpolate_endpoint_env(endpoints: &mut [EndpointConfig]) -> Result<()> {
+        for (i, endpoint) in endpoints.iter_mut().enumerate() {
+            let key = |field: &str| format!("endpoints[{}].{}", i, field);
+            endpoint.url = Self::interpolate_env(&endpoint.url, &key("url"))?;
+            for (field, value) in [
+                ("root_certificate_pem", &mut endpoint.root_certificate_pem),
+                ("proxy", &mut endpoint.proxy),
+                ("api_key_file", &mut *endpoint.api_key_file),
+                ("x_api_key_file", &mut *endpoint.x_api_key_file),
+                ("api_key", &mut endpoint.api_key),
+                ("x_api_key", &mut endpoint.x_api_key),
+                ("api_key_command", &mut endpoint.api_key_command),
+                ("x_api_key_command", &mut endpoint.x_api_key_command),
+            ] {
+                if let Some(value) = value {
+                    *value = Self::interpolate_env(value, &key(field))?;
+                }
+            }
+            if let Some(headers) = &mut endpoint.headers {
+                for (name, value) in headers.headers.iter_mut() {
+                    if let serde_json::Value::String(value) = value {
+                        *value = Self::interpolate_env(value, &key(&format!("headers.{}", name)))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn interpolate_env(value: &str, key: &str) -> Result<String> {
+        let re = regex::Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)(?::-([^}]*))?\}").unwrap();
+        let mut result = String::new();
+        let mut last = 0;
+        for cap in re.captures_iter(value) {
+            let m = cap.get(0).unwrap();
+            let var = &cap[1];
+            let expanded = match (std::env::var(var), cap.get(2)) {
+                (Ok(expanded), _) => expanded,
+                (Err(_), Some(default)) => default.as_str().to_string(),
+                (Err(_), None) => {
+                    return Err(anyhow::anyhow!(
+                        "Environment variable {} referenced by {} is not set",
+                        var,
+                        key
+                    ));
+                }
+            };
+            result.push_str(&value[last..m.start()]);
+            result.push_str(&expanded);
+            last = m.end();
+        }
+        result.push_str(&value[last..]);
+        Ok(result)
+    }
+
     fn trim_variants_whitespace(config: &mut EndpointTypeConfig) {
         if let EndpointTypeConfig::OpenAI { variants, .. }
-        | EndpointTypeConfig::Anthropic { variants, .. } = config
+        | EndpointTypeConfig::Anthropic { variants, .. }
+        | EndpointTypeConfig::Ollama { variants, .. } = config
             && let Some(variant_list) = variants
         {
             for variant in variant_list {
@@ -273,8 +1134,127 @@ impl Config {
     }
 
     fn validate_endpoint(endpoint: &EndpointConfig, index: usize, path: &Path) -> Result<()> {
+        for (header, sources) in [
+            (
+                "api_key",
+                [
+                    endpoint.api_key.is_some(),
+                    endpoint.api_key_file.is_some(),
+                    endpoint.api_key_command.is_some(),
+                ],
+            ),
+            (
+                "x_api_key",
+                [
+                    endpoint.x_api_key.is_some(),
+                    endpoint.x_api_key_file.is_some(),
+                    endpoint.x_api_key_command.is_some(),
+                ],
+            ),
+        ] {
+            if sources.iter().filter(|&&set| set).count() > 1 {
+                return Err(anyhow::anyhow!(
+                    "Endpoint {} in config file {} sets more than one of {}, {}_file and {}_command",
+                    index,
+                    path.display(),
+                    header,
+                    header,
+                    header
+                ));
+            }
+        }
+        if endpoint.max_concurrency == Some(0) {
+            return Err(anyhow::anyhow!(
+                "Endpoint {} in config file {} has max_concurrency 0",
+                index,
+                path.display()
+            ));
+        }
+        if [endpoint.input_price, endpoint.output_price]
+            .iter()
+            .flatten()
+            .any(|price| !price.is_finite() || *price < 0.0)
+        {
+            return Err(anyhow::anyhow!(
+                "Endpoint {} in config file {} has a negative token price",
+                index,
+                path.display()
+            ));
+        }
+        if let Some(proxy) = &endpoint.proxy {
+            if endpoint.no_proxy {
+                return Err(anyhow::anyhow!(
+                    "Endpoint {} in config file {} sets both proxy and no_proxy",
+                    index,
+                    path.display()
+                ));
+            }
+            match reqwest::Url::parse(proxy) {
+                Ok(url)
+                    if ["http", "https", "socks5", "socks5h"].contains(&url.scheme())
+                        && url.has_host() => {}
+                Ok(_) => {
+                    return Err(anyhow::anyhow!(
+                        "Endpoint {} in config file {} has proxy {} without an http, https, socks5 or socks5h scheme and a host",
+                        index,
+                        path.display(),
+                        proxy
+                    ));
+                }
+                Err(e) => {
+                    return Err(anyhow::anyhow!(
+                        "Endpoint {} in config file {} has a malformed proxy {}: {}",
+                        index,
+                        path.display(),
+                        proxy,
+                        e
+                    ));
+                }
+            }
+        }
+        if let EndpointTypeConfig::OpenAI {
+            deployment: Some(_),
+            api_version: None,
+            ..
+        } = &endpoint.config
+        {
+            return Err(anyhow::anyhow!(
+                "Endpoint {} in config file {} has a deployment without api_version",
+                index,
+                path.display()
+            ));
+        }
+        if let EndpointTypeConfig::OpenAI {
+            temperature, top_p, ..
+        }
+        | EndpointTypeConfig::Anthropic {
+            temperature, top_p, ..
+        } = &endpoint.config
+            && (temperature.is_some_and(|t| !t.is_finite() || t < 0.0)
+                || top_p.is_some_and(|p| !(p > 0.0 && p <= 1.0)))
+        {
+            return Err(anyhow::anyhow!(
+                "Endpoint {} in config file {} has a negative temperature or a top_p outside (0, 1]",
+                index,
+                path.display()
+            ));
+        }
+        if let EndpointTypeConfig::OpenAI {
+            models: Some(models),
+            variants,
+            ..
+        }
+        | EndpointTypeConfig::Anthropic {
+            models: Some(models),
+            variants,
+            ..
+        } = &endpoint.config
+        {
+            Self::validate_models(models, variants, endpoint, index, path)?;
+        }
         if let EndpointTypeConfig::OpenAI { variants, .. }
-        | EndpointTypeConfig::Anthropic { variants, .. } = &endpoint.config
+        | EndpointTypeConfig::Anthropic { variants, .. }
+        | EndpointTypeConfig::Ollama { variants, .. } = &endpoint.config
         {
             // Check that all variant names are unique
             Self::validate_variants(variants, index, path, &endpoint.json, &endpoint.context)?;
@@ -282,6 +1262,56 @@ impl Config {
         Ok(())
     }
 
+    fn validate_models(
+        models: &[String],
+        variants: &Option<Vec<EndpointVariants>>,
+        endpoint: &EndpointConfig,
+        index: usize,
+        path: &Path,
+    ) -> Result<()> {
+        if models.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Endpoint {} in config file {} has an empty models list",
+                index,
+                path.display()
+            ));
+        }
+        let mut seen_models = std::collections::HashSet::new();
+        for model in models {
+            if model.is_empty() || model.chars().any(|c| Self::FORBIDDEN_CHARS.contains(c)) {
+                return Err(anyhow::anyhow!(
+                    "Endpoint {} in config file {} has invalid model '{}' empty or containing {} chars",
+                    index,
+                    path.display(),
+                    model,
+                    Self::FORBIDDEN_CHARS
+                ));
+            }
+            if !seen_models.insert(model) {
+                return Err(anyhow::anyhow!(
+                    "Endpoint {} in config file {} has duplicate model '{}'",
+                    index,
+                    path.display(),
+                    model
+                ));
+            }
+        }
+        // The model of the payload comes from the models list
+        let variants_json = variants.iter().flatten().map(|variant| &variant.json);
+        if std::iter::once(&endpoint.json)
+            .chain(variants_json)
+            .flatten()
+            .any(|json| json.json.contains_key("model"))
+        {
+            return Err(anyhow::anyhow!(
+                "Endpoint {} in config file {} sets both models and a json model",
+                index,
+                path.display()
+            ));
+        }
+        Ok(())
+    }
+
     fn validate_variants(
         variants_list: &Option<Vec<EndpointVariants>>,
         endpoint_index: usize,
@@ -364,7 +1394,9 @@ impl Config {
                         j,
                         layout,
                         no_diff,
-                        no_training
+                        no_training,
+                        enclosing_symbol,
+                        system_prompt
                     );
                 }
             }
@@ -372,9 +1404,203 @@ impl Config {
         Ok(())
     }
 
+    /// The endpoints of the active profile
     pub fn get_all_endpoints(&self) -> &[EndpointConfig] {
         &self.endpoints
     }
+
+    /// The name of the active profile, if any
+    pub fn get_profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Stream the responses of all the endpoints
+    pub fn enable_stream(&mut self) {
+        self.endpoints
+            .iter_mut()
+            .for_each(|endpoint| endpoint.stream = true);
+    }
+
+    /// Override the global minimum confidence
+    pub fn set_min_confidence(&mut self, min_confidence: f64) {
+        self.min_confidence = Some(min_confidence);
+    }
+
+    pub fn set_early_abandon(&mut self) {
+        self.early_abandon = true;
+    }
+
+    pub fn get_early_abandon(&self) -> bool {
+        self.early_abandon
+    }
+
+    pub fn set_raw_logprob(&mut self) {
+        self.raw_logprob = true;
+    }
+
+    pub fn get_raw_logprob(&self) -> bool {
+        self.raw_logprob
+    }
+
+    pub fn get_confidence_base(&self) -> f64 {
+        self.confidence_base
+    }
+
+    /// Send the whole conflicted files up to this size in bytes as context
+    pub fn set_full_file_context(&mut self, max_size: usize) {
+        self.full_file_context = Some(max_size);
+    }
+
+    pub fn get_full_file_context(&self) -> Option<usize> {
+        self.full_file_context
+    }
+
+    /// Override the size of the conflict markers of all the files
+    pub fn set_marker_size(&mut self, marker_size: usize) {
+        self.marker_size = Some(marker_size);
+    }
+
+    pub fn get_marker_size(&self) -> Option<usize> {
+        self.marker_size
+    }
+
+    /// Override the seconds after which a conflict resolution is slow
+    pub fn set_slow_conflict(&mut self, seconds: f64) {
+        self.slow_conflict = seconds;
+    }
+
+    /// The seconds after which a conflict resolution is slow, if the
+    /// warning is enabled
+    pub fn get_slow_conflict(&self) -> Option<f64> {
+        Some(self.slow_conflict).filter(|seconds| *seconds > 0.0)
+    }
+
+    pub fn get_confidence_thresholds(&self) -> Result<ConfidenceThresholds> {
+        let check_range = |threshold: f64| {
+            if !(0.0..=100.0).contains(&threshold) {
+                return Err(anyhow::anyhow!(
+                    "Confidence threshold {} is not a percentage between 0 and 100",
+                    threshold
+                ));
+            }
+            Ok(threshold)
+        };
+        let global = self.min_confidence.map(check_range).transpose()?;
+        let mut scoped = Vec::new();
+        for threshold in &self.confidence_thresholds {
+            let matcher = Self::build_glob(&threshold.glob)?.compile_matcher();
+            scoped.push((matcher, check_range(threshold.min_confidence)?));
+        }
+        Ok(ConfidenceThresholds {
+            global,
+            scoped,
+            missing_logprob: self.missing_logprob,
+            confidence_base: self.confidence_base,
+        })
+    }
+
+    pub fn get_side_preferences(&self) -> Result<SidePreferences> {
+        let mut scoped = Vec::new();
+        for preference in &self.side_preferences {
+            let matcher = Self::build_glob(&preference.glob)?.compile_matcher();
+            scoped.push((matcher, preference.prefer));
+        }
+        Ok(SidePreferences { scoped })
+    }
+
+    /// The indexes of the fallback endpoints, in the fallback order
+    pub fn get_fallback(&self) -> Result<Vec<usize>> {
+        let mut fallback = Vec::new();
+        for name in &self.fallback {
+            let index = self
+                .endpoints
+                .iter()
+                .position(|endpoint| &endpoint.name == name)
+                .ok_or_else(|| anyhow::anyhow!("Fallback endpoint '{}' not found", name))?;
+            if fallback.contains(&index) {
+                return Err(anyhow::anyhow!("Fallback endpoint '{}' is repeated", name));
+            }
+            fallback.push(index);
+        }
+        if !self.endpoints.is_empty() && fallback.len() == self.endpoints.len() {
+            return Err(anyhow::anyhow!(
+                "All the endpoints are fallbacks, at least one must be queried first"
+            ));
+        }
+        Ok(fallback)
+    }
+
+    pub fn get_max_concurrency(&self) -> Option<usize> {
+        self.max_concurrency
+    }
+
+    /// The files are resolved one at a time by default
+    pub fn get_max_concurrent_files(&self) -> usize {
+        self.max_concurrent_files.unwrap_or(1)
+    }
+
+    pub fn get_eof_newline(&self) -> EofNewline {
+        self.eof_newline
+    }
+
+    pub fn set_no_diff(&mut self) {
+        self.no_diff = true;
+    }
+
+    pub fn get_no_diff(&self) -> bool {
+        self.no_diff
+    }
+
+    /// Print the prompts sent to the endpoints, with prompt_only
+    /// without sending them
+    pub fn set_print_prompt(&mut self, prompt_only: bool) {
+        self.print_prompt = true;
+        self.prompt_only = prompt_only;
+    }
+
+    pub fn get_print_prompt(&self) -> bool {
+        self.print_prompt
+    }
+
+    pub fn get_prompt_only(&self) -> bool {
+        self.prompt_only
+    }
+
+    pub fn get_agreement(&self) -> AgreementConfig {
+        self.agreement
+    }
+
+    pub fn get_assisted_by(&self) -> AssistedBy {
+        self.assisted_by
+    }
+
+    pub fn get_normalize(&self) -> Option<&NormalizeConfig> {
+        self.normalize.as_ref()
+    }
+
+    pub fn get_conflict_groups(&self) -> Result<ConflictGroups> {
+        let mut groups = Vec::new();
+        for globs in &self.conflict_groups {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(Self::build_glob(glob)?);
+            }
+            groups.push(builder.build()?);
+        }
+        Ok(ConflictGroups {
+            groups,
+            max_size: self.max_conflict_group_size,
+        })
+    }
+
+    /// Globs match paths relative to the git root and `*` doesn't
+    /// cross directories, like in gitattributes
+    fn build_glob(glob: &str) -> Result<Glob> {
+        GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob '{}'", glob))
+    }
 }
 
 #[cfg(test)]
@@ -385,7 +1611,7 @@ mod tests {
     fn test_config_loading() {
         let config_yaml = include_str!(concat!("../", env!("CARGO_PKG_NAME"), ".yaml"));
         let config: Config = serde_yaml::from_str(config_yaml).unwrap();
-        assert_eq!(config.endpoints.len(), 11);
+        assert_eq!(config.endpoints.len(), 12);
         assert_eq!(config.endpoints[0].name, "Gemini 3.1 Pro");
         assert_eq!(config.endpoints[1].name, "Claude Opus 4.6");
         assert_eq!(config.endpoints[2].name, "Vertex Claude Opus 4.6");
@@ -397,6 +1623,600 @@ mod tests {
         assert_eq!(config.endpoints[8].name, "llama.cpp vulkan minimal");
         assert_eq!(config.endpoints[9].name, "llama.cpp vulkan");
         assert_eq!(config.endpoints[10].name, "llama.cpp vulkan no_chat");
+        assert_eq!(config.endpoints[11].name, "Ollama");
+        assert!(matches!(
+            &config.endpoints[11].config,
+            EndpointTypeConfig::Ollama { model, keep_alive: Some(keep_alive), .. }
+                if model == "qwen2.5-coder:32b" && keep_alive == "10m"
+        ));
+    }
+
+    #[test]
+    fn test_confidence_thresholds() {
+        let config_yaml = r#"
+endpoints:
+  - name: "test"
+    url: "http://localhost"
+    type: "openai"
+min_confidence: 90
+confidence_thresholds:
+  - glob: "tests/**"
+    min_confidence: 50
+  - glob: "src/core/**"
+    min_confidence: 99
+  - glob: "*.md"
+    min_confidence: 0
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let thresholds = config.get_confidence_thresholds().unwrap();
+        assert_eq!(thresholds.threshold_for("tests/git/merge.rs"), Some(50.0));
+        assert_eq!(thresholds.threshold_for("src/core/apply.rs"), Some(99.0));
+        assert_eq!(thresholds.threshold_for("src/main.rs"), Some(90.0));
+        assert_eq!(thresholds.threshold_for("README.md"), Some(0.0));
+        assert_eq!(thresholds.threshold_for("docs/README.md"), Some(90.0));
+        assert_eq!(thresholds.missing_logprob, MissingLogprob::Pass);
+
+        // --min-confidence overrides the global threshold only
+        let mut config: Config =
+            serde_yaml::from_str(&format!("{}missing_logprob: fail\n", config_yaml)).unwrap();
+        config.set_min_confidence(75.0);
+        let thresholds = config.get_confidence_thresholds().unwrap();
+        assert_eq!(thresholds.threshold_for("src/main.rs"), Some(75.0));
+        assert_eq!(thresholds.threshold_for("tests/git/merge.rs"), Some(50.0));
+        assert_eq!(thresholds.missing_logprob, MissingLogprob::Fail);
+
+        let config_yaml = config_yaml.replace("min_confidence: 0\n", "min_confidence: 101\n");
+        let config: Config = serde_yaml::from_str(&config_yaml).unwrap();
+        assert!(config.get_confidence_thresholds().is_err());
+    }
+
+    #[test]
+    fn test_side_preferences() {
+        let config_yaml = r#"
+endpoints:
+  - name: "test"
+    url: "http://localhost"
+    type: "openai"
+side_preferences:
+  - glob: "Cargo.lock"
+    prefer: remote
+  - glob: "src/vendor/**"
+    prefer: local
+  - glob: "*.md"
+    prefer: base
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let preferences = config.get_side_preferences().unwrap();
+        assert_eq!(preferences.side_for("Cargo.lock"), Some(Side::Remote));
+        assert_eq!(preferences.side_for("src/vendor/a/b.rs"), Some(Side::Local));
+        assert_eq!(preferences.side_for("README.md"), Some(Side::Base));
+        assert_eq!(preferences.side_for("docs/README.md"), None);
+        assert_eq!(preferences.side_for("src/main.rs"), None);
+
+        let config_yaml = config_yaml.replace("prefer: base", "prefer: theirs");
+        assert!(serde_yaml::from_str::<Config>(&config_yaml).is_err());
+    }
+
+    #[test]
+    fn test_eof_newline() {
+        let cases = [
+            (EofNewline::Preserve, "a\nb\n", true, "a\nb\n"),
+            (EofNewline::Preserve, "a\nb\n", false, "a\nb"),
+            (EofNewline::Preserve, "a\nb", true, "a\nb\n"),
+            (EofNewline::Preserve, "a\nb", false, "a\nb"),
+            (EofNewline::Ensure, "a\nb\n", false, "a\nb\n"),
+            (EofNewline::Ensure, "a\nb", false, "a\nb\n"),
+            (EofNewline::Ensure, "", false, ""),
+            (EofNewline::Forbid, "a\nb\n", true, "a\nb"),
+            (EofNewline::Forbid, "a\r\nb\r\n", true, "a\r\nb"),
+            (EofNewline::Forbid, "a\nb", true, "a\nb"),
+        ];
+        for (policy, content, local_eof_newline, expected) in cases {
+            let mut content = content.to_string();
+            policy.apply(&mut content, local_eof_newline);
+            assert_eq!(content, expected, "{:?} {}", policy, local_eof_newline);
+        }
+
+        let config: Config = serde_yaml::from_str("endpoints: []\neof-newline: forbid\n").unwrap();
+        assert_eq!(config.get_eof_newline(), EofNewline::Forbid);
+        let config: Config = serde_yaml::from_str("endpoints: []\n").unwrap();
+        assert_eq!(config.get_eof_newline(), EofNewline::Preserve);
+        assert_eq!(config.get_assisted_by(), AssistedBy::ToolOnly);
+        let config: Config =
+            serde_yaml::from_str("endpoints: []\nassisted_by: with-models\n").unwrap();
+        assert_eq!(config.get_assisted_by(), AssistedBy::WithModels);
+    }
+
+    #[test]
+    fn test_repo_config_layering() {
+        let config_yaml = r#"
+endpoints:
+  - name: "test"
+    url: "http://localhost"
+    type: "openai"
+min_confidence: 90
+conflict_groups:
+  - ["src/**"]
+eof_newline: ensure
+"#;
+        let mut config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let git_root = tempfile::tempdir().unwrap();
+        config.load_repo_config(git_root.path()).unwrap();
+        assert_eq!(config.min_confidence, Some(90.0));
+
+        let repo_config_path = git_root
+            .path()
+            .join(concat!(".", env!("CARGO_PKG_NAME"), ".yaml"));
+        fs::write(
+            &repo_config_path,
+            "min_confidence: 50\nconfidence_thresholds:\n  - glob: \"*.md\"\n    min_confidence: 0\nno_diff: true\n",
+        )
+        .unwrap();
+        assert!(!config.get_no_diff());
+        config.load_repo_config(git_root.path()).unwrap();
+        assert!(config.get_no_diff());
+        let thresholds = config.get_confidence_thresholds().unwrap();
+        assert_eq!(thresholds.threshold_for("src/main.rs"), Some(50.0));
+        assert_eq!(thresholds.threshold_for("README.md"), Some(0.0));
+        // Fields not set in the repo config are inherited
+        assert_eq!(
+            config.get_conflict_groups().unwrap().group_of("src/a.c"),
+            Some(0)
+        );
+        assert_eq!(config.get_eof_newline(), EofNewline::Ensure);
+        assert_eq!(config.endpoints[0].url, "http://localhost");
+
+        // Endpoints and API keys are never taken from the repository
+        fs::write(
+            &repo_config_path,
+            "endpoints:\n  - name: \"evil\"\n    url: \"http://evil\"\n    type: \"openai\"\n",
+        )
+        .unwrap();
+        assert!(config.load_repo_config(git_root.path()).is_err());
+        fs::write(&repo_config_path, "api_key_file: \"/tmp/key\"\n").unwrap();
+        assert!(config.load_repo_config(git_root.path()).is_err());
+        assert_eq!(config.endpoints.len(), 1);
+
+        fs::write(&repo_config_path, "min_confidence: 101\n").unwrap();
+        assert!(config.load_repo_config(git_root.path()).is_err());
+    }
+
+    #[test]
+    fn test_repo_endpoint_overrides() {
+        let config_yaml = r#"
+endpoints:
+  - name: "test"
+    url: "http://localhost"
+    type: "openai"
+    api_key: "secret"
+    json:
+      model: "small"
+      reasoning_effort: "low"
+    context:
+      no_diff: true
+"#;
+        let mut config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let git_root = tempfile::tempdir().unwrap();
+        let repo_config_path = git_root
+            .path()
+            .join(concat!(".", env!("CARGO_PKG_NAME"), ".yaml"));
+        fs::write(
+            &repo_config_path,
+            "endpoints:\n  - name: \"test\"\n    json:\n      model: \"large\"\n    context:\n      enclosing_symbol: true\n  - name: \"unknown\"\n    json:\n      model: \"large\"\n",
+        )
+        .unwrap();
+        config.load_repo_config(git_root.path()).unwrap();
+        let endpoint = &config.endpoints[0];
+        let json = &endpoint.json.as_ref().unwrap().json;
+        assert_eq!(json["model"], "large");
+        assert_eq!(json["reasoning_effort"], "low");
+        let context = endpoint.context.as_ref().unwrap();
+        assert_eq!(context.no_diff, Some(true));
+        assert_eq!(context.enclosing_symbol, Some(true));
+        assert_eq!(endpoint.url, "http://localhost");
+        assert_eq!(endpoint.api_key.as_deref(), Some("secret"));
+
+        // Only the json keys choosing the model and its sampling
+        for json in ["stream: true", "max_tokens: 1", "logit_bias: {}"] {
+            fs::write(
+                &repo_config_path,
+                format!(
+                    "endpoints:\n  - name: \"test\"\n    json:\n      {}\n",
+                    json
+                ),
+            )
+            .unwrap();
+            assert!(config.load_repo_config(git_root.path()).is_err());
+        }
+        fs::write(
+            &repo_config_path,
+            "endpoints:\n  - name: \"test\"\n    json:\n      temperature: 0.5\n",
+        )
+        .unwrap();
+        config.load_repo_config(git_root.path()).unwrap();
+        assert_eq!(
+            config.endpoints[0].json.as_ref().unwrap().json["temperature"],
+            0.5
+        );
+
+        // The system_prompt only with the opt-in of the user
+        fs::write(
+            &repo_config_path,
+            "endpoints:\n  - name: \"test\"\n    context:\n      system_prompt: \"Prefer the remote side.\"\n",
+        )
+        .unwrap();
+        config.load_repo_config(git_root.path()).unwrap();
+        let context = config.endpoints[0].context.as_ref().unwrap();
+        assert_eq!(context.system_prompt, None);
+        config.repo_system_prompt = true;
+        config.load_repo_config(git_root.path()).unwrap();
+        let context = config.endpoints[0].context.as_ref().unwrap();
+        assert_eq!(
+            context.system_prompt.as_deref(),
+            Some("Prefer the remote side.")
+        );
+
+        // Where the code is sent and the credentials can't be overridden
+        for field in ["url: \"http://evil\"", "api_key: \"x\"", "headers: {}"] {
+            fs::write(
+                &repo_config_path,
+                format!("endpoints:\n  - name: \"test\"\n    {}\n", field),
+            )
+            .unwrap();
+            assert!(config.load_repo_config(git_root.path()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config_yaml = r#"
+endpoints:
+  - name: "common"
+    url: "http://localhost:8811/v1/chat/completions"
+    type: "openai"
+profiles:
+  default:
+    endpoints:
+      - name: "personal"
+        url: "http://localhost:11434"
+        type: "ollama"
+        model: "qwen3"
+  work:
+    endpoints:
+      - name: "work"
+        url: "https://api.anthropic.com/v1/messages"
+        type: "anthropic"
+"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("synthmerge.yaml");
+        fs::write(&path, config_yaml).unwrap();
+        let names = |config: &Config| {
+            config
+                .get_all_endpoints()
+                .iter()
+                .map(|endpoint| endpoint.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let config = Config::load_profile(&path, Some("work")).unwrap();
+        assert_eq!(names(&config), ["common", "work"]);
+        assert_eq!(config.get_profile(), Some("work"));
+        // Without SYNTHMERGE_PROFILE, whatever the environment of the test
+        let mut config = Config::load_selected_profile(&path, None).unwrap();
+        assert_eq!(names(&config), ["common", "personal"]);
+        assert!(Config::load_profile(&path, Some("missing")).is_err());
+
+        // The repository config picks the profile unless the user did
+        let repo_config_path = dir
+            .path()
+            .join(concat!(".", env!("CARGO_PKG_NAME"), ".yaml"));
+        fs::write(&repo_config_path, "profile: work\n").unwrap();
+        config.load_repo_config(dir.path()).unwrap();
+        assert_eq!(names(&config), ["common", "work"]);
+        fs::write(&repo_config_path, "profile: default\n").unwrap();
+        let mut config = Config::load_profile(&path, Some("work")).unwrap();
+        config.load_repo_config(dir.path()).unwrap();
+        assert_eq!(names(&config), ["common", "work"]);
+
+        // Without a default profile only the common endpoints are used
+        fs::write(&path, config_yaml.replace("  default:", "  personal:")).unwrap();
+        let config = Config::load_selected_profile(&path, None).unwrap();
+        assert_eq!(names(&config), ["common"]);
+        assert_eq!(config.get_profile(), None);
+    }
+
+    #[test]
+    fn test_azure_openai_endpoint() {
+        let config_yaml = r#"
+endpoints:
+  - name: "azure"
+    url: "https://example.openai.azure.com"
+    type: "openai"
+    deployment: "gpt-4o"
+    api_version: "2024-10-21"
+    api_key_file: "~/.azure-openai-key"
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let path = Path::new("synthmerge.yaml");
+        Config::validate_endpoint(&config.endpoints[0], 0, path).unwrap();
+        assert!(matches!(
+            &config.endpoints[0].config,
+            EndpointTypeConfig::OpenAI {
+                deployment: Some(_),
+                api_key_style: None,
+                ..
+            }
+        ));
+
+        let config_yaml = config_yaml.replace("    api_version: \"2024-10-21\"\n", "");
+        let config: Config = serde_yaml::from_str(&config_yaml).unwrap();
+        assert!(Config::validate_endpoint(&config.endpoints[0], 0, path).is_err());
+    }
+
+    #[test]
+    fn test_sampling_parameters() {
+        let config_yaml = r#"
+endpoints:
+  - name: "claude"
+    url: "https://api.anthropic.com/v1/messages"
+    type: "anthropic"
+    temperature: 0
+  - name: "llama.cpp"
+    url: "http://localhost:8811/v1/chat/completions"
+    type: "openai"
+    top_p: 0.8
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let path = Path::new("synthmerge.yaml");
+        Config::validate_endpoint(&config.endpoints[0], 0, path).unwrap();
+        Config::validate_endpoint(&config.endpoints[1], 1, path).unwrap();
+        assert!(matches!(
+            &config.endpoints[0].config,
+            EndpointTypeConfig::Anthropic {
+                temperature: Some(0.0),
+                top_p: None,
+                ..
+            }
+        ));
+
+        for invalid in ["temperature: -1", "top_p: 0", "top_p: 1.5"] {
+            let config_yaml = config_yaml.replace("top_p: 0.8", invalid);
+            let config: Config = serde_yaml::from_str(&config_yaml).unwrap();
+            assert!(Config::validate_endpoint(&config.endpoints[1], 1, path).is_err());
+        }
+    }
+
+    #[test]
+    fn test_models() {
+        let config_yaml = r#"
+endpoints:
+  - name: "openai"
+    url: "https://api.openai.com/v1/chat/completions"
+    type: "openai"
+    models: ["gpt-4o", "gpt-4o-mini"]
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let path = Path::new("synthmerge.yaml");
+        Config::validate_endpoint(&config.endpoints[0], 0, path).unwrap();
+
+        for invalid in [
+            "models: []",
+            "models: [\"gpt-4o\", \"gpt-4o\"]",
+            "models: [\"gpt(4o)\"]",
+            "models: [\"gpt-4o\"]\n    json:\n      model: \"gpt-4o\"",
+            "models: [\"gpt-4o\"]\n    variants:\n      - json:\n          model: \"gpt-4o\"",
+        ] {
+            let config_yaml = config_yaml.replace("models: [\"gpt-4o\", \"gpt-4o-mini\"]", invalid);
+            let config: Config = serde_yaml::from_str(&config_yaml).unwrap();
+            assert!(
+                Config::validate_endpoint(&config.endpoints[0], 0, path).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_proxy() {
+        let config_yaml = r#"
+endpoints:
+  - name: "remote"
+    url: "https://api.anthropic.com/v1/messages"
+    type: "anthropic"
+    proxy: "socks5h://proxy.example.com:1080"
+  - name: "ollama"
+    url: "http://localhost:11434"
+    type: "ollama"
+    model: "qwen3"
+    no_proxy: true
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let path = Path::new("synthmerge.yaml");
+        Config::validate_endpoint(&config.endpoints[0], 0, path).unwrap();
+        Config::validate_endpoint(&config.endpoints[1], 1, path).unwrap();
+        assert!(config.endpoints[1].no_proxy);
+
+        for invalid in [
+            "\"ftp://proxy.example.com\"",
+            "\"proxy.example.com:3128\"",
+            "\"http://[::1\"",
+        ] {
+            let config_yaml = config_yaml.replace("\"socks5h://proxy.example.com:1080\"", invalid);
+            let config: Config = serde_yaml::from_str(&config_yaml).unwrap();
+            assert!(Config::validate_endpoint(&config.endpoints[0], 0, path).is_err());
+        }
+        let config_yaml = config_yaml.replace("    no_proxy: true\n", "");
+        let config_yaml = config_yaml.replace(
+            "    proxy: \"socks5h://proxy.example.com:1080\"\n",
+            "    proxy: \"http://proxy.example.com:3128\"\n    no_proxy: true\n",
+        );
+        let config: Config = serde_yaml::from_str(&config_yaml).unwrap();
+        assert!(Config::validate_endpoint(&config.endpoints[0], 0, path).is_err());
+    }
+
+    #[test]
+    fn test_api_key_sources() {
+        let config_yaml = r#"
+endpoints:
+  - name: "remote"
+    url: "https://api.anthropic.com/v1/messages"
+    type: "anthropic"
+    x_api_key_command: "secret-tool lookup service anthropic"
+    api_key: "${ANTHROPIC_PROXY_KEY:-none}"
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let path = Path::new("synthmerge.yaml");
+        Config::validate_endpoint(&config.endpoints[0], 0, path).unwrap();
+
+        for extra in [
+            "    x_api_key: \"key\"\n",
+            "    x_api_key_file: \"~/.keys/anthropic.api-key\"\n",
+            "    api_key_command: \"pass show proxy\"\n",
+        ] {
+            let config: Config =
+                serde_yaml::from_str(&format!("{}{}", config_yaml, extra)).unwrap();
+            let err = Config::validate_endpoint(&config.endpoints[0], 0, path).unwrap_err();
+            assert!(err.to_string().contains("more than one of"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_max_concurrency() {
+        let config_yaml = r#"
+max_concurrency: 4
+max_concurrent_files: 2
+endpoints:
+  - name: "limited"
+    url: "http://localhost:8080/v1/chat/completions"
+    type: "openai"
+    max_concurrency: 1
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let path = Path::new("synthmerge.yaml");
+        config.validate(path).unwrap();
+        Config::validate_endpoint(&config.endpoints[0], 0, path).unwrap();
+        assert_eq!(config.get_max_concurrency(), Some(4));
+        assert_eq!(config.endpoints[0].max_concurrency, Some(1));
+        assert_eq!(config.get_max_concurrent_files(), 2);
+
+        let config: Config =
+            serde_yaml::from_str(&config_yaml.replace("concurrency: 1", "concurrency: 0")).unwrap();
+        assert!(Config::validate_endpoint(&config.endpoints[0], 0, path).is_err());
+        let config: Config =
+            serde_yaml::from_str(&config_yaml.replace("concurrency: 4", "concurrency: 0")).unwrap();
+        assert!(config.validate(path).is_err());
+        let config: Config =
+            serde_yaml::from_str(&config_yaml.replace("files: 2", "files: 0")).unwrap();
+        assert!(config.validate(path).is_err());
+        let config: Config =
+            serde_yaml::from_str(&config_yaml.replace("max_concurrent_files: 2\n", "")).unwrap();
+        assert_eq!(config.get_max_concurrent_files(), 1);
+    }
+
+    #[test]
+    fn test_marker_size() {
+        let path = Path::new("synthmerge.yaml");
+        let config: Config = serde_yaml::from_str("endpoints: []\nmarker_size: 9\n").unwrap();
+        config.validate(path).unwrap();
+        assert_eq!(config.get_marker_size(), Some(9));
+        let config: Config = serde_yaml::from_str("endpoints: []\nmarker_size: 5\n").unwrap();
+        assert!(config.validate(path).is_err());
+    }
+
+    #[test]
+    fn test_confidence_base() {
+        let path = Path::new("synthmerge.yaml");
+        let config: Config = serde_yaml::from_str("endpoints: []\n").unwrap();
+        assert_eq!(config.confidence_base, prob::DEFAULT_CONFIDENCE_BASE);
+        let config: Config =
+            serde_yaml::from_str("endpoints: []\nconfidence_base: 2.718281828\n").unwrap();
+        config.validate(path).unwrap();
+        for base in ["1", "0.5", "-3"] {
+            let config: Config =
+                serde_yaml::from_str(&format!("endpoints: []\nconfidence_base: {}\n", base))
+                    .unwrap();
+            assert!(config.validate(path).is_err(), "{}", base);
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let config_yaml = r#"
+fallback: ["second", "first"]
+endpoints:
+  - name: "main"
+    url: "http://localhost:8080/v1/chat/completions"
+    type: "openai"
+  - name: "first"
+    url: "http://localhost:8081/v1/chat/completions"
+    type: "openai"
+  - name: "second"
+    url: "http://localhost:8082/v1/chat/completions"
+    type: "openai"
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let path = Path::new("synthmerge.yaml");
+        config.validate(path).unwrap();
+        assert_eq!(config.get_fallback().unwrap(), vec![2, 1]);
+
+        for fallback in [
+            r#"["missing"]"#,
+            r#"["first", "first"]"#,
+            r#"["main", "first", "second"]"#,
+        ] {
+            let config: Config =
+                serde_yaml::from_str(&config_yaml.replace(r#"["second", "first"]"#, fallback))
+                    .unwrap();
+            assert!(config.validate(path).is_err(), "{}", fallback);
+        }
+    }
+
+    #[test]
+    fn test_interpolate_env() {
+        // cargo sets CARGO_PKG_NAME when running the tests
+        let name = env!("CARGO_PKG_NAME");
+        assert_eq!(
+            Config::interpolate_env("${CARGO_PKG_NAME}-key", "api_key").unwrap(),
+            format!("{}-key", name)
+        );
+        assert_eq!(
+            Config::interpolate_env("${CARGO_PKG_NAME:-x}", "api_key").unwrap(),
+            name
+        );
+        assert_eq!(
+            Config::interpolate_env(
+                "http://${SYNTHMERGE_TEST_UNSET_HOST:-localhost}:8080/${SYNTHMERGE_TEST_UNSET_PATH:-}",
+                "url"
+            )
+            .unwrap(),
+            "http://localhost:8080/"
+        );
+        assert_eq!(Config::interpolate_env("$HOME", "url").unwrap(), "$HOME");
+        let err = Config::interpolate_env("${SYNTHMERGE_TEST_UNSET}", "endpoints[0].api_key")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("SYNTHMERGE_TEST_UNSET"));
+        assert!(err.contains("endpoints[0].api_key"));
+    }
+
+    #[test]
+    fn test_conflict_groups() {
+        let config_yaml = r#"
+endpoints:
+  - name: "test"
+    url: "http://localhost"
+    type: "openai"
+conflict_groups:
+  - ["include/foo.h", "src/foo/*.c"]
+  - ["src/**"]
+"#;
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let groups = config.get_conflict_groups().unwrap();
+        assert_eq!(groups.max_size, default_max_conflict_group_size());
+        assert_eq!(groups.group_of("include/foo.h"), Some(0));
+        assert_eq!(groups.group_of("src/foo/foo.c"), Some(0));
+        assert_eq!(groups.group_of("src/foo/bar/foo.c"), Some(1));
+        assert_eq!(groups.group_of("include/bar.h"), None);
     }
 }
 
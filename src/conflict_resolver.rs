@@ -1,18 +1,32 @@
 // SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
 // Copyright (C) 2025-2026  Red Hat, Inc.
 
-use crate::api_client::{ApiClient, ApiRequest, ApiResponse};
-use crate::config::{Config, EndpointConfig, EndpointTypeConfig};
+use crate::api_client::{
+    ApiClient, ApiRequest, ApiRequestError, ApiResponse, ApiResponseEntry, CommandApiKeys,
+};
+use crate::config::{
+    ConfidenceThresholds, Config, EndpointConfig, EndpointTypeConfig, Side, SidePreferences,
+};
+use crate::error::SynthmergeError;
 use crate::lmdb_cache::{ApiCache, LmdbCacheImpl};
+use crate::logger;
+use crate::normalize::Normalizer;
 use crate::patch_locator::Hunk;
 use crate::prob;
 use anyhow::Result;
+use futures::StreamExt;
 use futures::future::select_all;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::sync::mpsc::UnboundedSender;
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CommitType {
     Clean,
     CleanDisabled,
@@ -21,6 +35,19 @@ pub enum CommitType {
     Conflict,
 }
 
+/// Whether a conflict is in the content of the file, or one side
+/// deleted the file the other side modified
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    #[default]
+    Content,
+    /// The local side deleted the file, the conflict has no markers
+    DeletedByUs,
+    /// The remote side deleted the file, the conflict has no markers
+    DeletedByThem,
+}
+
 impl CommitType {
     pub fn is_clean(&self) -> bool {
         matches!(self, CommitType::Clean | CommitType::CleanDisabled)
@@ -47,12 +74,21 @@ impl std::ops::Add for CommitType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
 pub struct Conflict {
     pub file_path: String,
+    /// The path the remote side renamed the file from
+    pub renamed_from: Option<String>,
+    pub kind: ConflictKind,
     pub conflict_code: String,
+    /// The base and remote sections of the conflict markers
+    pub base: String,
+    pub remote: String,
     pub head_context: String,
     pub tail_context: String,
+    /// The signature of the function or class enclosing the conflict,
+    /// empty if the language isn't supported or none was found
+    pub enclosing_symbol: String,
     pub conflict_patch: String,
     pub conflict_raw_patch: Option<String>,
     pub start_line: usize,
@@ -68,9 +104,20 @@ pub struct Conflict {
     pub nr_head_context_lines: usize,
     pub nr_tail_context_lines: usize,
     pub marker_size: usize,
+    /// The conflict already has the resolutions of a previous run
+    pub has_ai_block: bool,
     pub commit_type: CommitType,
+    /// The diff of the commit being applied, the prompt includes it as
+    /// the commit context. For a merge, the diffs of all the merged
+    /// heads, see [`GitUtils::find_commit_hashes`](crate::GitUtils::find_commit_hashes).
+    /// None with no_diff or without an operation in progress.
+    #[serde(skip)]
+    pub commit_context: Option<Arc<String>>,
+    #[serde(skip)]
     pub merged_local_lines: Arc<Vec<String>>,
+    #[serde(skip)]
     pub code_snippets: Arc<Vec<Snippet>>,
+    #[serde(skip)]
     pub hunks: Vec<Hunk>,
 }
 
@@ -78,9 +125,18 @@ pub struct Conflict {
 pub struct ResolvedConflict {
     pub conflict: Conflict,
     pub resolved_version: String,
+    /// The model, or the combined names of the models that agree after
+    /// the deduplication
     pub model: String,
+    /// The seconds of the request, the slowest model after the
+    /// deduplication
     pub duration: f64,
+    /// The tokens of the request, the sum over the agreeing models after
+    /// the deduplication
     pub total_tokens: Option<u64>,
+    pub input_tokens: Option<u64>,
+    /// The log probability of the resolution, the highest of the
+    /// agreeing models after the deduplication
     pub logprob: Option<f64>,
     pub deduplicated_conflicts: Vec<ResolvedConflict>,
     pub endpoint: usize,
@@ -88,9 +144,107 @@ pub struct ResolvedConflict {
     pub beam: Option<usize>,
 }
 
+impl ResolvedConflict {
+    /// An alternative completion branched off by the perplexity search,
+    /// or a beam of Patchpal after the first one. After the
+    /// deduplication, only if none of the agreeing models found it
+    /// with its first completion.
+    pub fn is_alternative(&self) -> bool {
+        if self.deduplicated_conflicts.is_empty() {
+            self.beam.is_some_and(|beam| beam > 0)
+        } else {
+            self.deduplicated_conflicts
+                .iter()
+                .all(ResolvedConflict::is_alternative)
+        }
+    }
+}
+
+/// Whether the file of a conflict is retried, accumulated over the
+/// results of the endpoints as they arrive
+struct RetryState {
+    /// An endpoint, not primary or primary, failed in a way worth a retry
+    recoverable: [bool; 2],
+    no_solutions: bool,
+}
+
+impl Default for RetryState {
+    fn default() -> Self {
+        RetryState {
+            recoverable: [false, false],
+            no_solutions: true,
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct ResolverErrors {
     pub errors: HashMap<String, usize>,
     pub retry_files: HashSet<String>,
+    /// The first request that failed, with the endpoint and HTTP status
+    pub api_error: Option<SynthmergeError>,
+}
+
+impl ResolverErrors {
+    /// Add the errors of the conflicts resolved separately
+    fn merge(&mut self, other: ResolverErrors) {
+        for (error, count) in other.errors {
+            *self.errors.entry(error).or_default() += count;
+        }
+        self.retry_files.extend(other.retry_files);
+        if self.api_error.is_none() {
+            self.api_error = other.api_error;
+        }
+    }
+}
+
+/// The limits of a whole run, shared by all its resolvers
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    /// No conflict is sent to the endpoints after the deadline and the
+    /// requests in flight are cancelled
+    deadline: Option<Instant>,
+    /// No request is sent to the endpoints once the total tokens of the
+    /// responses reached it, the requests in flight complete
+    max_tokens: Option<u64>,
+    tokens: Arc<AtomicU64>,
+}
+
+impl Budget {
+    pub fn new(deadline: Option<Instant>, max_tokens: Option<u64>) -> Self {
+        Budget {
+            deadline,
+            max_tokens,
+            tokens: Arc::default(),
+        }
+    }
+
+    /// The --time-budget deadline, if any
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// The total tokens of the responses so far
+    pub fn tokens(&self) -> u64 {
+        self.tokens.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_tokens(&self, tokens: u64) {
+        let total = self.tokens.fetch_add(tokens, Ordering::Relaxed) + tokens;
+        if let Some(max_tokens) = self.max_tokens {
+            log::info!("Used {} of the {} tokens of the budget", total, max_tokens);
+        }
+    }
+
+    fn out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub(crate) fn out_of_tokens(&self) -> bool {
+        self.max_tokens
+            .is_some_and(|max_tokens| self.tokens() >= max_tokens)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -102,11 +256,28 @@ pub struct Snippet {
 
 pub struct ConflictResolver<'a> {
     config: &'a Config,
-    git_diff: Option<String>,
     bench: bool,
     start_regex: Regex,
     end_regex: Regex,
     lmdb_cache: Option<Arc<LmdbCacheImpl>>,
+    normalizer: Option<Normalizer>,
+    /// The concurrency limits of each endpoint, the endpoint limit
+    /// comes before the global limit
+    semaphores: Vec<Vec<Arc<Semaphore>>>,
+    /// The output of the API key commands of each endpoint
+    command_api_keys: Vec<CommandApiKeys>,
+    /// The confidence thresholds the streamed responses are abandoned
+    /// below, with early_abandon
+    abandon_thresholds: Option<ConfidenceThresholds>,
+    /// The time and token budget of the run
+    budget: Budget,
+    /// The endpoints queried in order only for the conflicts left
+    /// without a resolution, the bench queries all the endpoints
+    fallback: Vec<usize>,
+    /// The side the prompt tells the models to favor, by file path
+    side_preferences: SidePreferences,
+    /// Receives each resolution as soon as its endpoint answered
+    pub(crate) sender: Option<UnboundedSender<ResolvedConflict>>,
 }
 
 impl<'a> ConflictResolver<'a> {
@@ -123,13 +294,14 @@ impl<'a> ConflictResolver<'a> {
     const CODE_SNIPPETS_END: &'static str = "<|/code_snippets|>";
     const CODE_SNIPPET_START: &'static str = "<|code_snippet|>";
     const CODE_SNIPPET_END: &'static str = "<|/code_snippet|>";
+    const FILE_START: &'static str = "<|file|>";
+    const FILE_END: &'static str = "<|/file|>";
     const REGEXP_PATCHED_CODE_START: &'static str =
         r"(?ms)^(?:```)?[{<|]{1,3}patched_code[|>}]{1,3}$\n";
     const REGEXP_PATCHED_CODE_END: &'static str =
         r"(?ms)^[{<|/]{1,4}patched_code[|>}]{1,3}(?:```)?$";
     pub fn new(
         config: &'a Config,
-        git_diff: Option<String>,
         bench: bool,
         cache_path: Option<String>,
         cache_overwrite: bool,
@@ -141,16 +313,119 @@ impl<'a> ConflictResolver<'a> {
                     .expect("Failed to create API cache"),
             ));
         }
+        let normalizer = config.get_normalize().and_then(Normalizer::new);
+        let global_semaphore = config
+            .get_max_concurrency()
+            .map(|permits| Arc::new(Semaphore::new(permits)));
+        let semaphores = config
+            .get_all_endpoints()
+            .iter()
+            .map(|endpoint| {
+                endpoint
+                    .max_concurrency
+                    .map(|permits| Arc::new(Semaphore::new(permits)))
+                    .into_iter()
+                    .chain(global_semaphore.clone())
+                    .collect()
+            })
+            .collect();
         ConflictResolver {
             config,
-            git_diff,
             bench,
             start_regex: Regex::new(Self::REGEXP_PATCHED_CODE_START).unwrap(),
             end_regex: Regex::new(Self::REGEXP_PATCHED_CODE_END).unwrap(),
             lmdb_cache,
+            normalizer,
+            semaphores,
+            command_api_keys: config
+                .get_all_endpoints()
+                .iter()
+                .map(|_| CommandApiKeys::default())
+                .collect(),
+            abandon_thresholds: if config.get_early_abandon() && !bench {
+                // Validated when the config was loaded
+                config.get_confidence_thresholds().ok()
+            } else {
+                None
+            },
+            budget: Budget::default(),
+            // Validated when the config was loaded
+            fallback: if bench {
+                Vec::new()
+            } else {
+                config.get_fallback().unwrap_or_default()
+            },
+            side_preferences: config.get_side_preferences().unwrap_or_default(),
+            sender: None,
+        }
+    }
+
+    /// Stop resolving the conflicts at the deadline or once the tokens
+    /// of the budget are used up, the conflicts left are unresolved
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = budget;
+    }
+
+    /// Send the new resolutions to the channel, if any
+    fn send(&self, resolved_conflicts: &[ResolvedConflict]) {
+        if let Some(sender) = &self.sender {
+            for resolved in resolved_conflicts {
+                // The receiver may stop listening, the resolutions are
+                // still returned
+                let _ = sender.send(resolved.clone());
+            }
         }
     }
 
+    fn over_budget(&self) -> bool {
+        self.budget.out_of_time() || self.budget.out_of_tokens()
+    }
+
+    fn api_client(
+        &self,
+        endpoint_index: usize,
+        endpoint: &EndpointConfig,
+        conflicts: &[&Conflict],
+    ) -> ApiClient {
+        let mut client = ApiClient::new(
+            endpoint.clone(),
+            self.lmdb_cache.clone(),
+            self.semaphores[endpoint_index].clone(),
+        );
+        client.set_command_api_keys(self.command_api_keys[endpoint_index].clone());
+        client.set_budget(self.budget.clone());
+        if let Some(logprob) = self.abandon_below(conflicts) {
+            client.set_abandon_below(logprob);
+        }
+        client
+    }
+
+    /// The logprob the streamed responses of the conflicts are
+    /// abandoned below, from the lowest threshold of their paths. None
+    /// if any of them has no threshold.
+    fn abandon_below(&self, conflicts: &[&Conflict]) -> Option<f64> {
+        let thresholds = self.abandon_thresholds.as_ref()?;
+        let threshold = conflicts
+            .iter()
+            .map(|conflict| thresholds.threshold_for(&conflict.file_path))
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .reduce(f64::min)?;
+        Some(prob::confidence_to_logprob(
+            threshold,
+            self.config.get_confidence_base(),
+        ))
+    }
+
+    /// Check the config of the endpoint, with ping also query it with a
+    /// tiny request to confirm it's reachable and the authentication works
+    pub async fn check_endpoint(endpoint: &EndpointConfig, ping: bool) -> Result<()> {
+        ApiClient::create_client(endpoint)?;
+        ApiClient::new(endpoint.clone(), None, Vec::new())
+            .check(ping)
+            .await
+    }
+
     /// Resolve all conflicts using AI
     pub async fn resolve_conflicts(
         self,
@@ -159,11 +434,14 @@ impl<'a> ConflictResolver<'a> {
     ) -> Result<(Vec<ResolvedConflict>, ResolverErrors)> {
         let config = &self.config;
         let endpoints = config.get_all_endpoints();
+        let conflict_groups = config.get_conflict_groups()?;
+        let mut pending_groups: BTreeMap<usize, Vec<&Conflict>> = BTreeMap::new();
+        // The conflicts of each file, in the order of the files
+        let mut pending_files: Vec<Vec<(usize, &Conflict)>> = Vec::new();
+        let mut file_slots: HashMap<&str, usize> = HashMap::new();
         let mut resolved_conflicts = Vec::new();
-        let mut resolver_errors = ResolverErrors {
-            errors: HashMap::new(),
-            retry_files: HashSet::new(),
-        };
+        let mut resolver_errors = ResolverErrors::default();
+        let mut over_budget = 0;
 
         for (conflict_index, conflict) in conflicts.iter().enumerate() {
             // Check if we have a previous resolved conflict that matches this one
@@ -176,6 +454,7 @@ impl<'a> ConflictResolver<'a> {
                         && prev_conflict.endpoint == endpoint_index
                     {
                         resolved_conflicts.push(prev_conflict.clone());
+                        self.send(std::slice::from_ref(prev_conflict));
                         skip_ai_resolution = true;
                         break;
                     }
@@ -196,81 +475,673 @@ impl<'a> ConflictResolver<'a> {
                 log::info!("{}", conflict_info);
                 continue;
             }
-            if !self.bench {
-                let conflict_info = format!(
-                    "Resolving conflict {} of {} in {}:{}->{}",
-                    conflict_index + 1,
+            // Conflicts in grouped files are resolved together later
+            if conflict.commit_type.is_conflict()
+                && let Some(group) = conflict_groups.group_of(&conflict.file_path)
+            {
+                pending_groups.entry(group).or_default().push(conflict);
+                continue;
+            }
+            let slot = *file_slots.entry(&conflict.file_path).or_insert_with(|| {
+                pending_files.push(Vec::new());
+                pending_files.len() - 1
+            });
+            pending_files[slot].push((conflict_index, conflict));
+        }
+
+        // The files are resolved concurrently, each one a conflict at a
+        // time
+        let files: Vec<_> = pending_files
+            .into_iter()
+            .map(|file_conflicts| {
+                self.resolve_file_conflicts(file_conflicts, conflicts.len(), endpoints)
+            })
+            .collect();
+        let mut files = futures::stream::iter(files).buffered(config.get_max_concurrent_files());
+        while let Some(result) = files.next().await {
+            let (file_resolved, file_errors, file_over_budget) = result?;
+            resolved_conflicts.extend(file_resolved);
+            resolver_errors.merge(file_errors);
+            over_budget += file_over_budget;
+        }
+
+        for (_, group) in pending_groups {
+            if self.over_budget() {
+                over_budget += group.len();
+                continue;
+            }
+            let group_size: usize = group
+                .iter()
+                .map(|c| {
+                    c.head_context.len()
+                        + c.conflict_code.len()
+                        + c.tail_context.len()
+                        + c.conflict_patch.len()
+                })
+                .sum::<usize>()
+                + self.file_context_size(&group);
+            if group.len() > 1 && group_size <= conflict_groups.max_size {
+                self.resolve_conflict_group(
+                    &mut resolved_conflicts,
+                    &mut resolver_errors,
+                    &group,
+                    endpoints,
+                )
+                .await?;
+                continue;
+            }
+            if group.len() > 1 {
+                log::info!(
+                    "Conflict group of {} conflicts is {} bytes, resolving each conflict separately",
+                    group.len(),
+                    group_size
+                );
+            }
+            for conflict in group {
+                if self.over_budget() {
+                    over_budget += 1;
+                    continue;
+                }
+                let conflict_index = conflicts.iter().position(|c| c == conflict).unwrap();
+                self.resolve_conflict(
+                    &mut resolved_conflicts,
+                    &mut resolver_errors,
+                    conflict,
+                    conflict_index,
                     conflicts.len(),
+                    endpoints,
+                )
+                .await?;
+            }
+        }
+
+        if over_budget > 0 {
+            log::warn!(
+                "{} budget exceeded, {} conflicts left unresolved",
+                if self.budget.out_of_time() {
+                    "Time"
+                } else {
+                    "Token"
+                },
+                over_budget
+            );
+        }
+
+        // The files and the endpoints complete in any order, the
+        // resolutions are sorted so the result is reproducible
+        Self::sort_resolutions(conflicts, &mut resolved_conflicts);
+
+        Ok((resolved_conflicts, resolver_errors))
+    }
+
+    /// Resolve the conflicts of a file one at a time. Returns the number
+    /// of conflicts left unresolved by the budget with the resolutions.
+    async fn resolve_file_conflicts(
+        &self,
+        file_conflicts: Vec<(usize, &Conflict)>,
+        nr_conflicts: usize,
+        endpoints: &[EndpointConfig],
+    ) -> Result<(Vec<ResolvedConflict>, ResolverErrors, usize)> {
+        let mut resolved_conflicts = Vec::new();
+        let mut resolver_errors = ResolverErrors::default();
+        let mut over_budget = 0;
+        for (conflict_index, conflict) in file_conflicts {
+            if self.over_budget() {
+                over_budget += 1;
+                continue;
+            }
+            self.resolve_conflict(
+                &mut resolved_conflicts,
+                &mut resolver_errors,
+                conflict,
+                conflict_index,
+                nr_conflicts,
+                endpoints,
+            )
+            .await?;
+        }
+        Ok((resolved_conflicts, resolver_errors, over_budget))
+    }
+
+    /// Sort the resolutions in the order of their conflicts, then of
+    /// the endpoints in the config, instead of the order they completed
+    fn sort_resolutions(conflicts: &[Conflict], resolved_conflicts: &mut [ResolvedConflict]) {
+        let positions: HashMap<(&str, usize), usize> = conflicts
+            .iter()
+            .enumerate()
+            .map(|(index, c)| ((c.file_path.as_str(), c.local_start), index))
+            .collect();
+        let position = |resolved: &ResolvedConflict| {
+            positions
+                .get(&(
+                    resolved.conflict.file_path.as_str(),
+                    resolved.conflict.local_start,
+                ))
+                .copied()
+                .unwrap_or(usize::MAX)
+        };
+        resolved_conflicts.sort_by(|a, b| {
+            (position(a), a.endpoint, &a.model, a.multi, a.beam).cmp(&(
+                position(b),
+                b.endpoint,
+                &b.model,
+                b.multi,
+                b.beam,
+            ))
+        });
+    }
+
+    /// Resolve a single conflict with all endpoints in parallel
+    async fn resolve_conflict(
+        &self,
+        resolved_conflicts: &mut Vec<ResolvedConflict>,
+        resolver_errors: &mut ResolverErrors,
+        conflict: &Conflict,
+        conflict_index: usize,
+        nr_conflicts: usize,
+        endpoints: &[EndpointConfig],
+    ) -> Result<()> {
+        if !self.bench {
+            let conflict_info = format!(
+                "Resolving conflict {} of {} in {}:{}->{}",
+                conflict_index + 1,
+                nr_conflicts,
+                conflict.file_path,
+                conflict.start_line,
+                conflict.local_start
+            );
+            // Logged at info level also when printed
+            if !logger::is_quiet() {
+                logger::progress!("{}", conflict_info);
+            }
+            log::info!("{}", conflict_info);
+        }
+
+        let normalized = self.normalize_conflict(conflict);
+        let first = resolved_conflicts.len();
+        // Each result is processed as soon as its endpoint answered
+        let mut retry = RetryState::default();
+        self.query_conflict(
+            &normalized,
+            endpoints,
+            |index, _| !self.fallback.contains(&index),
+            |result| {
+                self.process_normalized_results(
+                    resolved_conflicts,
+                    resolver_errors,
+                    &[result],
+                    conflict,
+                    &normalized,
+                    endpoints,
+                    &mut retry,
+                )
+            },
+        )
+        .await?;
+        Self::retry_unresolved(resolver_errors, conflict, &retry);
+        if resolved_conflicts.len() == first {
+            self.resolve_fallback(
+                resolved_conflicts,
+                resolver_errors,
+                conflict,
+                &normalized,
+                endpoints,
+            )
+            .await?;
+        }
+        self.report_duration(
+            &format!("conflict {}:{}", conflict.file_path, conflict.start_line),
+            &resolved_conflicts[first..],
+        );
+
+        Ok(())
+    }
+
+    /// Query the fallback endpoints in order for a conflict the other
+    /// endpoints produced no resolution for, until one resolves it
+    async fn resolve_fallback(
+        &self,
+        resolved_conflicts: &mut Vec<ResolvedConflict>,
+        resolver_errors: &mut ResolverErrors,
+        conflict: &Conflict,
+        normalized: &Conflict,
+        endpoints: &[EndpointConfig],
+    ) -> Result<()> {
+        for &fallback in &self.fallback {
+            if self.over_budget() {
+                break;
+            }
+            let name = &endpoints[fallback].name;
+            log::info!(
+                "No resolution for {}:{}, falling back to {}",
+                conflict.file_path,
+                conflict.start_line,
+                name
+            );
+            let first = resolved_conflicts.len();
+            let mut retry = RetryState::default();
+            self.query_conflict(
+                normalized,
+                endpoints,
+                |index, _| index == fallback,
+                |result| {
+                    self.process_normalized_results(
+                        resolved_conflicts,
+                        resolver_errors,
+                        &[result],
+                        conflict,
+                        normalized,
+                        endpoints,
+                        &mut retry,
+                    )
+                },
+            )
+            .await?;
+            Self::retry_unresolved(resolver_errors, conflict, &retry);
+            if resolved_conflicts.len() > first {
+                logger::status!(
+                    "Resolved conflict {}:{} with the fallback endpoint {}",
                     conflict.file_path,
                     conflict.start_line,
-                    conflict.local_start
+                    name
                 );
-                println!("{}", conflict_info);
-                log::info!("{}", conflict_info);
+                break;
             }
+        }
+        Ok(())
+    }
 
-            // Create the prompt for AI resolution
-            let prompt = self.create_prompt(conflict);
-            let patch = conflict.conflict_patch.clone();
-            let code = format!(
-                "{}{}{}",
-                conflict.head_context, conflict.conflict_code, conflict.tail_context
+    /// Print how long the slowest model took to resolve the conflict
+    /// and warn if it's over the slow_conflict threshold
+    fn report_duration(&self, label: &str, resolved: &[ResolvedConflict]) {
+        if self.bench {
+            return;
+        }
+        let Some(duration) = resolved
+            .iter()
+            .map(|resolved| resolved.duration)
+            .max_by(f64::total_cmp)
+        else {
+            return;
+        };
+        logger::status!("Resolved {} in {:.1}s", label, duration);
+        if let Some(threshold) = self.config.get_slow_conflict()
+            && duration > threshold
+        {
+            log::warn!(
+                "Resolving {} took {:.1}s, more than the slow_conflict threshold of {}s, consider resolving it by hand",
+                label,
+                duration,
+                threshold
             );
+        }
+    }
 
-            // Try to resolve with all endpoints in parallel
-            let mut futures = Vec::new();
-            for (endpoint_index, endpoint) in endpoints.iter().enumerate() {
-                if conflict.commit_type == CommitType::Clean && !endpoint.primary {
-                    continue;
-                }
-                let client = ApiClient::new(endpoint.clone(), self.lmdb_cache.clone());
-                let name = endpoint.name.clone();
-                let use_backticks = endpoint.use_backticks;
-                let message = self.create_message(&patch, &code, use_backticks);
-                let git_diff = self.create_git_diff(conflict, use_backticks);
-                let training = Self::create_training(use_backticks);
-                let api_request = ApiRequest {
-                    prompt: prompt.clone(),
-                    training,
-                    message,
-                    patch: patch.clone(),
-                    code: code.clone(),
-                    git_diff,
-                };
-                let handle = tokio::spawn(async move {
-                    let result = client.query(&api_request).await;
-                    (result, name, endpoint_index)
-                });
-                futures.push(handle);
-            }
-
-            let mut results = Vec::new();
-            while !futures.is_empty() {
-                let (result, _, remaining) = select_all(futures).await;
-                futures = remaining;
-                match result {
-                    Ok((result, name, endpoint_index)) => {
-                        println!(
-                            " - {}{}",
-                            name,
-                            self.print_api_response(&result, endpoints, endpoint_index)
-                        );
-                        results.push((result, endpoint_index))
-                    }
-                    Err(e) => return Err(anyhow::anyhow!("Task failed: {}", e)),
+    /// The conflict as presented to the models
+    fn normalize_conflict(&self, conflict: &Conflict) -> Conflict {
+        match &self.normalizer {
+            Some(normalizer) => normalizer.normalize_conflict(conflict),
+            None => conflict.clone(),
+        }
+    }
+
+    /// Process the results of a normalized conflict, mapping the
+    /// resolutions back to the formatting of the original conflict
+    #[allow(clippy::too_many_arguments)]
+    fn process_normalized_results(
+        &self,
+        resolved_conflicts: &mut Vec<ResolvedConflict>,
+        resolver_errors: &mut ResolverErrors,
+        results: &[(Result<ApiResponse>, usize)],
+        conflict: &Conflict,
+        normalized: &Conflict,
+        endpoints: &[EndpointConfig],
+        retry: &mut RetryState,
+    ) {
+        let first = resolved_conflicts.len();
+        self.process_results(
+            resolved_conflicts,
+            resolver_errors,
+            results,
+            normalized,
+            endpoints,
+            retry,
+        );
+        if let Some(normalizer) = &self.normalizer {
+            for resolved in &mut resolved_conflicts[first..] {
+                resolved.resolved_version =
+                    normalizer.denormalize(&resolved.resolved_version, conflict);
+                resolved.conflict = conflict.clone();
+            }
+        }
+        self.send(&resolved_conflicts[first..]);
+    }
+
+    /// Query the endpoints selected by the filter in parallel for a
+    /// single conflict
+    async fn query_conflict(
+        &self,
+        conflict: &Conflict,
+        endpoints: &[EndpointConfig],
+        endpoint_filter: impl Fn(usize, &EndpointConfig) -> bool,
+        on_result: impl FnMut((Result<ApiResponse>, usize)),
+    ) -> Result<()> {
+        // Create the prompt for AI resolution
+        let prompt = self.create_prompt(conflict);
+        let patch = conflict.conflict_patch.clone();
+        let code = format!(
+            "{}{}{}",
+            conflict.head_context, conflict.conflict_code, conflict.tail_context
+        );
+
+        // Try to resolve with all endpoints in parallel
+        let mut futures = Vec::new();
+        for (endpoint_index, endpoint) in endpoints.iter().enumerate() {
+            if conflict.commit_type == CommitType::Clean && !endpoint.primary {
+                continue;
+            }
+            if !endpoint_filter(endpoint_index, endpoint) {
+                continue;
+            }
+            let client = self.api_client(endpoint_index, endpoint, &[conflict]);
+            let name = endpoint.name.clone();
+            let use_backticks = endpoint.use_backticks;
+            let message = self.create_message(&patch, &code, use_backticks);
+            let git_diff = self.create_git_diff(conflict, use_backticks);
+            let training = Self::create_training(use_backticks);
+            let api_request = ApiRequest {
+                prompt: prompt.clone(),
+                training,
+                message,
+                patch: patch.clone(),
+                code: code.clone(),
+                git_diff,
+                enclosing_symbol: Self::enclosing_symbol_context(&[conflict]),
+                file_context: self.file_context(&[conflict], use_backticks),
+            };
+            if self.config.get_print_prompt() {
+                Self::print_prompt(&client, &api_request, &name);
+            }
+            if self.config.get_prompt_only() {
+                continue;
+            }
+            if self.budget.out_of_tokens() {
+                log::info!("Token budget exceeded, not querying {}", name);
+                continue;
+            }
+            futures.push(self.spawn_query(client, api_request, name, endpoint_index));
+        }
+
+        self.collect_results(futures, endpoints, on_result).await
+    }
+
+    /// Print the prompts of the request as sent to the endpoint
+    fn print_prompt(client: &ApiClient, api_request: &ApiRequest, name: &str) {
+        for (variant, prompt) in client.prompts(api_request) {
+            let name = match variant {
+                Some(variant) => format!("{} ({})", name, variant),
+                None => name.to_string(),
+            };
+            logger::progress!(
+                "=== Prompt of {} ===\n{}\n=== End of the prompt of {} ===",
+                name,
+                prompt,
+                name
+            );
+        }
+    }
+
+    /// Query an endpoint in a task. The query is cancelled if it's
+    /// still in flight when the time budget runs out.
+    fn spawn_query(
+        &self,
+        client: ApiClient,
+        api_request: ApiRequest,
+        name: String,
+        endpoint_index: usize,
+    ) -> tokio::task::JoinHandle<(Result<ApiResponse>, String, usize)> {
+        let deadline = self.budget.deadline;
+        tokio::spawn(async move {
+            let query = client.query(&api_request);
+            let result = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline.into(), query)
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Cancelled by the time budget"))),
+                None => query.await,
+            };
+            (result, name, endpoint_index)
+        })
+    }
+
+    /// Pass the result of each endpoint to on_result as soon as it
+    /// answered
+    async fn collect_results(
+        &self,
+        mut futures: Vec<tokio::task::JoinHandle<(Result<ApiResponse>, String, usize)>>,
+        endpoints: &[EndpointConfig],
+        mut on_result: impl FnMut((Result<ApiResponse>, usize)),
+    ) -> Result<()> {
+        while !futures.is_empty() {
+            let (result, _, remaining) = select_all(futures).await;
+            futures = remaining;
+            match result {
+                Ok((result, name, endpoint_index)) => {
+                    logger::status!(
+                        " - {}{}",
+                        name,
+                        self.print_api_response(&result, endpoints, endpoint_index)
+                    );
+                    on_result((result, endpoint_index))
                 }
+                Err(e) => return Err(anyhow::anyhow!("Task failed: {}", e)),
             }
+        }
+        Ok(())
+    }
 
-            self.process_results(
-                &mut resolved_conflicts,
-                &mut resolver_errors,
-                &results,
+    /// Patchpal is fine-tuned on a single conflict per request
+    fn can_group(endpoint: &EndpointConfig) -> bool {
+        !matches!(endpoint.config, EndpointTypeConfig::Patchpal { .. })
+    }
+
+    /// Resolve a group of related conflicts with a single prompt per
+    /// endpoint, so the model can resolve them coherently. The endpoints
+    /// that can't resolve a group are queried for each conflict.
+    async fn resolve_conflict_group(
+        &self,
+        resolved_conflicts: &mut Vec<ResolvedConflict>,
+        resolver_errors: &mut ResolverErrors,
+        group: &[&Conflict],
+        endpoints: &[EndpointConfig],
+    ) -> Result<()> {
+        if !self.bench {
+            let conflict_info = format!(
+                "Resolving group of {} conflicts in {}",
+                group.len(),
+                group
+                    .iter()
+                    .map(|c| format!("{}:{}->{}", c.file_path, c.start_line, c.local_start))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            // Logged at info level also when printed
+            if !logger::is_quiet() {
+                logger::progress!("{}", conflict_info);
+            }
+            log::info!("{}", conflict_info);
+        }
+
+        let original_group = group;
+        let normalized_group: Vec<Conflict> =
+            group.iter().map(|c| self.normalize_conflict(c)).collect();
+        let group: Vec<&Conflict> = normalized_group.iter().collect();
+        let group = group.as_slice();
+
+        let prompt = self.create_group_prompt(group);
+        let patch = group
+            .iter()
+            .map(|c| c.conflict_patch.as_str())
+            .collect::<String>();
+        let code = group
+            .iter()
+            .map(|c| format!("{}{}{}", c.head_context, c.conflict_code, c.tail_context))
+            .collect::<String>();
+
+        let mut futures = Vec::new();
+        for (endpoint_index, endpoint) in endpoints.iter().enumerate() {
+            if !Self::can_group(endpoint) || self.fallback.contains(&endpoint_index) {
+                continue;
+            }
+            let client = self.api_client(endpoint_index, endpoint, group);
+            let name = endpoint.name.clone();
+            let use_backticks = endpoint.use_backticks;
+            let message = self.create_group_message(group, use_backticks);
+            let git_diff = self.create_group_git_diff(group, use_backticks);
+            let training = Self::create_training(use_backticks);
+            let api_request = ApiRequest {
+                prompt: prompt.clone(),
+                training,
+                message,
+                patch: patch.clone(),
+                code: code.clone(),
+                git_diff,
+                enclosing_symbol: Self::enclosing_symbol_context(group),
+                file_context: self.file_context(group, use_backticks),
+            };
+            if self.config.get_print_prompt() {
+                Self::print_prompt(&client, &api_request, &name);
+            }
+            if self.config.get_prompt_only() {
+                continue;
+            }
+            if self.budget.out_of_tokens() {
+                log::info!("Token budget exceeded, not querying {}", name);
+                continue;
+            }
+            futures.push(self.spawn_query(client, api_request, name, endpoint_index));
+        }
+        let mut group_results = Vec::new();
+        self.collect_results(futures, endpoints, |result| group_results.push(result))
+            .await?;
+
+        // Split each grouped response into one response per conflict
+        let mut member_results: Vec<Vec<(Result<ApiResponse>, usize)>> =
+            group.iter().map(|_| Vec::new()).collect();
+        for (result, endpoint_index) in &group_results {
+            for (member, response) in self
+                .split_group_response(result, group.len())
+                .into_iter()
+                .enumerate()
+            {
+                member_results[member].push((response, *endpoint_index));
+            }
+        }
+
+        let first = resolved_conflicts.len();
+        for (member, conflict) in group.iter().enumerate() {
+            let ungrouped = |index, endpoint: &EndpointConfig| {
+                !Self::can_group(endpoint) && !self.fallback.contains(&index)
+            };
+            if endpoints
+                .iter()
+                .enumerate()
+                .any(|(index, endpoint)| ungrouped(index, endpoint))
+            {
+                self.query_conflict(conflict, endpoints, ungrouped, |result| {
+                    member_results[member].push(result)
+                })
+                .await?;
+            }
+            let member_first = resolved_conflicts.len();
+            let mut retry = RetryState::default();
+            self.process_normalized_results(
+                resolved_conflicts,
+                resolver_errors,
+                &member_results[member],
+                original_group[member],
                 conflict,
                 endpoints,
+                &mut retry,
             );
+            Self::retry_unresolved(resolver_errors, original_group[member], &retry);
+            if resolved_conflicts.len() == member_first {
+                self.resolve_fallback(
+                    resolved_conflicts,
+                    resolver_errors,
+                    original_group[member],
+                    conflict,
+                    endpoints,
+                )
+                .await?;
+            }
         }
+        self.report_duration(
+            &format!("group of {} conflicts", group.len()),
+            &resolved_conflicts[first..],
+        );
 
-        Ok((resolved_conflicts, resolver_errors))
+        Ok(())
+    }
+
+    /// Split a response to a grouped prompt into one response per
+    /// member conflict, each with its own patched code block
+    fn split_group_response(
+        &self,
+        result: &Result<ApiResponse>,
+        nr_members: usize,
+    ) -> Vec<Result<ApiResponse>> {
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                return (0..nr_members)
+                    .map(|_| Err(anyhow::anyhow!("{}", e)))
+                    .collect();
+            }
+        };
+        let mut split: Vec<ApiResponse> = (0..nr_members).map(|_| Vec::new()).collect();
+        for variants in result {
+            let mut split_variants: Vec<Vec<Result<ApiResponseEntry>>> =
+                (0..nr_members).map(|_| Vec::new()).collect();
+            for entry in variants {
+                let blocks = match entry {
+                    Ok(entry) => self.parse_response(&entry.response).and_then(|blocks| {
+                        if blocks.len() == nr_members {
+                            Ok(blocks)
+                        } else {
+                            Err(anyhow::anyhow!(
+                                "Expected {} patched code blocks for the conflict group, found {}",
+                                nr_members,
+                                blocks.len()
+                            ))
+                        }
+                    }),
+                    Err(e) => Err(anyhow::anyhow!("{}", e)),
+                };
+                for (member, split_variant) in split_variants.iter_mut().enumerate() {
+                    split_variant.push(match (&blocks, entry) {
+                        (Ok(blocks), Ok(entry)) => Ok(ApiResponseEntry {
+                            response: format!(
+                                "{}\n{}{}",
+                                Self::PATCHED_CODE_START,
+                                blocks[member],
+                                Self::PATCHED_CODE_END
+                            ),
+                            // The tokens of the grouped response are
+                            // accounted to the first member only
+                            total_tokens: entry.total_tokens.filter(|_| member == 0),
+                            input_tokens: entry.input_tokens.filter(|_| member == 0),
+                            ..entry.clone()
+                        }),
+                        (Err(e), _) => Err(anyhow::anyhow!("{}", e)),
+                        (_, Err(e)) => Err(anyhow::anyhow!("{}", e)),
+                    });
+                }
+            }
+            for (member, split_variant) in split_variants.into_iter().enumerate() {
+                split[member].push(split_variant);
+            }
+        }
+        split.into_iter().map(Ok).collect()
     }
 
     fn print_api_response(
@@ -310,7 +1181,16 @@ impl<'a> ConflictResolver<'a> {
                                 .unwrap_or_default();
                             let logprob_info = entry
                                 .logprob
-                                .map(|logprob| format!(" {:.1}%", prob::logprob_to_prob(logprob)))
+                                .map(|logprob| {
+                                    format!(
+                                        " {}",
+                                        prob::format_confidence(
+                                            logprob,
+                                            self.config.get_confidence_base(),
+                                            self.config.get_raw_logprob(),
+                                        )
+                                    )
+                                })
                                 .unwrap_or_default();
                             info.push_str(&format!(
                                 "{}{}{}{}{}",
@@ -324,6 +1204,8 @@ impl<'a> ConflictResolver<'a> {
             .unwrap_or_default()
     }
 
+    /// The endpoint name, followed by the model of the models list that
+    /// answered in brackets, then the variant, beam and sample suffix
     fn get_model_name_multi(
         &self,
         endpoints: &[EndpointConfig],
@@ -331,9 +1213,13 @@ impl<'a> ConflictResolver<'a> {
         variant: usize,
         beam: usize,
         multi: usize,
+        model: Option<&str>,
     ) -> String {
         let variant_name = self.get_variant_name(endpoints, endpoint, variant);
         let mut name = endpoints[endpoint].name.to_string();
+        if let Some(model) = model {
+            name.push_str(&format!(" [{}]", model));
+        }
         let mut open = false;
         if let Some(variant_name) = *variant_name {
             open = true;
@@ -366,8 +1252,9 @@ impl<'a> ConflictResolver<'a> {
         endpoint: usize,
         variant: usize,
         beam: usize,
+        model: Option<&str>,
     ) -> String {
-        self.get_model_name_multi(endpoints, endpoint, variant, beam, 0)
+        self.get_model_name_multi(endpoints, endpoint, variant, beam, 0, model)
     }
 
     fn get_variant_name(
@@ -379,7 +1266,8 @@ impl<'a> ConflictResolver<'a> {
         let endpoint = &endpoints[endpoint];
         match &endpoint.config {
             EndpointTypeConfig::OpenAI { variants, .. }
-            | EndpointTypeConfig::Anthropic { variants, .. } => {
+            | EndpointTypeConfig::Anthropic { variants, .. }
+            | EndpointTypeConfig::Ollama { variants, .. } => {
                 if let Some(variants) = variants {
                     if let Some(variant) = variants.get(variant) {
                         return variant.name.clone();
@@ -416,16 +1304,40 @@ impl<'a> ConflictResolver<'a> {
         code_has_patch_lines || patch_has_patch_lines
     }
 
+    /// The error of a failed request, the endpoint and the HTTP status
+    /// are known if the request failed in the api client
+    fn api_error(model: &str, e: &anyhow::Error) -> SynthmergeError {
+        match e.downcast_ref() {
+            Some(SynthmergeError::Api {
+                endpoint,
+                status,
+                message,
+            }) => SynthmergeError::Api {
+                endpoint: endpoint.clone(),
+                status: *status,
+                message: message.clone(),
+            },
+            _ => SynthmergeError::Api {
+                endpoint: model.to_string(),
+                status: None,
+                message: format!("{:#}", e),
+            },
+        }
+    }
+
     fn process_results(
         &self,
         resolved_conflicts: &mut Vec<ResolvedConflict>,
         resolver_errors: &mut ResolverErrors,
-        results: &Vec<(Result<ApiResponse>, usize)>,
+        results: &[(Result<ApiResponse>, usize)],
         conflict: &Conflict,
         endpoints: &[EndpointConfig],
+        retry: &mut RetryState,
     ) {
-        let mut recoverable = [false, false];
-        let mut no_solutions = true;
+        let RetryState {
+            recoverable,
+            no_solutions,
+        } = retry;
 
         // Validate that the content starts with head_context and ends with tail_context
         for result in results {
@@ -436,11 +1348,15 @@ impl<'a> ConflictResolver<'a> {
                     let model = &endpoints[endpoint].name;
                     log::error!("Skipping {} due to error: {}", model, e);
                     *resolver_errors.errors.entry(model.to_string()).or_insert(0) += 1;
+                    if resolver_errors.api_error.is_none() {
+                        resolver_errors.api_error = Some(Self::api_error(model, e));
+                    }
                     continue;
                 }
             };
 
             let primary = if endpoints[endpoint].primary { 1 } else { 0 };
+            let mut api_error = None;
 
             // Helper closure for error handling
             let mut record_error = |model: &str, retry: bool| {
@@ -454,9 +1370,21 @@ impl<'a> ConflictResolver<'a> {
                 for (beam, api_response_entry) in api_response_variant.iter().enumerate() {
                     let api_response_entry = match api_response_entry {
                         Ok(api_response_entry) => api_response_entry,
+                        Err(e)
+                            if matches!(
+                                e.downcast_ref::<ApiRequestError>(),
+                                Some(ApiRequestError::LowConfidence)
+                            ) =>
+                        {
+                            // Like a resolution dropped below the
+                            // threshold, not an error of the endpoint
+                            continue;
+                        }
                         Err(e) => {
-                            let model = self.get_model_name(endpoints, endpoint, variant, beam);
+                            let model =
+                                self.get_model_name(endpoints, endpoint, variant, beam, None);
                             log::error!("Skipping {} - {}", model, e);
+                            api_error.get_or_insert_with(|| Self::api_error(&model, e));
                             record_error(&model, false);
                             continue;
                         }
@@ -465,7 +1393,13 @@ impl<'a> ConflictResolver<'a> {
                     let resolved_strings = match self.parse_response(&api_response_entry.response) {
                         Ok(resolved_strings) => resolved_strings,
                         Err(e) => {
-                            let model = self.get_model_name(endpoints, endpoint, variant, beam);
+                            let model = self.get_model_name(
+                                endpoints,
+                                endpoint,
+                                variant,
+                                beam,
+                                api_response_entry.model.as_deref(),
+                            );
                             log::warn!("Skipping {} - {}", model, e);
                             record_error(&model, beam == 0);
                             continue;
@@ -476,8 +1410,14 @@ impl<'a> ConflictResolver<'a> {
 
                     let mut seen_resolved = std::collections::HashMap::new();
                     for (multi, resolved_string) in resolved_strings.iter().enumerate() {
-                        let model =
-                            self.get_model_name_multi(endpoints, endpoint, variant, beam, multi);
+                        let model = self.get_model_name_multi(
+                            endpoints,
+                            endpoint,
+                            variant,
+                            beam,
+                            multi,
+                            api_response_entry.model.as_deref(),
+                        );
                         let mut resolved_version = resolved_string.to_string();
 
                         let mut found_context = false;
@@ -574,6 +1514,7 @@ impl<'a> ConflictResolver<'a> {
                         seen_resolved.insert(key, model.clone());
 
                         let total_tokens = api_response_entry.total_tokens;
+                        let input_tokens = api_response_entry.input_tokens;
                         let logprob = api_response_entry.logprob;
                         let duration = api_response_entry.duration;
                         resolved_conflicts.push(ResolvedConflict {
@@ -582,46 +1523,56 @@ impl<'a> ConflictResolver<'a> {
                             model,
                             duration,
                             total_tokens,
+                            input_tokens,
                             logprob,
                             deduplicated_conflicts: Vec::new(),
                             endpoint,
                             beam: Some(beam),
                             multi: Some(multi),
                         });
-                        no_solutions = false;
+                        *no_solutions = false;
                     }
                 }
             }
+            if resolver_errors.api_error.is_none() {
+                resolver_errors.api_error = api_error;
+            }
         }
+    }
 
-        if recoverable[1] || (no_solutions && recoverable[0]) {
+    /// Retry the file of the conflict if a primary endpoint failed in a
+    /// recoverable way, or another endpoint did and nothing resolved it
+    fn retry_unresolved(
+        resolver_errors: &mut ResolverErrors,
+        conflict: &Conflict,
+        retry: &RetryState,
+    ) {
+        if retry.recoverable[1] || (retry.no_solutions && retry.recoverable[0]) {
             resolver_errors
                 .retry_files
                 .insert(conflict.file_path.clone());
         }
     }
 
-    /// Create a prompt for the AI to resolve the conflict
-    fn git_diff(git_diff: Option<String>, use_backticks: bool) -> Option<String> {
-        git_diff.map(|s| {
-            let mut diff_block = format!(
-                r#"{diff_start}
-{s}{diff_end}"#,
-                diff_start = Self::DIFF_START,
-                diff_end = Self::DIFF_END,
-            );
-            if use_backticks {
-                diff_block = format!("{}\n{}\n{}", Self::BACKTICK, diff_block, Self::BACKTICK);
-            }
-            format!(
-                r#"The PATCH originates from the DIFF between {diff_start}{diff_end}.
+    /// The labeled section of the prompt with the commit context
+    fn commit_context(diff: &str, use_backticks: bool) -> String {
+        let mut diff_block = format!(
+            r#"{diff_start}
+{diff}{diff_end}"#,
+            diff_start = Self::DIFF_START,
+            diff_end = Self::DIFF_END,
+        );
+        if use_backticks {
+            diff_block = format!("{}\n{}\n{}", Self::BACKTICK, diff_block, Self::BACKTICK);
+        }
+        format!(
+            r#"COMMIT CONTEXT: the PATCH originates from the DIFF between {diff_start}{diff_end}.
 
 {diff_block}"#,
-                diff_start = Self::DIFF_START,
-                diff_end = Self::DIFF_END,
-                diff_block = diff_block,
-            )
-        })
+            diff_start = Self::DIFF_START,
+            diff_end = Self::DIFF_END,
+            diff_block = diff_block,
+        )
     }
 
     fn code_snippets(conflict: &Conflict, use_backticks: bool) -> Option<String> {
@@ -696,11 +1647,10 @@ impl<'a> ConflictResolver<'a> {
     fn create_git_diff(&self, conflict: &Conflict, use_backticks: bool) -> Option<String> {
         let mut parts = Vec::new();
         let mut has_diff = false;
-        if let Some(diff) = &self.git_diff
+        if let Some(diff) = &conflict.commit_context
             && diff.contains(&conflict.file_path)
-            && let Some(formatted_diff) = Self::git_diff(Some(diff.clone()), use_backticks)
         {
-            parts.push(formatted_diff);
+            parts.push(Self::commit_context(diff, use_backticks));
             has_diff = true;
         }
         if conflict.commit_type == CommitType::ConflictAndClean {
@@ -724,38 +1674,145 @@ impl<'a> ConflictResolver<'a> {
         }
     }
 
-    /// Create a prompt for the AI to resolve the conflict
-    fn create_prompt(&self, conflict: &Conflict) -> String {
+    /// The whole files of the conflicts resolved to the local side, the
+    /// first conflict of each file carries them
+    fn context_files<'c>(conflicts: &[&'c Conflict]) -> Vec<&'c Conflict> {
+        let mut seen_files = HashSet::new();
+        conflicts
+            .iter()
+            .filter(|c| !c.merged_local_lines.is_empty() && seen_files.insert(&c.file_path))
+            .copied()
+            .collect()
+    }
+
+    /// The size in bytes of the whole files sent as context, zero if
+    /// they aren't sent
+    fn file_context_size(&self, conflicts: &[&Conflict]) -> usize {
+        let Some(max_size) = self.config.get_full_file_context() else {
+            return 0;
+        };
+        let size = Self::context_files(conflicts)
+            .iter()
+            .map(|c| c.merged_local_lines.iter().map(String::len).sum::<usize>())
+            .sum();
+        if size > max_size { 0 } else { size }
+    }
+
+    /// The whole files of the conflicts as context, None if disabled or
+    /// over the size budget, then the context lines have to be enough
+    fn file_context(&self, conflicts: &[&Conflict], use_backticks: bool) -> Option<String> {
+        let max_size = self.config.get_full_file_context()?;
+        let files = Self::context_files(conflicts);
+        let size = self.file_context_size(conflicts);
+        if size == 0 {
+            if !files.is_empty() {
+                log::info!(
+                    "Full file context of {} over {} bytes, only the context lines are sent",
+                    files[0].file_path,
+                    max_size
+                );
+            }
+            return None;
+        }
+        let parts: Vec<String> = files
+            .iter()
+            .map(|c| {
+                let mut file_block = format!(
+                    "{}\n{}{}",
+                    Self::FILE_START,
+                    c.merged_local_lines.join(""),
+                    Self::FILE_END
+                );
+                if use_backticks {
+                    file_block = format!("{}\n{}\n{}", Self::BACKTICK, file_block, Self::BACKTICK);
+                }
+                format!(
+                    r#"The whole {file_path}, with the conflicts resolved to the local side, is between {file_start}{file_end}. Only use it to understand the CODE.
+
+{file_block}"#,
+                    file_path = c.file_path,
+                    file_start = Self::FILE_START,
+                    file_end = Self::FILE_END,
+                )
+            })
+            .collect();
+        Some(parts.join("\n\n"))
+    }
+
+    /// Where the CODE is, from the signatures of the functions or
+    /// classes enclosing the conflicts, None if none is known
+    fn enclosing_symbol_context(conflicts: &[&Conflict]) -> Option<String> {
+        let lines: Vec<String> = conflicts
+            .iter()
+            .enumerate()
+            .filter(|(_, conflict)| !conflict.enclosing_symbol.is_empty())
+            .map(|(i, conflict)| {
+                let code = if conflicts.len() == 1 {
+                    "The CODE".to_string()
+                } else {
+                    format!("CODE {}", i + 1)
+                };
+                format!("{} is inside: {}", code, conflict.enclosing_symbol)
+            })
+            .collect();
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    /// Instruction to rewrite the head and tail context lines verbatim
+    fn context_instruction(conflict: &Conflict) -> Option<String> {
         let head_lines = conflict.nr_head_context_lines;
         let tail_lines = conflict.nr_tail_context_lines;
 
-        let context_instruction = if head_lines == 0 && tail_lines == 0 {
-            String::new()
+        if head_lines == 0 && tail_lines == 0 {
+            None
         } else if head_lines == 0 {
-            format!(
-                "\n\nRewrite the {nr_tail_context_lines} line{tail_plural} before {code_end} exactly the same, including all empty lines.",
+            Some(format!(
+                "Rewrite the {nr_tail_context_lines} line{tail_plural} before {code_end} exactly the same, including all empty lines.",
                 nr_tail_context_lines = tail_lines,
                 tail_plural = if tail_lines != 1 { "s" } else { "" },
                 code_end = Self::CODE_END,
-            )
+            ))
         } else if tail_lines == 0 {
-            format!(
-                "\n\nRewrite the {nr_head_context_lines} line{head_plural} after {code_start} exactly the same, including all empty lines.",
+            Some(format!(
+                "Rewrite the {nr_head_context_lines} line{head_plural} after {code_start} exactly the same, including all empty lines.",
                 nr_head_context_lines = head_lines,
                 head_plural = if head_lines != 1 { "s" } else { "" },
                 code_start = Self::CODE_START,
-            )
+            ))
         } else {
-            format!(
-                "\n\nRewrite the {nr_head_context_lines} line{head_plural} after {code_start} and the {nr_tail_context_lines} line{tail_plural} before {code_end} exactly the same, including all empty lines.",
+            Some(format!(
+                "Rewrite the {nr_head_context_lines} line{head_plural} after {code_start} and the {nr_tail_context_lines} line{tail_plural} before {code_end} exactly the same, including all empty lines.",
                 nr_head_context_lines = head_lines,
                 nr_tail_context_lines = tail_lines,
                 head_plural = if head_lines != 1 { "s" } else { "" },
                 tail_plural = if tail_lines != 1 { "s" } else { "" },
                 code_start = Self::CODE_START,
                 code_end = Self::CODE_END,
-            )
-        };
+            ))
+        }
+    }
+
+    /// The bias hint of the side preferred for the file of the conflict
+    fn prefer_instruction(&self, conflict: &Conflict) -> Option<String> {
+        let side = self.side_preferences.side_for(&conflict.file_path)?;
+        Some(match side {
+            Side::Local => "Where the PATCH and the CODE disagree, favor the CODE, while still applying the changes of the PATCH that don't conflict with it.".to_string(),
+            Side::Remote => "Where the PATCH and the CODE disagree, favor the PATCH, while still keeping the changes of the CODE that don't conflict with it.".to_string(),
+            Side::Base => "Where the PATCH and the CODE disagree, favor the code the PATCH removes, while still keeping the changes of the CODE and the PATCH that don't conflict with it.".to_string(),
+        })
+    }
+
+    /// Create a prompt for the AI to resolve the conflict
+    fn create_prompt(&self, conflict: &Conflict) -> String {
+        let context_instruction = Self::context_instruction(conflict)
+            .into_iter()
+            .chain(self.prefer_instruction(conflict))
+            .map(|instruction| format!("\n\n{}", instruction))
+            .collect::<String>();
 
         format!(
             r#"Apply the PATCH between {patch_start}{patch_end} to the CODE between {code_start}{code_end}.
@@ -771,6 +1828,81 @@ FINALLY answer with the final PATCHED CODE between {patched_code_start}{patched_
         )
     }
 
+    /// Create a prompt for the AI to resolve a group of related conflicts
+    fn create_group_prompt(&self, group: &[&Conflict]) -> String {
+        let context_instructions = group
+            .iter()
+            .enumerate()
+            .flat_map(|(i, conflict)| {
+                Self::context_instruction(conflict)
+                    .into_iter()
+                    .chain(self.prefer_instruction(conflict))
+                    .map(move |instruction| format!("\n\nIn CODE {}: {}", i + 1, instruction))
+            })
+            .collect::<String>();
+
+        format!(
+            r#"The {nr_conflicts} PATCH and CODE pairs below belong to the same change. Apply each PATCH between {patch_start}{patch_end} to the CODE between {code_start}{code_end} with the same number, resolving all of them consistently.
+
+FINALLY answer with {nr_conflicts} final PATCHED CODE blocks between {patched_code_start}{patched_code_end} instead of markdown fences, one for each CODE in the same order.{context_instructions}"#,
+            nr_conflicts = group.len(),
+            patch_start = Self::PATCH_START,
+            patch_end = Self::PATCH_END,
+            code_start = Self::CODE_START,
+            code_end = Self::CODE_END,
+            patched_code_start = Self::PATCHED_CODE_START,
+            patched_code_end = Self::PATCHED_CODE_END,
+            context_instructions = context_instructions,
+        )
+    }
+
+    fn create_group_message(&self, group: &[&Conflict], use_backticks: bool) -> String {
+        group
+            .iter()
+            .enumerate()
+            .map(|(i, conflict)| {
+                let code = format!(
+                    "{}{}{}",
+                    conflict.head_context, conflict.conflict_code, conflict.tail_context
+                );
+                format!(
+                    "PATCH {i} and CODE {i} are in {file_path}.\n\n{message}",
+                    i = i + 1,
+                    file_path = conflict.file_path,
+                    message = self.create_message(&conflict.conflict_patch, &code, use_backticks),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn create_group_git_diff(&self, group: &[&Conflict], use_backticks: bool) -> Option<String> {
+        let mut parts = Vec::new();
+        // The conflicts of a run share the commit context
+        if let Some(diff) = group.iter().find_map(|c| c.commit_context.as_ref())
+            && group.iter().any(|c| diff.contains(&c.file_path))
+        {
+            parts.push(Self::commit_context(diff, use_backticks));
+        }
+        let mut seen_files = HashSet::new();
+        for conflict in group {
+            if conflict.commit_type == CommitType::ConflictAndClean
+                && seen_files.insert(&conflict.file_path)
+            {
+                parts.push(Self::raw_patch(
+                    conflict.conflict_raw_patch.as_ref().unwrap(),
+                    &conflict.file_path,
+                    use_backticks,
+                ));
+            }
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("\n\n"))
+        }
+    }
+
     fn create_training(use_backticks: bool) -> String {
         let mut patch_block = format!(
             r#"{patch_start}
@@ -869,42 +2001,64 @@ static inline struct feat *get_special_something(double option, struct device *d
             .to_string()
     }
 
-    /// Parse the API response into 3 solutions
+    /// Extract the patched code blocks of the response. Each block must
+    /// be opened and closed by the sentinels on their own lines, without
+    /// nesting and without being empty, otherwise the whole response is
+    /// rejected and the conflict is left unresolved.
     fn parse_response(&self, response: &String) -> Result<Vec<String>> {
-        let start_regex = &self.start_regex;
-        let end_regex = &self.end_regex;
-
         log::info!("Response:\n{}", response);
 
+        let starts: Vec<_> = self.start_regex.find_iter(response).collect();
+        // The end regex also matches the start sentinels
+        let ends = self
+            .end_regex
+            .find_iter(response)
+            .filter(|end| !starts.iter().any(|start| start.start() == end.start()));
+        let mut sentinels: Vec<_> = starts
+            .iter()
+            .map(|start| (*start, true))
+            .chain(ends.map(|end| (end, false)))
+            .collect();
+        sentinels.sort_by_key(|(sentinel, _)| sentinel.start());
+
         let mut results = Vec::new();
-        let mut err: Option<Result<Vec<String>, anyhow::Error>> = None;
-        let mut start = 0;
-
-        while let Some(start_match) = start_regex.find_at(response, start) {
-            let start_pos = start_match.end();
-            let end_match = end_regex.find_at(response, start_pos);
-            if end_match.is_none() {
-                err = Some(Err(anyhow::anyhow!(
-                    "Invalid format: missing {}",
-                    Self::PATCHED_CODE_END
-                )));
-                break;
+        let mut open: Option<usize> = None;
+        for (sentinel, is_start) in sentinels {
+            match (open, is_start) {
+                (None, true) => open = Some(sentinel.end()),
+                (Some(_), true) => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid format: duplicate {} before {}",
+                        Self::PATCHED_CODE_START,
+                        Self::PATCHED_CODE_END
+                    ));
+                }
+                (None, false) => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid format: {} without {}",
+                        Self::PATCHED_CODE_END,
+                        Self::PATCHED_CODE_START
+                    ));
+                }
+                (Some(start_pos), false) => {
+                    let content = &response[start_pos..sentinel.start()];
+                    if content.is_empty() {
+                        return Err(anyhow::anyhow!("Invalid format: empty patched code block"));
+                    }
+                    results.push(content.to_string());
+                    open = None;
+                }
             }
-
-            let end_match = end_match.unwrap();
-            let end_pos = end_match.start();
-
-            let content = &response[start_pos..end_pos];
-            results.push(content.to_string());
-
-            start = end_match.end();
+        }
+        if open.is_some() {
+            return Err(anyhow::anyhow!(
+                "Invalid format: missing {}",
+                Self::PATCHED_CODE_END
+            ));
         }
 
         if results.is_empty() {
-            match err {
-                Some(err) => err,
-                None => Err(anyhow::anyhow!("No code blocks found in response")),
-            }
+            Err(anyhow::anyhow!("No code blocks found in response"))
         } else {
             Ok(results)
         }
@@ -971,6 +2125,136 @@ static inline struct feat *get_special_something(double option, struct device *d
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+endpoints:
+  - name: "mock"
+    url: "http://localhost:8080/v1/chat/completions"
+    type: "openai"
+"#;
+
+    #[test]
+    fn test_parse_response() {
+        let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        let resolver = ConflictResolver::new(&config, false, None, false);
+        let parse = |response: &str| resolver.parse_response(&response.to_string());
+        let start = ConflictResolver::PATCHED_CODE_START;
+        let end = ConflictResolver::PATCHED_CODE_END;
+
+        assert_eq!(
+            parse(&format!("ok\n{start}\na\n{end}\n{start}\nb\n{end}")).unwrap(),
+            ["a\n", "b\n"]
+        );
+        // The sentinels in the code are ignored unless on their own line
+        assert_eq!(
+            parse(&format!("```\n{start}\nx = \"{end}\";\n{end}\n```")).unwrap(),
+            [format!("x = \"{end}\";\n")]
+        );
+
+        let missing_end = parse(&format!("{start}\na\nb\n")).unwrap_err();
+        assert!(missing_end.to_string().contains("missing"));
+        let missing_end = parse(&format!("{start}\na\n{end}\n{start}\nb\n")).unwrap_err();
+        assert!(missing_end.to_string().contains("missing"));
+        let duplicate_start = parse(&format!("{start}\na\n{start}\nb\n{end}")).unwrap_err();
+        assert!(duplicate_start.to_string().contains("duplicate"));
+        let stray_end = parse(&format!("a\n{end}\n{start}\nb\n{end}")).unwrap_err();
+        assert!(stray_end.to_string().contains("without"));
+        let empty = parse(&format!("{start}\n{end}")).unwrap_err();
+        assert!(empty.to_string().contains("empty"));
+        assert!(parse("no code").is_err());
+    }
+
+    #[test]
+    fn test_budget() {
+        let budget = Budget::new(None, Some(100));
+        // The resolvers of a run share the tokens
+        let shared = budget.clone();
+        assert!(!budget.out_of_tokens());
+        shared.add_tokens(60);
+        assert!(!budget.out_of_tokens());
+        budget.add_tokens(40);
+        assert_eq!(shared.tokens(), 100);
+        assert!(shared.out_of_tokens());
+        assert!(!budget.out_of_time());
+
+        let budget = Budget::new(Some(Instant::now()), None);
+        budget.add_tokens(1_000_000);
+        assert!(budget.out_of_time());
+        assert!(!budget.out_of_tokens());
+    }
+
+    #[test]
+    fn test_prefer_instruction() {
+        let config: Config = serde_yaml::from_str(&format!(
+            "{}side_preferences:\n  - glob: \"*.lock\"\n    prefer: remote\n",
+            CONFIG
+        ))
+        .unwrap();
+        let resolver = ConflictResolver::new(&config, false, None, false);
+        let conflict = |file_path: &str| Conflict {
+            file_path: file_path.to_string(),
+            ..Default::default()
+        };
+        let hint = "favor the PATCH";
+        assert!(
+            resolver
+                .create_prompt(&conflict("Cargo.lock"))
+                .contains(hint)
+        );
+        assert!(
+            !resolver
+                .create_prompt(&conflict("src/main.rs"))
+                .contains(hint)
+        );
+        let (lock, main) = (conflict("Cargo.lock"), conflict("src/main.rs"));
+        let prompt = resolver.create_group_prompt(&[&main, &lock]);
+        assert!(prompt.contains(&format!(
+            "In CODE 2: Where the PATCH and the CODE disagree, {}",
+            hint
+        )));
+        assert!(!prompt.contains("In CODE 1"));
+    }
+
+    #[test]
+    fn test_commit_context() {
+        let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        let resolver = ConflictResolver::new(&config, false, None, false);
+        let diff = "diff --git a/f.txt b/f.txt\n-d\n+d-topic\n";
+        let conflict = |file_path: &str| Conflict {
+            file_path: file_path.to_string(),
+            commit_context: Some(Arc::new(diff.to_string())),
+            ..Default::default()
+        };
+        let context = resolver.create_git_diff(&conflict("f.txt"), false).unwrap();
+        assert!(context.starts_with("COMMIT CONTEXT: "));
+        assert!(context.contains(&format!(
+            "{}\n{}{}",
+            ConflictResolver::DIFF_START,
+            diff,
+            ConflictResolver::DIFF_END
+        )));
+        // The commit didn't change the file
+        assert!(
+            resolver
+                .create_git_diff(&conflict("g.txt"), false)
+                .is_none()
+        );
+        let (f, g) = (conflict("f.txt"), conflict("g.txt"));
+        assert_eq!(
+            resolver.create_group_git_diff(&[&g, &f], false),
+            Some(context)
+        );
+        let no_context = Conflict {
+            commit_context: None,
+            ..conflict("f.txt")
+        };
+        assert!(resolver.create_git_diff(&no_context, false).is_none());
+    }
+}
+
 // Local Variables:
 // rust-format-on-save: t
 // End:
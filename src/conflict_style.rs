@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+//! The `--conflict-style` choices, in a module of its own so build.rs
+//! can include it with the command line arguments
+
+/// The merge.conflictStyle the conflict markers were written with
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum ConflictStyle {
+    /// The conflicts have a base section
+    #[default]
+    Diff3,
+    /// The conflicts have no base section, the base is treated as empty
+    Merge,
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+//! The `--diff-algorithm` choices, in a module of its own so build.rs
+//! can include it with the command line arguments
+
+/// The `git diff --diff-algorithm` choices
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum DiffAlgorithm {
+    Myers,
+    Minimal,
+    Patience,
+    #[default]
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    pub(crate) fn git_arg(self) -> &'static str {
+        match self {
+            DiffAlgorithm::Myers => "--diff-algorithm=myers",
+            DiffAlgorithm::Minimal => "--diff-algorithm=minimal",
+            DiffAlgorithm::Patience => "--diff-algorithm=patience",
+            DiffAlgorithm::Histogram => "--diff-algorithm=histogram",
+        }
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
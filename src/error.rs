@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use std::process::Output;
+
+/// The errors of the public API, so the callers can tell the failure
+/// modes apart. Internally the errors are carried by anyhow and the
+/// variants raised inside it are recovered by the conversion.
+#[derive(Debug, thiserror::Error)]
+pub enum SynthmergeError {
+    #[error("Not in a git repository")]
+    NotAGitRepo,
+    #[error("Git {command} failed: {stderr}")]
+    Git {
+        command: String,
+        /// The exit code, None if git was killed by a signal
+        status: Option<i32>,
+        stderr: String,
+    },
+    #[error("Invalid conflict markers in file {file_path}: {reason}")]
+    ConflictMarkers { file_path: String, reason: String },
+    #[error("Request to endpoint {endpoint} failed: {message}")]
+    Api {
+        endpoint: String,
+        /// The HTTP status, None if no response was received
+        status: Option<u16>,
+        message: String,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl SynthmergeError {
+    /// The failure of a git command from its output
+    pub fn git(command: impl Into<String>, output: &Output) -> Self {
+        SynthmergeError::Git {
+            command: command.into(),
+            status: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for SynthmergeError {
+    fn from(error: anyhow::Error) -> Self {
+        // The downcast also finds the errors wrapped in a context
+        match error.downcast::<SynthmergeError>() {
+            Ok(error) => error,
+            Err(error) => match error.downcast::<std::io::Error>() {
+                Ok(error) => SynthmergeError::Io(error),
+                Err(error) => SynthmergeError::Other(error),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_from_anyhow() {
+        let error = anyhow::Error::from(SynthmergeError::NotAGitRepo);
+        assert!(matches!(
+            SynthmergeError::from(error),
+            SynthmergeError::NotAGitRepo
+        ));
+
+        let error = Err::<(), _>(SynthmergeError::Api {
+            endpoint: "mock".to_string(),
+            status: Some(401),
+            message: "Unauthorized".to_string(),
+        })
+        .context("Resolving f.txt")
+        .unwrap_err();
+        assert!(matches!(
+            SynthmergeError::from(error),
+            SynthmergeError::Api {
+                status: Some(401),
+                ..
+            }
+        ));
+
+        let error = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(
+            SynthmergeError::from(error),
+            SynthmergeError::Io(_)
+        ));
+
+        let error = anyhow::anyhow!("Something else");
+        let error = SynthmergeError::from(error);
+        assert!(matches!(error, SynthmergeError::Other(_)));
+        assert_eq!(error.to_string(), "Something else");
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
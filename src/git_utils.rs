@@ -1,26 +1,124 @@
 // SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
 // Copyright (C) 2025-2026  Red Hat, Inc.
 
-use crate::conflict_resolver::{CommitType, Conflict, ConflictResolver, ResolvedConflict};
+pub use crate::breadcrumbs::Breadcrumbs;
+use crate::config::{
+    AgreementConfig, AssistedBy, ConfidenceThresholds, EofNewline, MissingLogprob,
+};
+use crate::conflict_resolver::{
+    CommitType, Conflict, ConflictKind, ConflictResolver, ResolvedConflict,
+};
+pub use crate::conflict_style::ConflictStyle;
+pub use crate::diff_algorithm::DiffAlgorithm;
+use crate::error::SynthmergeError;
 use crate::lmdb_cache::{LmdbCacheImpl, PatchLocatorCache};
+use crate::logger;
+pub use crate::modify_delete::ModifyDelete;
+use crate::normalize;
 use crate::patch_locator::PatchLocator;
 use crate::prob;
 use anyhow::{Context, Result};
+use rayon::prelude::*;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::fs::{OpenOptions, Permissions};
+use std::io::Read;
 use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::sync::Arc;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
 
+/// The lines of context around the conflicts, the defaults are the
+/// ones of the command line
 #[derive(Debug, Clone, Copy)]
 pub struct ContextLines {
     pub code_context_lines: u32,
     pub diff_context_lines: u32,
     pub patch_context_lines: u32,
     pub extra_conflict_lines: u32,
+    /// The diff algorithm of the commit diff provided as context
+    pub diff_algorithm: DiffAlgorithm,
+}
+
+impl Default for ContextLines {
+    fn default() -> Self {
+        ContextLines {
+            code_context_lines: 3,
+            diff_context_lines: 3,
+            patch_context_lines: 3,
+            extra_conflict_lines: 0,
+            diff_algorithm: DiffAlgorithm::default(),
+        }
+    }
+}
+
+/// Options controlling how conflicts are parsed and how resolutions are
+/// applied to the working tree
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    pub conflict_style: ConflictStyle,
+    pub commit_per_file: bool,
+    pub confidence_thresholds: ConfidenceThresholds,
+    /// Show the logprobs in the conflict markers instead of the
+    /// confidence percentages
+    pub raw_logprob: bool,
+    pub breadcrumbs: Option<Breadcrumbs>,
+    pub stash: bool,
+    pub eof_newline: EofNewline,
+    /// Ask before vibe resolution overwrites the conflicts
+    pub confirm: bool,
+    /// Back up each file and its index entries before the first mutation
+    pub backup: bool,
+    /// Print the diff of the resolutions instead of applying them
+    pub dry_run: bool,
+    /// Write the diff of the vibe resolutions to this patch file
+    /// instead of printing it, with dry_run
+    pub output_patch: Option<PathBuf>,
+    /// Only the unmerged files matching these pathspecs are touched
+    pub pathspecs: Vec<String>,
+    /// Only this unmerged file is touched, taken literally
+    pub only_file: Option<String>,
+    /// The repository to run in like git -C, the current directory
+    /// if None
+    pub repo: Option<String>,
+    /// Record the vibe resolutions in the rerere database
+    pub rerere: bool,
+    /// How vibe mode resolves the files deleted by one side and
+    /// modified by the other, None leaves them unresolved
+    pub modify_delete: Option<ModifyDelete>,
+    /// Leave the marker-like blocks without a base section alone, as
+    /// part of the file content, instead of failing on them
+    pub ignore_stray_markers: bool,
+    /// The size of the conflict markers of all the files, instead of
+    /// the gitattributes and the detection
+    pub marker_size: Option<usize>,
+    /// Open the commit message in the git editor before continuing
+    pub edit_message: bool,
+    /// Skip the commit of the operation instead of continuing it, when
+    /// the resolutions left nothing to commit
+    pub skip_empty: bool,
+    /// How much the Assisted-by trailer of the commit message tells
+    pub assisted_by: AssistedBy,
+    /// The resolutions produced by fewer models are dropped
+    pub min_agreement: usize,
+    /// The whitespace differences ignored when comparing the resolutions
+    pub agreement: AgreementConfig,
+    /// Reindent the resolutions with the indentation unit of the
+    /// conflicted region
+    pub match_indent: bool,
+    /// Also find the conflicts that already have the resolutions of a
+    /// previous run outside of vibe mode, another block of resolutions
+    /// is added to them
+    pub force: bool,
+}
+
+/// Comment syntax of a file type
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CommentSyntax {
+    Line(&'static str),
+    Block(&'static str, &'static str),
 }
 
 #[derive(Debug, Clone)]
@@ -30,10 +128,34 @@ pub struct OperationHead {
     pub path: PathBuf,
 }
 
-// Wrapper around Command to allow inheritance-like behavior
+/// Wrapper around Command to allow inheritance-like behavior
+///
+/// The git calls of the hot path, run for each unmerged file or conflict
+/// while finding the conflicts (status, diff --no-index, show, merge-file
+/// and the show of the commit diff), have a timeout: git is killed and
+/// the call fails instead of hanging the run. The timeout ends at the
+/// --time-budget deadline, if any. git stays in the process group of
+/// synthmerge, so a Ctrl-C interrupts both. The calls concluding the
+/// operation (commit, the continue of the operation, rerere, add and
+/// rm) run the hooks and the editor of the user and have none.
 pub struct GitCommand {
     command: Command,
     verbose: bool,
+    timeout: Option<Duration>,
+    stdin: Option<Vec<u8>>,
+}
+
+/// A child process killed and reaped when dropped if it's still
+/// running, so an error or a panic never leaves an orphaned git behind
+struct KillOnDrop(std::process::Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        if let Ok(None) = self.0.try_wait() {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
 }
 
 /// Remove conflict markers from content
@@ -50,7 +172,37 @@ impl GitCommand {
         GitCommand {
             command: cmd,
             verbose: true,
+            timeout: None,
+            stdin: None,
+        }
+    }
+
+    /// git, running in the repository at repo like git -C, or in the
+    /// current directory
+    pub fn git(repo: Option<&str>) -> Self {
+        let mut git = Self::new("git");
+        if let Some(repo) = repo {
+            git.args(["-C", repo]);
         }
+        git
+    }
+
+    /// The timeout of the git calls of the hot path
+    pub const TIMEOUT: Duration = Duration::from_secs(300);
+    /// The timeout of the git calls past the --time-budget deadline, to
+    /// leave the working tree consistent
+    pub const MIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Kill the command if it's still running after the timeout
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Write the input to the stdin of the command
+    pub fn stdin(&mut self, input: Vec<u8>) -> &mut Self {
+        self.stdin = Some(input);
+        self
     }
 
     pub fn args<I, S>(&mut self, args: I) -> &mut Self
@@ -62,6 +214,15 @@ impl GitCommand {
         self
     }
 
+    pub fn env<K, V>(&mut self, key: K, val: V) -> &mut Self
+    where
+        K: AsRef<std::ffi::OsStr>,
+        V: AsRef<std::ffi::OsStr>,
+    {
+        self.command.env(key, val);
+        self
+    }
+
     pub fn verbose(&mut self, verbose: bool) -> &mut Self {
         self.verbose = verbose;
         self
@@ -75,16 +236,100 @@ impl GitCommand {
             .map(|arg| arg.to_string_lossy())
             .collect::<Vec<_>>()
             .join(" ");
-        let output = self.command.output().context("Failed to execute command")?;
+        let output = if self.timeout.is_some() || self.stdin.is_some() {
+            self.spawn_output().with_context(|| {
+                format!(
+                    "Failed to execute {}",
+                    logger::redact(&format!("{program} {args_str}"))
+                )
+            })?
+        } else {
+            self.command.output().context("Failed to execute command")?
+        };
         if self.verbose {
-            log::debug!("GitCommand: {program} {args_str} {{{}}}", output.status);
+            log::debug!(
+                "GitCommand: {} {{{}}}",
+                logger::redact(&format!("{program} {args_str}")),
+                output.status
+            );
             if !output.status.success() {
-                log::debug!("stdout: {}", String::from_utf8_lossy(&output.stdout));
-                log::debug!("stderr: {}", String::from_utf8_lossy(&output.stderr));
+                log::debug!(
+                    "stdout: {}",
+                    logger::redact(&String::from_utf8_lossy(&output.stdout))
+                );
+                log::debug!(
+                    "stderr: {}",
+                    logger::redact(&String::from_utf8_lossy(&output.stderr))
+                );
             }
         }
         Ok(output)
     }
+
+    /// Run the command like Command::output, a thread per pipe collects
+    /// the output and another one writes the stdin input, while a
+    /// watchdog thread kills the command at the timeout
+    fn spawn_output(&mut self) -> Result<std::process::Output> {
+        let input = self.stdin.take();
+        let mut child = self
+            .command
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // Written from another thread, git may fill the stdout pipe
+        // before reading all the input
+        let writer = input.map(|input| {
+            let mut stdin = child.stdin.take().unwrap();
+            std::thread::spawn(move || std::io::Write::write_all(&mut stdin, &input))
+        });
+        let read = |mut pipe: Box<dyn Read + Send>| {
+            std::thread::spawn(move || {
+                let mut buf = Vec::new();
+                pipe.read_to_end(&mut buf).map(|_| buf)
+            })
+        };
+        let stdout = read(Box::new(child.stdout.take().unwrap()));
+        let stderr = read(Box::new(child.stderr.take().unwrap()));
+        let child = Arc::new(Mutex::new(KillOnDrop(child)));
+
+        let (done, wait_done) = mpsc::channel::<()>();
+        let watchdog = self.timeout.map(|timeout| {
+            let child = child.clone();
+            std::thread::spawn(move || {
+                let timed_out =
+                    wait_done.recv_timeout(timeout) == Err(mpsc::RecvTimeoutError::Timeout);
+                if timed_out {
+                    // The pipes close and the readers return
+                    let _ = child.lock().unwrap().0.kill();
+                }
+                timed_out
+            })
+        });
+        let stdout = stdout.join().unwrap()?;
+        let stderr = stderr.join().unwrap()?;
+        drop(done);
+        let timed_out = watchdog.is_some_and(|watchdog| watchdog.join().unwrap());
+        let status = child.lock().unwrap().0.wait()?;
+        if timed_out {
+            anyhow::bail!(
+                "timed out after {} seconds",
+                self.timeout.unwrap_or_default().as_secs()
+            );
+        }
+        if let Some(writer) = writer {
+            writer.join().unwrap()?;
+        }
+        Ok(std::process::Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -94,6 +339,81 @@ pub enum ResolutionMode {
     VibeWithMarkers,
 }
 
+/// A vibe resolved file waiting to be committed on its own
+#[derive(Debug, Clone)]
+struct PerFileCommit {
+    file_path: String,
+    local_content: String,
+    resolved_content: String,
+    models: String,
+}
+
+/// Side selected by a `merge=ours` or `merge=theirs` gitattribute
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MergeDriverSide {
+    Ours,
+    Theirs,
+}
+
+/// An unmerged file with conflict markers, pending to be parsed
+struct UnmergedFile {
+    file_path: String,
+    path: PathBuf,
+    renamed_from: Option<String>,
+    local_content: Arc<String>,
+}
+
+/// The gitattributes of an unmerged file that affect its conflicts
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FileAttributes {
+    marker_size: Option<usize>,
+    merge_side: Option<MergeDriverSide>,
+    /// Any other merge driver than the default text merge
+    merge_driver: Option<String>,
+    binary: bool,
+}
+
+impl MergeDriverSide {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MergeDriverSide::Ours => "ours",
+            MergeDriverSide::Theirs => "theirs",
+        }
+    }
+}
+
+impl CommentSyntax {
+    /// Render the conflicts as a comment, or None if a block comment
+    /// would be terminated early by the conflict content
+    fn comment(&self, conflicts: &[String]) -> Option<String> {
+        let header = concat!(
+            env!("CARGO_PKG_NAME"),
+            ": conflicts replaced by vibe resolution"
+        );
+        match self {
+            CommentSyntax::Line(prefix) => {
+                let mut comment = format!("{} {}\n", prefix, header);
+                for line in conflicts.iter().flat_map(|c| c.lines()) {
+                    comment.push_str(&format!("{} {}\n", prefix, line));
+                }
+                Some(comment)
+            }
+            CommentSyntax::Block(start, end) => {
+                if conflicts.iter().any(|c| c.contains(end)) {
+                    return None;
+                }
+                // Indent the conflicts so their markers don't start a line
+                let mut comment = format!("{}\n{}\n", start, header);
+                for line in conflicts.iter().flat_map(|c| c.lines()) {
+                    comment.push_str(&format!("  {}\n", line));
+                }
+                comment.push_str(&format!("{}\n", end));
+                Some(comment)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 struct ConflictOffsets {
     local: usize,
@@ -101,16 +421,55 @@ struct ConflictOffsets {
     remote: usize,
 }
 
+/// The file path and local start of the conflict, the endpoint, the
+/// model, the beam and the sample of a resolution
+type AppliedKey = (String, usize, usize, String, Option<usize>, Option<usize>);
+
 pub struct GitUtils {
     context_lines: ContextLines,
     in_rebase: bool,
     git_root: Option<String>,
     git_dir: Option<String>,
-    blob_cache: HashMap<String, Arc<String>>,
+    blob_cache: HashMap<String, (Arc<String>, bool)>,
     lmdb_cache: Option<Arc<LmdbCacheImpl>>,
     resolution_mode: ResolutionMode,
     retries: usize,
     max_retries: usize,
+    apply_options: ApplyOptions,
+    per_file_commits: Vec<PerFileCommit>,
+    stash_files: Vec<(String, String)>,
+    local_eof_newlines: HashMap<String, bool>,
+    /// The unmerged files skipped as binary by the last scan
+    binary_files: Vec<String>,
+    /// The unmerged files and their merge driver skipped by the last
+    /// scan, the team manages them with the driver
+    driver_files: Vec<(String, String)>,
+    /// The unmerged files the last scan left to the side selected by
+    /// their merge gitattribute, resolved with the resolutions
+    merge_side_files: Vec<(String, MergeDriverSide)>,
+    /// The conflicts the last scan skipped because they already have
+    /// the resolutions of a previous run
+    already_resolved: usize,
+    /// The resolutions written to the working tree, by conflict and
+    /// model, see applied_keys
+    applied: HashSet<AppliedKey>,
+    backed_up_files: HashSet<String>,
+    /// The pathspecs of the apply options, relative to the git root
+    pathspecs: Vec<String>,
+    /// The gitattributes of the unmerged files, loaded with a single
+    /// git check-attr for all of them
+    file_attributes: HashMap<String, FileAttributes>,
+    /// The conflict marker size of each unmerged file, the conflicts
+    /// located by the patch locator don't carry it
+    marker_sizes: HashMap<String, usize>,
+    /// The files changed by either side since the --since commit or
+    /// time, the other unmerged files are left alone
+    since_files: Option<HashSet<String>>,
+    /// The diff of each file resolved for the output patch
+    file_patches: BTreeMap<String, String>,
+    /// The --time-budget deadline the git calls of the hot path are
+    /// killed at
+    deadline: Option<Instant>,
 }
 
 impl GitUtils {
@@ -118,7 +477,11 @@ impl GitUtils {
     const REBASE_MESSAGE_FILE: &str = "rebase-merge/message";
     const MERGE_MSG_FILE: &str = "MERGE_MSG";
 
-    const DEFAULT_MARKER_SIZE: usize = 7;
+    pub const DEFAULT_MARKER_SIZE: usize = 7;
+    const BREADCRUMBS_SUFFIX: &str = concat!(".", env!("CARGO_PKG_NAME"), "-conflicts");
+    const BACKUP_DIR: &str = concat!(env!("CARGO_PKG_NAME"), "/backups");
+    #[cfg(feature = "telemetry")]
+    const AUDIT_LOG_FILE: &str = concat!(env!("CARGO_PKG_NAME"), "/runs.jsonl");
 
     pub fn new(
         context_lines: ContextLines,
@@ -126,9 +489,31 @@ impl GitUtils {
         cache_overwrite: bool,
         resolution_mode: ResolutionMode,
         retries: usize,
+        apply_options: ApplyOptions,
     ) -> Self {
-        let git_root = Self::get_git_root_uncached().ok();
-        let git_dir = Self::get_git_dir_uncached().ok();
+        let repo = apply_options.repo.as_deref();
+        let git_root = Self::get_git_root_uncached(repo).ok();
+        let git_dir = Self::get_git_dir_uncached(repo).ok();
+        // git status runs in the git root, but the pathspecs are
+        // relative to the repo directory, or to the current one, like
+        // for any git command
+        let prefix = if apply_options.pathspecs.is_empty() && apply_options.only_file.is_none() {
+            String::new()
+        } else {
+            Self::get_git_prefix_uncached(repo).unwrap_or_default()
+        };
+        let pathspecs = match &apply_options.only_file {
+            Some(file_path) => vec![format!(":(literal){}{}", prefix, file_path)],
+            None => apply_options
+                .pathspecs
+                .iter()
+                .map(|pathspec| match pathspec.strip_prefix(':') {
+                    // Pathspec magic is left to git
+                    Some(_) => pathspec.clone(),
+                    None => format!("{}{}", prefix, pathspec),
+                })
+                .collect(),
+        };
         let lmdb_cache = cache_path.map(|path| {
             Arc::new(
                 PatchLocatorCache::create_from_path(&path, cache_overwrite)
@@ -145,6 +530,56 @@ impl GitUtils {
             resolution_mode,
             retries,
             max_retries: retries,
+            apply_options,
+            per_file_commits: Vec::new(),
+            stash_files: Vec::new(),
+            local_eof_newlines: HashMap::new(),
+            binary_files: Vec::new(),
+            driver_files: Vec::new(),
+            merge_side_files: Vec::new(),
+            already_resolved: 0,
+            applied: HashSet::new(),
+            backed_up_files: HashSet::new(),
+            pathspecs,
+            file_attributes: HashMap::new(),
+            marker_sizes: HashMap::new(),
+            since_files: None,
+            file_patches: BTreeMap::new(),
+            deadline: None,
+        }
+    }
+
+    /// git, running in the repository of the apply options
+    fn git(&self) -> GitCommand {
+        GitCommand::git(self.apply_options.repo.as_deref())
+    }
+
+    /// The base of the confidence percentages of the logprobs
+    pub fn confidence_base(&self) -> f64 {
+        self.apply_options.confidence_thresholds.confidence_base
+    }
+
+    /// The confidence of a logprob to show next to the model name
+    fn format_confidence(&self, logprob: f64) -> String {
+        prob::format_confidence(
+            logprob,
+            self.confidence_base(),
+            self.apply_options.raw_logprob,
+        )
+    }
+
+    /// Kill the git calls of the hot path still running at the deadline
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// The timeout of the git calls of the hot path, up to the deadline
+    fn git_timeout(&self) -> Duration {
+        match self.deadline {
+            Some(deadline) => deadline
+                .saturating_duration_since(Instant::now())
+                .clamp(GitCommand::MIN_TIMEOUT, GitCommand::TIMEOUT),
+            None => GitCommand::TIMEOUT,
         }
     }
 
@@ -175,7 +610,7 @@ impl GitUtils {
                 if self.context_lines.code_context_lines == 0 {
                     return false;
                 }
-                println!(
+                logger::status!(
                     "Retrying resolution with reduced --code-context-lines ({} -> {})",
                     self.context_lines.code_context_lines,
                     self.context_lines.code_context_lines.saturating_sub(1)
@@ -186,7 +621,7 @@ impl GitUtils {
                 true
             }
             ResolutionMode::VibeWithPatchLocator => {
-                println!(
+                logger::status!(
                     "Retrying resolution with increased --extra-conflict-lines ({} -> {})",
                     self.context_lines.extra_conflict_lines,
                     self.context_lines
@@ -201,7 +636,7 @@ impl GitUtils {
                 true
             }
             ResolutionMode::Interactive => {
-                println!(
+                logger::status!(
                     "You can retry with --vibe or with reduced --code-context-lines ({} -> {})",
                     self.context_lines.code_context_lines,
                     self.context_lines.code_context_lines.saturating_sub(1)
@@ -219,16 +654,110 @@ impl GitUtils {
         if let Some(p) = path {
             args.push("--");
             args.push(p);
+        } else if !self.pathspecs.is_empty() {
+            args.push("--");
+            args.extend(self.pathspecs.iter().map(|p| p.as_str()));
         }
-        GitCommand::new("git")
+        let mut output = self
+            .git()
             .args(&args)
+            .timeout(self.git_timeout())
             .output()
-            .context("Failed to execute git status --porcelain=v2 -z")
+            .context("Failed to execute git status --porcelain=v2 -z")?;
+        if let Some(since_files) = &self.since_files {
+            output.stdout = Self::filter_unmerged(&output.stdout, since_files);
+        }
+        Ok(output)
+    }
+
+    /// Drop the unmerged entries of the files outside the set from the
+    /// git status --porcelain=v2 -z output
+    fn filter_unmerged(status_output: &[u8], files: &HashSet<String>) -> Vec<u8> {
+        let mut filtered = Vec::new();
+        for line_bytes in status_output.split(|&b| b == b'\0') {
+            if line_bytes.starts_with(b"u ") {
+                let line = String::from_utf8_lossy(line_bytes);
+                let parts: Vec<&str> = line.splitn(11, ' ').collect();
+                if parts.len() >= 11 && !files.contains(parts[10]) {
+                    continue;
+                }
+            }
+            filtered.extend_from_slice(line_bytes);
+            filtered.push(b'\0');
+        }
+        // The output ends with a NUL, not followed by an empty entry
+        filtered.pop();
+        filtered
+    }
+
+    /// Only touch the unmerged files that HEAD or the commits being
+    /// applied changed since the commit or the time. The conflicted
+    /// files in the working tree always differ from any commit because
+    /// of their markers, so the sides of the conflicts are compared
+    /// instead.
+    pub fn set_since(&mut self, since: &str) -> Result<()> {
+        let commit_hashes = self.find_commit_hashes()?;
+        let output = self
+            .git()
+            .args(["rev-parse", "--verify", "--quiet"])
+            .args([format!("{}^{{commit}}", since)])
+            .output()?;
+        let range = if output.status.success() {
+            vec![
+                "--not".to_string(),
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            ]
+        } else {
+            vec![format!("--since={}", since)]
+        };
+        let git_root = self.git_root.as_ref().context("Not in a git repository")?;
+        let output = self
+            .git()
+            .args(["-C", git_root, "log", "--no-renames", "--name-only", "-z"])
+            .args(["--format=", "HEAD"])
+            .args(&commit_hashes)
+            .args(&range)
+            .args(["--"])
+            .timeout(self.git_timeout())
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to list the files changed since {}: {}",
+                since,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let since_files: HashSet<String> = output
+            .stdout
+            .split(|&b| b == b'\0')
+            .filter(|file| !file.is_empty())
+            .map(|file| String::from_utf8_lossy(file).into_owned())
+            .collect();
+        log::info!("{} files changed since {}", since_files.len(), since);
+        self.since_files = Some(since_files);
+        Ok(())
+    }
+
+    /// Check that the --only-file file is unmerged
+    pub fn check_only_file(&self) -> Result<()> {
+        let Some(file_path) = &self.apply_options.only_file else {
+            return Ok(());
+        };
+        let output = self.git_status_porcelain_v2(None)?;
+        if !output
+            .stdout
+            .split(|&b| b == b'\0')
+            .any(|line| line.starts_with(b"u "))
+        {
+            return Err(anyhow::anyhow!("No conflicts found in {}", file_path));
+        }
+        Ok(())
     }
 
     /// Check that git cherry-pick default is diff3 for merge.conflictStyle
     pub fn check_diff3(&self) -> Result<()> {
-        let output = GitCommand::new("git")
+        let output = self
+            .git()
             .args(["config", "--get", "merge.conflictStyle"])
             .output()
             .context("Failed to get git config")?;
@@ -256,256 +785,574 @@ impl GitUtils {
         &mut self,
         max_context_size: u32,
         prev_conflicts: &[ResolvedConflict],
-    ) -> Result<Vec<Conflict>> {
+    ) -> Result<Vec<Conflict>, SynthmergeError> {
+        if self.git_root.is_none() {
+            return Err(SynthmergeError::NotAGitRepo);
+        }
         // Run git status --porcelain=v2 -z to get blob hashes
         let output = self.git_status_porcelain_v2(None)?;
 
         // Parse the status output to find the unmerged entry for this file
         let status_output_bytes = &output.stdout;
-        let lines = status_output_bytes.split(|&b| b == b'\0').peekable();
+        let mut lines = status_output_bytes.split(|&b| b == b'\0');
+        let rename_sources = Self::rename_sources(status_output_bytes);
+        self.load_file_attributes(&Self::unmerged_paths(status_output_bytes))?;
 
         let mut all_conflicts: Vec<Conflict> = Vec::new();
-        for line_bytes in lines {
+        let mut unmerged_files = Vec::new();
+        self.binary_files.clear();
+        self.driver_files.clear();
+        self.merge_side_files.clear();
+        while let Some(line_bytes) = lines.next() {
             let line = String::from_utf8_lossy(line_bytes);
             // Skip header lines
             // if line.starts_with('#') {
             //     continue;
             // }
 
-            // Parse unmerged entries (format: u <XY> <sub> <m1> <m2> <m3> <mW> <h1> <h2> <h3> <path>)
-            if line.starts_with("u UU") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
+            // Renamed entries are followed by their original path
+            if line.starts_with("2 ") {
+                lines.next();
+                continue;
+            }
+
+            // Modify/delete conflicts have no conflict markers, only
+            // report them so they're never skipped silently
+            if line.starts_with("u DU") || line.starts_with("u UD") {
+                let parts: Vec<&str> = line.splitn(11, ' ').collect();
+                if parts.len() >= 11 {
+                    all_conflicts.push(Conflict {
+                        file_path: parts[10].to_string(),
+                        kind: if parts[1] == "DU" {
+                            ConflictKind::DeletedByUs
+                        } else {
+                            ConflictKind::DeletedByThem
+                        },
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+
+            // Parse unmerged entries (format: u <XY> <sub> <m1> <m2> <m3> <mW> <h1> <h2> <h3> <path>),
+            // both sides added the file in add/add and rename/rename conflicts
+            if line.starts_with("u UU") || line.starts_with("u AA") {
+                let parts: Vec<&str> = line.splitn(11, ' ').collect();
                 if parts.len() >= 11 {
                     // let (base_blob, remote_blob) = (parts[7].to_string(), parts[9].to_string());
                     let (local_blob, file_path) = (parts[8].to_string(), parts[10].to_string());
 
-                    let marker_size = self.get_marker_size_for_file(&file_path)?;
+                    // Already resolved into the stash by a previous pass
+                    if self.stash_files.iter().any(|(path, _)| *path == file_path) {
+                        continue;
+                    }
 
-                    let path = Path::new(self.git_root.as_ref().unwrap()).join(&file_path);
-                    let merged_content = Arc::new(
-                        fs::read_to_string(&path)
-                            .context(format!("Failed to read file: {}", file_path))?,
-                    );
+                    // The repository merge policy resolves these files
+                    // without a model
+                    if let Some(side) = self.get_merge_driver_side_for_file(&file_path) {
+                        self.merge_side_files.push((file_path, side));
+                        continue;
+                    }
 
-                    let mut conflicts = self.parse_conflicts(&merged_content, marker_size)?;
-                    if conflicts.is_empty() {
-                        return Err(anyhow::anyhow!(
-                            "No conflicts found in unmerged file: {}",
-                            file_path
-                        ));
+                    if let Some(driver) = self.get_merge_driver_for_file(&file_path) {
+                        log::warn!(
+                            "Skipping unmerged file {} managed by merge={}",
+                            file_path,
+                            driver
+                        );
+                        self.driver_files.push((file_path, driver));
+                        continue;
                     }
-                    for conflict in &mut conflicts {
-                        conflict.file_path = file_path.to_string();
-                        conflict.marker_size = marker_size;
+
+                    if self.is_binary_attribute_set(&file_path) {
+                        self.skip_binary_file(file_path);
+                        continue;
                     }
 
-                    // Get the blob contents
-                    let local_content = self.get_blob_content_cached(&local_blob)?;
-
-                    // Compute diff between base and remote using git command
-                    // let diff = GitCommand::new("git")
-                    //     .args([
-                    //         "diff",
-                    //         "--pretty=",
-                    //         "--no-color",
-                    //         "--histogram",
-                    //         &format!("-U{}", self.context_lines.patch_context_lines),
-                    //         &base_blob.to_string(),
-                    //         &remote_blob.to_string(),
-                    //     ])
-                    //     .output()
-                    //     .context("Failed to execute git diff for blob")?;
-
-                    // let diff = Arc::new(String::from_utf8_lossy(&diff.stdout).to_string());
-
-                    let merged_content_lines: Vec<String> = merged_content
-                        .split_inclusive('\n')
-                        .map(|s| s.to_string())
-                        .collect();
-
-                    let remove_conflict_markers =
-                        |mode: ConflictMarkerMode| -> Result<(Arc<Vec<String>>, Arc<String>)> {
-                            let cleaned_lines: Vec<String> = Self::remove_conflict_markers(
-                                &merged_content_lines
-                                    .iter()
-                                    .map(|s| s.as_str())
-                                    .collect::<Vec<_>>(),
-                                marker_size,
-                                mode,
-                            )?
-                            .iter()
-                            .map(|s| s.to_string())
-                            .collect();
-                            let cleaned_content = Arc::new(cleaned_lines.join(""));
-                            Ok((Arc::new(cleaned_lines), cleaned_content))
-                        };
-
-                    let (merged_local_lines, merged_local_content) =
-                        remove_conflict_markers(ConflictMarkerMode::Local)?;
-                    let (_, merged_base_content) =
-                        remove_conflict_markers(ConflictMarkerMode::Base)?;
-                    let (_, merged_remote_content) =
-                        remove_conflict_markers(ConflictMarkerMode::Remote)?;
-
-                    let clean_diff = Arc::new(ConflictResolver::create_diff(
-                        &local_content,
-                        &merged_local_content,
-                        self.context_lines.patch_context_lines,
-                    ));
-                    let conflict_diff = if true {
-                        Arc::new(ConflictResolver::create_diff(
-                            &merged_base_content,
-                            &merged_remote_content,
-                            self.context_lines.patch_context_lines,
-                        ))
-                    } else {
-                        let temp_dir = tempfile::Builder::new()
-                            .permissions(Permissions::from_mode(0o700))
-                            .prefix("synthmerge_")
-                            .tempdir_in("/dev/shm")?;
-
-                        let creat = &mut OpenOptions::new();
-                        creat.read(true).write(true).create_new(true).mode(0o600);
-
-                        let base_path = temp_dir.path().join("base");
-                        let remote_path = temp_dir.path().join("remote");
-
-                        let mut base = creat.open(&base_path)?;
-                        let mut remote = creat.open(&remote_path)?;
-
-                        std::io::Write::write_all(&mut base, merged_base_content.as_bytes())?;
-                        std::io::Write::write_all(&mut remote, merged_remote_content.as_bytes())?;
-
-                        let output = GitCommand::new("git")
-                            .verbose(false)
-                            .args([
-                                "diff",
-                                "--no-index",
-                                "--histogram",
-                                &format!("-U{}", self.context_lines.patch_context_lines),
-                                base_path.to_str().unwrap(),
-                                remote_path.to_str().unwrap(),
-                            ])
-                            .output()
-                            .context("Failed to execute git diff for conflicts")?;
-
-                        Arc::new(String::from_utf8_lossy(&output.stdout).to_string())
-                    };
-
-                    if self.resolution_mode == ResolutionMode::VibeWithPatchLocator {
-                        let patch_locator = PatchLocator::new(
-                            local_content.clone(),
-                            merged_local_content.clone(),
-                            merged_local_lines.clone(),
-                            clean_diff.clone(),
-                            conflict_diff.clone(),
-                            self.lmdb_cache.clone(),
-                            self.context_lines,
-                            max_context_size,
+                    let path = Path::new(self.git_root.as_ref().unwrap()).join(&file_path);
+                    if !path.is_file() {
+                        log::warn!(
+                            "Skipping unmerged file {}: not found in the working tree",
+                            file_path
                         );
-                        patch_locator.patch_locator(&mut conflicts)?;
+                        continue;
                     }
-
-                    for conflict in &mut conflicts {
-                        conflict.merged_local_lines = merged_local_lines.clone();
+                    let renamed_from = rename_sources.get(&local_blob).cloned();
+                    if let Some(renamed_from) = &renamed_from {
+                        log::info!("Conflict in {} renamed from {}", file_path, renamed_from);
                     }
+                    // The blob cache is filled serially, the files are
+                    // parsed in parallel below
+                    let (local_content, eof_newline) = self.get_blob_content_cached(&local_blob)?;
+                    self.local_eof_newlines
+                        .insert(file_path.to_string(), eof_newline);
+                    unmerged_files.push(UnmergedFile {
+                        file_path,
+                        path,
+                        renamed_from,
+                        local_content,
+                    });
+                }
+            }
+        }
 
-                    // Replace solved conflicts with previous ones if hunks are identical
-                    Self::replace_solved_conflicts(&mut conflicts, prev_conflicts);
+        // Each file is parsed independently, sort the conflicts so
+        // the order doesn't depend on the scheduling
+        let conflicts = unmerged_files
+            .par_iter()
+            .map(|unmerged_file| {
+                self.parse_unmerged_file(unmerged_file, max_context_size, prev_conflicts)
+            })
+            .collect::<Result<Vec<_>, SynthmergeError>>()?;
+        for (unmerged_file, parsed) in unmerged_files.iter().zip(conflicts) {
+            let Some((marker_size, conflicts)) = parsed else {
+                self.skip_binary_file(unmerged_file.file_path.clone());
+                continue;
+            };
+            self.marker_sizes
+                .insert(unmerged_file.file_path.clone(), marker_size);
+            all_conflicts.extend(conflicts);
+        }
+        all_conflicts.sort_by(|a, b| {
+            a.file_path
+                .cmp(&b.file_path)
+                .then(a.start_line.cmp(&b.start_line))
+        });
 
-                    all_conflicts.extend(conflicts);
+        // Another block of resolutions would stack over the one of the
+        // previous run, vibe mode replaces the whole conflict
+        self.already_resolved = 0;
+        if self.resolution_mode == ResolutionMode::Interactive && !self.apply_options.force {
+            all_conflicts.retain(|conflict| {
+                if conflict.has_ai_block {
+                    log::warn!(
+                        "Skipping conflict {}:{}, it already has the resolutions of a previous run",
+                        conflict.file_path,
+                        conflict.start_line
+                    );
+                    self.already_resolved += 1;
                 }
-            }
+                !conflict.has_ai_block
+            });
         }
 
         Ok(all_conflicts)
     }
 
-    /// Replace new conflicts with previous ones if their hunks are identical.
-    /// This is used to avoid re-resolving conflicts that haven't changed.
-    fn replace_solved_conflicts(conflicts: &mut [Conflict], prev_conflicts: &[ResolvedConflict]) {
-        for conflict in conflicts.iter_mut() {
-            if let Some(prev_conflict) = prev_conflicts.iter().find(|p| {
-                p.conflict.file_path == conflict.file_path
-                    && p.conflict.conflict_patch == conflict.conflict_patch
-            }) {
-                *conflict = prev_conflict.conflict.clone();
+    /// The unmerged files the last scan skipped as binary, they still
+    /// need a manual resolution
+    pub fn binary_files(&self) -> &[String] {
+        &self.binary_files
+    }
+
+    /// The unmerged files the last scan skipped because a custom merge
+    /// driver manages them, with the name of the driver
+    pub fn driver_files(&self) -> &[(String, String)] {
+        &self.driver_files
+    }
+
+    /// Resolve the unmerged files the last scan left to their merge
+    /// gitattribute, ours or theirs, and stage them. The apply
+    /// functions call it, call it directly if there's nothing else to
+    /// apply.
+    pub fn resolve_merge_side_files(&mut self) -> Result<(), SynthmergeError> {
+        for (file_path, side) in std::mem::take(&mut self.merge_side_files) {
+            if self.apply_options.dry_run {
+                println!("Would resolve {} with merge={}", file_path, side.as_str());
+            } else {
+                self.resolve_with_merge_driver(&file_path, side)?;
             }
         }
+        Ok(())
     }
 
-    /// Get the content of a git blob, using cache if available
-    fn get_blob_content_cached(&mut self, blob_hash: &str) -> Result<Arc<String>> {
-        if let Some(cached) = self.blob_cache.get(blob_hash) {
-            return Ok(cached.clone());
+    /// The number of conflicts the last scan skipped because they
+    /// already have the resolutions of a previous run, see
+    /// [`ApplyOptions::force`]
+    pub fn already_resolved(&self) -> usize {
+        self.already_resolved
+    }
+
+    /// If the resolution was written to the working tree, as the
+    /// resolved file in vibe mode or next to the conflict markers
+    pub fn is_applied(&self, resolved_conflict: &ResolvedConflict) -> bool {
+        Self::applied_keys(resolved_conflict).any(|key| self.applied.contains(&key))
+    }
+
+    fn record_applied(&mut self, resolved_conflicts: &[&ResolvedConflict]) {
+        for resolved_conflict in resolved_conflicts {
+            self.applied.extend(Self::applied_keys(resolved_conflict));
         }
+    }
 
-        let content = Arc::new(self.get_blob_content(blob_hash)?);
-        self.blob_cache
-            .insert(blob_hash.to_string(), content.clone());
-        Ok(content)
+    /// The conflict and the model of each resolution merged by the
+    /// deduplication. Not the resolved version, which --match-indent
+    /// and the normalization of the agreement can change.
+    fn applied_keys(resolved_conflict: &ResolvedConflict) -> impl Iterator<Item = AppliedKey> {
+        let resolutions = if resolved_conflict.deduplicated_conflicts.is_empty() {
+            std::slice::from_ref(resolved_conflict)
+        } else {
+            resolved_conflict.deduplicated_conflicts.as_slice()
+        };
+        resolutions.iter().map(|resolution| {
+            (
+                resolution.conflict.file_path.clone(),
+                resolution.conflict.local_start,
+                resolution.endpoint,
+                resolution.model.clone(),
+                resolution.beam,
+                resolution.multi,
+            )
+        })
     }
 
-    /// Get the content of a git blob
-    fn get_blob_content(&self, blob_hash: &str) -> Result<String> {
-        let git_root = self.git_root.as_ref().unwrap();
-        let output = GitCommand::new("git")
-            .args(["-C", git_root, "show", blob_hash])
-            .output()
-            .context("Failed to execute git show for blob")?;
+    fn skip_binary_file(&mut self, file_path: String) {
+        log::warn!("Skipping unmerged binary file {}", file_path);
+        self.binary_files.push(file_path);
+    }
 
-        if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Git show for blob {} failed: {}",
-                blob_hash,
-                String::from_utf8_lossy(&output.stderr)
-            ));
+    /// The text of a file, None if it's binary: a NUL byte in its
+    /// first 8000 bytes like git's heuristic, or not valid UTF-8
+    fn text_content(content: Vec<u8>) -> Option<String> {
+        if content.iter().take(8000).any(|&b| b == 0) {
+            return None;
         }
+        String::from_utf8(content).ok()
+    }
 
-        let mut content = String::from_utf8_lossy(&output.stdout).to_string();
+    /// Parse the conflicts of an unmerged file and locate their
+    /// patches, returning them with the marker size of the file, None
+    /// if the file is binary
+    fn parse_unmerged_file(
+        &self,
+        unmerged_file: &UnmergedFile,
+        max_context_size: u32,
+        prev_conflicts: &[ResolvedConflict],
+    ) -> Result<Option<(usize, Vec<Conflict>)>, SynthmergeError> {
+        let UnmergedFile {
+            file_path,
+            path,
+            renamed_from,
+            local_content,
+        } = unmerged_file;
+        let content = fs::read(path).context(format!("Failed to read file: {}", file_path))?;
+        let Some(merged_content) = Self::text_content(content) else {
+            return Ok(None);
+        };
+        let merged_content = Arc::new(merged_content);
 
-        // Check if the blob is empty
-        if content.is_empty() {
-            return Err(anyhow::anyhow!("Blob {} is empty", blob_hash));
-        }
+        let marker_size = self.get_marker_size_for_file(file_path, &merged_content)?;
 
-        // Check if the content is newline terminated
-        if !content.ends_with('\n') {
-            content.push('\n');
+        let mut conflicts = self.parse_file_conflicts(file_path, &merged_content, marker_size)?;
+        for conflict in &mut conflicts {
+            conflict.renamed_from = renamed_from.clone();
         }
 
-        Ok(content)
-    }
+        // Compute diff between base and remote using git command
+        // let diff = self.git()
+        //     .args([
+        //         "diff",
+        //         "--pretty=",
+        //         "--no-color",
+        //         "--histogram",
+        //         &format!("-U{}", self.context_lines.patch_context_lines),
+        //         &base_blob.to_string(),
+        //         &remote_blob.to_string(),
+        //     ])
+        //     .output()
+        //     .context("Failed to execute git diff for blob")?;
+
+        // let diff = Arc::new(String::from_utf8_lossy(&diff.stdout).to_string());
+
+        let merged_content_lines: Vec<String> = merged_content
+            .split_inclusive('\n')
+            .map(|s| s.to_string())
+            .collect();
 
-    /// Create a regex pattern for matching conflict markers
-    fn create_conflict_regex(marker_size: usize) -> Result<Regex> {
-        Regex::new(&format!(
-            r"(?ms)(^{}(?: .*?)?\n.*?^{}(?: .*?)?\n.*?^{}(?: .*?)?\n.*?^{}(?: .*?)?(?:\n|$))",
-            Self::create_local_marker(marker_size),
-            Self::create_base_marker(marker_size)
-                .chars()
-                .map(|c| format!(r"\{}", c))
-                .collect::<String>(),
-            Self::create_remote_marker(marker_size),
-            Self::create_end_marker(marker_size),
-        ))
-        .map_err(|e| anyhow::anyhow!("Failed to create conflict regex: {}", e))
-    }
+        let remove_conflict_markers =
+            |mode: ConflictMarkerMode| -> Result<(Arc<Vec<String>>, Arc<String>)> {
+                let cleaned_lines: Vec<String> = Self::remove_conflict_markers(
+                    &merged_content_lines
+                        .iter()
+                        .map(|s| s.as_str())
+                        .collect::<Vec<_>>(),
+                    marker_size,
+                    mode,
+                    self.ignore_stray_markers(),
+                )?
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+                let cleaned_content = Arc::new(cleaned_lines.join(""));
+                Ok((Arc::new(cleaned_lines), cleaned_content))
+            };
 
-    /// Parse conflicts from file content
-    fn parse_conflicts(&self, content: &str, marker_size: usize) -> Result<Vec<Conflict>> {
-        let mut conflicts = Vec::new();
+        let merged_local_lines = conflicts[0].merged_local_lines.clone();
+        let merged_local_content = Arc::new(merged_local_lines.join(""));
+        let (_, merged_base_content) = remove_conflict_markers(ConflictMarkerMode::Base)?;
+        let (_, merged_remote_content) = remove_conflict_markers(ConflictMarkerMode::Remote)?;
 
-        let re = Self::create_conflict_regex(marker_size)?;
+        let clean_diff = Arc::new(ConflictResolver::create_diff(
+            local_content,
+            &merged_local_content,
+            self.context_lines.patch_context_lines,
+        ));
+        let conflict_diff = if true {
+            Arc::new(ConflictResolver::create_diff(
+                &merged_base_content,
+                &merged_remote_content,
+                self.context_lines.patch_context_lines,
+            ))
+        } else {
+            let temp_dir = tempfile::Builder::new()
+                .permissions(Permissions::from_mode(0o700))
+                .prefix("synthmerge_")
+                .tempdir_in("/dev/shm")?;
+
+            let creat = &mut OpenOptions::new();
+            creat.read(true).write(true).create_new(true).mode(0o600);
+
+            let base_path = temp_dir.path().join("base");
+            let remote_path = temp_dir.path().join("remote");
+
+            let mut base = creat.open(&base_path)?;
+            let mut remote = creat.open(&remote_path)?;
+
+            std::io::Write::write_all(&mut base, merged_base_content.as_bytes())?;
+            std::io::Write::write_all(&mut remote, merged_remote_content.as_bytes())?;
+
+            let output = self
+                .git()
+                .verbose(false)
+                .args([
+                    "diff",
+                    "--no-index",
+                    "--histogram",
+                    &format!("-U{}", self.context_lines.patch_context_lines),
+                    base_path.to_str().unwrap(),
+                    remote_path.to_str().unwrap(),
+                ])
+                .output()
+                .context("Failed to execute git diff for conflicts")?;
 
-        let mut offsets = ConflictOffsets::default();
-        for cap in re.captures_iter(content) {
-            let this_cap = cap.get(0).unwrap();
-            let conflict_text = this_cap.as_str();
-            let start_line = content[..this_cap.start()]
-                .chars()
-                .filter(|&c| c == '\n')
-                .count();
+            Arc::new(String::from_utf8_lossy(&output.stdout).to_string())
+        };
+
+        if self.resolution_mode == ResolutionMode::VibeWithPatchLocator {
+            let patch_locator = PatchLocator::new(
+                local_content.clone(),
+                merged_local_content.clone(),
+                merged_local_lines.clone(),
+                clean_diff.clone(),
+                conflict_diff.clone(),
+                self.lmdb_cache.clone(),
+                self.context_lines,
+                max_context_size,
+            );
+            patch_locator.patch_locator(&mut conflicts)?;
+        }
+
+        for conflict in &mut conflicts {
+            conflict.merged_local_lines = merged_local_lines.clone();
+        }
+
+        // Replace solved conflicts with previous ones if hunks are identical
+        Self::replace_solved_conflicts(&mut conflicts, prev_conflicts);
+
+        Ok(Some((marker_size, conflicts)))
+    }
+
+    /// Parse the conflicts of the content of a file, without reading
+    /// anything from the repository. The merged lines of the conflicts
+    /// are the content with each conflict resolved to the local side.
+    pub fn parse_file_conflicts(
+        &self,
+        file_path: &str,
+        content: &str,
+        marker_size: usize,
+    ) -> Result<Vec<Conflict>, SynthmergeError> {
+        let conflict_markers_error = |reason: String| SynthmergeError::ConflictMarkers {
+            file_path: file_path.to_string(),
+            reason,
+        };
+        let mut conflicts = self
+            .parse_conflicts(content, marker_size)
+            .map_err(|e| conflict_markers_error(e.to_string()))?;
+        if conflicts.is_empty() {
+            return Err(conflict_markers_error(
+                "no conflicts found in unmerged file".to_string(),
+            ));
+        }
+        let content_lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let merged_local_lines = Arc::new(
+            Self::remove_conflict_markers(
+                &content_lines,
+                marker_size,
+                ConflictMarkerMode::Local,
+                self.ignore_stray_markers(),
+            )
+            .map_err(|e| conflict_markers_error(e.to_string()))?
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>(),
+        );
+        let definition_regex = Self::definition_regex(file_path);
+        for conflict in &mut conflicts {
+            conflict.file_path = file_path.to_string();
+            conflict.marker_size = marker_size;
+            conflict.merged_local_lines = merged_local_lines.clone();
+            if let Some(regex) = &definition_regex {
+                conflict.enclosing_symbol = Self::enclosing_symbol(
+                    regex,
+                    &merged_local_lines[..conflict.local_start.min(merged_local_lines.len())],
+                );
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// The paths of the files both sides changed, whose gitattributes
+    /// are loaded before the scan
+    fn unmerged_paths(status_output: &[u8]) -> Vec<String> {
+        let mut unmerged_paths = Vec::new();
+        let mut lines = status_output.split(|&b| b == b'\0');
+        while let Some(line_bytes) = lines.next() {
+            let line = String::from_utf8_lossy(line_bytes);
+            if line.starts_with("2 ") {
+                lines.next();
+                continue;
+            }
+            if line.starts_with("u UU") || line.starts_with("u AA") {
+                let parts: Vec<&str> = line.splitn(11, ' ').collect();
+                if parts.len() >= 11 {
+                    unmerged_paths.push(parts[10].to_string());
+                }
+            }
+        }
+        unmerged_paths
+    }
+
+    /// Map the blobs of the files whose deletion is staged to their
+    /// paths. When the remote side renamed a file, git stages the
+    /// deletion of the old path and the local side of the unmerged
+    /// destination path is the blob of the old path.
+    fn rename_sources(status_output: &[u8]) -> HashMap<String, String> {
+        let mut rename_sources = HashMap::new();
+        let mut lines = status_output.split(|&b| b == b'\0');
+        while let Some(line_bytes) = lines.next() {
+            let line = String::from_utf8_lossy(line_bytes);
+            if line.starts_with("2 ") {
+                lines.next();
+                continue;
+            }
+            // Format: 1 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <path>
+            if line.starts_with("1 D.") {
+                let parts: Vec<&str> = line.splitn(9, ' ').collect();
+                if parts.len() == 9 {
+                    rename_sources.insert(parts[6].to_string(), parts[8].to_string());
+                }
+            }
+        }
+        rename_sources
+    }
+
+    /// Replace new conflicts with previous ones if their hunks are identical.
+    /// This is used to avoid re-resolving conflicts that haven't changed.
+    fn replace_solved_conflicts(conflicts: &mut [Conflict], prev_conflicts: &[ResolvedConflict]) {
+        for conflict in conflicts.iter_mut() {
+            if let Some(prev_conflict) = prev_conflicts.iter().find(|p| {
+                p.conflict.file_path == conflict.file_path
+                    && p.conflict.conflict_patch == conflict.conflict_patch
+            }) {
+                *conflict = prev_conflict.conflict.clone();
+            }
+        }
+    }
+
+    /// Get the content of a git blob, using cache if available. Also
+    /// returns if the blob was newline terminated.
+    fn get_blob_content_cached(&mut self, blob_hash: &str) -> Result<(Arc<String>, bool)> {
+        if let Some(cached) = self.blob_cache.get(blob_hash) {
+            return Ok(cached.clone());
+        }
+
+        let (content, eof_newline) = self.get_blob_content(blob_hash)?;
+        let content = Arc::new(content);
+        self.blob_cache
+            .insert(blob_hash.to_string(), (content.clone(), eof_newline));
+        Ok((content, eof_newline))
+    }
+
+    /// Get the content of a git blob, always newline terminated, and
+    /// if the blob itself was newline terminated
+    fn get_blob_content(&self, blob_hash: &str) -> Result<(String, bool)> {
+        let git_root = self.git_root.as_ref().unwrap();
+        let output = self
+            .git()
+            .args(["-C", git_root, "show", blob_hash])
+            .timeout(self.git_timeout())
+            .output()
+            .context("Failed to execute git show for blob")?;
+
+        if !output.status.success() {
+            return Err(
+                SynthmergeError::git(format!("show for blob {}", blob_hash), &output).into(),
+            );
+        }
+
+        let mut content = String::from_utf8_lossy(&output.stdout).to_string();
+
+        // Check if the blob is empty
+        if content.is_empty() {
+            return Err(anyhow::anyhow!("Blob {} is empty", blob_hash));
+        }
+
+        // Check if the content is newline terminated
+        let eof_newline = content.ends_with('\n');
+        if !eof_newline {
+            content.push('\n');
+        }
+
+        Ok((content, eof_newline))
+    }
+
+    /// Create a regex pattern for matching conflict markers, the base
+    /// section is optional to match merge.conflictStyle=merge conflicts
+    fn create_conflict_regex(marker_size: usize) -> Result<Regex> {
+        Regex::new(&format!(
+            r"(?ms)(^{}(?: .*?)?\r?\n.*?(?:^{}(?: .*?)?\r?\n.*?)?^{}(?: .*?)?\r?\n.*?^{}(?: .*?)?(?:\r?\n|$))",
+            Self::create_local_marker(marker_size),
+            Self::create_base_marker(marker_size)
+                .chars()
+                .map(|c| format!(r"\{}", c))
+                .collect::<String>(),
+            Self::create_remote_marker(marker_size),
+            Self::create_end_marker(marker_size),
+        ))
+        .map_err(|e| anyhow::anyhow!("Failed to create conflict regex: {}", e))
+    }
+
+    /// Parse conflicts from file content
+    fn parse_conflicts(&self, content: &str, marker_size: usize) -> Result<Vec<Conflict>> {
+        let mut conflicts = Vec::new();
+
+        let re = Self::create_conflict_regex(marker_size)?;
+
+        let mut offsets = ConflictOffsets::default();
+        for cap in re.captures_iter(content) {
+            let this_cap = cap.get(0).unwrap();
+            let conflict_text = this_cap.as_str();
+            let start_line = content[..this_cap.start()]
+                .chars()
+                .filter(|&c| c == '\n')
+                .count();
+            if self.ignore_stray_markers() && Self::is_stray_conflict(conflict_text, marker_size) {
+                log::warn!(
+                    "Ignoring conflict markers without a base section at line {}",
+                    start_line + 1
+                );
+                continue;
+            }
             let conflict = self.parse_conflict_text(
                 conflict_text,
                 content,
@@ -519,6 +1366,41 @@ impl GitUtils {
         Ok(conflicts)
     }
 
+    /// Check if a line is a conflict marker of the given size, with or
+    /// without a label, so the context of a conflict never extends
+    /// into an adjacent one
+    fn is_any_marker_line(line: &str, marker_size: usize) -> bool {
+        [
+            Self::create_local_marker(marker_size),
+            Self::create_base_marker(marker_size),
+            Self::create_remote_marker(marker_size),
+            Self::create_ai_marker(marker_size),
+            Self::create_end_marker(marker_size),
+        ]
+        .iter()
+        .any(|marker| {
+            line.strip_prefix(marker.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\r', '\n']))
+        })
+    }
+
+    /// Stray markers can only be told apart from the conflicts with a
+    /// base section
+    fn ignore_stray_markers(&self) -> bool {
+        self.apply_options.ignore_stray_markers
+            && self.apply_options.conflict_style == ConflictStyle::Diff3
+    }
+
+    /// Check if a block matching the conflict regex has no base
+    /// section, so it can't be a conflict written with the diff3 style
+    fn is_stray_conflict(conflict_text: &str, marker_size: usize) -> bool {
+        let base_marker = Self::create_base_marker(marker_size);
+        !conflict_text.split_inclusive('\n').any(|line| {
+            line.strip_prefix(base_marker.as_str())
+                .is_some_and(|rest| rest.starts_with([' ', '\n']))
+        })
+    }
+
     fn gen_context(
         &self,
         conflict_lines: &[&str],
@@ -527,17 +1409,23 @@ impl GitUtils {
         marker_size: usize,
         mode: ConflictMarkerMode,
     ) -> Result<(usize, usize, String, String)> {
-        let head_context_end = start_line;
-        let head_content_lines = &content_lines[..head_context_end].to_vec();
+        // A conflict at the first line of the file has no head context
+        // and one ending at the last line has no tail context
+        let head_content_lines = content_lines.get(..start_line).unwrap_or_default();
 
         const CONTEXT_BEYOND_MARKER: bool = false;
         let head_content_lines = if CONTEXT_BEYOND_MARKER {
-            Self::remove_conflict_markers(head_content_lines, marker_size, mode)
+            Self::remove_conflict_markers(
+                head_content_lines,
+                marker_size,
+                mode,
+                self.ignore_stray_markers(),
+            )
         } else {
             Ok(head_content_lines
                 .iter()
                 .rev()
-                .take_while(|&&x| !x.starts_with(&Self::create_end_marker(marker_size)))
+                .take_while(|&&x| !Self::is_any_marker_line(x, marker_size))
                 .cloned()
                 .collect::<Vec<_>>()
                 .iter()
@@ -551,15 +1439,20 @@ impl GitUtils {
             .to_vec();
         let nr_head_context_lines = head_context_lines.len();
 
-        let tail_content_lines = &content_lines[start_line + conflict_lines.len()..];
+        let tail_content_lines = content_lines
+            .get(start_line + conflict_lines.len()..)
+            .unwrap_or_default();
         let tail_content_lines = if CONTEXT_BEYOND_MARKER {
-            Self::remove_conflict_markers(tail_content_lines, marker_size, mode)
+            Self::remove_conflict_markers(
+                tail_content_lines,
+                marker_size,
+                mode,
+                self.ignore_stray_markers(),
+            )
         } else {
             Ok(tail_content_lines
                 .iter()
-                .take_while(|&&x| {
-                    !x.starts_with(&format!("{} ", Self::create_local_marker(marker_size)))
-                })
+                .take_while(|&&x| !Self::is_any_marker_line(x, marker_size))
                 .cloned()
                 .collect::<Vec<_>>())
         }?;
@@ -611,18 +1504,23 @@ impl GitUtils {
             })
             .context("Failed to find head marker")?;
 
-        let base_start = conflict_lines
+        let remote_start = conflict_lines
             .iter()
             .position(|&line| {
-                line.starts_with(&format!("{} ", Self::create_base_marker(marker_size)))
+                // CRLF files get CRLF conflict markers
+                line.strip_prefix(&Self::create_remote_marker(marker_size))
+                    .is_some_and(|rest| rest == "\n" || rest == "\r\n")
             })
-            .context("Failed to find base marker")?;
-
-        let remote_start = conflict_lines
-            .iter()
-            .position(|&line| line == format!("{}\n", Self::create_remote_marker(marker_size)))
             .context("Failed to find conflict marker")?;
 
+        let base_start = conflict_lines.iter().position(|&line| {
+            line.starts_with(&format!("{} ", Self::create_base_marker(marker_size)))
+        });
+        if base_start.is_none() && self.apply_options.conflict_style == ConflictStyle::Diff3 {
+            anyhow::bail!("Failed to find base marker, use --conflict-style=merge");
+        }
+        let local_end = base_start.unwrap_or(remote_start);
+
         let remote_end = conflict_lines
             .iter()
             .position(|&line| line.starts_with(&Self::create_end_marker(marker_size)))
@@ -637,11 +1535,11 @@ impl GitUtils {
 
         if remote_end < ai_start
             || remote_end <= remote_start
-            || remote_start <= base_start
-            || base_start <= local_start
+            || base_start.is_some_and(|base_start| remote_start <= base_start)
+            || local_end <= local_start
         {
             anyhow::bail!(
-                "Invalid conflict markers: ai_start={}, remote_end={}, remote_start={}, base_start={}, local_start={}",
+                "Invalid conflict markers: ai_start={}, remote_end={}, remote_start={}, base_start={:?}, local_start={}",
                 ai_start,
                 remote_end,
                 remote_start,
@@ -658,9 +1556,14 @@ impl GitUtils {
             );
         }
 
-        let local_lines: Vec<&str> = conflict_lines[local_start + 1..base_start].to_vec();
-        let base_lines: Vec<&str> = conflict_lines[base_start + 1..remote_start].to_vec();
+        let local_lines: Vec<&str> = conflict_lines[local_start + 1..local_end].to_vec();
+        // Without a base section the base is empty
+        let base_lines: Vec<&str> = match base_start {
+            Some(base_start) => conflict_lines[base_start + 1..remote_start].to_vec(),
+            None => Vec::new(),
+        };
         let remote_lines: Vec<&str> = conflict_lines[remote_start + 1..ai_start].to_vec();
+        let has_ai_block = ai_start != remote_end;
 
         let content_lines: Vec<&str> = content.split_inclusive('\n').collect();
 
@@ -705,6 +1608,8 @@ impl GitUtils {
 
         Ok(Conflict {
             conflict_code,
+            base,
+            remote,
             conflict_patch,
             conflict_raw_patch,
             head_context,
@@ -719,40 +1624,220 @@ impl GitUtils {
             remote_end,
             nr_head_context_lines,
             nr_tail_context_lines,
+            has_ai_block,
             ..Default::default()
         })
     }
 
-    /// Get the marker size for a specific file from gitattributes
-    fn get_marker_size_for_file(&self, file_path: &str) -> Result<usize> {
-        // Check if we can find the marker size in gitattributes for this file
+    /// Get the marker size for a specific file from the override of the
+    /// options or from gitattributes, falling back to the marker size
+    /// detected from its content
+    fn get_marker_size_for_file(&self, file_path: &str, content: &str) -> Result<usize> {
+        if let Some(size) = self.apply_options.marker_size.or_else(|| {
+            self.file_attributes
+                .get(file_path)
+                .and_then(|attributes| attributes.marker_size)
+        }) {
+            return Ok(size);
+        }
+
+        // Detect it from the content, or default to 7
+        Ok(Self::detect_marker_size(content)
+            .map_err(|e| SynthmergeError::ConflictMarkers {
+                file_path: file_path.to_string(),
+                reason: e.to_string(),
+            })?
+            .unwrap_or(Self::DEFAULT_MARKER_SIZE))
+    }
+
+    /// Merge the local, base and remote files of `git mergetool` with
+    /// `git merge-file`, returning the merged content with diff3 conflict
+    /// markers, the marker size and whether it has any conflict. The
+    /// markers are longer than any marker-like line of the inputs.
+    pub fn merge_file(local: &str, base: &str, remote: &str) -> Result<(String, usize, bool)> {
+        let mut marker_size = Self::DEFAULT_MARKER_SIZE;
+        for path in [local, base, remote] {
+            let content = fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+            for line in content.split(|&c| c == b'\n') {
+                let Some(&first) = line.first() else {
+                    continue;
+                };
+                if b"<|=>".contains(&first) {
+                    let run = line.iter().take_while(|&&c| c == first).count();
+                    marker_size = marker_size.max(run + 1);
+                }
+            }
+        }
         let output = GitCommand::new("git")
+            .verbose(false)
+            .args([
+                "merge-file",
+                "-p",
+                "--diff3",
+                &format!("--marker-size={}", marker_size),
+                "-L",
+                "LOCAL",
+                "-L",
+                "BASE",
+                "-L",
+                "REMOTE",
+                local,
+                base,
+                remote,
+            ])
+            .timeout(GitCommand::TIMEOUT)
+            .output()?;
+        // The exit status is the number of conflicts, negative on errors
+        match output.status.code() {
+            Some(code) if (0..128).contains(&code) => Ok((
+                String::from_utf8(output.stdout).context("The merged file is not UTF-8")?,
+                marker_size,
+                code > 0,
+            )),
+            _ => Err(anyhow::anyhow!(
+                "git merge-file failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        }
+    }
+
+    /// Detect the marker size from the run length of the first local
+    /// marker whose conflict has remote and end markers of the same size
+    pub fn detect_marker_size(content: &str) -> Result<Option<usize>> {
+        let local_re = Regex::new(r"(?m)^(<{3,})(?: .*)?$").unwrap();
+        let mut first_line = None;
+        for cap in local_re.captures_iter(content) {
+            let local_marker = cap.get(1).unwrap();
+            let marker_size = local_marker.len();
+            let re = Self::create_conflict_regex(marker_size)?;
+            if let Some(m) = re.find_at(content, local_marker.start())
+                && m.start() == local_marker.start()
+            {
+                return Ok(Some(marker_size));
+            }
+            first_line
+                .get_or_insert_with(|| content[..local_marker.start()].matches('\n').count() + 1);
+        }
+        match first_line {
+            Some(line) => Err(anyhow::anyhow!(
+                "Conflict at line {} doesn't have remote and end markers of the same size",
+                line
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the side selected by the merge gitattribute of a file, if
+    /// it is `ours` or `theirs`
+    fn get_merge_driver_side_for_file(&self, file_path: &str) -> Option<MergeDriverSide> {
+        self.file_attributes
+            .get(file_path)
+            .and_then(|attributes| attributes.merge_side)
+    }
+
+    /// Get the custom merge driver of a file, if its merge gitattribute
+    /// selects anything but the text merge or `ours` and `theirs`
+    fn get_merge_driver_for_file(&self, file_path: &str) -> Option<String> {
+        self.file_attributes
+            .get(file_path)
+            .and_then(|attributes| attributes.merge_driver.clone())
+    }
+
+    /// Check if the file has the binary gitattribute
+    fn is_binary_attribute_set(&self, file_path: &str) -> bool {
+        self.file_attributes
+            .get(file_path)
+            .is_some_and(|attributes| attributes.binary)
+    }
+
+    /// Load the gitattributes of the files not loaded yet, with a
+    /// single git check-attr instead of one per file
+    fn load_file_attributes(&mut self, file_paths: &[String]) -> Result<()> {
+        let file_paths: Vec<&String> = file_paths
+            .iter()
+            .filter(|file_path| !self.file_attributes.contains_key(*file_path))
+            .collect();
+        if file_paths.is_empty() {
+            return Ok(());
+        }
+        let input: Vec<u8> = file_paths
+            .iter()
+            .flat_map(|file_path| file_path.bytes().chain([b'\0']))
+            .collect();
+        let output = self
+            .git()
             .args([
                 "-C",
                 self.git_root.as_ref().unwrap(),
                 "check-attr",
+                "-z",
+                "--stdin",
                 "conflict-marker-size",
-                "--",
-                file_path,
+                "merge",
+                "binary",
             ])
-            .output()
-            .with_context(|| format!("Failed to execute git check-attr for file: {}", file_path))?;
-
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                if let Some(size_str) = line
-                    .strip_prefix(format!("{}:", file_path).as_str())
-                    .and_then(|s| s.trim().strip_prefix("conflict-marker-size: "))
-                    && let Ok(size) = size_str.parse::<usize>()
-                {
-                    return Ok(size);
-                }
+            .stdin(input)
+            .timeout(self.git_timeout())
+            .output()?;
+        if !output.status.success() {
+            return Err(SynthmergeError::git("check-attr", &output).into());
+        }
+
+        for file_path in file_paths {
+            self.file_attributes
+                .insert(file_path.clone(), FileAttributes::default());
+        }
+        self.file_attributes
+            .extend(Self::parse_check_attr(&output.stdout));
+        Ok(())
+    }
+
+    /// Parse the `<path> NUL <attribute> NUL <info> NUL` output of git
+    /// check-attr -z
+    fn parse_check_attr(output: &[u8]) -> HashMap<String, FileAttributes> {
+        let mut file_attributes: HashMap<String, FileAttributes> = HashMap::new();
+        let fields: Vec<String> = output
+            .split(|&b| b == b'\0')
+            .map(|field| String::from_utf8_lossy(field).to_string())
+            .collect();
+        for entry in fields.chunks_exact(3) {
+            let attributes = file_attributes.entry(entry[0].clone()).or_default();
+            match (entry[1].as_str(), entry[2].as_str()) {
+                ("conflict-marker-size", size) => attributes.marker_size = size.parse().ok(),
+                ("merge", "ours") => attributes.merge_side = Some(MergeDriverSide::Ours),
+                ("merge", "theirs") => attributes.merge_side = Some(MergeDriverSide::Theirs),
+                ("merge", "unspecified" | "set" | "text") => {}
+                ("merge", driver) => attributes.merge_driver = Some(driver.to_string()),
+                ("binary", "set") => attributes.binary = true,
+                _ => {}
             }
         }
+        file_attributes
+    }
 
-        // Default to 7 if not found
-        Ok(Self::DEFAULT_MARKER_SIZE)
+    /// Resolve an unmerged file to the side selected by its merge
+    /// gitattribute and stage it
+    fn resolve_with_merge_driver(&mut self, file_path: &str, side: MergeDriverSide) -> Result<()> {
+        self.backup_file(file_path)?;
+        let git_root = self.git_root.as_ref().unwrap();
+        let checkout_side = format!("--{}", side.as_str());
+        for args in [
+            vec!["-C", git_root, "checkout", &checkout_side, "--", file_path],
+            vec!["-C", git_root, "add", "--", file_path],
+        ] {
+            let output = self
+                .git()
+                .args(&args)
+                .output()
+                .with_context(|| format!("Failed to execute git {}", args[2]))?;
+            if !output.status.success() {
+                return Err(
+                    SynthmergeError::git(format!("{} {}", args[2], file_path), &output).into(),
+                );
+            }
+        }
+        logger::status!("Driver-resolved {} with merge={}", file_path, side.as_str());
+        Ok(())
     }
 
     /// Create a marker with specified size
@@ -789,6 +1874,7 @@ impl GitUtils {
         content_lines: &[&'a str],
         marker_size: usize,
         mode: ConflictMarkerMode,
+        ignore_stray_markers: bool,
     ) -> Result<Vec<&'a str>> {
         let content_str = content_lines.join("");
         let re = Self::create_conflict_regex(marker_size)?;
@@ -799,6 +1885,9 @@ impl GitUtils {
 
         for cap in re.captures_iter(&content_str) {
             let m = cap.get(0).unwrap();
+            if ignore_stray_markers && Self::is_stray_conflict(m.as_str(), marker_size) {
+                continue;
+            }
             while current_byte < m.start() && current_line < content_lines.len() {
                 current_byte += content_lines[current_line].len();
                 current_line += 1;
@@ -825,6 +1914,7 @@ impl GitUtils {
             line.starts_with(marker)
                 && (line.len() == marker_size
                     || line.as_bytes()[marker_size] == b'\n'
+                    || line.as_bytes()[marker_size] == b'\r'
                     || line.as_bytes()[marker_size] == b' ')
         };
 
@@ -864,30 +1954,43 @@ impl GitUtils {
             .map(|(_, line)| *line)
             .collect();
 
-        // Check for nested conflict markers
-        let result_str = result.join("");
-        let re = Regex::new(&format!(
-            r"(?ms)^<{{{},}}.*?^={{{},}}.*?^>{{{},}}",
-            Self::DEFAULT_MARKER_SIZE,
-            Self::DEFAULT_MARKER_SIZE,
-            Self::DEFAULT_MARKER_SIZE,
-        ))
-        .unwrap();
-        let has_nested_markers = re.is_match(&result_str);
-
-        if has_nested_markers {
+        // Check for nested conflict markers, the stray ones are left in
+        // the content on purpose
+        if !ignore_stray_markers && Self::has_nested_markers(&result.join(""), marker_size) {
             log::error!("Nested conflict markers found in file");
         }
         Ok(result)
     }
 
+    /// Check for conflict markers of the file's marker size left in
+    /// content that had its conflicts removed
+    fn has_nested_markers(content: &str, marker_size: usize) -> bool {
+        Regex::new(&format!(
+            r"(?ms)^{}(?: [^\n]*)?$.*?^{}\r?$.*?^{}(?: [^\n]*)?$",
+            Self::create_local_marker(marker_size),
+            Self::create_remote_marker(marker_size),
+            Self::create_end_marker(marker_size),
+        ))
+        .unwrap()
+        .is_match(content)
+    }
+
     /// Apply resolved conflicts back to the repository
-    pub fn apply_resolved_conflicts(&self, conflicts: &[ResolvedConflict]) -> Result<()> {
-        let conflicts = Self::deduplicate_conflicts(conflicts);
-        let mut assisted = false;
+    pub fn apply_resolved_conflicts(
+        &mut self,
+        conflicts: &[ResolvedConflict],
+    ) -> Result<(), SynthmergeError> {
+        self.resolve_merge_side_files()?;
+        let conflicts = self.select_resolutions(Self::deduplicate_conflicts(
+            conflicts,
+            &self.apply_options.agreement,
+        ));
+        let mut assisted = Vec::new();
+        // The dry run contents of the files, in the order they're changed
+        let mut dry_run_files: Vec<(String, String, String)> = Vec::new();
 
         for conflict in conflicts.iter().rev() {
-            println!(
+            logger::status!(
                 "Applying resolved conflict for: {}:{}->{} - {}",
                 conflict.conflict.file_path,
                 conflict.conflict.start_line,
@@ -899,8 +2002,15 @@ impl GitUtils {
             // Read the file
             let path =
                 Path::new(self.git_root.as_ref().unwrap()).join(&conflict.conflict.file_path);
-            let content = fs::read_to_string(&path)
-                .with_context(|| format!("Failed to read file: {}", conflict.conflict.file_path))?;
+            let content = match dry_run_files
+                .iter()
+                .find(|(file_path, _, _)| *file_path == conflict.conflict.file_path)
+            {
+                Some((_, _, content)) => content.clone(),
+                None => fs::read_to_string(&path).with_context(|| {
+                    format!("Failed to read file: {}", conflict.conflict.file_path)
+                })?,
+            };
             // Split content into lines
             let mut lines: Vec<String> = content
                 .split_inclusive('\n')
@@ -923,7 +2033,7 @@ impl GitUtils {
                 conflict.model,
                 conflict
                     .logprob
-                    .map(|p| format!(" {:.1}%", prob::logprob_to_prob(p)))
+                    .map(|p| format!(" {}", self.format_confidence(p)))
                     .unwrap_or_default(),
             );
             let current_line = &lines[insert_line];
@@ -938,25 +2048,40 @@ impl GitUtils {
                 continue;
             }
             lines.insert(insert_line, marker);
-            let resolved_lines: Vec<String> = conflict
-                .resolved_version
-                .split_inclusive('\n')
-                .map(|s| s.to_string())
-                .collect();
+            // The end marker follows the resolution
+            let resolved_lines = Self::split_resolved_lines(&conflict.resolved_version, true);
             for (i, line) in resolved_lines.iter().enumerate() {
                 lines.insert(insert_line + 1 + i, line.to_string());
             }
 
+            if self.apply_options.dry_run {
+                let file_path = &conflict.conflict.file_path;
+                match dry_run_files
+                    .iter_mut()
+                    .find(|(path, _, _)| path == file_path)
+                {
+                    Some((_, _, dry_run_content)) => *dry_run_content = lines.join(""),
+                    None => dry_run_files.push((file_path.clone(), content, lines.join(""))),
+                }
+                continue;
+            }
+
             // Write back to file
+            self.backup_file(&conflict.conflict.file_path)?;
             fs::write(&path, lines.join("")).with_context(|| {
                 format!("Failed to write file: {}", conflict.conflict.file_path)
             })?;
-            assisted = true;
+            assisted.push(conflict);
+        }
+
+        for (file_path, content, dry_run_content) in &dry_run_files {
+            Self::print_dry_run_diff(file_path, content, dry_run_content);
         }
 
         // Add Assisted-by line to merge message
-        if assisted {
-            self.update_merge_message()?;
+        if !assisted.is_empty() {
+            self.record_applied(&assisted);
+            self.update_merge_message(&assisted)?;
         }
 
         Ok(())
@@ -968,8 +2093,15 @@ impl GitUtils {
         conflicts: &[Conflict],
         resolved_conflicts: &[ResolvedConflict],
         retry_files: &HashSet<String>,
-    ) -> Result<bool> {
-        let resolved_conflicts = Self::deduplicate_conflicts_vibe(resolved_conflicts);
+    ) -> Result<bool, SynthmergeError> {
+        if self.git_root.is_none() {
+            return Err(SynthmergeError::NotAGitRepo);
+        }
+        self.resolve_merge_side_files()?;
+        let resolved_conflicts = self.select_resolutions(Self::deduplicate_conflicts_vibe(
+            &self.filter_conflict_markers(resolved_conflicts),
+            &self.apply_options.agreement,
+        ));
 
         // if true {
         //     // if self.context_lines.extra_conflict_lines == 0 {
@@ -978,8 +2110,8 @@ impl GitUtils {
         //     resolved_conflicts.pop();
         // }
 
-        // Group conflicts by file
-        let mut conflicts_by_file = std::collections::HashMap::new();
+        // Group conflicts by file, the files are applied in sorted order
+        let mut conflicts_by_file = BTreeMap::new();
         for conflict in conflicts {
             conflicts_by_file
                 .entry(&conflict.file_path)
@@ -987,18 +2119,43 @@ impl GitUtils {
                 .push(conflict);
         }
 
+        if self.apply_options.confirm
+            && !self.apply_options.stash
+            && !self.apply_options.dry_run
+            && !Self::confirm_vibe_resolution(
+                &conflicts_by_file,
+                &resolved_conflicts,
+                retry_files,
+                self.retries,
+            )?
+        {
+            return Err(anyhow::anyhow!("Vibe resolution aborted").into());
+        }
+
+        let rerere =
+            self.apply_options.rerere && !self.apply_options.stash && !self.apply_options.dry_run;
+        if rerere {
+            // Drop any stale resolution of the files about to be
+            // resolved, then record the preimages while the conflict
+            // markers are still in the working tree
+            for file_path in conflicts_by_file.keys() {
+                self.git_rerere(&["forget", "--", file_path])?;
+            }
+            self.git_rerere(&[])?;
+        }
+
         let mut needs_retry = false;
         let mut recoverable = true;
-        let mut assisted = false;
+        let mut assisted = Vec::new();
 
         // Process each file
         for (file_path, file_conflicts) in &conflicts_by_file {
             if self.retries > 0 && retry_files.contains(*file_path) {
-                println!("Will retry file: {}", file_path);
+                logger::status!("Will retry file: {}", file_path);
                 needs_retry = true;
                 continue;
             }
-            println!("Processing file: {}", file_path);
+            logger::status!("Processing file: {}", file_path);
 
             // Sort conflicts by start line (ascending)
             let mut sorted_conflicts: Vec<&Conflict> = file_conflicts.to_vec();
@@ -1011,10 +2168,58 @@ impl GitUtils {
                 // Write back to file
                 let path = Path::new(self.git_root.as_ref().unwrap()).join(file_path);
 
-                fs::write(&path, content.join(""))
+                let mut resolved_content = content.join("");
+                if let Some(breadcrumbs) = self.apply_options.breadcrumbs {
+                    resolved_content =
+                        self.leave_breadcrumbs(breadcrumbs, &path, file_path, resolved_content)?;
+                }
+                let local_eof_newline = self
+                    .local_eof_newlines
+                    .get(file_path.as_str())
+                    .copied()
+                    .unwrap_or(true);
+                self.apply_options
+                    .eof_newline
+                    .apply(&mut resolved_content, local_eof_newline);
+                if self.apply_options.dry_run {
+                    let content = fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read file: {}", file_path))?;
+                    if self.apply_options.output_patch.is_some() {
+                        let file_patch = self.file_patch(file_path, &content, &resolved_content);
+                        self.file_patches.insert(file_path.to_string(), file_patch);
+                    } else {
+                        Self::print_dry_run_diff(file_path, &content, &resolved_content);
+                    }
+                    continue;
+                }
+                if self.apply_options.stash {
+                    self.stash_files
+                        .push((file_path.to_string(), resolved_content));
+                    continue;
+                }
+                self.backup_file(file_path)?;
+                fs::write(&path, &resolved_content)
                     .with_context(|| format!("Failed to write file: {}", file_path))?;
                 self.git_update_index(Some(file_path))?;
-                assisted = true;
+
+                let file_resolved: Vec<&ResolvedConflict> = sorted_conflicts
+                    .iter()
+                    .filter_map(|conflict| {
+                        resolved_conflicts.iter().find(|r| {
+                            r.conflict.file_path == conflict.file_path
+                                && r.conflict.local_start == conflict.local_start
+                        })
+                    })
+                    .collect();
+                if self.apply_options.commit_per_file {
+                    self.per_file_commits.push(PerFileCommit {
+                        file_path: file_path.to_string(),
+                        local_content: sorted_conflicts[0].merged_local_lines.join(""),
+                        resolved_content,
+                        models: Self::contributing_models(&file_resolved),
+                    });
+                }
+                assisted.extend(file_resolved);
             } else {
                 needs_retry = true;
                 if !retry_files.contains(*file_path) {
@@ -1023,24 +2228,932 @@ impl GitUtils {
             }
         }
 
-        // Add Assisted-by line to merge message
-        if assisted {
-            self.update_merge_message()?;
+        if let Some(output_patch) = &self.apply_options.output_patch {
+            self.write_output_patch(output_patch)?;
+        }
+
+        self.record_applied(&assisted);
+
+        // Add Assisted-by line to merge message, unless each file gets
+        // its own commit with its own Assisted-by line
+        if !assisted.is_empty() && !self.apply_options.commit_per_file {
+            self.update_merge_message(&assisted)?;
+        }
+
+        // Record the postimages of the resolved files, so git can reuse
+        // the resolutions when the same conflicts recur
+        if rerere && !assisted.is_empty() {
+            self.git_rerere(&[])?;
         }
 
         if needs_retry {
             if recoverable && self.can_retry() {
                 return Ok(false);
             } else {
-                return Err(anyhow::anyhow!("Incomplete conflict resolution"));
+                self.stash_resolution()?;
+                return Err(anyhow::anyhow!("Incomplete conflict resolution").into());
             }
         }
 
+        self.stash_resolution()?;
+        self.remove_backups()?;
         Ok(true)
     }
 
-    /// Apply vibe resolution using conflict markers
-    fn apply_vibe_resolution_to_file(
+    /// Report the modify/delete conflicts and resolve them with the
+    /// modify_delete policy. Returns the number of conflicts left
+    /// unresolved.
+    pub fn resolve_modify_delete(&mut self, conflicts: &[Conflict]) -> Result<usize> {
+        let mut unresolved = 0;
+        for conflict in conflicts {
+            let file_path = &conflict.file_path;
+            let deleted_by = match conflict.kind {
+                ConflictKind::DeletedByUs => "us",
+                ConflictKind::DeletedByThem => "them",
+                ConflictKind::Content => continue,
+            };
+            logger::status!(
+                "Modify/delete conflict in {}: deleted by {}",
+                file_path,
+                deleted_by
+            );
+            let policy = match self.apply_options.modify_delete {
+                Some(policy) if !self.apply_options.stash => policy,
+                _ => {
+                    unresolved += 1;
+                    continue;
+                }
+            };
+            let action = match policy {
+                ModifyDelete::Keep => "keep",
+                ModifyDelete::Delete => "delete",
+            };
+            if self.apply_options.dry_run {
+                println!("Would {} {}", action, file_path);
+                continue;
+            }
+            self.backup_file(file_path)?;
+            match policy {
+                ModifyDelete::Keep => self.git_update_index(Some(file_path))?,
+                ModifyDelete::Delete => self.git_rm(file_path)?,
+            }
+        }
+        Ok(unresolved)
+    }
+
+    /// Print the unified diff between the current content of a file and
+    /// the content a resolution would write
+    fn print_dry_run_diff(file_path: &str, content: &str, resolved_content: &str) {
+        use similar::TextDiff;
+        print!(
+            "{}",
+            TextDiff::from_lines(content, resolved_content)
+                .unified_diff()
+                .header(&format!("a/{}", file_path), &format!("b/{}", file_path))
+        );
+    }
+
+    /// The git diff of a file from its current content, with the
+    /// conflict markers, to its resolved content, with patch_context_lines
+    /// of context, so git apply takes it on the working tree
+    fn file_patch(&self, file_path: &str, content: &str, resolved_content: &str) -> String {
+        use similar::TextDiff;
+        let diff = TextDiff::from_lines(content, resolved_content);
+        let mut unified_diff = diff.unified_diff();
+        unified_diff
+            .context_radius(self.context_lines.patch_context_lines as usize)
+            .header(&format!("a/{}", file_path), &format!("b/{}", file_path));
+        format!(
+            "diff --git a/{} b/{}\n{}",
+            file_path, file_path, unified_diff
+        )
+    }
+
+    /// Write the diff of all the resolved files to the output patch
+    fn write_output_patch(&self, output_patch: &Path) -> Result<()> {
+        let patch: String = self.file_patches.values().map(String::as_str).collect();
+        fs::write(output_patch, patch)
+            .with_context(|| format!("Failed to write {}", output_patch.display()))?;
+        logger::status!(
+            "Wrote the resolutions of {} files to {}",
+            self.file_patches.len(),
+            output_patch.display()
+        );
+        Ok(())
+    }
+
+    /// The local audit log of the runs
+    #[cfg(feature = "telemetry")]
+    pub fn audit_log_path(&self) -> PathBuf {
+        Path::new(self.git_dir.as_ref().unwrap()).join(Self::AUDIT_LOG_FILE)
+    }
+
+    fn backup_dir(&self) -> PathBuf {
+        Path::new(self.git_dir.as_ref().unwrap()).join(Self::BACKUP_DIR)
+    }
+
+    /// Save the working tree content and the index entries of a file
+    /// before its first mutation, so the conflicted state can be
+    /// restored with --restore
+    fn backup_file(&mut self, file_path: &str) -> Result<()> {
+        if !self.apply_options.backup || !self.backed_up_files.insert(file_path.to_string()) {
+            return Ok(());
+        }
+        let backup_dir = self.backup_dir();
+        let path = Path::new(self.git_root.as_ref().unwrap()).join(file_path);
+        let entries = self.git_plumbing(&["ls-files", "--stage", "--", file_path], None)?;
+        for (dir, content) in [
+            ("worktree", fs::read(&path)?),
+            ("index", format!("{}\n", entries).into_bytes()),
+        ] {
+            let backup_path = backup_dir.join(dir).join(file_path);
+            fs::create_dir_all(backup_path.parent().unwrap())?;
+            fs::write(&backup_path, content)
+                .with_context(|| format!("Failed to back up file: {}", file_path))?;
+        }
+        log::info!("Backed up {}", file_path);
+        Ok(())
+    }
+
+    /// Remove the backups taken by this run
+    fn remove_backups(&mut self) -> Result<()> {
+        let backup_dir = self.backup_dir();
+        for file_path in std::mem::take(&mut self.backed_up_files) {
+            for dir in ["worktree", "index"] {
+                let backup_path = backup_dir.join(dir).join(&file_path);
+                fs::remove_file(&backup_path)?;
+                // Prune the directories left empty
+                for parent in backup_path.ancestors().skip(1) {
+                    if !parent.starts_with(&backup_dir) || fs::remove_dir(parent).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the backed up files over the working tree and restore their
+    /// index entries, bringing back the conflicted state
+    pub fn restore_backups(&mut self) -> Result<()> {
+        let backup_dir = self.backup_dir();
+        let mut file_paths = Vec::new();
+        Self::list_backups(&backup_dir.join("worktree"), "", &mut file_paths)?;
+        if file_paths.is_empty() {
+            logger::status!("No backups found.");
+            return Ok(());
+        }
+        file_paths.sort();
+        for file_path in &file_paths {
+            let path = Path::new(self.git_root.as_ref().unwrap()).join(file_path);
+            fs::copy(backup_dir.join("worktree").join(file_path), &path)
+                .with_context(|| format!("Failed to restore file: {}", file_path))?;
+            let entries = fs::read_to_string(backup_dir.join("index").join(file_path))
+                .with_context(|| format!("Failed to read index backup: {}", file_path))?;
+            // Drop the stage 0 entry before adding back the unmerged ones
+            let index_info = format!(
+                "0 0000000000000000000000000000000000000000\t{}\n{}",
+                file_path, entries
+            );
+            self.git_update_index_info(&index_info)?;
+            logger::status!("Restored {}", file_path);
+            self.backed_up_files.insert(file_path.clone());
+        }
+        self.remove_backups()
+    }
+
+    /// Remove the resolutions inserted by the interactive mode from the
+    /// unmerged files, restoring the original conflicts
+    pub fn undo_resolutions(&mut self) -> Result<()> {
+        let output = self.git_status_porcelain_v2(None)?;
+        let mut undone = false;
+        for line_bytes in output.stdout.split(|&b| b == b'\0') {
+            let line = String::from_utf8_lossy(line_bytes);
+            if !line.starts_with("u UU") && !line.starts_with("u AA") {
+                continue;
+            }
+            let parts: Vec<&str> = line.splitn(11, ' ').collect();
+            if parts.len() < 11 {
+                continue;
+            }
+            let file_path = parts[10];
+            let path = Path::new(self.git_root.as_ref().unwrap()).join(file_path);
+            if !path.is_file() {
+                continue;
+            }
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read file: {}", file_path))?;
+            let marker_size = self.get_marker_size_for_file(file_path, &content)?;
+            let (undone_content, nr_resolutions) = Self::remove_ai_blocks(&content, marker_size);
+            if nr_resolutions == 0 {
+                continue;
+            }
+            undone = true;
+            if self.apply_options.dry_run {
+                Self::print_dry_run_diff(file_path, &content, &undone_content);
+                continue;
+            }
+            self.backup_file(file_path)?;
+            fs::write(&path, undone_content)
+                .with_context(|| format!("Failed to write file: {}", file_path))?;
+            logger::status!("Removed {} resolutions from {}", nr_resolutions, file_path);
+        }
+        if !undone {
+            logger::status!("No resolutions found.");
+        }
+        Ok(())
+    }
+
+    /// Remove the AI blocks between the remote section and the end
+    /// marker of each conflict. Returns the content and the number of
+    /// blocks removed.
+    fn remove_ai_blocks(content: &str, marker_size: usize) -> (String, usize) {
+        let is_marker = |line: &str, marker: &str| {
+            line.strip_prefix(marker)
+                .is_some_and(|rest| rest.trim_end().is_empty() || rest.starts_with(' '))
+        };
+        let local_marker = Self::create_local_marker(marker_size);
+        let end_marker = Self::create_end_marker(marker_size);
+        let ai_marker = format!(
+            "{} {}: ",
+            Self::create_ai_marker(marker_size),
+            env!("CARGO_PKG_NAME")
+        );
+
+        let mut result = String::with_capacity(content.len());
+        let mut nr_blocks = 0;
+        let mut in_conflict = false;
+        let mut in_ai_block = false;
+        for line in content.split_inclusive('\n') {
+            if in_conflict && line.starts_with(&ai_marker) {
+                in_ai_block = true;
+                nr_blocks += 1;
+                continue;
+            }
+            if is_marker(line, &local_marker) {
+                in_conflict = true;
+            } else if in_conflict && is_marker(line, &end_marker) {
+                in_conflict = false;
+                in_ai_block = false;
+            }
+            if !in_ai_block {
+                result.push_str(line);
+            }
+        }
+        (result, nr_blocks)
+    }
+
+    fn list_backups(dir: &Path, prefix: &str, file_paths: &mut Vec<String>) -> Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_path = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                Self::list_backups(&entry.path(), &format!("{}/", file_path), file_paths)?;
+            } else {
+                file_paths.push(file_path);
+            }
+        }
+        Ok(())
+    }
+
+    fn git_update_index_info(&self, index_info: &str) -> Result<()> {
+        let output = self
+            .git()
+            .args([
+                "-C",
+                self.git_root.as_ref().unwrap(),
+                "update-index",
+                "--index-info",
+            ])
+            .stdin(index_info.as_bytes().to_vec())
+            .output()?;
+        if !output.status.success() {
+            return Err(SynthmergeError::git("update-index --index-info", &output).into());
+        }
+        Ok(())
+    }
+
+    /// Store the vibe resolved files in a new stash entry, without
+    /// touching the working tree, the index or the operation in
+    /// progress. The stash is built with plumbing commands because
+    /// git stash refuses to run with unmerged paths.
+    fn stash_resolution(&mut self) -> Result<()> {
+        if self.stash_files.is_empty() {
+            return Ok(());
+        }
+        let git_dir = fs::canonicalize(self.git_dir.as_ref().unwrap())?;
+        let git_dir = git_dir.as_path();
+        let index = tempfile::Builder::new()
+            .prefix(concat!(env!("CARGO_PKG_NAME"), "_index"))
+            .tempfile_in(git_dir)?;
+        let index_file = Some(index.path());
+
+        let branch = self.git_plumbing(&["rev-parse", "--abbrev-ref", "HEAD"], None)?;
+        let head = self.git_plumbing(&["log", "-1", "--format=%h %s", "HEAD"], None)?;
+        let head_tree = self.git_plumbing(&["rev-parse", "HEAD^{tree}"], None)?;
+
+        self.git_plumbing(&["read-tree", "HEAD"], index_file)?;
+        let mut stash_files = std::mem::take(&mut self.stash_files);
+        stash_files.sort();
+        for (file_path, content) in &stash_files {
+            let mut blob = tempfile::NamedTempFile::new_in(git_dir)?;
+            std::io::Write::write_all(&mut blob, content.as_bytes())?;
+            let blob_path = blob.path().to_string_lossy().into_owned();
+            let blob_hash = self.git_plumbing(
+                &[
+                    "hash-object",
+                    "-w",
+                    &format!("--path={}", file_path),
+                    &blob_path,
+                ],
+                None,
+            )?;
+            let entry = self.git_plumbing(&["ls-tree", "HEAD", "--", file_path], None)?;
+            let mode = entry.split_whitespace().next().unwrap_or("100644");
+            self.git_plumbing(
+                &[
+                    "update-index",
+                    "--add",
+                    "--cacheinfo",
+                    &format!("{},{},{}", mode, blob_hash, file_path),
+                ],
+                index_file,
+            )?;
+        }
+        let tree = self.git_plumbing(&["write-tree"], index_file)?;
+
+        let index_commit = self.git_plumbing(
+            &[
+                "commit-tree",
+                &head_tree,
+                "-p",
+                "HEAD",
+                "-m",
+                &format!("index on {}: {}", branch, head),
+            ],
+            None,
+        )?;
+        let files = stash_files
+            .iter()
+            .map(|(file_path, _)| file_path.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let message = format!(
+            "On {}: {} vibe resolution of {}",
+            branch,
+            env!("CARGO_PKG_NAME"),
+            files
+        );
+        let stash_commit = self.git_plumbing(
+            &[
+                "commit-tree",
+                &tree,
+                "-p",
+                "HEAD",
+                "-p",
+                &index_commit,
+                "-m",
+                &message,
+            ],
+            None,
+        )?;
+        self.git_plumbing(&["stash", "store", "-m", &message, &stash_commit], None)?;
+        logger::status!("Stashed vibe resolution of {}", files);
+        Ok(())
+    }
+
+    /// Run a git plumbing command, optionally on a separate index
+    /// file, and return its trimmed stdout
+    fn git_plumbing(&self, args: &[&str], index_file: Option<&Path>) -> Result<String> {
+        let mut command = self.git();
+        command
+            .args(["-C", self.git_root.as_ref().unwrap()])
+            .args(args);
+        if let Some(index_file) = index_file {
+            command.env("GIT_INDEX_FILE", index_file);
+        }
+        let output = command
+            .output()
+            .with_context(|| format!("Failed to execute git {}", args[0]))?;
+        if !output.status.success() {
+            return Err(SynthmergeError::git(args[0], &output).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Keep the conflicts of a file about to be overwritten, either as
+    /// a comment appended to the resolved content or in a sidecar file.
+    /// Files without a known comment syntax always get a sidecar file.
+    fn leave_breadcrumbs(
+        &self,
+        breadcrumbs: Breadcrumbs,
+        path: &Path,
+        file_path: &str,
+        mut resolved_content: String,
+    ) -> Result<String> {
+        let original = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", file_path))?;
+        let marker_size = self.get_marker_size_for_file(file_path, &original)?;
+        let conflicts = Self::conflict_breadcrumbs(&original, marker_size)?;
+        if conflicts.is_empty() {
+            return Ok(resolved_content);
+        }
+
+        if breadcrumbs == Breadcrumbs::Comment
+            && let Some(comment) =
+                Self::comment_syntax(file_path).and_then(|syntax| syntax.comment(&conflicts))
+        {
+            if !resolved_content.is_empty() && !resolved_content.ends_with('\n') {
+                resolved_content.push('\n');
+            }
+            resolved_content.push_str(&comment);
+            return Ok(resolved_content);
+        }
+        if breadcrumbs == Breadcrumbs::Comment {
+            log::warn!(
+                "No comment syntax known for {}, writing a sidecar file",
+                file_path
+            );
+        }
+
+        if self.apply_options.dry_run {
+            println!("Would write {}{}", file_path, Self::BREADCRUMBS_SUFFIX);
+            return Ok(resolved_content);
+        }
+        let mut sidecar_path = path.as_os_str().to_owned();
+        sidecar_path.push(Self::BREADCRUMBS_SUFFIX);
+        fs::write(&sidecar_path, conflicts.join("\n")).with_context(|| {
+            format!(
+                "Failed to write file: {}{}",
+                file_path,
+                Self::BREADCRUMBS_SUFFIX
+            )
+        })?;
+        logger::status!(
+            "Replaced conflicts of {} saved in {}{}",
+            file_path,
+            file_path,
+            Self::BREADCRUMBS_SUFFIX
+        );
+        Ok(resolved_content)
+    }
+
+    /// Extract each conflict block of the content with its local and
+    /// remote sections, dropping the base section
+    fn conflict_breadcrumbs(content: &str, marker_size: usize) -> Result<Vec<String>> {
+        let re = Self::create_conflict_regex(marker_size)?;
+        let base_marker = Self::create_base_marker(marker_size);
+        let remote_marker = Self::create_remote_marker(marker_size);
+        let mut conflicts = Vec::new();
+        for cap in re.captures_iter(content) {
+            let mut in_base = false;
+            let mut conflict = String::new();
+            for line in cap.get(0).unwrap().as_str().split_inclusive('\n') {
+                if line.starts_with(&base_marker) {
+                    in_base = true;
+                } else if line.starts_with(&remote_marker) {
+                    in_base = false;
+                }
+                if !in_base {
+                    conflict.push_str(line);
+                }
+            }
+            if !conflict.ends_with('\n') {
+                conflict.push('\n');
+            }
+            conflicts.push(conflict);
+        }
+        Ok(conflicts)
+    }
+
+    fn comment_syntax(file_path: &str) -> Option<CommentSyntax> {
+        let path = Path::new(file_path);
+        let file_name = path.file_name()?.to_str()?;
+        if matches!(
+            file_name,
+            "Makefile" | "Kconfig" | "Dockerfile" | "CMakeLists.txt"
+        ) {
+            return Some(CommentSyntax::Line("#"));
+        }
+        let syntax = match path.extension()?.to_str()? {
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" | "rs" | "go" | "java"
+            | "js" | "jsx" | "ts" | "tsx" | "kt" | "kts" | "swift" | "scala" | "cs" | "dart"
+            | "zig" | "proto" => CommentSyntax::Line("//"),
+            "py" | "sh" | "bash" | "zsh" | "rb" | "pl" | "pm" | "yaml" | "yml" | "toml"
+            | "cmake" | "mk" | "r" | "conf" | "nix" | "spec" => CommentSyntax::Line("#"),
+            "sql" | "lua" | "hs" => CommentSyntax::Line("--"),
+            "el" | "lisp" | "clj" | "scm" | "ini" | "S" | "s" | "asm" => CommentSyntax::Line(";"),
+            "tex" | "erl" => CommentSyntax::Line("%"),
+            "vim" => CommentSyntax::Line("\""),
+            "css" => CommentSyntax::Block("/*", "*/"),
+            "html" | "htm" | "xml" | "svg" | "md" => CommentSyntax::Block("<!--", "-->"),
+            _ => return None,
+        };
+        Some(syntax)
+    }
+
+    /// The regex matching the lines defining a function or a class in
+    /// the language of the file, None if the language isn't known
+    fn definition_regex(file_path: &str) -> Option<Regex> {
+        let regex = match Path::new(file_path).extension()?.to_str()? {
+            // Like the default funcname of git, the definitions start
+            // at the first column, labels and declarations don't count
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => {
+                r"^[A-Za-z_$][^;]*[({][^;]*$"
+            }
+            "rs" => {
+                r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|extern\s+"[^"]*")\s+)*(?:fn|impl|struct|enum|trait|mod)\b"#
+            }
+            "py" => r"^\s*(?:async\s+)?(?:def|class)\s",
+            "go" => r"^(?:func|type)\s",
+            "js" | "jsx" | "ts" | "tsx" => {
+                r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?(?:function\b|class\s)"
+            }
+            "rb" => r"^\s*(?:def|class|module)\s",
+            "sh" | "bash" | "zsh" => r"^\s*(?:function\s+)?[A-Za-z_][\w-]*\s*\(\)",
+            _ => return None,
+        };
+        Some(Regex::new(regex).unwrap())
+    }
+
+    /// The nearest line before the conflict defining a function or a
+    /// class, empty if there's none
+    fn enclosing_symbol(definition_regex: &Regex, lines_before: &[String]) -> String {
+        lines_before
+            .iter()
+            .rev()
+            .find(|line| definition_regex.is_match(line))
+            .map(|line| line.trim().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Show each proposed resolution next to its conflict and ask to
+    /// accept, reject or edit it. The identical resolutions of different
+    /// models are reviewed once, the rejected ones are dropped.
+    pub fn review_resolutions(
+        resolved_conflicts: Vec<ResolvedConflict>,
+    ) -> Result<Vec<ResolvedConflict>> {
+        Self::review_resolutions_with(
+            resolved_conflicts,
+            &mut std::io::stdin().lock(),
+            Self::edit_resolution,
+        )
+    }
+
+    /// Review the resolutions with the answers read from input and the
+    /// resolutions edited by edit
+    fn review_resolutions_with(
+        mut resolved_conflicts: Vec<ResolvedConflict>,
+        input: &mut impl std::io::BufRead,
+        edit: impl Fn(&str) -> Result<String>,
+    ) -> Result<Vec<ResolvedConflict>> {
+        let mut order: Vec<usize> = (0..resolved_conflicts.len()).collect();
+        order.sort_by_key(|&i| {
+            let conflict = &resolved_conflicts[i].conflict;
+            (conflict.file_path.clone(), conflict.local_start)
+        });
+        let mut rejected = HashSet::new();
+        // Track the indices, not the versions: an edit changes the
+        // version of all the agreeing resolutions reviewed with it
+        let mut reviewed = HashSet::new();
+        for i in order {
+            if reviewed.contains(&i) {
+                continue;
+            }
+            let resolved = &resolved_conflicts[i];
+            let conflict = &resolved.conflict;
+            let same: Vec<usize> = (0..resolved_conflicts.len())
+                .filter(|&j| {
+                    let other = &resolved_conflicts[j];
+                    other.conflict.file_path == conflict.file_path
+                        && other.conflict.local_start == conflict.local_start
+                        && other.resolved_version == resolved.resolved_version
+                })
+                .collect();
+            reviewed.extend(same.iter().copied());
+            let models: Vec<&str> = same
+                .iter()
+                .map(|&j| resolved_conflicts[j].model.as_str())
+                .collect();
+
+            // The patch locator doesn't keep the markers of the
+            // conflict, show the change to apply and the local code
+            println!(
+                "Conflict in {}:{}, patch to apply:\n{}",
+                conflict.file_path, conflict.local_start, conflict.conflict_patch
+            );
+            // The alternatives of the perplexity search are reviewed
+            // like the first completions, accept one and reject the
+            // others to pick it instead
+            let alternative = same.iter().all(|&j| resolved_conflicts[j].is_alternative());
+            println!(
+                "{} of {}:\n{}",
+                if alternative {
+                    "Alternative resolution"
+                } else {
+                    "Resolution"
+                },
+                models.join(", "),
+                ConflictResolver::create_diff(
+                    &conflict.conflict_code,
+                    &resolved.resolved_version,
+                    3
+                )
+            );
+
+            let answer = loop {
+                print!("[a]ccept, [r]eject or [e]dit? ");
+                std::io::Write::flush(&mut std::io::stdout())?;
+                let mut answer = String::new();
+                if input.read_line(&mut answer)? == 0 {
+                    return Err(anyhow::anyhow!("Review aborted"));
+                }
+                match answer.trim() {
+                    "a" | "accept" => break 'a',
+                    "r" | "reject" => break 'r',
+                    "e" | "edit" => break 'e',
+                    _ => continue,
+                }
+            };
+            match answer {
+                'r' => rejected.extend(same),
+                'e' => {
+                    let edited = edit(&resolved.resolved_version)?;
+                    for j in same {
+                        resolved_conflicts[j].resolved_version = edited.clone();
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(resolved_conflicts
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !rejected.contains(i))
+            .map(|(_, resolved)| resolved)
+            .collect())
+    }
+
+    /// Open $VISUAL or $EDITOR on a resolution and return the saved text
+    fn edit_resolution(resolved_version: &str) -> Result<String> {
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let mut file = tempfile::NamedTempFile::new()?;
+        std::io::Write::write_all(&mut file, resolved_version.as_bytes())?;
+        // The editor may be a command with arguments
+        let status = Command::new("sh")
+            .args(["-c", &format!("{} \"$1\"", editor), "sh"])
+            .arg(file.path())
+            .status()
+            .with_context(|| format!("Failed to run the editor {}", editor))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("The editor {} failed: {}", editor, status));
+        }
+        let mut edited = fs::read_to_string(file.path())?;
+        if !edited.is_empty() && !edited.ends_with('\n') {
+            edited.push('\n');
+        }
+        Ok(edited)
+    }
+
+    /// Show the conflicts about to be overwritten and the ones that
+    /// will be left unresolved, and ask to proceed
+    fn confirm_vibe_resolution(
+        conflicts_by_file: &BTreeMap<&String, Vec<&Conflict>>,
+        resolved_conflicts: &[ResolvedConflict],
+        retry_files: &HashSet<String>,
+        retries: usize,
+    ) -> Result<bool> {
+        let mut summary = Vec::new();
+        let (mut resolved_total, mut unresolved_total, mut files_total) = (0, 0, 0);
+        for (file_path, file_conflicts) in conflicts_by_file {
+            let is_resolved = |conflict: &&&Conflict| {
+                resolved_conflicts.iter().any(|r| {
+                    r.conflict.file_path == conflict.file_path
+                        && r.conflict.local_start == conflict.local_start
+                })
+            };
+            let resolved = file_conflicts.iter().filter(is_resolved).count();
+            // The file is written only if no conflict is left behind
+            let written = !(retries > 0 && retry_files.contains(*file_path))
+                && file_conflicts
+                    .iter()
+                    .all(|c| c.commit_type == CommitType::Clean || is_resolved(&c));
+            let (resolved, unresolved) = if written {
+                (resolved, file_conflicts.len() - resolved)
+            } else {
+                (0, file_conflicts.len())
+            };
+            if resolved > 0 {
+                files_total += 1;
+            }
+            resolved_total += resolved;
+            unresolved_total += unresolved;
+            summary.push(format!(
+                "  {}: {} to overwrite, {} unresolved",
+                file_path, resolved, unresolved
+            ));
+        }
+
+        println!(
+            "About to overwrite {} conflicts across {} files, {} conflicts will be left unresolved:",
+            resolved_total, files_total, unresolved_total
+        );
+        for line in summary {
+            println!("{}", line);
+        }
+        if resolved_total == 0 {
+            return Ok(true);
+        }
+        print!("Proceed? [y/N] ");
+        std::io::Write::flush(&mut std::io::stdout())?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+    }
+
+    /// Drop the resolutions that still contain conflict markers of
+    /// their file, so the file is left unresolved instead of being
+    /// staged with markers in it
+    fn filter_conflict_markers(
+        &self,
+        resolved_conflicts: &[ResolvedConflict],
+    ) -> Vec<ResolvedConflict> {
+        resolved_conflicts
+            .iter()
+            .filter(|resolved| {
+                let conflict = &resolved.conflict;
+                let marker_size = self
+                    .marker_sizes
+                    .get(&conflict.file_path)
+                    .copied()
+                    .unwrap_or(Self::DEFAULT_MARKER_SIZE);
+                if resolved
+                    .resolved_version
+                    .split_inclusive('\n')
+                    .any(|line| Self::is_any_marker_line(line, marker_size))
+                {
+                    logger::status!(
+                        "Rejected resolution for: {}:{}->{} - {} with conflict markers",
+                        conflict.file_path,
+                        conflict.start_line,
+                        conflict.local_start,
+                        resolved.model
+                    );
+                    return false;
+                }
+                true
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Drop the deduplicated resolutions that don't meet the thresholds
+    /// and post-process the others before they're applied
+    fn select_resolutions(
+        &self,
+        resolved_conflicts: Vec<ResolvedConflict>,
+    ) -> Vec<ResolvedConflict> {
+        let resolved_conflicts =
+            self.filter_by_agreement(self.filter_by_confidence(resolved_conflicts));
+        if self.apply_options.match_indent {
+            self.match_indent(resolved_conflicts)
+        } else {
+            resolved_conflicts
+        }
+    }
+
+    /// Reindent the resolutions with the indentation unit of their
+    /// conflicts, leaving them alone if either is ambiguous
+    fn match_indent(&self, resolved_conflicts: Vec<ResolvedConflict>) -> Vec<ResolvedConflict> {
+        resolved_conflicts
+            .into_iter()
+            .map(|mut resolved| {
+                if let Some(reindented) =
+                    normalize::match_indent(&resolved.resolved_version, &resolved.conflict)
+                {
+                    log::info!(
+                        "Reindented resolution for: {}:{}->{} - {}",
+                        resolved.conflict.file_path,
+                        resolved.conflict.start_line,
+                        resolved.conflict.local_start,
+                        resolved.model
+                    );
+                    resolved.resolved_version = reindented;
+                }
+                resolved
+            })
+            .collect()
+    }
+
+    /// Drop the resolutions below the confidence threshold of their
+    /// path. Resolutions without a logprob are never dropped.
+    fn filter_by_confidence(
+        &self,
+        resolved_conflicts: Vec<ResolvedConflict>,
+    ) -> Vec<ResolvedConflict> {
+        let thresholds = &self.apply_options.confidence_thresholds;
+        resolved_conflicts
+            .into_iter()
+            .filter(|resolved| {
+                let conflict = &resolved.conflict;
+                let Some(threshold) = thresholds.threshold_for(&conflict.file_path) else {
+                    return true;
+                };
+                log::info!(
+                    "Confidence threshold for {}:{}->{} - {}: {:.1}%",
+                    conflict.file_path,
+                    conflict.start_line,
+                    conflict.local_start,
+                    resolved.model,
+                    threshold
+                );
+                let Some(logprob) = resolved.logprob else {
+                    if thresholds.missing_logprob == MissingLogprob::Fail {
+                        logger::status!(
+                            "Rejected resolution for: {}:{}->{} - {} without logprobs",
+                            conflict.file_path,
+                            conflict.start_line,
+                            conflict.local_start,
+                            resolved.model
+                        );
+                        return false;
+                    }
+                    return true;
+                };
+                let confidence = prob::logprob_to_prob(logprob, thresholds.confidence_base);
+                if confidence < threshold {
+                    logger::status!(
+                        "Rejected resolution for: {}:{}->{} - {} {:.1}% < {:.1}%",
+                        conflict.file_path,
+                        conflict.start_line,
+                        conflict.local_start,
+                        resolved.model,
+                        confidence,
+                        threshold
+                    );
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+
+    pub fn agreement(&self) -> &AgreementConfig {
+        &self.apply_options.agreement
+    }
+
+    /// Drop the deduplicated resolutions produced by fewer than
+    /// min_agreement models. The samples of the multi and beam search
+    /// of the same model don't count as agreement. Without a
+    /// min_agreement above one nothing is dropped, not even the
+    /// resolutions found only by a later beam or sample.
+    fn filter_by_agreement(
+        &self,
+        resolved_conflicts: Vec<ResolvedConflict>,
+    ) -> Vec<ResolvedConflict> {
+        let min_agreement = self.apply_options.min_agreement;
+        if min_agreement <= 1 {
+            return resolved_conflicts;
+        }
+        resolved_conflicts
+            .into_iter()
+            .filter(|resolved| {
+                let nr_models = Self::nr_agreeing_models(resolved);
+                if nr_models < min_agreement {
+                    let conflict = &resolved.conflict;
+                    logger::status!(
+                        "Rejected resolution for: {}:{}->{} - {} agreed by {} < {} models",
+                        conflict.file_path,
+                        conflict.start_line,
+                        conflict.local_start,
+                        resolved.model,
+                        nr_models,
+                        min_agreement
+                    );
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+
+    /// The number of endpoints that produced a deduplicated resolution
+    fn nr_agreeing_models(resolved: &ResolvedConflict) -> usize {
+        resolved
+            .deduplicated_conflicts
+            .iter()
+            .map(|c| c.endpoint)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Apply vibe resolution using conflict markers
+    fn apply_vibe_resolution_to_file(
         &self,
         sorted_conflicts: &[&Conflict],
         resolved_conflicts: &[ResolvedConflict],
@@ -1080,17 +3193,20 @@ impl GitUtils {
 
             let resolved_conflict = resolved_conflict.unwrap();
 
-            println!(
+            logger::status!(
                 "Found vibe resolution for: {}:{}->{}",
-                conflict.file_path, conflict.start_line, conflict.local_start
+                conflict.file_path,
+                conflict.start_line,
+                conflict.local_start
             );
 
-            // Replace the entire conflict with the resolved version
-            let resolved_lines: Vec<String> = resolved_conflict
-                .resolved_version
-                .split_inclusive('\n')
-                .map(|s| s.to_string())
-                .collect();
+            // Replace the entire conflict with the resolved version.
+            // At the end of file the eof_newline policy decides if the
+            // last line is terminated.
+            let resolved_lines = Self::split_resolved_lines(
+                &resolved_conflict.resolved_version,
+                conflict.local_end < lines.len(),
+            );
 
             // Replace the conflict
             lines.splice(conflict.local_start..conflict.local_end, resolved_lines);
@@ -1099,28 +3215,121 @@ impl GitUtils {
         Ok(Some(lines))
     }
 
+    /// Apply the resolutions to the content the conflicts were parsed
+    /// from with parse_file_conflicts, failing if any conflict is left
+    /// unresolved. The content is never read from or written to the
+    /// repository.
+    pub fn resolve_file_content(
+        &mut self,
+        conflicts: &[Conflict],
+        resolved_conflicts: &[ResolvedConflict],
+        local_eof_newline: bool,
+    ) -> Result<String, SynthmergeError> {
+        for conflict in conflicts {
+            self.marker_sizes
+                .insert(conflict.file_path.clone(), conflict.marker_size);
+        }
+        let resolved_conflicts = self.select_resolutions(Self::deduplicate_conflicts_vibe(
+            &self.filter_conflict_markers(resolved_conflicts),
+            &self.apply_options.agreement,
+        ));
+        let mut sorted_conflicts: Vec<&Conflict> = conflicts.iter().collect();
+        sorted_conflicts.sort_by_key(|c| c.local_start);
+        let lines = self
+            .apply_vibe_resolution_to_file(&sorted_conflicts, &resolved_conflicts)?
+            .ok_or_else(|| anyhow::anyhow!("Not all the conflicts were resolved"))?;
+        let mut content = lines.join("");
+        self.apply_options
+            .eof_newline
+            .apply(&mut content, local_eof_newline);
+        Ok(content)
+    }
+
+    /// Split a resolution into lines, terminating its last line if more
+    /// lines follow it, so it isn't joined with the next line
+    fn split_resolved_lines(resolved_version: &str, terminate: bool) -> Vec<String> {
+        let mut resolved_lines: Vec<String> = resolved_version
+            .split_inclusive('\n')
+            .map(|s| s.to_string())
+            .collect();
+        if terminate
+            && let Some(last) = resolved_lines.last_mut()
+            && !last.ends_with('\n')
+        {
+            last.push('\n');
+        }
+        resolved_lines
+    }
+
     /// Continue the current cherry-pick, rebase, revert, or merge operation
     pub fn continue_operation(&mut self, context_lines: &ContextLines) -> Result<bool> {
-        let git_dir = self.git_dir.as_ref().unwrap();
-
         // Check if we're in a cherry-pick, rebase, revert, or merge
-        let operation = match self.find_operation_head(git_dir)? {
+        let operation = match self.find_operation_head()? {
             Some(op) => op,
             None => return Ok(false),
         };
 
+        // Git can't continue with the binary files still unmerged
+        if !self.binary_files.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Resolve the binary files before continuing: {}",
+                self.binary_files.join(", ")
+            ));
+        }
+        if !self.driver_files.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Resolve the files managed by a merge driver before continuing: {}",
+                self.driver_files
+                    .iter()
+                    .map(|(file_path, _)| file_path.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
         // Restore context lines before continuing
         self.restore_context_lines(context_lines);
 
-        // Always delete unmerged files if any before continuing
+        // Resolve the modify/delete conflicts with the policy before
+        // continuing
         self.git_add_delete_unmerged()?;
 
+        // The resolutions may have undone the whole change of the
+        // commit, a merge is still worth committing
+        let skip = self.apply_options.skip_empty
+            && operation.command != "merge"
+            && self.index_matches_head()?;
+
+        if self.apply_options.edit_message && !skip {
+            self.edit_merge_message()?;
+        }
+
         // Function to commit and continue operation
-        if operation.command == "rebase" {
-            // Commit the changes
-            println!("Committing changes");
-            let output = GitCommand::new("git")
-                .args(["commit", "--no-edit"])
+        if skip {
+            logger::status!(
+                "Nothing to commit after the resolution, skipping the commit of the {}",
+                operation.command
+            );
+        } else if self.apply_options.commit_per_file && !self.per_file_commits.is_empty() {
+            self.commit_per_file()?;
+
+            // Committing concludes a single cherry-pick, revert or
+            // merge, only rebase and sequencer ranges have more to do
+            let sequencer = self.git_path("sequencer")?;
+            if !operation.path.exists() && !sequencer.exists() {
+                return Ok(false);
+            }
+        } else if operation.command == "rebase" {
+            // Commit the changes, with the edited rebase message
+            logger::status!("Committing changes");
+            let merge_msg_path = self.merge_message_path()?;
+            let mut args = vec!["commit", "--no-edit"];
+            if self.in_rebase && self.apply_options.edit_message {
+                args.extend(["--cleanup=strip", "-F", merge_msg_path.to_str().unwrap()]);
+            }
+            let output = self
+                .git()
+                .args(&args)
                 .output()
                 .context("Failed to execute git commit --no-edit")?;
 
@@ -1135,14 +3344,12 @@ impl GitUtils {
             }
         }
 
-        let mut subcmd = "--continue";
+        let mut subcmd = if skip { "--skip" } else { "--continue" };
         loop {
-            let before_head = std::fs::read_to_string(&operation.path)
-                .with_context(|| format!("Failed to read {}", operation.file))?
-                .trim()
-                .to_string();
-            println!("Executing git {} {}", operation.command, subcmd);
-            let output = GitCommand::new("git")
+            let before_head = Self::read_operation_head(&operation)?;
+            logger::status!("Executing git {} {}", operation.command, subcmd);
+            let output = self
+                .git()
                 .args(vec![&operation.command, subcmd])
                 .args(if operation.command != "rebase" {
                     vec!["--no-edit"]
@@ -1151,8 +3358,8 @@ impl GitUtils {
                 })
                 .output()
                 .context(format!(
-                    "Failed to execute git {} --continue",
-                    operation.command
+                    "Failed to execute git {} {}",
+                    operation.command, subcmd
                 ))?;
 
             if !output.status.success() {
@@ -1165,10 +3372,7 @@ impl GitUtils {
                     print!("{}", String::from_utf8_lossy(&output.stderr));
                 }
 
-                let after_head = std::fs::read_to_string(&operation.path)
-                    .with_context(|| format!("Failed to read {}", operation.file))?
-                    .trim()
-                    .to_string();
+                let after_head = Self::read_operation_head(&operation)?;
 
                 log::debug!(
                     "commit after_head: {} before_head: {}",
@@ -1186,25 +3390,124 @@ impl GitUtils {
         Ok(false)
     }
 
-    pub fn deduplicate_conflicts_vibe(conflicts: &[ResolvedConflict]) -> Vec<ResolvedConflict> {
+    /// Whether the index has the same tree as HEAD, so there's nothing
+    /// to commit
+    fn index_matches_head(&self) -> Result<bool> {
+        let output = self
+            .git()
+            .args(["diff", "--cached", "--quiet", "HEAD", "--"])
+            .output()
+            .context("Failed to execute git diff --cached")?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(anyhow::anyhow!(
+                "Git diff --cached failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        }
+    }
+
+    /// Read the operation HEAD file, empty if the commit already
+    /// concluded the operation
+    fn read_operation_head(operation: &OperationHead) -> Result<String> {
+        if !operation.path.exists() {
+            return Ok(String::new());
+        }
+        Ok(std::fs::read_to_string(&operation.path)
+            .with_context(|| format!("Failed to read {}", operation.file))?
+            .trim()
+            .to_string())
+    }
+
+    /// Commit the operation with the conflicts resolved to the local
+    /// side, then commit the AI resolution of each file on top of it
+    fn commit_per_file(&mut self) -> Result<()> {
+        let mut commits = std::mem::take(&mut self.per_file_commits);
+        commits.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        let git_root = self.git_root.clone().unwrap();
+
+        for commit in &commits {
+            let path = Path::new(&git_root).join(&commit.file_path);
+            fs::write(&path, &commit.local_content)
+                .with_context(|| format!("Failed to write file: {}", commit.file_path))?;
+            self.git_update_index(Some(&commit.file_path))?;
+        }
+        logger::status!("Committing changes with conflicts resolved to the local side");
+        self.git_commit(&["--no-edit", "--cleanup=strip", "--allow-empty"])?;
+
+        for commit in &commits {
+            if commit.resolved_content == commit.local_content {
+                logger::status!("No changes to commit for {}", commit.file_path);
+                continue;
+            }
+            let path = Path::new(&git_root).join(&commit.file_path);
+            fs::write(&path, &commit.resolved_content)
+                .with_context(|| format!("Failed to write file: {}", commit.file_path))?;
+            self.git_update_index(Some(&commit.file_path))?;
+            let mut message = format!(
+                "{}: resolve conflicts in {}\n\nResolved by: {}\n",
+                env!("CARGO_PKG_NAME"),
+                commit.file_path,
+                commit.models,
+            );
+            if let Some(assisted_by_line) = self.assisted_by_line(&commit.models) {
+                message.push_str(&format!("\n{}\n", assisted_by_line));
+            }
+            logger::status!("Committing resolution of {}", commit.file_path);
+            self.git_commit(&["-m", &message])?;
+        }
+
+        Ok(())
+    }
+
+    /// Run git commit with the given arguments
+    fn git_commit(&self, args: &[&str]) -> Result<()> {
+        let output = self
+            .git()
+            .args(["commit"])
+            .args(args)
+            .output()
+            .context("Failed to execute git commit")?;
+
+        if !output.status.success() {
+            return Err(SynthmergeError::git("commit", &output).into());
+        }
+        Ok(())
+    }
+
+    pub fn deduplicate_conflicts_vibe(
+        conflicts: &[ResolvedConflict],
+        agreement: &AgreementConfig,
+    ) -> Vec<ResolvedConflict> {
         let filtered: Vec<_> = conflicts
             .iter()
             .filter(|c| c.multi == Some(0) && c.beam == Some(0))
             .cloned()
             .collect();
-        Self::deduplicate_conflicts(&filtered)
+        Self::deduplicate_conflicts(&filtered, agreement)
     }
 
-    fn deduplicate_conflicts(conflicts: &[ResolvedConflict]) -> Vec<ResolvedConflict> {
-        use std::collections::HashMap;
-        let mut map: HashMap<(String, usize, &str), Vec<&ResolvedConflict>> = HashMap::new();
-
-        // Group conflicts by resolved_version, local_start and file_path
+    /// Merge the identical resolutions of the same conflict, combining
+    /// the model names. The resolutions are compared ignoring the
+    /// whitespace differences of the agreement config, the text of the
+    /// first model of each group in the endpoint order is kept verbatim.
+    /// The order doesn't depend on the order of the input: by file,
+    /// line, number of models that agree (descending), the adapted clean
+    /// hunks first, the lowest endpoint, beam and sample, and finally the
+    /// resolution text.
+    pub fn deduplicate_conflicts(
+        conflicts: &[ResolvedConflict],
+        agreement: &AgreementConfig,
+    ) -> Vec<ResolvedConflict> {
+        let mut map: BTreeMap<(&str, usize, String), Vec<&ResolvedConflict>> = BTreeMap::new();
+
+        // Group conflicts by file_path, local_start and resolved_version
         for conflict in conflicts {
             map.entry((
-                conflict.resolved_version.clone(),
-                conflict.conflict.local_start,
                 &conflict.conflict.file_path,
+                conflict.conflict.local_start,
+                agreement.key(&conflict.resolved_version),
             ))
             .or_default()
             .push(conflict);
@@ -1212,128 +3515,87 @@ impl GitUtils {
 
         // For each group, create a new conflict with combined model names
         let mut result = Vec::new();
-        for ((resolved_version, _, _), group) in map {
+        for (_, mut group) in map {
+            group.sort_by(|a, b| {
+                (a.endpoint, a.beam, a.multi, &a.model)
+                    .cmp(&(b.endpoint, b.beam, b.multi, &b.model))
+            });
+            let resolved_version = group[0].resolved_version.clone();
             let model = Self::combine_model_names(group.as_slice());
 
             // Use the first conflict in the group as the base
             let base_conflict = &group[0].conflict;
-            let total_tokens = if group.iter().any(|c| c.total_tokens.is_some()) {
-                Some(
-                    group.iter().filter_map(|c| c.total_tokens).sum::<u64>()
-                        / group.iter().filter_map(|c| c.total_tokens).count() as u64,
-                )
-            } else {
-                None
+            // The tokens are the combined spend of the agreeing models
+            let sum_tokens = |tokens: fn(&ResolvedConflict) -> Option<u64>| {
+                group.iter().filter_map(|c| tokens(c)).reduce(|a, b| a + b)
             };
-            let logprob = if group.iter().any(|c| c.logprob.is_some()) {
-                Some(
-                    group.iter().filter_map(|c| c.logprob).sum::<f64>()
-                        / group.iter().filter_map(|c| c.logprob).count() as f64,
-                )
-            } else {
-                None
-            };
-            result.push(ResolvedConflict {
-                conflict: base_conflict.clone(),
-                resolved_version,
-                model,
-                duration: group
-                    .iter()
-                    .map(|c| c.duration)
-                    .max_by(|a, b| a.partial_cmp(b).unwrap())
-                    .unwrap_or(0.0),
-                total_tokens,
-                logprob,
-                endpoint: group.iter().map(|c| c.endpoint).min().unwrap(),
-                deduplicated_conflicts: group
-                    .into_iter()
-                    .filter(|x| {
-                        assert!(&x.conflict == base_conflict);
-                        true
-                    })
-                    .cloned()
-                    .collect(),
-                beam: None,
-                multi: None,
-            });
+            let total_tokens = sum_tokens(|c| c.total_tokens);
+            let input_tokens = sum_tokens(|c| c.input_tokens);
+            // The confidence is the one of the most confident model: the
+            // agreement of the other models can only add to it
+            let logprob = group
+                .iter()
+                .filter_map(|c| c.logprob)
+                .max_by(|a, b| a.total_cmp(b));
+            // The best beam and sample of the group, for the ordering
+            let rank = (group[0].endpoint, group[0].beam, group[0].multi);
+            result.push((
+                rank,
+                ResolvedConflict {
+                    conflict: base_conflict.clone(),
+                    resolved_version,
+                    model,
+                    duration: group
+                        .iter()
+                        .map(|c| c.duration)
+                        .max_by(|a, b| a.partial_cmp(b).unwrap())
+                        .unwrap_or(0.0),
+                    total_tokens,
+                    input_tokens,
+                    logprob,
+                    endpoint: group[0].endpoint,
+                    deduplicated_conflicts: group
+                        .into_iter()
+                        .filter(|x| {
+                            assert!(&x.conflict == base_conflict);
+                            true
+                        })
+                        .cloned()
+                        .collect(),
+                    beam: None,
+                    multi: None,
+                },
+            ));
         }
 
-        // Sort by number of models that agree on the resolved conflict (descending)
-        // When equal, maintain original order
-        let mut seen = std::collections::HashSet::new();
-
-        // First pass: collect all unique resolved conflicts with their original order positions
-        let mut unique_conflicts: Vec<(String, &str, usize, usize, usize, bool)> = Vec::new();
-        for original in conflicts {
-            let key = (
-                &original.resolved_version,
-                original.conflict.local_start,
-                &original.conflict.file_path,
-            );
-            if seen.insert(key) {
-                let pos = result
-                    .iter()
-                    .position(|r| {
-                        (
-                            &r.resolved_version,
-                            r.conflict.local_start,
-                            &r.conflict.file_path,
-                        ) == key
-                    })
-                    .unwrap();
-                let num_models = result[pos].deduplicated_conflicts.len();
-
-                // Calculate difference between resolved_version and merged_local_lines for Clean commits
-                let adapted = if result[pos].conflict.commit_type.is_clean() {
-                    let merged_lines = &result[pos].conflict.merged_local_lines;
-                    let local_start = result[pos].conflict.local_start;
-                    let local_end = result[pos].conflict.local_end;
-                    let expected = merged_lines[local_start..local_end].join("");
-                    let resolved = &result[pos].resolved_version;
-
-                    expected != *resolved
-                } else {
-                    true
-                };
-
-                unique_conflicts.push((
-                    result[pos].resolved_version.clone(),
-                    &result[pos].conflict.file_path,
-                    result[pos].conflict.local_start,
-                    num_models,
-                    result[pos].endpoint,
-                    adapted,
-                ));
-            }
-        }
-
-        // Sort by file, line, number of models (descending),
-        // difference score for Clean (descending), and finally with
-        // the original "endpoint" order
-        unique_conflicts.sort_by(|a, b| {
-            a.1.cmp(b.1)
-                .then(a.2.cmp(&b.2))
-                .then(b.3.cmp(&a.3))
-                .then(b.5.cmp(&a.5))
-                .then(a.4.cmp(&b.4))
+        // Whether a resolution of a Clean commit differs from the
+        // merged local lines
+        let adapted = |resolved: &ResolvedConflict| {
+            if resolved.conflict.commit_type.is_clean() {
+                let merged_lines = &resolved.conflict.merged_local_lines;
+                let expected = merged_lines
+                    [resolved.conflict.local_start..resolved.conflict.local_end]
+                    .join("");
+                expected != resolved.resolved_version
+            } else {
+                true
+            }
+        };
+        result.sort_by(|(a_rank, a), (b_rank, b)| {
+            a.conflict
+                .file_path
+                .cmp(&b.conflict.file_path)
+                .then(a.conflict.local_start.cmp(&b.conflict.local_start))
+                .then(
+                    b.deduplicated_conflicts
+                        .len()
+                        .cmp(&a.deduplicated_conflicts.len()),
+                )
+                .then(adapted(b).cmp(&adapted(a)))
+                .then(a_rank.cmp(b_rank))
+                .then(a.resolved_version.cmp(&b.resolved_version))
         });
-
-        // Build the final ordered result
-        let mut ordered_result = Vec::new();
-        for (resolved_version, local_start, file_path, _, _, _) in unique_conflicts {
-            let pos = result
-                .iter()
-                .position(|r| {
-                    (
-                        &r.resolved_version,
-                        r.conflict.local_start,
-                        r.conflict.file_path.as_str(),
-                    ) == (&resolved_version, file_path, local_start)
-                })
-                .unwrap();
-            ordered_result.push(result[pos].clone());
-        }
-        ordered_result
+        result.into_iter().map(|(_, resolved)| resolved).collect()
     }
 
     fn combine_model_names(group: &[&ResolvedConflict]) -> String {
@@ -1438,18 +3700,15 @@ impl GitUtils {
                             || (c0 == Some('U') && c1 == Some('D'))
                         {
                             let path = parts[10];
-                            // Run git rm on this file
-                            let rm_output = GitCommand::new("git")
-                                .args(["rm", path])
-                                .output()
-                                .context(format!("Failed to execute git rm --cached {}", path))?;
-
-                            if !rm_output.status.success() {
-                                return Err(anyhow::anyhow!(
-                                    "Git rm --cached {} failed: {}",
-                                    path,
-                                    String::from_utf8_lossy(&rm_output.stderr)
-                                ));
+                            match self.apply_options.modify_delete {
+                                Some(ModifyDelete::Keep) => self.git_update_index(Some(path))?,
+                                Some(ModifyDelete::Delete) => self.git_rm(path)?,
+                                None => {
+                                    return Err(anyhow::anyhow!(
+                                        "Modify/delete conflict in {}, use --modify-delete to keep or delete it",
+                                        path
+                                    ));
+                                }
                             }
                         } else if (c0 == Some('U') && c1 == Some('A'))
                             || (c0 == Some('A') && c1 == Some('U'))
@@ -1465,61 +3724,108 @@ impl GitUtils {
         Ok(())
     }
 
+    /// Delete a file from the working tree and the index
+    fn git_rm(&self, file_path: &str) -> Result<()> {
+        let output = self
+            .git()
+            .args(["-C", self.git_root.as_ref().unwrap(), "rm", "--", file_path])
+            .output()
+            .context(format!("Failed to execute git rm {}", file_path))?;
+
+        if !output.status.success() {
+            return Err(SynthmergeError::git(format!("rm {}", file_path), &output).into());
+        }
+        logger::status!("Deleted {}", file_path);
+        Ok(())
+    }
+
+    /// Run git rerere, enabled regardless of the rerere.enabled config
+    fn git_rerere(&self, args: &[&str]) -> Result<()> {
+        let output = self
+            .git()
+            .args([
+                "-C",
+                self.git_root.as_ref().unwrap(),
+                "-c",
+                "rerere.enabled=true",
+                "rerere",
+            ])
+            .args(args)
+            .output()
+            .context("Failed to execute git rerere")?;
+        if !output.status.success() {
+            return Err(SynthmergeError::git("rerere", &output).into());
+        }
+        Ok(())
+    }
+
     /// Update the git index
     fn git_update_index(&self, file_path: Option<&str>) -> Result<()> {
-        let mut args = vec!["add", "-u"];
+        // Paths are relative to the git root, not to the current directory
+        let mut args = vec!["-C", self.git_root.as_ref().unwrap(), "add", "-u"];
         if let Some(fp) = file_path {
+            args.push("--");
             args.push(fp);
         }
-        let output = GitCommand::new("git")
+        let output = self
+            .git()
             .args(&args)
             .output()
             .context("Failed to execute git add -u")?;
 
         if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Git add -u failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            return Err(SynthmergeError::git("add -u", &output).into());
         }
         if let Some(fp) = file_path {
-            println!("Updated git index for {}", fp);
+            logger::status!("Updated git index for {}", fp);
         } else {
-            println!("Updated git index");
+            logger::status!("Updated git index");
         }
         Ok(())
     }
 
     /// Get the git root directory
-    fn get_git_root_uncached() -> Result<String> {
-        let output = GitCommand::new("git")
+    pub fn get_git_root_uncached(repo: Option<&str>) -> Result<String> {
+        let output = GitCommand::git(repo)
             .args(["rev-parse", "--show-toplevel"])
             .output()
             .context("Failed to execute git rev-parse")?;
 
         if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Git rev-parse failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            if String::from_utf8_lossy(&output.stderr).contains("not a git repository") {
+                return Err(SynthmergeError::NotAGitRepo.into());
+            }
+            return Err(SynthmergeError::git("rev-parse", &output).into());
+        }
+
+        let git_root = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(git_root)
+    }
+
+    /// Get the path of the current directory relative to the git root
+    fn get_git_prefix_uncached(repo: Option<&str>) -> Result<String> {
+        let output = GitCommand::git(repo)
+            .args(["rev-parse", "--show-prefix"])
+            .output()
+            .context("Failed to execute git rev-parse")?;
+
+        if !output.status.success() {
+            return Err(SynthmergeError::git("rev-parse", &output).into());
         }
 
-        let git_root = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        Ok(git_root)
+        let prefix = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(prefix)
     }
 
     /// Get the git directory
-    fn get_git_dir_uncached() -> Result<String> {
-        let output = GitCommand::new("git")
-            .args(["rev-parse", "--git-dir"])
+    fn get_git_dir_uncached(repo: Option<&str>) -> Result<String> {
+        let output = GitCommand::git(repo)
+            .args(["rev-parse", "--absolute-git-dir"])
             .output()
             .context("Failed to execute git rev-parse")?;
 
         if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Git rev-parse failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            return Err(SynthmergeError::git("rev-parse", &output).into());
         }
 
         let git_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -1528,7 +3834,7 @@ impl GitUtils {
 
     /// Check if cherry-pick was run without -x
     fn check_cherry_pick_x(&self, merge_msg_content: &str) -> Result<()> {
-        let operation = self.find_operation_head(self.git_dir.as_ref().unwrap())?;
+        let operation = self.find_operation_head()?;
         if let Some(op) = operation {
             if op.command != "cherry-pick" {
                 return Ok(());
@@ -1544,21 +3850,114 @@ impl GitUtils {
         Ok(())
     }
 
-    /// Update the git merge message to include Assisted-by line
-    fn update_merge_message(&self) -> Result<()> {
-        let git_dir = self.git_dir.as_ref().unwrap();
+    /// Resolve a path inside the git directory with git rev-parse
+    /// --git-path, so the per-worktree files of a linked worktree,
+    /// like MERGE_MSG and the rebase state, are found in its own git
+    /// directory and the shared ones in the common git directory
+    fn git_path(&self, path: &str) -> Result<PathBuf> {
+        let git_root = self
+            .git_root
+            .as_ref()
+            .context("Not running in a git repository")?;
+        let output = self
+            .git()
+            .verbose(false)
+            .args(["-C", git_root, "rev-parse", "--git-path", path])
+            .output()
+            .context("Failed to execute git rev-parse --git-path")?;
+        if !output.status.success() {
+            return Err(SynthmergeError::git("rev-parse --git-path", &output).into());
+        }
+        // A relative path is relative to the directory git ran in
+        Ok(Path::new(git_root).join(String::from_utf8_lossy(&output.stdout).trim()))
+    }
 
-        let merge_msg_path = if self.in_rebase {
-            Path::new(git_dir).join(Self::REBASE_MESSAGE_FILE)
+    /// The message file of the commit concluding the operation
+    fn merge_message_path(&self) -> Result<PathBuf> {
+        self.git_path(if self.in_rebase {
+            Self::REBASE_MESSAGE_FILE
         } else {
-            Path::new(git_dir).join(Self::MERGE_MSG_FILE)
+            Self::MERGE_MSG_FILE
+        })
+    }
+
+    /// Open the commit message in the git editor, which is resolved
+    /// like git commit does from GIT_EDITOR, core.editor, VISUAL and
+    /// EDITOR
+    fn edit_merge_message(&self) -> Result<()> {
+        let merge_msg_path = self.merge_message_path()?;
+        if !merge_msg_path.exists() {
+            log::warn!(
+                "No commit message to edit: {} not found",
+                merge_msg_path.display()
+            );
+            return Ok(());
+        }
+        let output = self
+            .git()
+            .args(["var", "GIT_EDITOR"])
+            .output()
+            .context("Failed to execute git var GIT_EDITOR")?;
+        if !output.status.success() {
+            return Err(SynthmergeError::git("var GIT_EDITOR", &output).into());
+        }
+        let editor = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        // The editor may be a command with arguments
+        let status = Command::new("sh")
+            .args(["-c", &format!("{} \"$1\"", editor), "sh"])
+            .arg(&merge_msg_path)
+            .status()
+            .with_context(|| format!("Failed to run the editor {}", editor))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("The editor {} failed: {}", editor, status));
+        }
+        Ok(())
+    }
+
+    /// The models that contributed to the resolutions, each listed
+    /// once even if it resolved several conflicts
+    fn contributing_models(resolved: &[&ResolvedConflict]) -> String {
+        let mut models: Vec<&ResolvedConflict> = Vec::new();
+        for conflict in resolved {
+            // The deduplicated resolutions carry the resolutions of each
+            // model that agreed on them
+            let group = if conflict.deduplicated_conflicts.is_empty() {
+                std::slice::from_ref(*conflict)
+            } else {
+                conflict.deduplicated_conflicts.as_slice()
+            };
+            for c in group {
+                if !models.iter().any(|m| m.model == c.model) {
+                    models.push(c);
+                }
+            }
+        }
+        Self::combine_model_names(&models)
+    }
+
+    /// The Assisted-by trailer for the resolutions of the given models,
+    /// None if the commit message shouldn't get one
+    fn assisted_by_line(&self, models: &str) -> Option<String> {
+        match self.apply_options.assisted_by {
+            AssistedBy::None => None,
+            AssistedBy::ToolOnly => Some(Self::ASSISTED_BY_LINE.to_string()),
+            AssistedBy::WithModels => Some(format!("{} ({})", Self::ASSISTED_BY_LINE, models)),
+        }
+    }
+
+    /// Update the git merge message to include Assisted-by line
+    fn update_merge_message(&self, resolved: &[&ResolvedConflict]) -> Result<()> {
+        let Some(assisted_by_line) = self.assisted_by_line(&Self::contributing_models(resolved))
+        else {
+            return Ok(());
         };
+        let merge_msg_path = self.merge_message_path()?;
         let merge_msg_content = match fs::read_to_string(&merge_msg_path) {
             Ok(content) => content,
             Err(_) => {
-                println!(
+                logger::status!(
                     "If you use the AI generated code please add \"{}\"",
-                    Self::ASSISTED_BY_LINE
+                    assisted_by_line
                 );
                 return Ok(());
             }
@@ -1603,7 +4002,7 @@ impl GitUtils {
         }
 
         // Insert the Assisted-by line after the last non-empty line
-        let assisted_line = format!("{}{}\n", prefix_newline, Self::ASSISTED_BY_LINE);
+        let assisted_line = format!("{}{}\n", prefix_newline, assisted_by_line);
         lines.insert(insert_pos + 1, assisted_line);
 
         let updated_content = lines.join("");
@@ -1614,7 +4013,7 @@ impl GitUtils {
             )
         })?;
 
-        println!("Added \"{}\"", Self::ASSISTED_BY_LINE);
+        logger::status!("Added \"{}\"", assisted_by_line);
 
         // Check for cherry-pick without -x flag
         self.check_cherry_pick_x(&merge_msg_content)?;
@@ -1623,25 +4022,22 @@ impl GitUtils {
     }
 
     /// Check if we are currently in a cherry-pick, merge, or rebase state
-    pub fn find_commit_hash(&mut self) -> Result<Option<String>> {
-        let git_dir = self
-            .git_dir
-            .as_ref()
-            .context("Not running in a git repository")?;
-
+    /// and return the commits whose diff is provided as context: the
+    /// commit being applied by a cherry-pick, revert or rebase, and the
+    /// merged heads of a merge, one per line of MERGE_HEAD for an
+    /// octopus merge. Empty if no operation is in progress.
+    pub fn find_commit_hashes(&mut self) -> Result<Vec<String>> {
         // Check for cherry-pick, merge, and rebase HEAD files
-        let operation = self.find_operation_head(git_dir)?;
+        let operation = self.find_operation_head()?;
 
-        let content = if let Some(operation) = operation {
+        let commit_hashes = if let Some(operation) = operation {
             let content = std::fs::read_to_string(&operation.path)
-                .with_context(|| format!("Failed to read {}", operation.file))?
-                .trim()
-                .to_string();
+                .with_context(|| format!("Failed to read {}", operation.file))?;
 
             // Check if it's a rebase
             if operation.command == "rebase" {
                 // Also check if the rebase message file exists
-                let rebase_msg_path = Path::new(git_dir).join(Self::REBASE_MESSAGE_FILE);
+                let rebase_msg_path = self.git_path(Self::REBASE_MESSAGE_FILE)?;
                 if rebase_msg_path.exists() {
                     self.in_rebase = true;
                 }
@@ -1653,16 +4049,24 @@ impl GitUtils {
                 }
             }
 
-            Some(content)
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect()
         } else {
-            None
+            // A git stash apply or a git apply --3way leave no HEAD file
+            // behind, their conflicts are resolved without the commit diff
+            log::info!("No cherry-pick, rebase, revert or merge in progress");
+            Vec::new()
         };
 
-        Ok(content)
+        Ok(commit_hashes)
     }
 
     /// Find the most recent operation HEAD file
-    fn find_operation_head(&self, git_dir: &str) -> Result<Option<OperationHead>> {
+    fn find_operation_head(&self) -> Result<Option<OperationHead>> {
         // Check each file and return the most recent one
         let mut retval: Option<OperationHead> = None;
         let mut latest_time = std::time::SystemTime::UNIX_EPOCH;
@@ -1673,7 +4077,7 @@ impl GitUtils {
             ("REVERT_HEAD", "revert"),
             ("MERGE_HEAD", "merge"),
         ] {
-            let path = Path::new(git_dir).join(file);
+            let path = self.git_path(file)?;
             if path.exists() {
                 let metadata = std::fs::metadata(&path)
                     .with_context(|| format!("Failed to get metadata for {}", file))?;
@@ -1695,9 +4099,18 @@ impl GitUtils {
         Ok(retval)
     }
 
-    /// Extract the patch from a specific commit hash
-    pub fn extract_diff(&self, commit_hash: &str, max_context_size: u32) -> Result<Option<String>> {
-        let diff = self.git_show_in_dir(commit_hash, None, None)?;
+    /// Extract the patch of the commits, concatenated in order
+    pub fn extract_diff(
+        &self,
+        commit_hashes: &[String],
+        max_context_size: u32,
+    ) -> Result<Option<String>> {
+        let mut diff: Option<String> = None;
+        for commit_hash in commit_hashes {
+            if let Some(commit_diff) = self.git_show_in_dir(commit_hash, None, None)? {
+                diff.get_or_insert_default().push_str(&commit_diff);
+            }
+        }
         Ok(diff.and_then(|d| {
             if d.len() <= max_context_size.try_into().unwrap() {
                 Some(d)
@@ -1727,8 +4140,9 @@ impl GitUtils {
         let output = if let Some(file) = filename {
             let filearg = &format!("{}:{}", commit_hash, file);
             let args = vec!["-C", &dir, "show", filearg];
-            GitCommand::new("git")
+            self.git()
                 .args(&args)
+                .timeout(self.git_timeout())
                 .output()
                 .context("Failed to execute git show")?
         } else {
@@ -1738,12 +4152,13 @@ impl GitUtils {
                 "show",
                 "--pretty=",
                 "--no-color",
-                "--histogram",
+                self.context_lines.diff_algorithm.git_arg(),
                 diff_context_lines,
                 commit_hash,
             ];
-            GitCommand::new("git")
+            self.git()
                 .args(&args)
+                .timeout(self.git_timeout())
                 .output()
                 .context("Failed to execute git show")?
         };
@@ -1777,6 +4192,1227 @@ impl GitUtils {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFLICT_SIZE_5: &str =
+        "a\n<<<<< HEAD\nlocal\n||||| base\nbase\n=====\nremote\n>>>>> remote\nb\n";
+    const CONFLICT_SIZE_10: &str = "a\n<<<<<<<<<< HEAD\nlocal\n|||||||||| base\nbase\n==========\nremote\n>>>>>>>>>> remote\nb\n";
+
+    fn lines(content: &str) -> Vec<&str> {
+        content.split_inclusive('\n').collect()
+    }
+
+    /// A GitUtils with the default context and apply options
+    fn new_git_utils(resolution_mode: ResolutionMode) -> GitUtils {
+        GitUtils::new(
+            ContextLines::default(),
+            None,
+            false,
+            resolution_mode,
+            0,
+            ApplyOptions::default(),
+        )
+    }
+
+    #[test]
+    fn test_remove_conflict_markers_size_5() {
+        let content = lines(CONFLICT_SIZE_5);
+        let local =
+            GitUtils::remove_conflict_markers(&content, 5, ConflictMarkerMode::Local, false)
+                .unwrap();
+        assert_eq!(local.join(""), "a\nlocal\nb\n");
+        let remote =
+            GitUtils::remove_conflict_markers(&content, 5, ConflictMarkerMode::Remote, false)
+                .unwrap();
+        assert_eq!(remote.join(""), "a\nremote\nb\n");
+        assert!(!GitUtils::has_nested_markers(&local.join(""), 5));
+
+        // A size 5 conflict left in the content of a size 5 file is nested
+        let nested = format!("{}{}", local.join(""), CONFLICT_SIZE_5);
+        assert!(GitUtils::has_nested_markers(&nested, 5));
+        assert!(!GitUtils::has_nested_markers(&nested, 7));
+    }
+
+    #[test]
+    fn test_detect_marker_size() {
+        assert_eq!(
+            GitUtils::detect_marker_size(CONFLICT_SIZE_5).unwrap(),
+            Some(5)
+        );
+        assert_eq!(
+            GitUtils::detect_marker_size(CONFLICT_SIZE_10).unwrap(),
+            Some(10)
+        );
+        let conflict_size_9 = CONFLICT_SIZE_10
+            .replace("<<<<<<<<<<", "<<<<<<<<<")
+            .replace("||||||||||", "|||||||||")
+            .replace("==========", "=========")
+            .replace(">>>>>>>>>>", ">>>>>>>>>");
+        assert_eq!(
+            GitUtils::detect_marker_size(&conflict_size_9).unwrap(),
+            Some(9)
+        );
+        let markers = lines(&conflict_size_9);
+        let conflicts = GitUtils::conflict_breadcrumbs(&conflict_size_9, 9).unwrap();
+        assert_eq!(
+            conflicts[0],
+            [markers[1], markers[2], markers[5], markers[6], markers[7]].concat()
+        );
+        assert_eq!(GitUtils::detect_marker_size("a\nb\n").unwrap(), None);
+        let legit = format!("<<< not a marker\n{}", CONFLICT_SIZE_5);
+        assert_eq!(GitUtils::detect_marker_size(&legit).unwrap(), Some(5));
+
+        // Mismatching marker sizes in the same conflict
+        let mismatch = CONFLICT_SIZE_5.replace("=====", "=======");
+        assert!(GitUtils::detect_marker_size(&mismatch).is_err());
+        let mismatch = CONFLICT_SIZE_5.replace(">>>>>", ">>>>>>");
+        assert!(GitUtils::detect_marker_size(&mismatch).is_err());
+    }
+
+    #[test]
+    fn test_has_ai_block() {
+        let git_utils = new_git_utils(ResolutionMode::Interactive);
+        let conflict =
+            "a\n<<<<<<< HEAD\nlocal\n||||||| base\nbase\n=======\nremote\n>>>>>>> remote\nb\n";
+        let conflicts = git_utils.parse_file_conflicts("f", conflict, 7).unwrap();
+        assert!(!conflicts[0].has_ai_block);
+        let resolved = conflict.replace(
+            ">>>>>>> remote",
+            "&&&&&&& synthmerge: mock\nmerged\n>>>>>>> remote",
+        );
+        let conflicts = git_utils.parse_file_conflicts("f", &resolved, 7).unwrap();
+        assert!(conflicts[0].has_ai_block);
+        assert_eq!(conflicts[0].remote, "remote\n");
+    }
+
+    #[test]
+    fn test_git_command_timeout() {
+        let output = GitCommand::new("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .timeout(Duration::from_secs(60))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        // The input is written while the output is read, more than a
+        // pipe holds
+        let input = vec![b'x'; 1 << 20];
+        for timeout in [None, Some(Duration::from_secs(60))] {
+            let mut cat = GitCommand::new("cat");
+            cat.stdin(input.clone());
+            if let Some(timeout) = timeout {
+                cat.timeout(timeout);
+            }
+            let output = cat.output().unwrap();
+            assert!(output.status.success());
+            assert_eq!(output.stdout, input);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let start = std::time::Instant::now();
+        let result = GitCommand::new("sh")
+            .args([
+                "-c",
+                &format!("echo $$ > {}; exec sleep 60", pid_file.display()),
+            ])
+            .timeout(Duration::from_millis(200))
+            .output();
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(30));
+        // The command was killed and reaped
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(!Path::new(&format!("/proc/{}", pid.trim())).exists());
+
+        // The timeout ends at the deadline
+        let mut git_utils = new_git_utils(ResolutionMode::Interactive);
+        assert_eq!(git_utils.git_timeout(), GitCommand::TIMEOUT);
+        git_utils.set_deadline(Some(std::time::Instant::now() + Duration::from_secs(60)));
+        let timeout = git_utils.git_timeout();
+        assert!(timeout <= Duration::from_secs(60) && timeout > Duration::from_secs(50));
+        git_utils.set_deadline(Some(std::time::Instant::now()));
+        assert_eq!(git_utils.git_timeout(), GitCommand::MIN_TIMEOUT);
+    }
+
+    #[test]
+    fn test_diff_algorithm() {
+        let repo = tempfile::tempdir().unwrap();
+        let dir = repo.path().to_str().unwrap();
+        let git = |args: &[&str]| {
+            let output = GitCommand::new("git")
+                .args(["-C", dir])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?}", args);
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "test"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "commit.gpgsign", "false"]);
+        let frobnitz = "// Frobs foo\nint frobnitz(int foo)\n{\n    int i;\n    for(i = 0; i < 10; i++)\n    {\n        printf(\"Answer: \");\n        printf(\"%d\\n\", foo);\n    }\n}\n\n";
+        let fact = "int fact(int n)\n{\n    if(n > 1)\n    {\n        return fact(n-1) * n;\n    }\n    return 1;\n}\n\n";
+        let fib = "int fib(int n)\n{\n    if(n > 2)\n    {\n        return fib(n-1) + fib(n-2);\n    }\n    return 1;\n}\n\n";
+        let new_frobnitz = frobnitz.replace("        printf(\"Answer: \");\n", "");
+        std::fs::write(repo.path().join("f.c"), format!("{}{}", frobnitz, fact)).unwrap();
+        git(&["add", "f.c"]);
+        git(&["commit", "-q", "-m", "fact"]);
+        std::fs::write(repo.path().join("f.c"), format!("{}{}", fib, new_frobnitz)).unwrap();
+        git(&["commit", "-q", "-am", "fib"]);
+
+        let show = |diff_algorithm| {
+            let git_utils = GitUtils::new(
+                ContextLines {
+                    diff_algorithm,
+                    ..Default::default()
+                },
+                None,
+                false,
+                ResolutionMode::Interactive,
+                0,
+                ApplyOptions::default(),
+            );
+            git_utils
+                .git_show_in_dir("HEAD", Some(dir), None)
+                .unwrap()
+                .unwrap()
+        };
+        // Myers interleaves the two functions, histogram keeps frobnitz
+        // untouched but for the removed line
+        assert!(show(DiffAlgorithm::Myers).contains("\n-// Frobs foo\n"));
+        assert!(!show(DiffAlgorithm::Histogram).contains("\n-// Frobs foo\n"));
+        assert_eq!(
+            <DiffAlgorithm as clap::ValueEnum>::from_str("patience", false),
+            Ok(DiffAlgorithm::Patience)
+        );
+        assert!(<DiffAlgorithm as clap::ValueEnum>::from_str("hstogram", false).is_err());
+        assert_eq!(
+            ContextLines::default().diff_algorithm,
+            DiffAlgorithm::Histogram
+        );
+    }
+
+    #[test]
+    fn test_marker_size_override() {
+        let mut git_utils = new_git_utils(ResolutionMode::Interactive);
+        git_utils.file_attributes.insert(
+            "f.c".to_string(),
+            FileAttributes {
+                marker_size: Some(10),
+                ..Default::default()
+            },
+        );
+        let size = |git_utils: &GitUtils, file_path| {
+            git_utils
+                .get_marker_size_for_file(file_path, CONFLICT_SIZE_5)
+                .unwrap()
+        };
+        assert_eq!(size(&git_utils, "f.c"), 10);
+        assert_eq!(size(&git_utils, "g.c"), 5);
+        // The override wins over the gitattributes and the detection
+        git_utils.apply_options.marker_size = Some(12);
+        assert_eq!(size(&git_utils, "f.c"), 12);
+        assert_eq!(size(&git_utils, "g.c"), 12);
+    }
+
+    #[test]
+    fn test_merge_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let base = write("base", "a\nb\nc\n");
+        let local = write("local", "a\nlocal\nc\n");
+        let remote = write("remote", "a\nb\nc\nremote\n");
+        let (content, marker_size, conflicted) =
+            GitUtils::merge_file(&local, &base, &remote).unwrap();
+        assert_eq!(content, "a\nlocal\nc\nremote\n");
+        assert_eq!(marker_size, GitUtils::DEFAULT_MARKER_SIZE);
+        assert!(!conflicted);
+
+        // A marker-like line of the inputs makes the markers longer
+        let remote = write("remote", "a\n========\nc\n");
+        let (content, marker_size, conflicted) =
+            GitUtils::merge_file(&local, &base, &remote).unwrap();
+        assert_eq!(marker_size, 9);
+        assert!(conflicted);
+        assert_eq!(
+            content,
+            "a\n<<<<<<<<< LOCAL\nlocal\n||||||||| BASE\nb\n=========\n========\n>>>>>>>>> REMOTE\nc\n"
+        );
+        let git_utils = new_git_utils(ResolutionMode::VibeWithMarkers);
+        let conflicts = git_utils
+            .parse_file_conflicts("merged", &content, marker_size)
+            .unwrap();
+        assert_eq!(conflicts.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_conflicts_at_file_boundaries() {
+        let git_utils = new_git_utils(ResolutionMode::Interactive);
+
+        // Conflict at the first line
+        let content = "<<<<< HEAD\nlocal\n||||| base\nbase\n=====\nremote\n>>>>> remote\nb\nc\n";
+        let conflicts = git_utils.parse_conflicts(content, 5).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].start_line, 0);
+        assert_eq!(conflicts[0].nr_head_context_lines, 0);
+        assert_eq!(conflicts[0].head_context, "");
+        assert_eq!(conflicts[0].nr_tail_context_lines, 2);
+        assert_eq!(conflicts[0].tail_context, "b\nc\n");
+        assert_eq!(conflicts[0].local_start, 0);
+        assert_eq!(conflicts[0].local_end, 1);
+
+        // Conflict at the last line, with and without newline at EOF
+        for eof in ["\n", ""] {
+            let content = format!(
+                "a\nb\n<<<<< HEAD\nlocal\n||||| base\nbase\n=====\nremote\n>>>>> remote{}",
+                eof
+            );
+            let conflicts = git_utils.parse_conflicts(&content, 5).unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].start_line, 2);
+            assert_eq!(conflicts[0].nr_head_context_lines, 2);
+            assert_eq!(conflicts[0].head_context, "a\nb\n");
+            assert_eq!(conflicts[0].nr_tail_context_lines, 0);
+            assert_eq!(conflicts[0].tail_context, "");
+            assert_eq!(conflicts[0].conflict_code, "local\n");
+            assert_eq!(conflicts[0].remote, "remote\n");
+        }
+
+        // The whole file is a conflict
+        let conflicts = git_utils
+            .parse_conflicts(&CONFLICT_SIZE_5[2..CONFLICT_SIZE_5.len() - 2], 5)
+            .unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].nr_head_context_lines, 0);
+        assert_eq!(conflicts[0].nr_tail_context_lines, 0);
+    }
+
+    #[test]
+    fn test_parse_back_to_back_conflicts() {
+        let git_utils = new_git_utils(ResolutionMode::Interactive);
+        let content = concat!(
+            "a\n",
+            "<<<<< HEAD\nlocal1\n||||| base\nbase1\n=====\nremote1\n>>>>> remote\n",
+            "<<<<< HEAD\nlocal2\n||||| base\nbase2\n=====\nremote2\n>>>>> remote\n",
+            "b\n",
+        );
+        let conflicts = git_utils.parse_conflicts(content, 5).unwrap();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].head_context, "a\n");
+        assert_eq!(conflicts[0].nr_tail_context_lines, 0);
+        assert_eq!(conflicts[0].tail_context, "");
+        assert_eq!(conflicts[1].nr_head_context_lines, 0);
+        assert_eq!(conflicts[1].head_context, "");
+        assert_eq!(conflicts[1].tail_context, "b\n");
+        for (conflict, other) in conflicts.iter().zip(["2", "1"]) {
+            let other_lines = ["local", "base", "remote"].map(|side| format!("{}{}", side, other));
+            for context in [&conflict.head_context, &conflict.tail_context] {
+                assert!(!context.contains("<<<<<"));
+                assert!(!context.contains("|||||"));
+                assert!(!context.contains("====="));
+                assert!(!context.contains(">>>>>"));
+                assert!(other_lines.iter().all(|line| !context.contains(line)));
+            }
+            assert!(
+                other_lines
+                    .iter()
+                    .all(|line| !conflict.conflict_patch.contains(line))
+            );
+        }
+
+        // A single line shared as context by both conflicts, a longer
+        // run of marker characters isn't a marker
+        let content = content.replace(">>>>> remote\n<<<<<", ">>>>> remote\n>>>>>>>>>>\n<<<<<");
+        let conflicts = git_utils.parse_conflicts(&content, 5).unwrap();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].tail_context, ">>>>>>>>>>\n");
+        assert_eq!(conflicts[1].head_context, ">>>>>>>>>>\n");
+    }
+
+    #[test]
+    fn test_ignore_stray_markers() {
+        let git_utils = |ignore_stray_markers| {
+            GitUtils::new(
+                ContextLines::default(),
+                None,
+                false,
+                ResolutionMode::Interactive,
+                0,
+                ApplyOptions {
+                    ignore_stray_markers,
+                    ..Default::default()
+                },
+            )
+        };
+        let stray = "<<<<< ours\nx\n=====\ny\n>>>>> theirs\n";
+        let content = format!("doc\n{}mid\n{}", stray, &CONFLICT_SIZE_5[2..]);
+        assert!(git_utils(false).parse_conflicts(&content, 5).is_err());
+
+        let conflicts = git_utils(true).parse_conflicts(&content, 5).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].conflict_code, "local\n");
+        assert_eq!(conflicts[0].start_line, 7);
+        // The stray markers are part of the local content
+        assert_eq!(conflicts[0].local_start, 7);
+        assert_eq!(conflicts[0].head_context, "mid\n");
+
+        let local =
+            GitUtils::remove_conflict_markers(&lines(&content), 5, ConflictMarkerMode::Local, true)
+                .unwrap();
+        assert_eq!(local.join(""), format!("doc\n{}mid\nlocal\nb\n", stray));
+    }
+
+    #[test]
+    fn test_parse_conflicts_merge_style() {
+        let git_utils = |conflict_style| {
+            GitUtils::new(
+                ContextLines {
+                    code_context_lines: 3,
+                    diff_context_lines: 3,
+                    patch_context_lines: 3,
+                    extra_conflict_lines: 0,
+                    diff_algorithm: DiffAlgorithm::Histogram,
+                },
+                None,
+                false,
+                ResolutionMode::Interactive,
+                0,
+                ApplyOptions {
+                    conflict_style,
+                    ..Default::default()
+                },
+            )
+        };
+        let content = "a\n<<<<< HEAD\nlocal\n=====\nremote\n>>>>> remote\nb\n";
+        assert!(
+            git_utils(ConflictStyle::Diff3)
+                .parse_conflicts(content, 5)
+                .is_err()
+        );
+        let conflicts = git_utils(ConflictStyle::Merge)
+            .parse_conflicts(content, 5)
+            .unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].conflict_code, "local\n");
+        assert_eq!(conflicts[0].base, "");
+        assert_eq!(conflicts[0].remote, "remote\n");
+        assert_eq!(conflicts[0].head_context, "a\n");
+        assert_eq!(conflicts[0].tail_context, "b\n");
+        assert_eq!(conflicts[0].base_start, conflicts[0].base_end);
+        assert!(conflicts[0].conflict_patch.contains("\n+remote\n"));
+
+        // Both the local side and the base can be empty
+        let added = "a\n<<<<< HEAD\n=====\nremote\n>>>>> remote\nb\n";
+        let conflicts = git_utils(ConflictStyle::Merge)
+            .parse_conflicts(added, 5)
+            .unwrap();
+        assert_eq!(conflicts[0].conflict_code, "");
+        assert_eq!(conflicts[0].base, "");
+        assert_eq!(conflicts[0].remote, "remote\n");
+
+        // diff3 conflicts parse the same in both styles
+        let diff3 = git_utils(ConflictStyle::Diff3)
+            .parse_conflicts(CONFLICT_SIZE_5, 5)
+            .unwrap();
+        let merge = git_utils(ConflictStyle::Merge)
+            .parse_conflicts(CONFLICT_SIZE_5, 5)
+            .unwrap();
+        assert_eq!(diff3[0].conflict_patch, merge[0].conflict_patch);
+        assert_eq!(diff3[0].conflict_code, "local\n");
+    }
+
+    #[test]
+    fn test_patch_context_lines() {
+        let git_utils = |patch_context_lines| {
+            GitUtils::new(
+                ContextLines {
+                    code_context_lines: 5,
+                    diff_context_lines: 3,
+                    patch_context_lines,
+                    extra_conflict_lines: 0,
+                    diff_algorithm: DiffAlgorithm::Histogram,
+                },
+                None,
+                false,
+                ResolutionMode::Interactive,
+                0,
+                ApplyOptions::default(),
+            )
+        };
+        let content = "h1\nh2\nh3\nh4\nh5\n<<<<< HEAD\nlocal\n||||| base\nbase\n=====\nremote\n>>>>> remote\nt1\nt2\nt3\nt4\nt5\n";
+        let resolved = "h1\nh2\nh3\nh4\nh5\nmerged\nt1\nt2\nt3\nt4\nt5\n";
+        for (patch_context_lines, inside, outside) in [(1, "h5", "h4"), (3, "h3", "h2")] {
+            let git_utils = git_utils(patch_context_lines);
+            // The patch of the conflict in the prompt
+            let conflicts = git_utils.parse_conflicts(content, 5).unwrap();
+            let conflict_patch = &conflicts[0].conflict_patch;
+            assert!(conflict_patch.contains(&format!("\n {}\n", inside)));
+            assert!(!conflict_patch.contains(outside));
+            // The hunks of the output patch
+            let file_patch = git_utils.file_patch("f", content, resolved);
+            assert!(file_patch.contains(&format!("\n {}\n", inside)));
+            assert!(!file_patch.contains(outside));
+        }
+    }
+
+    /// The local side of a file lacking a trailing newline
+    const NO_EOF_NEWLINE: &str = "a\nb\nc\nd\ne";
+
+    #[test]
+    fn test_vibe_resolution_no_eof_newline() {
+        let git_utils = GitUtils::new(
+            ContextLines {
+                code_context_lines: 3,
+                diff_context_lines: 3,
+                patch_context_lines: 3,
+                extra_conflict_lines: 0,
+                diff_algorithm: DiffAlgorithm::Histogram,
+            },
+            None,
+            false,
+            ResolutionMode::VibeWithPatchLocator,
+            0,
+            ApplyOptions::default(),
+        );
+        // The blob content is newline terminated by get_blob_content
+        let merged_local_lines: Vec<String> = format!("{}\n", NO_EOF_NEWLINE)
+            .split_inclusive('\n')
+            .map(|s| s.to_string())
+            .collect();
+        let conflict = |local_start, local_end| Conflict {
+            file_path: "f".to_string(),
+            local_start,
+            local_end,
+            merged_local_lines: Arc::new(merged_local_lines.clone()),
+            ..Default::default()
+        };
+        let resolved = |conflict: &Conflict, resolved_version: &str| ResolvedConflict {
+            conflict: conflict.clone(),
+            resolved_version: resolved_version.to_string(),
+            model: "model".to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: None,
+            beam: None,
+        };
+        let apply = |conflicts: &[&Conflict], resolved_conflicts: &[ResolvedConflict]| {
+            let lines = git_utils
+                .apply_vibe_resolution_to_file(conflicts, resolved_conflicts)
+                .unwrap()
+                .unwrap();
+            let mut content = lines.join("");
+            EofNewline::Preserve.apply(&mut content, false);
+            content
+        };
+
+        // A resolution lacking a newline in the middle of the file
+        // isn't joined with the next line, and the untouched tail is
+        // reproduced byte for byte
+        let middle = conflict(1, 2);
+        let content = apply(&[&middle], &[resolved(&middle, "B")]);
+        assert_eq!(content, "a\nB\nc\nd\ne");
+        assert_eq!(content.as_bytes()[3..], NO_EOF_NEWLINE.as_bytes()[3..]);
+
+        // A newline terminated resolution at the end of file doesn't
+        // add a newline the local side doesn't have
+        let tail = conflict(4, 5);
+        let content = apply(
+            &[&middle, &tail],
+            &[resolved(&middle, "b\n"), resolved(&tail, "E\n")],
+        );
+        assert_eq!(content, "a\nb\nc\nd\nE");
+        let content = apply(&[&tail], &[resolved(&tail, "")]);
+        assert_eq!(content, "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn test_is_applied() {
+        let mut git_utils = new_git_utils(ResolutionMode::VibeWithMarkers);
+        let conflict = Conflict {
+            file_path: "f".to_string(),
+            local_start: 1,
+            ..Default::default()
+        };
+        let resolved = |endpoint, model: &str| ResolvedConflict {
+            conflict: conflict.clone(),
+            resolved_version: "    x++;\n".to_string(),
+            model: model.to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint,
+            multi: Some(0),
+            beam: Some(0),
+        };
+        let resolutions = [resolved(0, "a"), resolved(1, "b"), resolved(2, "c")];
+        let applied = GitUtils::deduplicate_conflicts(&resolutions[..2], &Default::default());
+
+        // Reindented by --match-indent before being written
+        git_utils.record_applied(&[&ResolvedConflict {
+            resolved_version: "\tx++;\n".to_string(),
+            ..applied[0].clone()
+        }]);
+        assert!(git_utils.is_applied(&resolutions[0]));
+        assert!(git_utils.is_applied(&resolutions[1]));
+        assert!(!git_utils.is_applied(&resolutions[2]));
+        // Deduplicated again with the other models by the report
+        let reported = GitUtils::deduplicate_conflicts(&resolutions, &Default::default());
+        assert_eq!(reported.len(), 1);
+        assert!(git_utils.is_applied(&reported[0]));
+    }
+
+    #[test]
+    fn test_filter_conflict_markers() {
+        let mut git_utils = new_git_utils(ResolutionMode::VibeWithPatchLocator);
+        git_utils.marker_sizes.insert("f10".to_string(), 10);
+        let resolved = |file_path: &str, resolved_version: &str| ResolvedConflict {
+            conflict: Conflict {
+                file_path: file_path.to_string(),
+                ..Default::default()
+            },
+            resolved_version: resolved_version.to_string(),
+            model: "model".to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: None,
+            beam: None,
+        };
+        let filtered = git_utils.filter_conflict_markers(&[
+            resolved("f", "a\nb\n"),
+            resolved("f", "a\n<<<<<<< HEAD\nb\n"),
+            resolved("f", "a\n=======\nb\n"),
+            resolved("f", "a\nb\n>>>>>>>"),
+            // Not markers of the file's size
+            resolved("f", "a\n==========\n>>>>>>>>\n"),
+            resolved("f10", "a\n=======\nb\n"),
+            resolved("f10", "a\n>>>>>>>>>> theirs\nb\n"),
+        ]);
+        assert_eq!(
+            filtered
+                .iter()
+                .map(|r| (r.conflict.file_path.as_str(), r.resolved_version.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("f", "a\nb\n"),
+                ("f", "a\n==========\n>>>>>>>>\n"),
+                ("f10", "a\n=======\nb\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_file_content() {
+        let mut git_utils = new_git_utils(ResolutionMode::VibeWithMarkers);
+        let content = concat!(
+            "a\n",
+            "<<<<<<< HEAD\nb-local\n||||||| base\nb\n=======\nb-remote\n>>>>>>> topic\n",
+            "c\n",
+            "<<<<<<< HEAD\nd-local\n||||||| base\nd\n=======\nd-remote\n>>>>>>> topic\n",
+        );
+        let conflicts = git_utils.parse_file_conflicts("f", content, 7).unwrap();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(
+            conflicts[0].merged_local_lines.join(""),
+            "a\nb-local\nc\nd-local\n"
+        );
+        let resolved = |conflict: &Conflict, resolved_version: &str| ResolvedConflict {
+            conflict: conflict.clone(),
+            resolved_version: resolved_version.to_string(),
+            model: "model".to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: Some(0),
+            beam: Some(0),
+        };
+        let resolved_conflicts = vec![
+            resolved(&conflicts[0], "b-merged\n"),
+            resolved(&conflicts[1], "d-merged"),
+        ];
+        assert_eq!(
+            git_utils
+                .resolve_file_content(&conflicts, &resolved_conflicts, false)
+                .unwrap(),
+            "a\nb-merged\nc\nd-merged"
+        );
+        assert!(
+            git_utils
+                .resolve_file_content(&conflicts, &resolved_conflicts[..1], true)
+                .is_err()
+        );
+        assert!(git_utils.parse_file_conflicts("f", "a\n", 7).is_err());
+    }
+
+    #[test]
+    fn test_min_agreement() {
+        let mut git_utils = GitUtils::new(
+            ContextLines::default(),
+            None,
+            false,
+            ResolutionMode::VibeWithMarkers,
+            0,
+            ApplyOptions {
+                min_agreement: 2,
+                ..Default::default()
+            },
+        );
+        let content = "<<<<<<< HEAD\nlocal\n||||||| base\nbase\n=======\nremote\n>>>>>>> topic\n";
+        let conflicts = git_utils.parse_file_conflicts("f", content, 7).unwrap();
+        let resolved = |model: &str, resolved_version: &str, multi| ResolvedConflict {
+            conflict: conflicts[0].clone(),
+            resolved_version: resolved_version.to_string(),
+            model: model.to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: model.as_bytes()[0] as usize,
+            multi: Some(multi),
+            beam: Some(0),
+        };
+        // A single model doesn't meet the bar
+        assert!(
+            git_utils
+                .resolve_file_content(
+                    &conflicts,
+                    &[resolved("a", "merged\n", 0), resolved("b", "other\n", 0)],
+                    true
+                )
+                .is_err()
+        );
+        assert_eq!(
+            GitUtils::nr_agreeing_models(
+                &GitUtils::deduplicate_conflicts(
+                    &[
+                        resolved("a", "merged\n", 0),
+                        resolved("a (1)", "merged\n", 1),
+                    ],
+                    &AgreementConfig::default(),
+                )[0]
+            ),
+            1
+        );
+        assert_eq!(
+            git_utils
+                .resolve_file_content(
+                    &conflicts,
+                    &[
+                        resolved("a", "merged\n", 0),
+                        resolved("b", "other\n", 0),
+                        resolved("c", "merged\n", 0),
+                    ],
+                    true
+                )
+                .unwrap(),
+            "merged\n"
+        );
+    }
+
+    #[test]
+    fn test_min_agreement_default() {
+        let content = "<<<<<<< HEAD\nlocal\n||||||| base\nbase\n=======\nremote\n>>>>>>> topic\n";
+        for min_agreement in [0, 1] {
+            let git_utils = GitUtils::new(
+                ContextLines::default(),
+                None,
+                false,
+                ResolutionMode::Interactive,
+                0,
+                ApplyOptions {
+                    min_agreement,
+                    ..Default::default()
+                },
+            );
+            let conflicts = git_utils.parse_file_conflicts("f", content, 7).unwrap();
+            // Only the second beam of the model found the resolution
+            let resolved = ResolvedConflict {
+                conflict: conflicts[0].clone(),
+                resolved_version: "merged\n".to_string(),
+                model: "a (#1)".to_string(),
+                duration: 0.0,
+                total_tokens: None,
+                input_tokens: None,
+                logprob: None,
+                deduplicated_conflicts: Vec::new(),
+                endpoint: 0,
+                multi: Some(0),
+                beam: Some(1),
+            };
+            let selected = git_utils.select_resolutions(GitUtils::deduplicate_conflicts(
+                &[resolved],
+                &AgreementConfig::default(),
+            ));
+            assert_eq!(selected.len(), 1);
+            assert_eq!(selected[0].resolved_version, "merged\n");
+        }
+    }
+
+    #[test]
+    fn test_agreement_whitespace() {
+        let resolved = |model: &str, resolved_version: &str| ResolvedConflict {
+            conflict: Conflict::default(),
+            resolved_version: resolved_version.to_string(),
+            model: model.to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: Some(0),
+            beam: Some(0),
+        };
+        let resolutions = [
+            resolved("a", "if (x) {\n\ty();\n}\n"),
+            resolved("b", "if (x) { \n\ty();\n}"),
+            resolved("c", "if (x) {\n    y();\n}\n"),
+        ];
+        let models = |agreement: &AgreementConfig| {
+            GitUtils::deduplicate_conflicts(&resolutions, agreement)
+                .into_iter()
+                .map(|r| (r.model, r.resolved_version))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(models(&AgreementConfig::default()).len(), 3);
+        // The trailing space and the missing final newline don't count
+        // as disagreement, the text of the first model is applied
+        let mut agreement = AgreementConfig {
+            ignore_trailing_whitespace: true,
+            ignore_eof_newline: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            models(&agreement),
+            vec![
+                ("a, b".to_string(), "if (x) {\n\ty();\n}\n".to_string()),
+                ("c".to_string(), "if (x) {\n    y();\n}\n".to_string()),
+            ]
+        );
+        agreement.ignore_indentation = true;
+        assert_eq!(models(&agreement).len(), 1);
+    }
+
+    #[test]
+    fn test_deduplicate_aggregation() {
+        let resolved = |model: &str, total_tokens, logprob, duration| ResolvedConflict {
+            conflict: Conflict::default(),
+            resolved_version: "x\n".to_string(),
+            model: model.to_string(),
+            duration,
+            total_tokens,
+            input_tokens: total_tokens.map(|t| t / 2),
+            logprob,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: Some(0),
+            beam: Some(0),
+        };
+        let agreement = AgreementConfig::default();
+        let dedup = GitUtils::deduplicate_conflicts(
+            &[
+                resolved("a", Some(101), Some(-0.5), 2.0),
+                resolved("b", Some(100), Some(-0.1), 3.0),
+                resolved("c", None, None, 1.0),
+            ],
+            &agreement,
+        );
+        assert_eq!(dedup.len(), 1);
+        assert_eq!(dedup[0].model, "a, b, c");
+        assert_eq!(dedup[0].total_tokens, Some(201));
+        assert_eq!(dedup[0].input_tokens, Some(100));
+        assert_eq!(dedup[0].logprob, Some(-0.1));
+        assert_eq!(dedup[0].duration, 3.0);
+
+        let dedup = GitUtils::deduplicate_conflicts(&[resolved("c", None, None, 1.0)], &agreement);
+        assert_eq!(dedup[0].total_tokens, None);
+        assert_eq!(dedup[0].input_tokens, None);
+        assert_eq!(dedup[0].logprob, None);
+    }
+
+    #[test]
+    fn test_deduplicate_order() {
+        let resolved = |file_path: &str, endpoint: usize, beam: usize, resolved_version: &str| {
+            ResolvedConflict {
+                conflict: Conflict {
+                    file_path: file_path.to_string(),
+                    local_start: 1,
+                    ..Default::default()
+                },
+                resolved_version: resolved_version.to_string(),
+                model: format!("m{} ({})", endpoint, beam),
+                duration: endpoint as f64,
+                total_tokens: None,
+                input_tokens: None,
+                logprob: Some(-0.1 * (endpoint + beam) as f64),
+                deduplicated_conflicts: Vec::new(),
+                endpoint,
+                multi: Some(0),
+                beam: Some(beam),
+            }
+        };
+        let mut resolutions = vec![
+            resolved("b", 0, 0, "x\n"),
+            resolved("b", 1, 0, "z\n"),
+            resolved("b", 2, 0, "y\n"),
+            resolved("b", 2, 1, "w\n"),
+            resolved("a", 0, 0, "x\n"),
+            resolved("a", 1, 0, "x\n"),
+            resolved("a", 2, 0, "y\n"),
+            resolved("a", 2, 1, "y\n"),
+        ];
+        let agreement = AgreementConfig::default();
+        let expected = GitUtils::deduplicate_conflicts(&resolutions, &agreement);
+        assert_eq!(
+            expected
+                .iter()
+                .map(|r| (r.conflict.file_path.as_str(), r.model.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("a", "m0 (0), m1 (0)"),
+                ("a", "m2 (0|1)"),
+                ("b", "m0 (0)"),
+                ("b", "m1 (0)"),
+                ("b", "m2 (0)"),
+                ("b", "m2 (1)"),
+            ]
+        );
+        // A fixed pseudo random shuffle of the input
+        let mut seed = 1u64;
+        for _ in 0..20 {
+            for i in (1..resolutions.len()).rev() {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                resolutions.swap(i, (seed >> 33) as usize % (i + 1));
+            }
+            assert_eq!(
+                GitUtils::deduplicate_conflicts(&resolutions, &agreement),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_enclosing_symbol() {
+        let git_utils = new_git_utils(ResolutionMode::VibeWithMarkers);
+        let conflict =
+            "<<<<<<< HEAD\n\tb = 1;\n||||||| base\n\tb = 0;\n=======\n\tb = 2;\n>>>>>>> topic\n";
+        let content = format!(
+            "#include <a.h>\n{}static int foo(int a,\n\t       int b)\n{{\n\tint b;\nout:\n{}}}\n",
+            conflict, conflict
+        );
+        let conflicts = git_utils.parse_file_conflicts("a.c", &content, 7).unwrap();
+        assert_eq!(conflicts[0].enclosing_symbol, "");
+        assert_eq!(conflicts[1].enclosing_symbol, "static int foo(int a,");
+
+        let content = format!(
+            "impl Foo {{\n    pub(crate) async fn bar(&self) {{\n        let x = 1;\n{}    }}\n}}\n",
+            conflict
+        );
+        let conflicts = git_utils.parse_file_conflicts("a.rs", &content, 7).unwrap();
+        assert_eq!(
+            conflicts[0].enclosing_symbol,
+            "pub(crate) async fn bar(&self) {"
+        );
+        // Unknown languages have no enclosing symbol
+        let conflicts = git_utils
+            .parse_file_conflicts("a.txt", &content, 7)
+            .unwrap();
+        assert_eq!(conflicts[0].enclosing_symbol, "");
+    }
+
+    #[test]
+    fn test_contributing_models() {
+        let resolved = |local_start: usize, resolved_version: &str, model: &str| ResolvedConflict {
+            // The models are ordered by endpoint
+            endpoint: usize::from(model == "claude"),
+            conflict: Conflict {
+                file_path: "f".to_string(),
+                local_start,
+                ..Default::default()
+            },
+            resolved_version: resolved_version.to_string(),
+            model: model.to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            multi: None,
+            beam: None,
+        };
+        let deduplicated = GitUtils::deduplicate_conflicts(
+            &[
+                resolved(1, "a\n", "gpt (low)"),
+                resolved(1, "a\n", "claude"),
+                resolved(9, "b\n", "gpt (high)"),
+                resolved(9, "b\n", "claude"),
+            ],
+            &AgreementConfig::default(),
+        );
+        let deduplicated: Vec<&ResolvedConflict> = deduplicated.iter().collect();
+        assert_eq!(
+            GitUtils::contributing_models(&deduplicated),
+            "gpt (low|high), claude"
+        );
+
+        let mut git_utils = new_git_utils(ResolutionMode::VibeWithPatchLocator);
+        assert_eq!(
+            git_utils.assisted_by_line("claude").as_deref(),
+            Some("Assisted-by: synthmerge")
+        );
+        git_utils.apply_options.assisted_by = AssistedBy::WithModels;
+        assert_eq!(
+            git_utils.assisted_by_line("claude").as_deref(),
+            Some("Assisted-by: synthmerge (claude)")
+        );
+        git_utils.apply_options.assisted_by = AssistedBy::None;
+        assert_eq!(git_utils.assisted_by_line("claude"), None);
+    }
+
+    #[test]
+    fn test_review_resolutions() {
+        let resolved = |local_start: usize, resolved_version: &str, model: &str| ResolvedConflict {
+            conflict: Conflict {
+                file_path: "f".to_string(),
+                local_start,
+                ..Default::default()
+            },
+            resolved_version: resolved_version.to_string(),
+            model: model.to_string(),
+            duration: 0.0,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: None,
+            beam: None,
+        };
+        let resolved_conflicts = vec![
+            resolved(9, "c\n", "gpt"),
+            resolved(1, "a\n", "gpt"),
+            resolved(1, "a\n", "claude"),
+            resolved(1, "b\n", "qwen"),
+            resolved(5, "d\n", "gpt"),
+        ];
+        // The agreeing models are reviewed once, in the order of the
+        // file, an unknown answer is asked again
+        let mut input = "e\nreject\nx\na\nr\n".as_bytes();
+        let reviewed = GitUtils::review_resolutions_with(
+            resolved_conflicts.clone(),
+            &mut input,
+            |resolved_version| Ok(resolved_version.to_uppercase()),
+        )
+        .unwrap();
+        let reviewed: Vec<(usize, &str, &str)> = reviewed
+            .iter()
+            .map(|r| {
+                (
+                    r.conflict.local_start,
+                    r.resolved_version.as_str(),
+                    r.model.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            reviewed,
+            [(1, "A\n", "gpt"), (1, "A\n", "claude"), (5, "d\n", "gpt")]
+        );
+        assert!(input.is_empty());
+
+        // The end of the input aborts the review
+        let error = GitUtils::review_resolutions_with(
+            resolved_conflicts,
+            &mut "a\n".as_bytes(),
+            |_| unreachable!(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("aborted"));
+    }
+
+    #[test]
+    fn test_remove_ai_blocks() {
+        let conflict = "a\n\
+<<<<<<< HEAD\n\
+local\n\
+||||||| base\n\
+base\n\
+=======\n\
+remote\n\
+>>>>>>> remote\n\
+b\n";
+        let resolved = "a\n\
+<<<<<<< HEAD\n\
+local\n\
+||||||| base\n\
+base\n\
+=======\n\
+remote\n\
+&&&&&&& synthmerge: Model A 90.0%\n\
+merged\n\
+&&&&&&& synthmerge: Model B\n\
+merged\n\
+again\n\
+>>>>>>> remote\n\
+b\n\
+&&&&&&& synthmerge: not in a conflict\n";
+        let expected = format!("{}&&&&&&& synthmerge: not in a conflict\n", conflict);
+        assert_eq!(
+            GitUtils::remove_ai_blocks(resolved, GitUtils::DEFAULT_MARKER_SIZE),
+            (expected.clone(), 2)
+        );
+        assert_eq!(
+            GitUtils::remove_ai_blocks(&expected, GitUtils::DEFAULT_MARKER_SIZE),
+            (expected, 0)
+        );
+        // The markers of another size are content
+        assert_eq!(GitUtils::remove_ai_blocks(resolved, 5).1, 0);
+    }
+
+    #[test]
+    fn test_rename_sources() {
+        let status = concat!(
+            "1 D. N... 100644 000000 000000 eb65b883 00000000 old name.c\0",
+            "2 R. N... 100644 100644 100644 0ff3bbb9 0ff3bbb9 R100 moved.c\0",
+            "orig.c\0",
+            "u UU N... 100644 100644 100644 100644 0ff3bbb9 eb65b883 4c9c98d8 new name.c\0",
+        );
+        let rename_sources = GitUtils::rename_sources(status.as_bytes());
+        assert_eq!(rename_sources.len(), 1);
+        assert_eq!(rename_sources["eb65b883"], "old name.c");
+    }
+
+    #[test]
+    fn test_parse_check_attr() {
+        let status = concat!(
+            "2 R. N... 100644 100644 100644 0ff3bbb9 0ff3bbb9 R100 moved.c\0",
+            "u UU orig.c\0",
+            "u UU N... 100644 100644 100644 100644 0ff3bbb9 eb65b883 4c9c98d8 new name.c\0",
+            "u AA N... 100644 100644 100644 100644 0ff3bbb9 eb65b883 4c9c98d8 added.c\0",
+        );
+        assert_eq!(
+            GitUtils::unmerged_paths(status.as_bytes()),
+            vec!["new name.c", "added.c"]
+        );
+
+        let output = concat!(
+            "new name.c\0conflict-marker-size\010\0new name.c\0merge\0unspecified\0",
+            "new name.c\0binary\0unspecified\0",
+            "added.c\0conflict-marker-size\0unspecified\0added.c\0merge\0theirs\0",
+            "added.c\0binary\0set\0",
+            "Cargo.lock\0conflict-marker-size\0unspecified\0Cargo.lock\0merge\0lockfile\0",
+            "Cargo.lock\0binary\0unspecified\0",
+            "a.c\0merge\0set\0b.c\0merge\0unset\0",
+        );
+        let file_attributes = GitUtils::parse_check_attr(output.as_bytes());
+        assert_eq!(
+            file_attributes["new name.c"],
+            FileAttributes {
+                marker_size: Some(10),
+                merge_side: None,
+                merge_driver: None,
+                binary: false,
+            }
+        );
+        assert_eq!(
+            file_attributes["added.c"],
+            FileAttributes {
+                marker_size: None,
+                merge_side: Some(MergeDriverSide::Theirs),
+                merge_driver: None,
+                binary: true,
+            }
+        );
+        assert_eq!(
+            file_attributes["Cargo.lock"].merge_driver.as_deref(),
+            Some("lockfile")
+        );
+        assert_eq!(file_attributes["a.c"].merge_driver, None);
+        // -merge leaves the file unmerged without conflict markers
+        assert_eq!(
+            file_attributes["b.c"].merge_driver.as_deref(),
+            Some("unset")
+        );
+    }
+
+    #[test]
+    fn test_text_content() {
+        assert_eq!(
+            GitUtils::text_content(b"a\nb\n".to_vec()).as_deref(),
+            Some("a\nb\n")
+        );
+        assert_eq!(GitUtils::text_content(b"PNG\0\x01".to_vec()), None);
+        assert_eq!(GitUtils::text_content(b"caf\xe9\n".to_vec()), None);
+        // Only the start of the file is checked for NUL bytes
+        let mut content = vec![b'a'; 8000];
+        content.push(0);
+        assert!(GitUtils::text_content(content).is_some());
+    }
+
+    #[test]
+    fn test_conflict_breadcrumbs() {
+        let content = format!("{}{}", CONFLICT_SIZE_5, CONFLICT_SIZE_5);
+        let conflicts = GitUtils::conflict_breadcrumbs(&content, 5).unwrap();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(
+            conflicts[0],
+            "<<<<< HEAD\nlocal\n=====\nremote\n>>>>> remote\n"
+        );
+
+        let comment = GitUtils::comment_syntax("src/main.rs")
+            .unwrap()
+            .comment(&conflicts[..1])
+            .unwrap();
+        assert!(comment.ends_with("// local\n// =====\n// remote\n// >>>>> remote\n"));
+        let comment = GitUtils::comment_syntax("README.md")
+            .unwrap()
+            .comment(&conflicts[..1])
+            .unwrap();
+        assert!(comment.starts_with("<!--\n"));
+        assert!(comment.ends_with("  >>>>> remote\n-->\n"));
+        assert_eq!(
+            CommentSyntax::Block("/*", "*/").comment(&["*/\n".to_string()]),
+            None
+        );
+        assert_eq!(GitUtils::comment_syntax("data.json"), None);
+    }
+
+    #[test]
+    fn test_remove_conflict_markers_size_10() {
+        let content = lines(CONFLICT_SIZE_10);
+        let base = GitUtils::remove_conflict_markers(&content, 10, ConflictMarkerMode::Base, false)
+            .unwrap();
+        assert_eq!(base.join(""), "a\nbase\nb\n");
+
+        // Legitimate content with size 7 markers in a size 10 file
+        let content = "<<<<<<< a\n=======\n>>>>>>> b\n";
+        assert!(!GitUtils::has_nested_markers(content, 10));
+        assert!(GitUtils::has_nested_markers(content, 7));
+        assert!(GitUtils::has_nested_markers(CONFLICT_SIZE_10, 10));
+    }
+}
+
 // Local Variables:
 // rust-format-on-save: t
 // End:
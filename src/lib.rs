@@ -1,16 +1,117 @@
 // SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
 // Copyright (C) 2025-2026  Red Hat, Inc.
 
+//! Resolve the conflicts left by git cherry-pick, rebase, merge and
+//! the other git operations with AI models.
+//!
+//! [`GitUtils::find_conflicts`] parses the conflicts of the unmerged
+//! files of the repository in the current directory, [`resolve_conflicts`]
+//! queries the endpoints of the [`Config`] and returns their
+//! resolutions, which [`GitUtils::apply_resolved_conflicts`] and
+//! [`GitUtils::apply_vibe_resolution`] write to the working tree.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use synthmerge::{ApplyOptions, Config, ContextLines, GitUtils, ResolutionMode};
+//!
+//! let config = Config::load(std::path::Path::new("synthmerge.yaml"))?;
+//! let mut git_utils = GitUtils::new(
+//!     ContextLines::default(),
+//!     None,
+//!     false,
+//!     ResolutionMode::Interactive,
+//!     0,
+//!     ApplyOptions::default(),
+//! );
+//! let conflicts = git_utils.find_conflicts(200000, &[])?;
+//! let resolved_conflicts = synthmerge::resolve_conflicts(&config, &conflicts).await?;
+//! git_utils.apply_resolved_conflicts(&resolved_conflicts)?;
+//! # Ok(())
+//! # }
+//! ```
+
 mod api_client;
 pub mod bench;
 pub mod bench_args;
+mod breadcrumbs;
 pub mod config;
 pub mod conflict_resolver;
+mod conflict_style;
+mod diff_algorithm;
+pub mod error;
 pub mod git_utils;
 mod lmdb_cache;
 pub mod logger;
+mod modify_delete;
+mod normalize;
 mod patch_locator;
 mod prob;
+mod stream;
+
+pub use config::Config;
+pub use conflict_resolver::{Conflict, ConflictKind, ConflictResolver, ResolvedConflict};
+pub use error::SynthmergeError;
+pub use git_utils::{ApplyOptions, ContextLines, DiffAlgorithm, GitUtils, ResolutionMode};
+
+/// Resolve the conflicts with all the endpoints of the config, without
+/// any response cache. The resolutions of all the models are returned
+/// in the order of the conflicts, then of the endpoints, regardless of
+/// which endpoint answered first. The endpoints that failed are logged
+/// and skipped. If there were
+/// conflicts and no endpoint resolved any, the first failed request is
+/// returned as [`SynthmergeError::Api`]. The diff of the commit being
+/// applied is provided as context with [`Conflict::commit_context`],
+/// use [`ConflictResolver`] directly to enable the cache.
+pub async fn resolve_conflicts(
+    config: &Config,
+    conflicts: &[Conflict],
+) -> Result<Vec<ResolvedConflict>, SynthmergeError> {
+    let resolver = ConflictResolver::new(config, false, None, false);
+    resolve(resolver, conflicts).await
+}
+
+/// Like [`resolve_conflicts`], but each resolution is sent to the
+/// channel as soon as the endpoint that produced it answered, so a user
+/// interface can show them while the slower endpoints are still
+/// working. Each [`ResolvedConflict`] carries its conflict and the
+/// index of its endpoint in [`Config::get_all_endpoints`]. The sender
+/// is dropped when all the conflicts are resolved, closing the channel.
+///
+/// ```no_run
+/// # async fn example(config: synthmerge::Config, conflicts: Vec<synthmerge::Conflict>) {
+/// let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+/// let resolving = synthmerge::resolve_conflicts_streaming(&config, &conflicts, sender);
+/// let showing = async {
+///     while let Some(resolved) = receiver.recv().await {
+///         println!("{}:{} {}", resolved.conflict.file_path, resolved.conflict.start_line, resolved.model);
+///     }
+/// };
+/// let (result, _) = tokio::join!(resolving, showing);
+/// # }
+/// ```
+pub async fn resolve_conflicts_streaming(
+    config: &Config,
+    conflicts: &[Conflict],
+    sender: tokio::sync::mpsc::UnboundedSender<ResolvedConflict>,
+) -> Result<(), SynthmergeError> {
+    let mut resolver = ConflictResolver::new(config, false, None, false);
+    resolver.sender = Some(sender);
+    resolve(resolver, conflicts).await?;
+    Ok(())
+}
+
+async fn resolve(
+    resolver: ConflictResolver<'_>,
+    conflicts: &[Conflict],
+) -> Result<Vec<ResolvedConflict>, SynthmergeError> {
+    let (resolved_conflicts, resolver_errors) = resolver.resolve_conflicts(conflicts, &[]).await?;
+    if resolved_conflicts.is_empty()
+        && let Some(api_error) = resolver_errors.api_error
+    {
+        return Err(api_error);
+    }
+    Ok(resolved_conflicts)
+}
 
 // Local Variables:
 // rust-format-on-save: t
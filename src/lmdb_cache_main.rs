@@ -67,6 +67,24 @@ impl LmdbCacheImpl {
 
         Ok(())
     }
+
+    /// Drop all the cached API responses, the patch locator cache is
+    /// kept as it doesn't depend on the endpoints.
+    pub fn clear_api_cache(&self) -> Result<usize> {
+        let db = self
+            .lmdb_env
+            .create_db(Some(Self::API_CACHE_DB), lmdb::DatabaseFlags::empty())
+            .context("open api cache db")?;
+        let mut txn = self.lmdb_env.begin_rw_txn().context("begin rw txn")?;
+        let entries = txn
+            .open_ro_cursor(db)
+            .context("open ro cursor")?
+            .iter()
+            .count();
+        txn.clear_db(db).context("clear api cache db")?;
+        txn.commit().context("commit clear txn")?;
+        Ok(entries)
+    }
 }
 
 // Local Variables:
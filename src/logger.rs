@@ -1,11 +1,136 @@
 // SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
 // Copyright (C) 2025  Red Hat, Inc.
 
-pub fn log_init() {
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// The status lines are logged at info level instead of printed
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// The progress is printed to stderr, stdout carries the output of the
+/// command
+static STDERR: AtomicBool = AtomicBool::new(false);
+
+/// The API keys of the config, replaced by [`redact`] before logging
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The values of the authorization headers, in the header maps, in the
+/// curl or git command lines and in the HTTP dumps
+static AUTH_HEADER: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r#"(?i)\b(authorization|api-key|x-api-key)(["']?\s*[:=]\s*["']?)((?:bearer|basic)\s+)?[^\s"',}]+"#,
+    )
+    .unwrap()
+});
+
+const REDACTED: &str = "***";
+
+/// Initialize the logger, the level overrides RUST_LOG
+pub fn log_init(level: Option<&str>) {
     let env = env_logger::Env::default().default_filter_or("warn");
-    env_logger::Builder::from_env(env)
-        .format_timestamp(None)
-        .init();
+    let mut builder = env_logger::Builder::from_env(env);
+    if let Some(level) = level {
+        builder.parse_filters(level);
+    }
+    builder.format_timestamp(None).init();
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_stderr(stderr: bool) {
+    STDERR.store(stderr, Ordering::Relaxed);
+}
+
+pub fn is_stderr() -> bool {
+    STDERR.load(Ordering::Relaxed)
+}
+
+/// Print a line of progress to stdout, or to stderr if stdout carries
+/// the output of the command
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::logger::is_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use progress;
+
+/// Print a status line like [`progress`], or log it at info level in
+/// quiet mode
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::logger::is_quiet() {
+            log::info!($($arg)*)
+        } else {
+            $crate::logger::progress!($($arg)*)
+        }
+    };
+}
+pub(crate) use status;
+
+/// Register a secret to be redacted from the logs. The secrets shorter
+/// than 4 characters are ignored, they would redact unrelated text.
+pub fn add_secret(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < 4 {
+        return;
+    }
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+        // The longest first, so a secret containing another one is
+        // redacted entirely
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// Replace the registered secrets and the values of the
+/// `Authorization`, `api-key` and `x-api-key` headers with `***`
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in SECRETS.lock().unwrap().iter() {
+        if text.contains(secret.as_str()) {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+    }
+    AUTH_HEADER
+        .replace_all(&text, format!("${{1}}${{2}}${{3}}{}", REDACTED))
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        add_secret("sk-test-1234");
+        add_secret("sk-test-1234-long");
+        add_secret("x");
+        assert_eq!(
+            redact("key sk-test-1234-long and sk-test-1234 x"),
+            "key *** and *** x"
+        );
+        assert_eq!(
+            redact("{\"authorization\": \"Bearer abcd\", \"content-type\": \"json\"}"),
+            "{\"authorization\": \"Bearer ***\", \"content-type\": \"json\"}"
+        );
+        assert_eq!(
+            redact("git -c http.extraHeader=Authorization: Basic Zm9v fetch"),
+            "git -c http.extraHeader=Authorization: Basic *** fetch"
+        );
+        assert_eq!(redact("api-key: abcd\n"), "api-key: ***\n");
+        assert_eq!(redact("nothing to hide"), "nothing to hide");
+    }
 }
 
 // Local Variables:
@@ -2,22 +2,41 @@
 // Copyright (C) 2025-2026  Red Hat, Inc.
 
 use crate::config::Config;
-use crate::conflict_resolver::ConflictResolver;
-use crate::git_utils::{ContextLines, GitUtils, ResolutionMode};
-use anyhow::Result;
+use crate::conflict_resolver::{Budget, Conflict, ConflictKind, ConflictResolver};
+use crate::git_utils::{
+    ApplyOptions, Breadcrumbs, ConflictStyle, ContextLines, DiffAlgorithm, GitUtils, ModifyDelete,
+    ResolutionMode,
+};
+use crate::output_format::OutputFormat;
+use anyhow::{Context, Result};
 use clap::Parser;
+use std::io::{IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 mod api_client;
+mod breadcrumbs;
 mod config;
 mod conflict_resolver;
+mod conflict_style;
+mod diff_algorithm;
+mod error;
 mod git_utils;
 mod lmdb_cache;
 mod lmdb_cache_main;
 mod logger;
+mod modify_delete;
+mod normalize;
+mod output_format;
 mod patch_locator;
+mod pre_rebase;
 mod prob;
+mod report;
+mod stream;
 #[cfg(feature = "telemetry")]
 mod telemetry;
+mod usage;
 
 include!("main_args.rs");
 
@@ -32,6 +51,16 @@ impl Args {
             Some(self.cache_path.clone())
         }
     }
+
+    /// The log filter of --log-level or -v, RUST_LOG otherwise
+    fn get_log_level(&self) -> Option<&str> {
+        match self.verbose {
+            0 => self.log_level.as_deref(),
+            1 => Some("info"),
+            2 => Some("debug"),
+            _ => Some("trace"),
+        }
+    }
 }
 
 fn import_cache(args: &Args) -> Result<bool> {
@@ -42,27 +71,371 @@ fn import_cache(args: &Args) -> Result<bool> {
             .ok_or_else(|| anyhow::anyhow!("Cache must be enabled to import cache"))?;
         let cache = lmdb_cache_main::create_from_path(&cache_path, false)?;
         cache.import_from_path(import_path)?;
-        println!("Cache imported successfully.");
+        logger::status!("Cache imported successfully.");
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+fn clear_cache(args: &Args) -> Result<bool> {
+    if args.clear_cache {
+        use crate::lmdb_cache_main;
+        let cache_path = args
+            .get_cache_path()
+            .ok_or_else(|| anyhow::anyhow!("Cache must be enabled to clear cache"))?;
+        let cache = lmdb_cache_main::create_from_path(&cache_path, false)?;
+        let entries = cache.clear_api_cache()?;
+        logger::status!("Cache cleared: {} responses removed.", entries);
         return Ok(true);
     }
     Ok(false)
 }
 
+fn report_usage(args: &Args, usage_stats: &usage::UsageStats) -> Result<()> {
+    usage_stats.print();
+    if let Some(json_stats) = &args.json_stats {
+        usage_stats.write_json(json_stats)?;
+    }
+    Ok(())
+}
+
+fn write_report(args: &Args, report: &report::Report) -> Result<()> {
+    if let Some(path) = &args.report {
+        report.write_json(path)?;
+    }
+    Ok(())
+}
+
+/// List the binary files and the files managed by a merge driver
+/// skipped by the scan, git left them unmerged
+fn report_skipped_files(git_utils: &GitUtils) {
+    let binary_files = git_utils.binary_files();
+    if !binary_files.is_empty() {
+        logger::status!(
+            "{} binary files skipped, resolve them manually:",
+            binary_files.len()
+        );
+        for file_path in binary_files {
+            logger::status!("  {}", file_path);
+        }
+    }
+    let driver_files = git_utils.driver_files();
+    if !driver_files.is_empty() {
+        logger::status!(
+            "{} files managed by a merge driver intentionally skipped:",
+            driver_files.len()
+        );
+        for (file_path, driver) in driver_files {
+            logger::status!("  {} (merge={})", file_path, driver);
+        }
+    }
+}
+
+/// Check each endpoint of the loaded config and print an OK/FAIL
+/// table, failing if any endpoint failed
+async fn check_config(args: &Args, config: &Config) -> Result<()> {
+    let endpoints = config.get_all_endpoints();
+    let width = endpoints
+        .iter()
+        .map(|endpoint| endpoint.name.len())
+        .max()
+        .unwrap_or_default();
+    let mut failures = 0;
+    for endpoint in endpoints {
+        let result = ConflictResolver::check_endpoint(endpoint, args.ping).await;
+        match result {
+            Ok(()) => println!("{:width$}  OK", endpoint.name),
+            Err(e) => {
+                failures += 1;
+                println!("{:width$}  FAIL {:#}", endpoint.name, e);
+            }
+        }
+    }
+    if failures > 0 {
+        anyhow::bail!(
+            "{} of {} endpoints failed the check",
+            failures,
+            endpoints.len()
+        );
+    }
+    println!("Config file {} is valid", args.config_path);
+    Ok(())
+}
+
+/// The --time-budget and --max-tokens-total budget of the run
+fn run_budget(args: &Args) -> Result<Budget> {
+    let deadline = match args.time_budget {
+        None => None,
+        Some(seconds) if !seconds.is_finite() || seconds <= 0.0 => {
+            anyhow::bail!("--time-budget must be a positive number of seconds");
+        }
+        Some(seconds) => Some(Instant::now() + Duration::from_secs_f64(seconds)),
+    };
+    Ok(Budget::new(deadline, args.max_tokens_total))
+}
+
+/// Resolve the conflicts of the file read from stdin and write the
+/// resolved file to stdout, without touching any git repository
+async fn resolve_stdin(
+    args: &Args,
+    config: &Config,
+    git_utils: &mut GitUtils,
+    budget: &Budget,
+) -> Result<()> {
+    let mut content = String::new();
+    std::io::stdin().read_to_string(&mut content)?;
+    // Parsed newline terminated like the blobs, the eof_newline policy
+    // decides if the resolved file is
+    let eof_newline = content.ends_with('\n');
+    if !eof_newline && !content.is_empty() {
+        content.push('\n');
+    }
+
+    let marker_size = match config.get_marker_size() {
+        Some(marker_size) => marker_size,
+        None => GitUtils::detect_marker_size(&content)?.unwrap_or(GitUtils::DEFAULT_MARKER_SIZE),
+    };
+    let conflicts = git_utils.parse_file_conflicts("stdin", &content, marker_size)?;
+    let Some(resolved_content) =
+        resolve_content(args, config, git_utils, budget, &conflicts, eof_newline).await?
+    else {
+        return Ok(());
+    };
+    std::io::stdout().write_all(resolved_content.as_bytes())?;
+    Ok(())
+}
+
+/// Resolve the conflicts of the LOCAL, BASE and REMOTE files passed by
+/// `git mergetool` and write the resolved file to MERGED. A conflict
+/// left unresolved fails the merge, so git restores MERGED.
+async fn resolve_mergetool(
+    args: &Args,
+    config: &Config,
+    git_utils: &mut GitUtils,
+    budget: &Budget,
+    files: &[String],
+) -> Result<()> {
+    let [local, base, remote, merged] = files else {
+        unreachable!("--mergetool takes 4 files");
+    };
+    let (mut content, marker_size, conflicted) = GitUtils::merge_file(local, base, remote)?;
+    if !conflicted {
+        logger::status!("No conflicts to resolve in {}", merged);
+        std::fs::write(merged, content).with_context(|| format!("Failed to write {}", merged))?;
+        return Ok(());
+    }
+
+    let eof_newline = content.ends_with('\n');
+    if !eof_newline && !content.is_empty() {
+        content.push('\n');
+    }
+    let conflicts = git_utils.parse_file_conflicts(merged, &content, marker_size)?;
+    let Some(resolved_content) =
+        resolve_content(args, config, git_utils, budget, &conflicts, eof_newline).await?
+    else {
+        // MERGED still has the conflict markers, a zero exit status
+        // would tell git the merge succeeded
+        std::process::exit(1);
+    };
+    std::fs::write(merged, resolved_content)
+        .with_context(|| format!("Failed to write {}", merged))?;
+    logger::status!("Resolved {}", merged);
+    Ok(())
+}
+
+/// Provide the diff of the commit being applied to the prompts of the
+/// conflicts
+fn set_commit_context(conflicts: &mut [Conflict], commit_context: Option<Arc<String>>) {
+    for conflict in conflicts {
+        conflict.commit_context = commit_context.clone();
+    }
+}
+
+/// Resolve all the conflicts of a file outside of the index and
+/// return the resolved content, None with --prompt-only
+async fn resolve_content(
+    args: &Args,
+    config: &Config,
+    git_utils: &mut GitUtils,
+    budget: &Budget,
+    conflicts: &[Conflict],
+    eof_newline: bool,
+) -> Result<Option<String>> {
+    logger::status!("Found {} conflicts to resolve", conflicts.len());
+
+    let mut resolver =
+        ConflictResolver::new(config, false, args.get_cache_path(), args.cache_overwrite);
+    resolver.set_budget(budget.clone());
+    let (resolved_conflicts, _) = resolver.resolve_conflicts(conflicts, &[]).await?;
+    if config.get_prompt_only() {
+        return Ok(None);
+    }
+    let mut usage_stats = usage::UsageStats::new(config.get_all_endpoints());
+    usage_stats.add(&resolved_conflicts);
+    report_usage(args, &usage_stats)?;
+
+    Ok(Some(git_utils.resolve_file_content(
+        conflicts,
+        &resolved_conflicts,
+        eof_newline,
+    )?))
+}
+
+/// Rebase the --pre-rebase branch onto the base in the throwaway
+/// worktree, collecting the conflicts of each commit that stops the
+/// rebase and resolving them with --pre-rebase-resolve, without
+/// applying them
+async fn resolve_pre_rebase(
+    args: &Args,
+    config: &Config,
+    git_utils: &mut GitUtils,
+    budget: &Budget,
+    worktree: pre_rebase::Worktree,
+    onto: &str,
+    nr_commits: usize,
+) -> Result<()> {
+    let mut usage_stats = usage::UsageStats::new(config.get_all_endpoints());
+    let mut report = report::Report::default();
+    let mut nr_conflicted_commits = 0;
+    logger::status!(
+        "Rebasing {} onto {} in a throwaway worktree",
+        args.pre_rebase.as_deref().unwrap_or_default(),
+        args.onto.as_deref().unwrap_or_default()
+    );
+    let mut in_progress = pre_rebase::start(onto, args.conflict_style)?;
+    while in_progress {
+        let commit_hashes = git_utils.find_commit_hashes()?;
+        let conflicts = git_utils.find_conflicts(args.max_context_size, &[])?;
+        let commit = commit_hashes.first().cloned();
+        logger::status!(
+            "{} conflicts applying {}",
+            conflicts.len(),
+            commit.as_deref().unwrap_or("the next commit")
+        );
+        nr_conflicted_commits += 1;
+        report.set_commit(commit);
+
+        let (mut conflicts, _): (Vec<_>, Vec<_>) = conflicts
+            .into_iter()
+            .partition(|conflict| conflict.kind == ConflictKind::Content);
+        let mut resolved_conflicts = Vec::new();
+        if args.pre_rebase_resolve && !conflicts.is_empty() {
+            if !config.get_no_diff() {
+                let git_diff = git_utils.extract_diff(&commit_hashes, args.max_context_size)?;
+                set_commit_context(&mut conflicts, git_diff.map(Arc::new));
+            }
+            let mut resolver =
+                ConflictResolver::new(config, false, args.get_cache_path(), args.cache_overwrite);
+            resolver.set_budget(budget.clone());
+            (resolved_conflicts, _) = resolver.resolve_conflicts(&conflicts, &[]).await?;
+            if config.get_prompt_only() {
+                return Ok(());
+            }
+            usage_stats.add(&resolved_conflicts);
+        }
+        report.add(&conflicts, &resolved_conflicts, git_utils);
+
+        in_progress = pre_rebase::take_theirs_and_continue(args.conflict_style)?;
+    }
+    // Back to the checkout of the user, the paths of the report and of
+    // the stats are relative to it
+    drop(worktree);
+
+    logger::status!(
+        "{} of {} commits conflict, with {} conflicts",
+        nr_conflicted_commits,
+        nr_commits,
+        report.nr_conflicts
+    );
+    if args.pre_rebase_resolve {
+        logger::status!(
+            "Resolved {} of {} conflicts",
+            report.nr_resolved,
+            report.nr_conflicts
+        );
+        report_usage(args, &usage_stats)?;
+    }
+    write_report(args, &report)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    logger::log_init();
     let args = Args::parse();
+    logger::log_init(args.get_log_level());
+    logger::set_quiet(args.quiet);
+    // With --stdin stdout only gets the resolved file
+    logger::set_stderr(args.stdin);
+    if let Some(repo) = &args.repo
+        && !std::path::Path::new(repo).is_dir()
+    {
+        anyhow::bail!("The repository {} is not a directory", repo);
+    }
+    // The budget runs from the start
+    let budget = run_budget(&args)?;
 
     // If import_cache is provided, import cache and exit
     if import_cache(&args)? {
         return Ok(());
     }
 
+    // If clear_cache is provided, clear the cached responses and exit
+    if clear_cache(&args)? {
+        return Ok(());
+    }
+
+    // Without a base section every stray marker block is a conflict
+    if args.ignore_stray_markers && args.conflict_style == ConflictStyle::Merge {
+        anyhow::bail!("--ignore-stray-markers can't be used with --conflict-style=merge");
+    }
+
+    #[cfg(not(feature = "telemetry"))]
+    if args.audit_log {
+        anyhow::bail!("--audit-log requires the telemetry feature");
+    }
+
     // Load configuration
     let config_path = shellexpand::full(&args.config_path)?;
-    let config = Config::load(std::path::Path::new(config_path.as_ref()))?;
+    let config_path = std::path::Path::new(config_path.as_ref());
+    let mut config = Config::load_profile(config_path, args.profile.as_deref())?;
+    if let Ok(git_root) = GitUtils::get_git_root_uncached(args.repo.as_deref()) {
+        config.load_repo_config(std::path::Path::new(&git_root))?;
+    }
+    if args.stream {
+        config.enable_stream();
+    }
+    if args.no_diff {
+        config.set_no_diff();
+    }
+    if args.raw_logprob {
+        config.set_raw_logprob();
+    }
+    if args.print_prompt {
+        config.set_print_prompt(args.prompt_only);
+    }
+    if let Some(min_confidence) = args.min_confidence {
+        config.set_min_confidence(min_confidence);
+    }
+    if args.early_abandon {
+        config.set_early_abandon();
+    }
+    if let Some(seconds) = args.slow_conflict {
+        config.set_slow_conflict(seconds);
+    }
+    if let Some(max_size) = args.full_file_context {
+        config.set_full_file_context(max_size);
+    }
+    if let Some(marker_size) = args.marker_size {
+        config.set_marker_size(marker_size as usize);
+    }
 
     log::info!("Using config file: {}", args.config_path);
+    if let Some(profile) = config.get_profile() {
+        log::info!("Using profile: {}", profile);
+    }
+
+    if args.check_config {
+        return check_config(&args, &config).await;
+    }
 
     // Determine resolution mode
     let resolution_mode = if args.vibe {
@@ -71,15 +444,32 @@ async fn main() -> Result<()> {
         } else {
             ResolutionMode::VibeWithPatchLocator
         }
+    } else if args.stdin || args.mergetool.is_some() {
+        // Without the index there's no patch to locate
+        ResolutionMode::VibeWithMarkers
     } else {
         ResolutionMode::Interactive
     };
 
+    // The pre-rebase analysis runs in a throwaway worktree, the current
+    // directory of all the git commands until it's dropped
+    let pre_rebase = match (&args.pre_rebase, &args.onto) {
+        (Some(branch), Some(onto)) => {
+            let repo = args.repo.as_deref();
+            let branch = pre_rebase::rev_parse(repo, branch)?;
+            let onto = pre_rebase::rev_parse(repo, onto)?;
+            let nr_commits = pre_rebase::count_commits(repo, &onto, &branch)?;
+            Some((pre_rebase::Worktree::add(repo, &branch)?, onto, nr_commits))
+        }
+        _ => None,
+    };
+
     let context_lines = ContextLines {
         code_context_lines: args.code_context_lines,
         diff_context_lines: args.diff_context_lines,
         patch_context_lines: args.patch_context_lines,
         extra_conflict_lines: args.extra_conflict_lines,
+        diff_algorithm: args.diff_algorithm,
     };
     // Initialize git utilities
     let mut git_utils = GitUtils::new(
@@ -88,58 +478,197 @@ async fn main() -> Result<()> {
         args.cache_overwrite,
         resolution_mode,
         args.retries as usize,
+        ApplyOptions {
+            conflict_style: args.conflict_style,
+            commit_per_file: args.commit_per_file,
+            confidence_thresholds: config.get_confidence_thresholds()?,
+            raw_logprob: config.get_raw_logprob(),
+            breadcrumbs: args.breadcrumbs,
+            stash: args.stash,
+            eof_newline: config.get_eof_newline(),
+            assisted_by: config.get_assisted_by(),
+            min_agreement: args.min_agreement.unwrap_or(1) as usize,
+            agreement: config.get_agreement(),
+            match_indent: args.match_indent,
+            // Nothing is applied with --no-apply and the JSON output,
+            // the conflicts resolved by a previous run are analyzed too
+            force: args.force || args.no_apply || matches!(args.format, OutputFormat::Json),
+            confirm: !args.yes && std::io::stdin().is_terminal(),
+            backup: args.backup,
+            rerere: args.rerere,
+            ignore_stray_markers: args.ignore_stray_markers,
+            marker_size: config.get_marker_size(),
+            edit_message: args.edit_message,
+            skip_empty: args.skip_empty,
+            modify_delete: args.modify_delete,
+            // The JSON output and --no-apply are pure analysis passes
+            dry_run: args.dry_run
+                || args.no_apply
+                || matches!(args.format, OutputFormat::Json)
+                || args.output_patch.is_some(),
+            output_patch: args.output_patch.as_ref().map(PathBuf::from),
+            pathspecs: args.pathspecs.clone(),
+            only_file: args.only_file.clone(),
+            repo: match &pre_rebase {
+                Some((worktree, _, _)) => Some(worktree.path().to_string_lossy().into_owned()),
+                None => args.repo.clone(),
+            },
+        },
     );
+    git_utils.set_deadline(budget.deadline());
+
+    if args.stdin {
+        return resolve_stdin(&args, &config, &mut git_utils, &budget).await;
+    }
+
+    if let Some((worktree, onto, nr_commits)) = pre_rebase {
+        return resolve_pre_rebase(
+            &args,
+            &config,
+            &mut git_utils,
+            &budget,
+            worktree,
+            &onto,
+            nr_commits,
+        )
+        .await;
+    }
+
+    if let Some(files) = &args.mergetool {
+        return resolve_mergetool(&args, &config, &mut git_utils, &budget, files).await;
+    }
+
+    if args.restore {
+        return git_utils.restore_backups();
+    }
+
+    git_utils.check_only_file()?;
+    if let Some(since) = &args.since {
+        git_utils.set_since(since)?;
+    }
+
+    if args.interactive && !std::io::stdin().is_terminal() {
+        anyhow::bail!("--interactive requires a terminal");
+    }
+
+    if args.edit_message && !std::io::stdin().is_terminal() {
+        anyhow::bail!("--edit-message requires a terminal");
+    }
+
+    if args.undo {
+        return git_utils.undo_resolutions();
+    }
 
     // Try to cherry-pick with diff3 mode
     let result = git_utils.check_diff3();
-    if result.is_err() {
-        eprintln!("Diff3 check failed. Run 'git config merge.conflictStyle diff3' to fix this.");
-        std::process::exit(1);
+    if let Err(e) = result {
+        if args.conflict_style != ConflictStyle::Merge {
+            eprintln!(
+                "Diff3 check failed. Run 'git config merge.conflictStyle diff3' to fix this."
+            );
+            std::process::exit(1);
+        }
+        log::warn!("{}, conflicts without a base section have an empty base", e);
     }
 
-    let git_diff = if let Some(commit_hash) = git_utils.find_commit_hash()? {
-        log::info!("Extracting diff for commit {}", commit_hash);
-        git_utils.extract_diff(&commit_hash, args.max_context_size)?
-    } else {
+    let commit_hashes = git_utils.find_commit_hashes()?;
+    let git_diff = if commit_hashes.is_empty() {
         None
+    } else if config.get_no_diff() {
+        log::info!("The commit diff is disabled by no_diff");
+        None
+    } else {
+        log::info!("Extracting diff for commits {}", commit_hashes.join(" "));
+        git_utils
+            .extract_diff(&commit_hashes, args.max_context_size)?
+            .map(Arc::new)
     };
 
+    let mut usage_stats = usage::UsageStats::new(config.get_all_endpoints());
+    let mut report = report::Report::default();
     let mut prev_conflicts = Vec::new();
     loop {
         // Check if we're in a cherry-pick and extract commit if needed
         // Check if there are conflicts
-        let conflicts = git_utils.find_conflicts(args.max_context_size, &prev_conflicts)?;
+        let mut conflicts = git_utils.find_conflicts(args.max_context_size, &prev_conflicts)?;
+        set_commit_context(&mut conflicts, git_diff.clone());
+
+        if matches!(args.format, OutputFormat::Json) {
+            println!("{}", serde_json::to_string_pretty(&conflicts)?);
+            return Ok(());
+        }
+
+        // Modify/delete conflicts have no markers for the models
+        let (conflicts, modify_delete): (Vec<_>, Vec<_>) = conflicts
+            .into_iter()
+            .partition(|conflict| conflict.kind == ConflictKind::Content);
+        let unresolved = git_utils.resolve_modify_delete(&modify_delete)?;
+        if unresolved > 0 {
+            logger::status!(
+                "{} modify/delete conflicts left unresolved, use --vibe --modify-delete=keep|delete",
+                unresolved
+            );
+        }
+
+        let already_resolved = git_utils.already_resolved();
+        if already_resolved > 0 {
+            logger::status!(
+                "{} conflicts already resolved by a previous run, use --force to resolve them again",
+                already_resolved
+            );
+        }
 
         if conflicts.is_empty() {
-            println!("No conflicts found.");
+            git_utils.resolve_merge_side_files()?;
+            if unresolved == 0
+                && already_resolved == 0
+                && git_utils.binary_files().is_empty()
+                && git_utils.driver_files().is_empty()
+            {
+                logger::status!("No conflicts found.");
+            }
+            report_skipped_files(&git_utils);
             if args.continue_op && git_utils.continue_operation(&context_lines)? {
                 continue;
             }
-            return Ok(());
+            write_report(&args, &report)?;
+            return report_usage(&args, &usage_stats);
         }
 
-        println!("Found {} conflicts to resolve", conflicts.len());
+        logger::status!("Found {} conflicts to resolve", conflicts.len());
 
         // Resolve conflicts using AI
-        let resolver = ConflictResolver::new(
-            &config,
-            git_diff.clone(),
-            false,
-            args.get_cache_path(),
-            args.cache_overwrite,
-        );
+        let mut resolver =
+            ConflictResolver::new(&config, false, args.get_cache_path(), args.cache_overwrite);
+        resolver.set_budget(budget.clone());
         let resolved = resolver
             .resolve_conflicts(&conflicts, &prev_conflicts)
             .await?;
-        let (resolved_conflicts, resolved_errors) = resolved;
+        if config.get_prompt_only() {
+            return Ok(());
+        }
+        let (mut resolved_conflicts, resolved_errors) = resolved;
+        usage_stats.add(&resolved_conflicts);
+        if args.interactive {
+            resolved_conflicts = GitUtils::review_resolutions(resolved_conflicts)?;
+        }
 
         let mut repeat = false;
-        if args.vibe {
-            match git_utils.apply_vibe_resolution(
+        if args.no_apply {
+            report.add(&conflicts, &resolved_conflicts, &git_utils);
+            logger::status!(
+                "Resolved {} of {} conflicts, none applied",
+                report.nr_resolved,
+                report.nr_conflicts
+            );
+        } else if args.vibe {
+            let applied = git_utils.apply_vibe_resolution(
                 &conflicts,
                 &resolved_conflicts,
                 &resolved_errors.retry_files,
-            ) {
+            );
+            report.add(&conflicts, &resolved_conflicts, &git_utils);
+            match applied {
                 Ok(no_conflicts_left) => {
                     if no_conflicts_left {
                         if args.continue_op {
@@ -151,16 +680,31 @@ async fn main() -> Result<()> {
                 }
                 Err(e) => {
                     eprintln!("Failed to apply vibe resolution: {}", e);
+                    write_report(&args, &report)?;
                     std::process::exit(2);
                 }
             }
         } else {
             git_utils.apply_resolved_conflicts(&resolved_conflicts)?;
+            report.add(&conflicts, &resolved_conflicts, &git_utils);
         }
 
         #[cfg(feature = "telemetry")]
         {
-            let telemetry = telemetry::Telemetry::new(&config, &conflicts, &resolved_conflicts);
+            if args.audit_log {
+                telemetry::Telemetry::append_audit_log(
+                    &config,
+                    &conflicts,
+                    &resolved_conflicts,
+                    &git_utils.audit_log_path(),
+                )?;
+            }
+            let telemetry = telemetry::Telemetry::new(
+                &config,
+                &conflicts,
+                &resolved_conflicts,
+                args.no_telemetry,
+            );
             telemetry.submit().await?;
         }
 
@@ -176,8 +720,12 @@ async fn main() -> Result<()> {
         );
     }
 
-    if !args.vibe {
-        println!(
+    report_skipped_files(&git_utils);
+    write_report(&args, &report)?;
+    report_usage(&args, &usage_stats)?;
+
+    if !args.vibe && !args.no_apply {
+        logger::status!(
             "Interactive mode restricts the solution within diff3 conflict markers.\n\
              Use --vibe for enhanced resolution, but always review \
              the result with `git diff --cached`."
@@ -4,6 +4,40 @@
 #[derive(Parser, Debug)]
 #[command(version)]
 struct Args {
+    /// Only resolve the unmerged files matching these pathspecs, the other
+    /// files are left untouched
+    #[arg(value_name = "PATHSPEC")]
+    pathspecs: Vec<String>,
+
+    /// Only resolve this unmerged file, taken as a literal path. It's an
+    /// error if it has no conflicts.
+    #[arg(long = "only-file", value_name = "PATH", conflicts_with = "pathspecs")]
+    only_file: Option<String>,
+
+    /// Only resolve the unmerged files that HEAD or the commits being
+    /// applied changed since REF, a commit or a time like "2 hours ago".
+    /// The conflicts of the other files are left alone.
+    #[arg(long = "since", value_name = "REF", conflicts_with_all = ["stdin", "mergetool", "restore"])]
+    since: Option<String>,
+
+    /// Resolve the conflicts of the repository at PATH instead of the
+    /// current directory, the pathspecs are relative to it
+    #[arg(long = "repo", value_name = "PATH", conflicts_with_all = ["stdin", "mergetool"])]
+    repo: Option<String>,
+
+    /// Log the status lines at info level instead of printing them, so
+    /// stdout is left to the output of the command
+    #[arg(short = 'q', long = "quiet", default_value = "false")]
+    quiet: bool,
+
+    /// Log filter, in the RUST_LOG syntax, overriding RUST_LOG
+    #[arg(long = "log-level", value_name = "FILTER", conflicts_with = "verbose")]
+    log_level: Option<String>,
+
+    /// Log at info level, debug with -vv and trace with -vvv
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
     /// Path to configuration file
     #[arg(
         short = 'c',
@@ -12,6 +46,11 @@ struct Args {
     )]
     config_path: String,
 
+    /// Use the endpoints of this profile of the config file, overriding
+    /// SYNTHMERGE_PROFILE. The `default` profile is used otherwise.
+    #[arg(long = "profile")]
+    profile: Option<String>,
+
     /// Number of context lines to include around conflict markers
     #[arg(long = "code-context-lines", default_value = "3", value_parser = clap::value_parser!(u32).range(0..))]
     code_context_lines: u32,
@@ -20,7 +59,12 @@ struct Args {
     #[arg(long = "diff-context-lines", default_value = "3", value_parser = clap::value_parser!(u32).range(0..))]
     diff_context_lines: u32,
 
-    /// Number of context lines of the patch
+    /// The diff algorithm of the git_diff provided as context
+    #[arg(long = "diff-algorithm", value_enum, default_value_t = DiffAlgorithm::Histogram)]
+    diff_algorithm: DiffAlgorithm,
+
+    /// Number of context lines of the patch of each conflict and of the
+    /// --output-patch hunks
     #[arg(long = "patch-context-lines", default_value = "3", value_parser = clap::value_parser!(u32).range(0..))]
     patch_context_lines: u32,
 
@@ -28,6 +72,19 @@ struct Args {
     #[arg(long = "extra-conflict-lines", default_value = "0", value_parser = clap::value_parser!(u32).range(0..))]
     extra_conflict_lines: u32,
 
+    /// Send the whole conflicted file, resolved to the local side, as
+    /// context. Files larger than BYTES only get the context lines. The
+    /// size is given as `--full-file-context=BYTES`, so a pathspec after
+    /// the flag isn't taken as the size.
+    #[arg(
+        long = "full-file-context",
+        value_name = "BYTES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100000"
+    )]
+    full_file_context: Option<usize>,
+
     /// Maximum size of the diff provided as context in bytes
     #[arg(long = "max-context-size", default_value = "200000", value_parser = clap::value_parser!(u32).range(0..))]
     max_context_size: u32,
@@ -56,14 +113,293 @@ struct Args {
     #[arg(long = "import-cache", conflicts_with = "no_cache")]
     import_cache: Option<String>,
 
+    /// Remove all the cached API responses from the cache database and exit
+    #[arg(long = "clear-cache", conflicts_with_all = ["no_cache", "import_cache"])]
+    clear_cache: bool,
+
+    /// Validate the config file and each endpoint, print the result and
+    /// exit, without touching the repository
+    #[arg(
+        long = "check-config",
+        conflicts_with_all = ["vibe", "interactive", "restore", "undo", "stdin"],
+        default_value = "false"
+    )]
+    check_config: bool,
+
+    /// With --check-config also send a tiny request to each endpoint to
+    /// confirm it's reachable and the authentication works
+    #[arg(long = "ping", requires = "check_config", default_value = "false")]
+    ping: bool,
+
     /// Automatically resolve conflicts and update the git index.
     #[arg(long = "vibe", default_value = "false")]
     vibe: bool,
 
+    /// Don't ask for confirmation before vibe resolution overwrites the
+    /// conflicts. Confirmation is only asked when stdin is a terminal.
+    #[arg(short = 'y', long = "yes", requires = "vibe", default_value = "false")]
+    yes: bool,
+
     /// Continue the current cherry-pick, rebase, revert, or merge operation after resolving conflicts
     #[arg(long = "continue", requires = "vibe", default_value = "false")]
     continue_op: bool,
 
+    /// Commit each resolved file separately before continuing the operation.
+    /// The operation commit gets the conflicts resolved to the local side.
+    #[arg(long = "commit-per-file", requires = "continue_op", default_value = "false")]
+    commit_per_file: bool,
+
+    /// Open the commit message in the git editor before continuing the
+    /// operation, to review it with the Assisted-by line added
+    #[arg(
+        long = "edit-message",
+        requires = "continue_op",
+        conflicts_with = "commit_per_file"
+    )]
+    edit_message: bool,
+
+    /// Skip the commit being applied instead of continuing, when the
+    /// resolutions left its tree identical to the parent
+    #[arg(long = "skip-empty", requires = "continue_op", default_value = "false")]
+    skip_empty: bool,
+
+    /// Store the vibe resolution in a new stash entry instead of writing it to
+    /// the working tree. The index and the operation in progress are left alone.
+    #[arg(
+        long = "stash",
+        requires = "vibe",
+        conflicts_with = "continue_op",
+        default_value = "false"
+    )]
+    stash: bool,
+
+    /// Keep the conflicts replaced by vibe resolution as a comment appended
+    /// to each file, or in a <file>.synthmerge-conflicts sidecar file.
+    #[arg(long = "breadcrumbs", requires = "vibe", value_enum)]
+    breadcrumbs: Option<Breadcrumbs>,
+
+    /// Stream the responses of the OpenAI and Anthropic endpoints and
+    /// print the progress of the long resolutions
+    #[arg(long = "stream", default_value = "false")]
+    stream: bool,
+
+    /// Never send the diff of the commit being applied to the endpoints,
+    /// like the no_diff of the config
+    #[arg(long = "no-diff", default_value = "false")]
+    no_diff: bool,
+
+    /// Print the exact prompt sent to each endpoint for each conflict,
+    /// to debug the resolutions and the system_prompt overrides
+    #[arg(long = "print-prompt", default_value = "false")]
+    print_prompt: bool,
+
+    /// Exit after printing the prompts, without querying the endpoints
+    #[arg(long = "prompt-only", requires = "print_prompt", default_value = "false")]
+    prompt_only: bool,
+
+    /// Show the logprob of the resolutions instead of the confidence
+    /// percentage computed with the confidence_base of the config
+    #[arg(long = "raw-logprob", default_value = "false")]
+    raw_logprob: bool,
+
+    /// Minimum confidence percentage of the resolutions, overriding the
+    /// min_confidence of the config. The resolutions below it are
+    /// dropped and their conflicts are left unresolved.
+    #[arg(long = "min-confidence", value_name = "PERCENT")]
+    min_confidence: Option<f64>,
+
+    /// Cancel the streamed responses as soon as the confidence of their
+    /// patched code can no longer reach the confidence threshold. Only
+    /// with the min and sum logprob_aggregation. It may cancel some
+    /// generations that would have been accepted otherwise.
+    #[arg(long = "early-abandon", default_value = "false")]
+    early_abandon: bool,
+
+    /// Reindent the resolutions with the tabs or spaces of the conflicted
+    /// region, unless its indentation is ambiguous
+    #[arg(long = "match-indent", default_value = "false")]
+    match_indent: bool,
+
+    /// Minimum number of models that must produce the identical
+    /// resolution. The resolutions produced by fewer models are dropped
+    /// and their conflicts are left unresolved.
+    #[arg(long = "min-agreement", value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    min_agreement: Option<u64>,
+
+    /// Stop sending conflicts to the endpoints SECONDS after the start
+    /// and cancel the requests still in flight. The resolutions completed
+    /// so far are applied, the other conflicts are left unresolved.
+    #[arg(long = "time-budget", value_name = "SECONDS")]
+    time_budget: Option<f64>,
+
+    /// Stop sending requests to the endpoints once the responses used
+    /// TOKENS total tokens, counted across all the endpoints and the
+    /// retries. The requests in flight complete, the conflicts left are
+    /// unresolved.
+    #[arg(long = "max-tokens-total", value_name = "TOKENS", value_parser = clap::value_parser!(u64).range(1..))]
+    max_tokens_total: Option<u64>,
+
+    /// Warn about the conflicts whose resolution took more than SECONDS,
+    /// overriding the slow_conflict of the config. 0 disables it.
+    #[arg(long = "slow-conflict", value_name = "SECONDS")]
+    slow_conflict: Option<f64>,
+
+    /// Never send telemetry, regardless of the telemetry setting of the
+    /// patchpal endpoints. Setting SYNTHMERGE_NO_TELEMETRY=1 does the same.
+    #[arg(long = "no-telemetry")]
+    no_telemetry: bool,
+
+    /// Append a JSON line recording the conflicts, the models and the
+    /// durations of each run to .git/synthmerge/runs.jsonl, whether or
+    /// not telemetry is sent
+    #[arg(long = "audit-log")]
+    audit_log: bool,
+
+    /// Review each proposed resolution before it's applied: accept it,
+    /// reject it leaving the conflict markers in place, or edit it with
+    /// $VISUAL or $EDITOR
+    #[arg(long = "interactive")]
+    interactive: bool,
+
+    /// Write the token usage and the estimated cost of the run to FILE
+    /// as JSON, for CI to check the run stayed within its budget
+    #[arg(long = "json-stats", value_name = "FILE")]
+    json_stats: Option<String>,
+
+    /// Write each conflict with the models, confidence, tokens and
+    /// duration of its resolutions and whether they were applied to FILE
+    /// as JSON, including the unresolved conflicts
+    #[arg(long = "report", value_name = "FILE", conflicts_with = "stdin")]
+    report: Option<String>,
+
+    /// Write the vibe resolutions to FILE as a patch for git apply,
+    /// instead of applying them. The working tree, the index and the
+    /// operation in progress are left alone.
+    #[arg(
+        long = "output-patch",
+        value_name = "FILE",
+        requires = "vibe",
+        conflicts_with_all = ["dry_run", "continue_op", "stash", "backup", "rerere", "commit_per_file", "modify_delete", "stdin", "mergetool"]
+    )]
+    output_patch: Option<String>,
+
+    /// The merge.conflictStyle of the conflicts. With `merge` the diff3
+    /// check is only a warning and conflicts without a base section are
+    /// parsed with an empty base.
+    #[arg(long = "conflict-style", value_enum, default_value_t = ConflictStyle::Diff3)]
+    conflict_style: ConflictStyle,
+
+    /// Leave the marker-like blocks without a base section as they are,
+    /// like a file documenting the conflict markers, instead of failing
+    /// on them. Only the conflicts with a base section are resolved,
+    /// so it can't be used with `--conflict-style=merge`.
+    #[arg(long = "ignore-stray-markers")]
+    ignore_stray_markers: bool,
+
+    /// Also resolve the conflicts that already have the resolutions of a
+    /// previous run without --vibe, adding another block of resolutions
+    #[arg(long = "force", conflicts_with = "vibe")]
+    force: bool,
+
+    /// Output format. With `json` the conflicts found are printed as a JSON
+    /// array and synthmerge exits without contacting any endpoint.
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Print the diff of the resolutions instead of applying them. The
+    /// working tree, the index and the operation in progress are left alone.
+    #[arg(
+        long = "dry-run",
+        conflicts_with_all = ["continue_op", "stash", "backup"],
+        default_value = "false"
+    )]
+    dry_run: bool,
+
+    /// Query the endpoints for every conflict and cache their responses
+    /// and write the --report, without applying the resolutions nor
+    /// showing their diff. The working tree and the index are left alone.
+    #[arg(
+        long = "no-apply",
+        conflicts_with_all = ["interactive", "dry_run", "continue_op", "stash", "backup", "rerere", "modify_delete", "stdin"],
+        default_value = "false"
+    )]
+    no_apply: bool,
+
+    /// Back up each file and its index entries under .git/synthmerge/backups
+    /// before it is modified. Vibe resolution removes the backups on success.
+    #[arg(long = "backup", default_value = "false")]
+    backup: bool,
+
+    /// Remove the resolutions inserted by the interactive mode from the
+    /// unmerged files, restoring the original conflicts, then exit
+    #[arg(
+        long = "undo",
+        conflicts_with_all = ["vibe", "restore"],
+        default_value = "false"
+    )]
+    undo: bool,
+
+    /// Record the vibe resolutions in git's rerere database, so git
+    /// reapplies them by itself when the same conflicts recur
+    #[arg(long = "rerere", requires = "vibe", default_value = "false")]
+    rerere: bool,
+
+    /// Resolve the files deleted by one side and modified by the other by
+    /// keeping or deleting them. Without it they're reported and left
+    /// unresolved.
+    #[arg(long = "modify-delete", requires = "vibe", value_enum)]
+    modify_delete: Option<ModifyDelete>,
+
+    /// Restore the backed up files and their conflicts, then exit
+    #[arg(long = "restore", conflicts_with_all = ["vibe", "backup"], default_value = "false")]
+    restore: bool,
+
+    /// Rebase BRANCH onto the --onto base in a throwaway worktree and
+    /// report the conflicts of each commit, to estimate the pain of the
+    /// rebase. The checkout of the user is left alone. Each conflicted
+    /// commit is concluded with its own side of the files.
+    #[arg(
+        long = "pre-rebase",
+        value_name = "BRANCH",
+        requires = "onto",
+        conflicts_with_all = ["vibe", "interactive", "restore", "undo", "stdin", "mergetool", "dry_run", "no_apply", "backup", "pathspecs", "only_file", "since"]
+    )]
+    pre_rebase: Option<String>,
+
+    /// The base the --pre-rebase branch is rebased onto
+    #[arg(long = "onto", value_name = "BASE", requires = "pre_rebase")]
+    onto: Option<String>,
+
+    /// Also resolve the conflicts of --pre-rebase, without applying them
+    #[arg(long = "pre-rebase-resolve", requires = "pre_rebase", default_value = "false")]
+    pre_rebase_resolve: bool,
+
+    /// Read a conflicted file from stdin and write it resolved to stdout,
+    /// without a git repository. The progress is printed to stderr.
+    #[arg(
+        long = "stdin",
+        conflicts_with_all = ["vibe", "interactive", "restore", "undo", "pathspecs", "only_file"],
+        default_value = "false"
+    )]
+    stdin: bool,
+
+    /// Run as the `git mergetool` command: merge the LOCAL, BASE and
+    /// REMOTE files, resolve the conflicts and write the result to
+    /// MERGED. The exit status is nonzero if any conflict is left.
+    #[arg(
+        long = "mergetool",
+        num_args = 4,
+        value_names = ["LOCAL", "BASE", "REMOTE", "MERGED"],
+        conflicts_with_all = ["vibe", "interactive", "restore", "undo", "pathspecs", "only_file", "stdin"]
+    )]
+    mergetool: Option<Vec<String>>,
+
+    /// Size of the conflict markers of all the files, overriding the
+    /// conflict-marker-size gitattribute and the marker_size config.
+    /// Detected from the content by default.
+    #[arg(long = "marker-size", conflicts_with = "mergetool", value_parser = clap::value_parser!(u32).range(7..))]
+    marker_size: Option<u32>,
+
     /// Use conflict markers instead of patch locator for vibe resolution.
     /// This restricts the vibe mode to the capabilities of the interactive mode.
     #[arg(long = "with-markers", default_value = "false")]
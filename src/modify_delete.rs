@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+//! The `--modify-delete` choices, in a module of its own so build.rs
+//! can include it with the command line arguments
+
+/// Resolution of a modify/delete conflict
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ModifyDelete {
+    /// Keep the modified file
+    Keep,
+    /// Delete the file
+    Delete,
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,393 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use crate::config::NormalizeConfig;
+use crate::conflict_resolver::{Conflict, Snippet};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Whitespace normalization of the prompt inputs, so the models don't
+/// have to reason about whitespace policy drift between the two sides.
+/// Resolutions are mapped back to the formatting of the file.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    tab_width: Option<usize>,
+    trim_trailing_whitespace: bool,
+}
+
+/// Formatting convention of the local side of a file
+struct Convention {
+    tabs: bool,
+    line_ending: &'static str,
+}
+
+impl Normalizer {
+    pub fn new(config: &NormalizeConfig) -> Option<Self> {
+        let tab_width = config.tab_width.filter(|&width| width > 0);
+        if tab_width.is_none() && !config.trim_trailing_whitespace {
+            return None;
+        }
+        Some(Normalizer {
+            tab_width,
+            trim_trailing_whitespace: config.trim_trailing_whitespace,
+        })
+    }
+
+    /// Split a line into its content and its line ending
+    fn split_line_ending(line: &str) -> (&str, &str) {
+        if let Some(content) = line.strip_suffix("\r\n") {
+            (content, "\r\n")
+        } else if let Some(content) = line.strip_suffix('\n') {
+            (content, "\n")
+        } else {
+            (line, "")
+        }
+    }
+
+    fn normalize_line_content(&self, content: &str) -> String {
+        let mut normalized = String::with_capacity(content.len());
+        match self.tab_width {
+            Some(tab_width) => {
+                let mut column = 0;
+                for c in content.chars() {
+                    if c == '\t' {
+                        let spaces = tab_width - column % tab_width;
+                        normalized.extend(std::iter::repeat_n(' ', spaces));
+                        column += spaces;
+                    } else {
+                        normalized.push(c);
+                        column += 1;
+                    }
+                }
+            }
+            None => normalized.push_str(content),
+        }
+        if self.trim_trailing_whitespace {
+            normalized.truncate(normalized.trim_end().len());
+        }
+        normalized
+    }
+
+    /// Normalize code, line endings become '\n'
+    pub fn normalize(&self, code: &str) -> String {
+        code.split_inclusive('\n')
+            .map(|line| {
+                let (content, line_ending) = Self::split_line_ending(line);
+                let line_ending = if line_ending.is_empty() { "" } else { "\n" };
+                format!("{}{}", self.normalize_line_content(content), line_ending)
+            })
+            .collect()
+    }
+
+    /// Normalize the content of the lines of a unified diff, leaving the
+    /// headers and the line prefixes alone
+    pub fn normalize_patch(&self, patch: &str) -> String {
+        patch
+            .split_inclusive('\n')
+            .map(|line| match line.chars().next() {
+                Some(prefix @ (' ' | '+' | '-')) => {
+                    format!("{}{}", prefix, self.normalize(&line[1..]))
+                }
+                _ => line.to_string(),
+            })
+            .collect()
+    }
+
+    /// Normalize all the prompt inputs of a conflict
+    pub fn normalize_conflict(&self, conflict: &Conflict) -> Conflict {
+        let mut normalized = conflict.clone();
+        normalized.head_context = self.normalize(&conflict.head_context);
+        normalized.conflict_code = self.normalize(&conflict.conflict_code);
+        normalized.tail_context = self.normalize(&conflict.tail_context);
+        normalized.conflict_patch = self.normalize_patch(&conflict.conflict_patch);
+        normalized.conflict_raw_patch = conflict
+            .conflict_raw_patch
+            .as_ref()
+            .map(|patch| self.normalize_patch(patch));
+        normalized.commit_context = conflict
+            .commit_context
+            .as_ref()
+            .map(|diff| Arc::new(self.normalize_patch(diff)));
+        normalized.code_snippets = Arc::new(
+            conflict
+                .code_snippets
+                .iter()
+                .map(|snippet| Snippet {
+                    snippet: self.normalize(&snippet.snippet),
+                    ..snippet.clone()
+                })
+                .collect(),
+        );
+        normalized
+    }
+
+    fn detect_convention(lines: &[&str]) -> Convention {
+        let tabs = lines.iter().filter(|line| line.starts_with('\t')).count();
+        let spaces = lines.iter().filter(|line| line.starts_with(' ')).count();
+        let crlf = lines.iter().filter(|line| line.ends_with("\r\n")).count();
+        let lf = lines.iter().filter(|line| line.ends_with('\n')).count() - crlf;
+        Convention {
+            tabs: tabs > spaces,
+            line_ending: if crlf > lf { "\r\n" } else { "\n" },
+        }
+    }
+
+    /// Map a normalized resolution back to the formatting of the local
+    /// side of the file. Lines that exist in the local side get their
+    /// original bytes back, new lines follow the indentation and line
+    /// ending convention of the file.
+    pub fn denormalize(&self, resolved: &str, conflict: &Conflict) -> String {
+        let region = format!(
+            "{}{}{}",
+            conflict.head_context, conflict.conflict_code, conflict.tail_context
+        );
+        let local_lines: Vec<&str> = region
+            .split_inclusive('\n')
+            .chain(conflict.merged_local_lines.iter().map(|line| line.as_str()))
+            .collect();
+        let convention = Self::detect_convention(&local_lines);
+
+        // The conflict region comes first, so its lines win
+        let mut original_lines: HashMap<String, &str> = HashMap::new();
+        for line in &local_lines {
+            let (content, _) = Self::split_line_ending(line);
+            original_lines
+                .entry(self.normalize_line_content(content))
+                .or_insert(content);
+        }
+
+        resolved
+            .split_inclusive('\n')
+            .map(|line| {
+                let (content, line_ending) = Self::split_line_ending(line);
+                let line_ending = if line_ending.is_empty() {
+                    ""
+                } else {
+                    convention.line_ending
+                };
+                let content = match original_lines.get(&self.normalize_line_content(content)) {
+                    Some(original) => original.to_string(),
+                    None => self.reindent(content, &convention),
+                };
+                format!("{}{}", content, line_ending)
+            })
+            .collect()
+    }
+
+    fn reindent(&self, content: &str, convention: &Convention) -> String {
+        match self.tab_width {
+            Some(tab_width) if convention.tabs => {
+                let indent = content.len() - content.trim_start_matches(' ').len();
+                format!(
+                    "{}{}{}",
+                    "\t".repeat(indent / tab_width),
+                    " ".repeat(indent % tab_width),
+                    &content[indent..]
+                )
+            }
+            _ => content.to_string(),
+        }
+    }
+}
+
+/// The unit of indentation of a block of code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndentUnit {
+    Tab,
+    Spaces(usize),
+}
+
+impl IndentUnit {
+    /// Detect the indentation unit of the lines, None if no line is
+    /// indented or if tabs and spaces are mixed. The unit of the spaces
+    /// is the most frequent step of indentation between the lines, None
+    /// if all the lines have the same indentation.
+    pub fn detect<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut tabs = false;
+        let mut steps: HashMap<usize, usize> = HashMap::new();
+        let mut previous = None;
+        for line in lines {
+            // The continuation lines of the block comments are aligned
+            // with a space, even in the files indented with tabs
+            if line.trim().is_empty() || line.trim_start().starts_with('*') {
+                continue;
+            }
+            if line.starts_with('\t') {
+                tabs = true;
+                continue;
+            }
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            // The lines of a region all nested at the same depth don't
+            // tell its unit
+            if let Some(previous) = previous
+                && indent != previous
+            {
+                *steps.entry(indent.abs_diff(previous)).or_default() += 1;
+            }
+            previous = Some(indent);
+        }
+        // On a tie the larger step wins, the smaller one is more likely
+        // the alignment of a continuation line
+        let step = steps
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)))
+            .map(|(step, _)| step);
+        match (tabs, step) {
+            (true, None) => Some(IndentUnit::Tab),
+            // A single space is more likely alignment than indentation
+            (false, Some(step)) if step > 1 => Some(IndentUnit::Spaces(step)),
+            _ => None,
+        }
+    }
+
+    /// Split the indentation of a line into its levels and the
+    /// leftover alignment spaces
+    fn levels<'a>(&self, line: &'a str) -> (usize, &'a str) {
+        match self {
+            IndentUnit::Tab => {
+                let content = line.trim_start_matches('\t');
+                (line.len() - content.len(), content)
+            }
+            IndentUnit::Spaces(width) => {
+                let indent = line.len() - line.trim_start_matches(' ').len();
+                (indent / width, &line[indent - indent % width..])
+            }
+        }
+    }
+
+    fn indent(&self, levels: usize) -> String {
+        match self {
+            IndentUnit::Tab => "\t".repeat(levels),
+            IndentUnit::Spaces(width) => " ".repeat(levels * width),
+        }
+    }
+}
+
+/// Reindent a resolution with the indentation unit of the local and base
+/// sections of its conflict and their context. None if either side
+/// is ambiguous or they already match.
+pub fn match_indent(resolved: &str, conflict: &Conflict) -> Option<String> {
+    let region = [
+        &conflict.head_context,
+        &conflict.conflict_code,
+        &conflict.base,
+        &conflict.tail_context,
+    ];
+    let unit = IndentUnit::detect(region.iter().flat_map(|code| code.lines()))?;
+    let resolved_unit = IndentUnit::detect(resolved.lines())?;
+    if unit == resolved_unit {
+        return None;
+    }
+    Some(
+        resolved
+            .split_inclusive('\n')
+            .map(|line| {
+                let (levels, content) = resolved_unit.levels(line);
+                format!("{}{}", unit.indent(levels), content)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer() -> Normalizer {
+        Normalizer::new(&NormalizeConfig {
+            tab_width: Some(4),
+            trim_trailing_whitespace: true,
+        })
+        .unwrap()
+    }
+
+    fn conflict(head_context: &str, conflict_code: &str, tail_context: &str) -> Conflict {
+        Conflict {
+            head_context: head_context.to_string(),
+            conflict_code: conflict_code.to_string(),
+            tail_context: tail_context.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalizer = normalizer();
+        assert_eq!(normalizer.normalize("\tif (x) {  \r\n"), "    if (x) {\n");
+        assert_eq!(normalizer.normalize("ab\tc \t"), "ab  c");
+        assert_eq!(
+            normalizer.normalize_patch("@@ -1,2 +1,2 @@\n-\tfoo \n+\tbar\n \tbaz\n"),
+            "@@ -1,2 +1,2 @@\n-    foo\n+    bar\n     baz\n"
+        );
+        assert!(
+            Normalizer::new(&NormalizeConfig {
+                tab_width: None,
+                trim_trailing_whitespace: false,
+            })
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_denormalize_tabs_and_trailing_whitespace() {
+        let normalizer = normalizer();
+        let conflict = conflict("int f(void)\n{\n", "\tint x = 1; \n", "\treturn x;\n}\n");
+        let normalized = normalizer.normalize_conflict(&conflict);
+        assert_eq!(normalized.conflict_code, "    int x = 1;\n");
+
+        // The model resolves against the normalized form
+        let resolved = "int f(void)\n{\n    int x = 1;\n        x++;\n    return x;\n}\n";
+        let applied = normalizer.denormalize(resolved, &conflict);
+        assert_eq!(
+            applied,
+            "int f(void)\n{\n\tint x = 1; \n\t\tx++;\n\treturn x;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_match_indent() {
+        assert_eq!(
+            IndentUnit::detect("a\n    b\n        c\n          d\n    e\n".lines()),
+            Some(IndentUnit::Spaces(4))
+        );
+        assert_eq!(IndentUnit::detect("a\n\tb\n    c\n".lines()), None);
+        assert_eq!(IndentUnit::detect("a\n b\n".lines()), None);
+        // A region nested two levels deep in a file indented by 4
+        assert_eq!(
+            IndentUnit::detect("        a;\n        b;\n        c;\n".lines()),
+            None
+        );
+        assert_eq!(
+            IndentUnit::detect("        a;\n            b;\n        c;\n".lines()),
+            Some(IndentUnit::Spaces(4))
+        );
+        assert_eq!(
+            IndentUnit::detect("/*\n * a\n */\n\tb\n".lines()),
+            Some(IndentUnit::Tab)
+        );
+
+        let tabs = conflict("int f(void)\n{\n", "\tint x = 1;\n", "\treturn x;\n}\n");
+        let resolved = "    int x = 1;\n    if (x)\n        x++;\n      /* aligned */\n";
+        assert_eq!(
+            match_indent(resolved, &tabs).as_deref(),
+            Some("\tint x = 1;\n\tif (x)\n\t\tx++;\n\t  /* aligned */\n")
+        );
+        assert_eq!(match_indent("\tint x = 1;\n", &tabs), None);
+        // The file mixes tabs and spaces
+        let mixed = conflict("{\n", "\tint x = 1;\n", "    return x;\n");
+        assert_eq!(match_indent(resolved, &mixed), None);
+    }
+
+    #[test]
+    fn test_denormalize_spaces_and_crlf() {
+        let normalizer = normalizer();
+        let conflict = conflict("def f():\r\n", "  x = 1\r\n", "  return x\r\n");
+        let resolved = "def f():\n  x = 1\n  y = 2\n  return x\n";
+        let applied = normalizer.denormalize(resolved, &conflict);
+        assert_eq!(applied, "def f():\r\n  x = 1\r\n  y = 2\r\n  return x\r\n");
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
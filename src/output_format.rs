@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+//! The `--format` choices, in a module of its own so build.rs can
+//! include it with the command line arguments
+
+/// How the conflicts found are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Resolve the conflicts and print the status lines
+    #[default]
+    Text,
+    /// Print the conflicts as a JSON array without resolving them
+    Json,
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -70,9 +70,10 @@ impl Hunk {
     fn get_ranges(&self) -> (usize, usize, usize, usize) {
         let head = self.get_head_context().len();
         let tail = self.get_tail_context().len();
-        let hunk_base_start = self.base_start - 1 + head;
+        // A start of 0 is an empty side, like a file added by both sides
+        let hunk_base_start = self.base_start.max(1) - 1 + head;
         let hunk_base_end = hunk_base_start + self.base_len - head - tail;
-        let hunk_remote_start = self.remote_start - 1 + head;
+        let hunk_remote_start = self.remote_start.max(1) - 1 + head;
         let hunk_remote_end = hunk_remote_start + self.remote_len - head - tail;
         (
             hunk_base_start,
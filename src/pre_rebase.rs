@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use crate::git_utils::{ConflictStyle, GitCommand};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// A throwaway detached worktree of the repository, the current
+/// directory while it's alive. Dropping it goes back to the previous
+/// directory and removes the worktree with the rebase in progress, the
+/// checkout of the user is never touched.
+pub struct Worktree {
+    dir: tempfile::TempDir,
+    prev_dir: PathBuf,
+    repo: Option<String>,
+}
+
+impl Worktree {
+    /// Add the worktree to the repository at repo, or to the one of
+    /// the current directory
+    pub fn add(repo: Option<&str>, commit: &str) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix(concat!(env!("CARGO_PKG_NAME"), "-pre-rebase-"))
+            .tempdir()
+            .context("Failed to create the directory of the worktree")?;
+        let output = GitCommand::git(repo)
+            .args(["worktree", "add", "--detach", "--quiet"])
+            .args([dir.path().as_os_str(), commit.as_ref()])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to add a worktree for {}: {}",
+                commit,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let prev_dir = std::env::current_dir()?;
+        let worktree = Worktree {
+            dir,
+            prev_dir,
+            repo: repo.map(str::to_string),
+        };
+        std::env::set_current_dir(worktree.dir.path())?;
+        Ok(worktree)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.prev_dir);
+        let removed = GitCommand::git(self.repo.as_deref())
+            .args(["worktree", "remove", "--force", "--force"])
+            .args([self.dir.path()])
+            .output();
+        if !removed.is_ok_and(|output| output.status.success()) {
+            log::warn!(
+                "Failed to remove the worktree {}, run git worktree prune",
+                self.dir.path().display()
+            );
+        }
+    }
+}
+
+/// Resolve a commit-ish to its hash
+pub fn rev_parse(repo: Option<&str>, rev: &str) -> Result<String> {
+    let output = GitCommand::git(repo)
+        .args(["rev-parse", "--verify", "--quiet"])
+        .args([format!("{}^{{commit}}", rev)])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("{} is not a commit", rev);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The number of commits of the branch to rebase onto the base
+pub fn count_commits(repo: Option<&str>, onto: &str, branch: &str) -> Result<usize> {
+    let output = GitCommand::git(repo)
+        .args(["rev-list", "--count", "--no-merges"])
+        .args([format!("{}..{}", onto, branch)])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("Failed to count the commits of {}..{}", onto, branch);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().parse()?)
+}
+
+/// The git -c option writing the conflicts in the --conflict-style
+fn conflict_style_config(conflict_style: ConflictStyle) -> &'static str {
+    match conflict_style {
+        ConflictStyle::Diff3 => "merge.conflictStyle=diff3",
+        ConflictStyle::Merge => "merge.conflictStyle=merge",
+    }
+}
+
+/// Start rebasing the worktree onto the base, the rebase stops at the
+/// first conflict. Returns if the rebase is still in progress.
+pub fn start(onto: &str, conflict_style: ConflictStyle) -> Result<bool> {
+    let output = GitCommand::new("git")
+        .args(["-c", conflict_style_config(conflict_style)])
+        .args(["rebase", "--quiet", onto])
+        .env("GIT_EDITOR", "true")
+        .output()?;
+    stopped(&output)
+}
+
+/// Conclude the commit the rebase stopped on with the side of the
+/// commit for every unmerged file, as if the user kept the change
+/// being applied, and continue the rebase to the next conflict.
+/// Returns if the rebase is still in progress.
+pub fn take_theirs_and_continue(conflict_style: ConflictStyle) -> Result<bool> {
+    for file_path in unmerged_files()? {
+        // The side of the commit, or its deletion
+        let checkout = GitCommand::new("git")
+            .args(["checkout", "--theirs", "--", &file_path])
+            .output()?;
+        let update: &[&str] = if checkout.status.success() {
+            &["add"]
+        } else {
+            &["rm", "--quiet"]
+        };
+        let output = GitCommand::new("git")
+            .args(update)
+            .args(["--", &file_path])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to {} {}: {}",
+                update[0],
+                file_path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+    }
+    let output = GitCommand::new("git")
+        .args(["-c", conflict_style_config(conflict_style)])
+        .args(["rebase", "--continue"])
+        .env("GIT_EDITOR", "true")
+        .output()?;
+    if !output.status.success() && in_progress()? && unmerged_files()?.is_empty() {
+        // The commit became empty, it's dropped
+        let output = GitCommand::new("git")
+            .args(["-c", conflict_style_config(conflict_style)])
+            .args(["rebase", "--skip"])
+            .env("GIT_EDITOR", "true")
+            .output()?;
+        return stopped(&output);
+    }
+    stopped(&output)
+}
+
+/// Whether the rebase stopped on a conflict, an error if it stopped
+/// for any other reason
+fn stopped(output: &std::process::Output) -> Result<bool> {
+    if output.status.success() && !in_progress()? {
+        return Ok(false);
+    }
+    if unmerged_files()?.is_empty() {
+        anyhow::bail!(
+            "The rebase stopped without conflicts: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(true)
+}
+
+fn in_progress() -> Result<bool> {
+    let output = GitCommand::new("git")
+        .args(["rev-parse", "--git-path", "rebase-merge"])
+        .output()?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(output.status.success() && std::path::Path::new(&path).exists())
+}
+
+fn unmerged_files() -> Result<Vec<String>> {
+    let output = GitCommand::new("git")
+        .args(["diff", "--name-only", "--diff-filter=U", "-z"])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("Failed to list the unmerged files");
+    }
+    let mut files: Vec<_> = output
+        .stdout
+        .split(|&b| b == b'\0')
+        .filter(|file| !file.is_empty())
+        .map(|file| String::from_utf8_lossy(file).into_owned())
+        .collect();
+    files.dedup();
+    Ok(files)
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -1,13 +1,27 @@
 // SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
 // Copyright (C) 2025  Red Hat, Inc.
 
+use crate::config::LogprobAggregation;
 use crate::conflict_resolver::ConflictResolver;
 use serde_json::Value;
 
+/// The default base of the confidence percentage of a logprob
+pub const DEFAULT_CONFIDENCE_BASE: f64 = 1_000_000.;
+
 /// Calculate the response logprob from the token logprobs
 ///
-/// If no logprobs are available, returns None
-pub fn logprob(json: &Value, perplexity: &mut Vec<String>) -> Option<f64> {
+/// The prefixes of up to beams-1 alternative completions are pushed to
+/// perplexity, the most uncertain token first. With a single beam the
+/// perplexity search is skipped.
+///
+/// The token logprobs of the patched code are aggregated as configured
+/// in the endpoint. If no logprobs are available, returns None
+pub fn logprob(
+    json: &Value,
+    perplexity: &mut Vec<String>,
+    beams: usize,
+    aggregation: LogprobAggregation,
+) -> Option<f64> {
     // Check if logprobs exist in the response
     let logprobs = json
         .get("choices")
@@ -33,6 +47,8 @@ pub fn logprob(json: &Value, perplexity: &mut Vec<String>) -> Option<f64> {
     // Find minimum log probability and track position of token with smallest distance in top_logprobs
     let mut min_logprob = f64::INFINITY;
     let mut min_logprob_pos: Option<usize> = None;
+    let mut sum_logprob = 0.;
+    let mut nr_logprobs = 0;
     let mut raw_min_logprob = f64::INFINITY;
     let mut raw_min_logprob_pos: Option<usize> = None;
     let mut perplexity_pos = Vec::new();
@@ -73,7 +89,8 @@ pub fn logprob(json: &Value, perplexity: &mut Vec<String>) -> Option<f64> {
         }
 
         // Check top_logprobs for this token to find the one with smallest distance
-        if let Some(top_logprobs) = token_logprob.get("top_logprobs").and_then(|t| t.as_array())
+        if beams > 1
+            && let Some(top_logprobs) = token_logprob.get("top_logprobs").and_then(|t| t.as_array())
             && top_logprobs.len() == 2
         {
             let mut min_top_logprob = f64::INFINITY;
@@ -91,6 +108,8 @@ pub fn logprob(json: &Value, perplexity: &mut Vec<String>) -> Option<f64> {
             min_logprob = logprob;
             min_logprob_pos = Some(i);
         }
+        sum_logprob += logprob;
+        nr_logprobs += 1;
     }
 
     // If no valid logprobs found, return None
@@ -106,7 +125,7 @@ pub fn logprob(json: &Value, perplexity: &mut Vec<String>) -> Option<f64> {
 
     perplexity_pos.sort_unstable_by(|a, b| f64::total_cmp(&b.0, &a.0));
     let perplexity_pos: Vec<_> = perplexity_pos.iter().map(|x| x.1).collect();
-    perplexity_search(content_logprobs, tokens, &perplexity_pos, perplexity)?;
+    perplexity_search(content_logprobs, tokens, &perplexity_pos, perplexity, beams)?;
 
     // Call function with json and position of lowest logprob token
     print_logprob_diff(tokens, raw_min_logprob_pos, "~~~");
@@ -114,7 +133,94 @@ pub fn logprob(json: &Value, perplexity: &mut Vec<String>) -> Option<f64> {
         print_logprob_diff(tokens, min_logprob_pos, "~=~");
     }
 
-    Some(min_logprob)
+    Some(match aggregation {
+        LogprobAggregation::Min => min_logprob,
+        LogprobAggregation::Mean => sum_logprob / nr_logprobs as f64,
+        LogprobAggregation::Sum => sum_logprob,
+    })
+}
+
+/// The aggregated logprob of the patched code of a streamed response
+/// as its tokens arrive, with the same patched code region as
+/// [`logprob`]. The tokens that may still turn out to be the start of
+/// the end sentinel are held back until more text arrives.
+pub struct RunningLogprob {
+    aggregation: LogprobAggregation,
+    text: String,
+    /// The offset of the patched code, once its start sentinel arrived
+    start_pos: Option<usize>,
+    /// The end sentinel arrived, the aggregate is final
+    closed: bool,
+    /// The offsets and logprobs of the tokens held back
+    pending: Vec<(usize, f64)>,
+    min: Option<f64>,
+    sum: f64,
+}
+
+impl RunningLogprob {
+    pub fn new(aggregation: LogprobAggregation) -> Self {
+        RunningLogprob {
+            aggregation,
+            text: String::new(),
+            start_pos: None,
+            closed: false,
+            pending: Vec::new(),
+            min: None,
+            sum: 0.,
+        }
+    }
+
+    /// Add the next token of the stream. Returns the aggregated logprob
+    /// of the tokens known to be in the patched code so far, if it can
+    /// only decrease: never with the mean aggregation.
+    pub fn push(&mut self, token: &str, logprob: f64) -> Option<f64> {
+        let offset = self.text.len();
+        self.text.push_str(token);
+        if self.closed {
+            return self.aggregate();
+        }
+        let Some(start_pos) = self.start_pos else {
+            // The tokens up to the start sentinel are all before it
+            let patched_code_start = format!("{}\n", ConflictResolver::PATCHED_CODE_START);
+            self.start_pos = self
+                .text
+                .find(&patched_code_start)
+                .map(|pos| pos + patched_code_start.len());
+            return None;
+        };
+        if offset >= start_pos {
+            self.pending.push((offset, logprob));
+        }
+
+        let patched_code_end = ConflictResolver::PATCHED_CODE_END;
+        let confirmed = match self.text.find(patched_code_end) {
+            Some(end_pos) => {
+                self.closed = true;
+                end_pos
+            }
+            // A token can't be part of an end sentinel that would start
+            // after it
+            None => (self.text.len() + 1).saturating_sub(patched_code_end.len()),
+        };
+        let pending = std::mem::take(&mut self.pending);
+        for (offset, logprob) in pending {
+            if offset < confirmed {
+                self.min = Some(self.min.map_or(logprob, |min: f64| min.min(logprob)));
+                self.sum += logprob;
+            } else if !self.closed {
+                self.pending.push((offset, logprob));
+            }
+        }
+        self.aggregate()
+    }
+
+    fn aggregate(&self) -> Option<f64> {
+        match self.aggregation {
+            LogprobAggregation::Min => self.min,
+            LogprobAggregation::Sum => self.min.map(|_| self.sum),
+            LogprobAggregation::Mean => None,
+        }
+    }
 }
 
 fn perplexity_search(
@@ -122,8 +228,11 @@ fn perplexity_search(
     tokens: &[Value],
     perplexity_pos: &Vec<usize>,
     perplexity: &mut Vec<String>,
+    beams: usize,
 ) -> Option<()> {
-    const PERPLEXITY_BEAMS: usize = 3;
+    if beams <= 1 {
+        return Some(());
+    }
     for pos in perplexity_pos {
         let token = tokens.get(*pos)?;
         let text = token.get("token").and_then(|t| t.as_str())?;
@@ -148,7 +257,7 @@ fn perplexity_search(
                 break;
             }
         }
-        if perplexity.len() >= PERPLEXITY_BEAMS - 1 {
+        if perplexity.len() >= beams - 1 {
             break;
         }
     }
@@ -185,9 +294,29 @@ fn print_logprob_diff(tokens: &[Value], pos: Option<usize>, separator: &str) ->
     Some(())
 }
 
-pub fn logprob_to_prob(logprob: f64) -> f64 {
-    //logprob.exp().min(1.0) * 100.
-    1000000_f64.powf(logprob).clamp(0., 1.) * 100.
+/// The confidence percentage of a logprob, base^logprob. A base of e
+/// gives the probability itself, the default base 10^6 stretches the
+/// range of the confident resolutions: it's the probability raised to
+/// ln(10^6) ~ 13.8, so a least likely token at 95% maps to 49.2% and
+/// only the resolutions with all tokens above 99.2% get over 90%.
+pub fn logprob_to_prob(logprob: f64, base: f64) -> f64 {
+    base.powf(logprob).clamp(0., 1.) * 100.
+}
+
+/// The logprob whose confidence percentage is the given one, the
+/// inverse of [`logprob_to_prob`]
+pub fn confidence_to_logprob(confidence: f64, base: f64) -> f64 {
+    (confidence / 100.).ln() / base.ln()
+}
+
+/// The confidence of a logprob to show next to the model name, the
+/// percentage or with --raw-logprob the logprob itself
+pub fn format_confidence(logprob: f64, base: f64, raw_logprob: bool) -> String {
+    if raw_logprob {
+        format!("logprob {:.4}", logprob)
+    } else {
+        format!("{:.1}%", logprob_to_prob(logprob, base))
+    }
 }
 
 #[cfg(test)]
@@ -229,13 +358,136 @@ mod tests {
 
         let json: Value = serde_json::from_str(json_str).unwrap();
         let mut perplexity = Vec::<String>::new();
-        let prob = logprob(&json, &mut perplexity);
+        let prob = logprob(&json, &mut perplexity, 3, LogprobAggregation::Min);
         assert!(prob.is_some());
         assert!(
             prob.unwrap() == -2.0,
             "wrong prob: {} expected -2.0",
             prob.unwrap()
         );
+
+        // Only the tokens of the patched code are aggregated
+        for (aggregation, expected) in [
+            (LogprobAggregation::Mean, -1.5),
+            (LogprobAggregation::Sum, -3.0),
+        ] {
+            let prob = logprob(&json, &mut Vec::new(), 3, aggregation);
+            assert_eq!(prob, Some(expected), "{:?}", aggregation);
+        }
+    }
+
+    #[test]
+    fn test_logprob_to_prob() {
+        let percent = |logprob: f64| {
+            (logprob_to_prob(logprob, DEFAULT_CONFIDENCE_BASE) * 100.).round() / 100.
+        };
+        for (logprob, expected) in [
+            (0.0, 100.0),
+            (0.1, 100.0),
+            (-0.0001, 99.86),
+            (-0.001, 98.63),
+            (-0.0051, 93.2),
+            (-0.01, 87.1),
+            (-0.05, 50.12),
+            (-0.1, 25.12),
+            (-0.5, 0.1),
+            (-10.0, 0.0),
+        ] {
+            assert_eq!(percent(logprob), expected, "logprob {}", logprob);
+        }
+        assert_eq!(
+            format_confidence(-0.01, DEFAULT_CONFIDENCE_BASE, false),
+            "87.1%"
+        );
+        assert_eq!(
+            format_confidence(-0.01, DEFAULT_CONFIDENCE_BASE, true),
+            "logprob -0.0100"
+        );
+        assert_eq!(
+            logprob_to_prob(-0.01, std::f64::consts::E),
+            (-0.01f64).exp() * 100.
+        );
+    }
+
+    #[test]
+    fn test_running_logprob() {
+        let start = format!("{}\n", ConflictResolver::PATCHED_CODE_START);
+        let end = ConflictResolver::PATCHED_CODE_END;
+        let mut running = RunningLogprob::new(LogprobAggregation::Min);
+        // The tokens before the patched code don't count
+        assert_eq!(running.push("Sure:\n", -5.0), None);
+        assert_eq!(running.push(&start, -0.1), None);
+        assert_eq!(running.push("x = 1;\n", -0.5), None);
+        // Held back until it can't be the start of the end sentinel
+        assert_eq!(running.push("<", -0.2), None);
+        assert_eq!(running.push(" 2; // a long comment\n", -0.3), Some(-0.5));
+        assert_eq!(running.push(&end[..3], -2.0), Some(-0.5));
+        assert_eq!(running.push(&end[3..], -3.0), Some(-0.5));
+        assert_eq!(running.push("\nmore", -9.0), Some(-0.5));
+
+        let mut running = RunningLogprob::new(LogprobAggregation::Sum);
+        running.push(&start, 0.0);
+        running.push("a", -0.25);
+        let sum = running.push(&format!("b\n{}", end), -0.5);
+        assert_eq!(sum, Some(-0.75));
+
+        // The mean can recover, it never abandons
+        let mut running = RunningLogprob::new(LogprobAggregation::Mean);
+        running.push(&start, 0.0);
+        assert_eq!(running.push(&format!("a\n{}", end), -9.0), None);
+    }
+
+    #[test]
+    fn test_confidence_to_logprob() {
+        for logprob in [-0.0001, -0.01, -0.5] {
+            let base = DEFAULT_CONFIDENCE_BASE;
+            let confidence = logprob_to_prob(logprob, base);
+            assert!((confidence_to_logprob(confidence, base) - logprob).abs() < 1e-12);
+        }
+        assert_eq!(confidence_to_logprob(0., 2.), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_perplexity_beams() {
+        let token = |token: &str, logprob: f64, alternative: Option<(&str, f64)>| {
+            let mut token = serde_json::json!({ "token": token, "logprob": logprob });
+            if let Some((alternative, alternative_logprob)) = alternative {
+                token["top_logprobs"] = serde_json::json!([
+                    { "token": token["token"], "logprob": logprob },
+                    { "token": alternative, "logprob": alternative_logprob },
+                ]);
+            }
+            token
+        };
+        let json = serde_json::json!({
+            "choices": [{
+                "logprobs": {
+                    "content": [
+                        token(&format!("{}\n", ConflictResolver::PATCHED_CODE_START), 0.0, None),
+                        token("a", -0.1, Some(("x", -2.0))),
+                        token("b", -0.5, Some(("y", -0.7))),
+                        token("c", -0.2, Some(("z", -3.0))),
+                        token(ConflictResolver::PATCHED_CODE_END, 0.0, None),
+                    ]
+                }
+            }]
+        });
+        let start = format!("{}\n", ConflictResolver::PATCHED_CODE_START);
+
+        // The widest distance between the top logprobs comes first
+        let expected = [
+            format!("{}abz", start),
+            format!("{}x", start),
+            format!("{}ay", start),
+        ];
+        for beams in 1..=4 {
+            let mut perplexity = Vec::<String>::new();
+            assert_eq!(
+                logprob(&json, &mut perplexity, beams, LogprobAggregation::Min),
+                Some(-0.5)
+            );
+            assert_eq!(perplexity, expected[..beams - 1]);
+        }
     }
 
     #[test]
@@ -252,7 +504,7 @@ mod tests {
 
         let json: Value = serde_json::from_str(json_str).unwrap();
         let mut perplexity = Vec::<String>::new();
-        let prob = logprob(&json, &mut perplexity);
+        let prob = logprob(&json, &mut perplexity, 3, LogprobAggregation::Min);
         assert!(prob.is_none());
     }
 
@@ -270,7 +522,7 @@ mod tests {
 
         let json: Value = serde_json::from_str(json_str).unwrap();
         let mut perplexity = Vec::<String>::new();
-        let prob = logprob(&json, &mut perplexity);
+        let prob = logprob(&json, &mut perplexity, 3, LogprobAggregation::Min);
         assert!(prob.is_none());
     }
 }
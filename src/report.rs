@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use crate::conflict_resolver::{Conflict, ResolvedConflict};
+use crate::git_utils::GitUtils;
+use crate::prob;
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+pub struct ResolutionReport {
+    /// The models that agree on the resolution
+    pub model: String,
+    /// The confidence percentage, if the endpoints returned logprobs
+    pub confidence: Option<f64>,
+    /// The logprob the confidence is computed from
+    pub logprob: Option<f64>,
+    pub total_tokens: Option<u64>,
+    pub duration: f64,
+    /// Only the perplexity search found the resolution
+    pub alternative: bool,
+    pub applied: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConflictReport {
+    /// The commit the conflict was hit applying, by --pre-rebase
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub file_path: String,
+    pub start_line: usize,
+    /// Any resolution was written to the working tree
+    pub applied: bool,
+    /// The resolutions of the models, empty if the conflict is
+    /// unresolved
+    pub resolutions: Vec<ResolutionReport>,
+}
+
+/// The conflicts of the run and their resolutions, accumulated over the
+/// rounds of resolution, for the CI pipelines to parse
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub nr_conflicts: usize,
+    pub nr_resolved: usize,
+    pub nr_applied: usize,
+    pub nr_unresolved: usize,
+    /// The resolutions found only by the perplexity search
+    pub nr_alternatives: usize,
+    pub conflicts: Vec<ConflictReport>,
+    /// The commit of the conflicts added next
+    #[serde(skip)]
+    commit: Option<String>,
+}
+
+impl Report {
+    /// Tag the conflicts added next with the commit being applied
+    pub fn set_commit(&mut self, commit: Option<String>) {
+        self.commit = commit;
+    }
+
+    pub fn add(
+        &mut self,
+        conflicts: &[Conflict],
+        resolved_conflicts: &[ResolvedConflict],
+        git_utils: &GitUtils,
+    ) {
+        let resolved_conflicts =
+            GitUtils::deduplicate_conflicts(resolved_conflicts, git_utils.agreement());
+        for conflict in conflicts {
+            // The conflicts of the files left for a retry are reported
+            // again by the next round
+            self.conflicts.retain(|report| {
+                report.applied
+                    || report.commit != self.commit
+                    || report.file_path != conflict.file_path
+                    || report.start_line != conflict.start_line
+            });
+            let resolutions: Vec<_> = resolved_conflicts
+                .iter()
+                .filter(|resolved| {
+                    resolved.conflict.file_path == conflict.file_path
+                        && resolved.conflict.start_line == conflict.start_line
+                })
+                .map(|resolved| ResolutionReport {
+                    model: resolved.model.clone(),
+                    confidence: resolved
+                        .logprob
+                        .map(|logprob| prob::logprob_to_prob(logprob, git_utils.confidence_base())),
+                    logprob: resolved.logprob,
+                    total_tokens: resolved.total_tokens,
+                    duration: resolved.duration,
+                    alternative: resolved.is_alternative(),
+                    applied: git_utils.is_applied(resolved),
+                })
+                .collect();
+            self.conflicts.push(ConflictReport {
+                commit: self.commit.clone(),
+                file_path: conflict.file_path.clone(),
+                start_line: conflict.start_line,
+                applied: resolutions.iter().any(|resolution| resolution.applied),
+                resolutions,
+            });
+        }
+
+        self.nr_conflicts = self.conflicts.len();
+        self.nr_resolved = self
+            .conflicts
+            .iter()
+            .filter(|report| !report.resolutions.is_empty())
+            .count();
+        self.nr_applied = self
+            .conflicts
+            .iter()
+            .filter(|report| report.applied)
+            .count();
+        self.nr_unresolved = self.nr_conflicts - self.nr_resolved;
+        self.nr_alternatives = self
+            .conflicts
+            .iter()
+            .flat_map(|report| &report.resolutions)
+            .filter(|resolution| resolution.alternative)
+            .count();
+    }
+
+    pub fn write_json(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write the report to {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_utils::{ApplyOptions, ContextLines, ResolutionMode};
+
+    #[test]
+    fn test_report() {
+        let git_utils = GitUtils::new(
+            ContextLines::default(),
+            None,
+            false,
+            ResolutionMode::Interactive,
+            0,
+            ApplyOptions::default(),
+        );
+        let conflict = |file_path: &str, start_line| Conflict {
+            file_path: file_path.to_string(),
+            start_line,
+            local_start: start_line,
+            ..Default::default()
+        };
+        let resolved = |conflict: &Conflict, model: &str, beam| ResolvedConflict {
+            conflict: conflict.clone(),
+            resolved_version: "resolved\n".to_string(),
+            model: model.to_string(),
+            duration: 1.0,
+            total_tokens: Some(100),
+            input_tokens: None,
+            logprob: Some(0.0),
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: Some(0),
+            beam: Some(beam),
+        };
+        let conflicts = [conflict("a.c", 10), conflict("b.c", 20)];
+
+        let mut report = Report::default();
+        report.add(
+            &conflicts,
+            &[
+                resolved(&conflicts[0], "x (high)", 0),
+                resolved(&conflicts[0], "x (low)", 1),
+            ],
+            &git_utils,
+        );
+        assert_eq!(report.nr_conflicts, 2);
+        assert_eq!(report.nr_resolved, 1);
+        assert_eq!(report.nr_unresolved, 1);
+        assert_eq!(report.nr_applied, 0);
+        assert_eq!(report.conflicts[0].resolutions.len(), 1);
+        assert_eq!(report.conflicts[0].resolutions[0].model, "x (high|low)");
+        assert_eq!(report.conflicts[0].resolutions[0].confidence, Some(100.0));
+        // The first completion of x (high) agrees with the alternative
+        assert!(!report.conflicts[0].resolutions[0].alternative);
+        assert_eq!(report.nr_alternatives, 0);
+        assert!(report.conflicts[1].resolutions.is_empty());
+
+        // A retry of the unresolved conflict replaces it
+        report.add(
+            &conflicts[1..],
+            &[
+                resolved(&conflicts[1], "y", 0),
+                ResolvedConflict {
+                    resolved_version: "other\n".to_string(),
+                    ..resolved(&conflicts[1], "y (#1)", 1)
+                },
+            ],
+            &git_utils,
+        );
+        assert_eq!(report.nr_conflicts, 2);
+        assert_eq!(report.nr_unresolved, 0);
+        assert_eq!(report.conflicts[1].resolutions.len(), 2);
+        assert!(report.conflicts[1].resolutions[1].alternative);
+        assert_eq!(report.nr_alternatives, 1);
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use serde_json::{Value, json};
+
+// Reassemble the server-sent events of a streamed response into the
+// JSON of the non streamed response, so the response handlers, the
+// cache and the logprob computation work the same way.
+
+/// The JSON payloads of the `data:` lines of the events
+fn data_events(sse: &str) -> Vec<Value> {
+    sse.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(str::trim)
+        .filter(|data| *data != "[DONE]")
+        .filter_map(|data| serde_json::from_str(data).ok())
+        .collect()
+}
+
+/// The tokens and logprobs of an OpenAI stream event line, as they
+/// arrive
+pub fn token_logprobs(line: &str) -> Vec<(String, f64)> {
+    let Some(event) = data_events(line).pop() else {
+        return Vec::new();
+    };
+    event
+        .pointer("/choices/0/logprobs/content")
+        .and_then(|content| content.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|token| {
+            Some((
+                token.get("token")?.as_str()?.to_string(),
+                token.get("logprob")?.as_f64()?,
+            ))
+        })
+        .collect()
+}
+
+/// Reassemble an OpenAI chat or completions stream, the per-token
+/// logprobs of the deltas are concatenated. Returns None if the text
+/// isn't an event stream.
+pub fn assemble_openai(sse: &str) -> Option<String> {
+    let events = data_events(sse);
+    if events.is_empty() {
+        return None;
+    }
+
+    let mut content = String::new();
+    let mut text = String::new();
+    let mut chat = false;
+    let mut logprobs = Vec::new();
+    let mut finish_reason = Value::Null;
+    let mut usage = Value::Null;
+    for event in events {
+        // Errors in the middle of the stream are reported as is
+        if event.get("error").is_some() {
+            return Some(event.to_string());
+        }
+        if let Some(event_usage) = event.get("usage")
+            && !event_usage.is_null()
+        {
+            usage = event_usage.clone();
+        }
+        let Some(choice) = event.get("choices").and_then(|choices| choices.get(0)) else {
+            continue;
+        };
+        if let Some(delta) = choice.get("delta") {
+            chat = true;
+            if let Some(delta_content) = delta.get("content").and_then(|c| c.as_str()) {
+                content.push_str(delta_content);
+            }
+        }
+        if let Some(delta_text) = choice.get("text").and_then(|t| t.as_str()) {
+            text.push_str(delta_text);
+        }
+        if let Some(delta_logprobs) = choice
+            .get("logprobs")
+            .and_then(|logprobs| logprobs.get("content"))
+            .and_then(|content| content.as_array())
+        {
+            logprobs.extend(delta_logprobs.iter().cloned());
+        }
+        if let Some(reason) = choice.get("finish_reason")
+            && !reason.is_null()
+        {
+            finish_reason = reason.clone();
+        }
+    }
+
+    let mut choice = if chat {
+        json!({ "message": { "role": "assistant", "content": content } })
+    } else {
+        json!({ "text": text })
+    };
+    choice["finish_reason"] = finish_reason;
+    if !logprobs.is_empty() {
+        choice["logprobs"] = json!({ "content": logprobs });
+    }
+    Some(json!({ "choices": [choice], "usage": usage }).to_string())
+}
+
+/// Reassemble an Anthropic messages stream into its text content.
+/// Returns None if the text isn't an event stream.
+pub fn assemble_anthropic(sse: &str) -> Option<String> {
+    let events = data_events(sse);
+    if events.is_empty() {
+        return None;
+    }
+
+    let mut text = String::new();
+    let mut stop_reason = Value::Null;
+    // The usage of message_start also has the prompt cache tokens
+    let mut usage = json!({ "input_tokens": Value::Null });
+    let mut output_tokens = Value::Null;
+    for event in events {
+        match event.get("type").and_then(|t| t.as_str()) {
+            Some("error") => return Some(event.to_string()),
+            Some("message_start") => {
+                if let Some(message_usage) = event.pointer("/message/usage") {
+                    usage = message_usage.clone();
+                }
+            }
+            Some("content_block_delta") => {
+                if let Some(delta_text) = event.pointer("/delta/text").and_then(|t| t.as_str()) {
+                    text.push_str(delta_text);
+                }
+            }
+            Some("message_delta") => {
+                if let Some(reason) = event.pointer("/delta/stop_reason") {
+                    stop_reason = reason.clone();
+                }
+                if let Some(tokens) = event.pointer("/usage/output_tokens") {
+                    output_tokens = tokens.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    usage["output_tokens"] = output_tokens;
+    Some(
+        json!({
+            "content": [{ "type": "text", "text": text }],
+            "stop_reason": stop_reason,
+            "usage": usage,
+        })
+        .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LogprobAggregation;
+    use crate::conflict_resolver::ConflictResolver;
+
+    #[test]
+    fn test_assemble_openai() {
+        let start = format!("{}\n", ConflictResolver::PATCHED_CODE_START);
+        let token = |token: &str, logprob: f64| json!({ "token": token, "logprob": logprob });
+        let delta = |content: &str, logprobs: Vec<Value>| {
+            json!({
+                "choices": [{
+                    "index": 0,
+                    "delta": { "content": content },
+                    "logprobs": { "content": logprobs },
+                    "finish_reason": null,
+                }]
+            })
+        };
+        let events = [
+            delta(&start, vec![token(&start, 0.0)]),
+            delta("x = 1;\n", vec![token("x", -0.5), token(" = 1;\n", -0.25)]),
+            delta(
+                ConflictResolver::PATCHED_CODE_END,
+                vec![token(ConflictResolver::PATCHED_CODE_END, 0.0)],
+            ),
+            json!({ "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }] }),
+            json!({ "choices": [], "usage": { "total_tokens": 42 } }),
+        ];
+        let sse = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect::<String>()
+            + "data: [DONE]\n\n";
+
+        let assembled: Value = serde_json::from_str(&assemble_openai(&sse).unwrap()).unwrap();
+        assert_eq!(
+            assembled["choices"][0]["message"]["content"],
+            format!("{}x = 1;\n{}", start, ConflictResolver::PATCHED_CODE_END)
+        );
+        assert_eq!(assembled["choices"][0]["finish_reason"], "stop");
+        assert_eq!(assembled["usage"]["total_tokens"], 42);
+
+        // The logprobs are computed over the reassembled tokens
+        let logprob = crate::prob::logprob(&assembled, &mut Vec::new(), 1, LogprobAggregation::Sum);
+        assert_eq!(logprob, Some(-0.75));
+
+        assert_eq!(assemble_openai(r#"{"choices": []}"#), None);
+    }
+
+    #[test]
+    fn test_assemble_anthropic() {
+        let sse = r#"event: message_start
+data: {"type": "message_start", "message": {"usage": {"input_tokens": 30, "cache_read_input_tokens": 900, "output_tokens": 1}}}
+
+event: content_block_start
+data: {"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}
+
+event: content_block_delta
+data: {"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hello"}}
+
+event: content_block_delta
+data: {"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": " world"}}
+
+event: message_delta
+data: {"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 12}}
+
+event: message_stop
+data: {"type": "message_stop"}
+"#;
+        let assembled: Value = serde_json::from_str(&assemble_anthropic(sse).unwrap()).unwrap();
+        assert_eq!(assembled["content"][0]["text"], "Hello world");
+        assert_eq!(assembled["stop_reason"], "end_turn");
+        assert_eq!(assembled["usage"]["input_tokens"], 30);
+        assert_eq!(assembled["usage"]["cache_read_input_tokens"], 900);
+        assert_eq!(assembled["usage"]["output_tokens"], 12);
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
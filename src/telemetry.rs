@@ -2,15 +2,19 @@
 // Copyright (C) 2025  Red Hat, Inc.
 
 use crate::api_client::ApiClient;
-use crate::config::{Config, EndpointConfig, EndpointTypeConfig};
+use crate::config::{Config, EndpointConfig, EndpointTypeConfig, TelemetryId};
 use crate::conflict_resolver::{Conflict, ResolvedConflict};
-use anyhow::Result;
+use crate::logger;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct TelemetryData {
-    id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<Uuid>,
     endpoints: Vec<String>,
     version: String,
     nr_conflicts: usize,
@@ -19,6 +23,24 @@ struct TelemetryData {
     duration: f64,
 }
 
+#[derive(Debug, Serialize)]
+struct AuditResolution {
+    file_path: String,
+    local_start: usize,
+    model: String,
+    duration: f64,
+}
+
+/// A line of the local audit log
+#[derive(Debug, Serialize)]
+struct AuditRecord {
+    /// Seconds since the epoch
+    time: u64,
+    #[serde(flatten)]
+    data: TelemetryData,
+    resolutions: Vec<AuditResolution>,
+}
+
 pub struct Telemetry {
     data: Option<TelemetryData>,
     endpoint: Option<EndpointConfig>,
@@ -29,6 +51,7 @@ impl Telemetry {
         config: &Config,
         conflicts: &[Conflict],
         resolved_conflicts: &[ResolvedConflict],
+        no_telemetry: bool,
     ) -> Self {
         let endpoints = config.get_all_endpoints();
         let mut patchpal_endpoint = endpoints.iter().find(|e| {
@@ -38,48 +61,114 @@ impl Telemetry {
             )
         });
         if let Some(e) = patchpal_endpoint
-            && let EndpointTypeConfig::Patchpal {
-                telemetry,
-                n_beams: _,
-            } = &e.config
+            && let EndpointTypeConfig::Patchpal { telemetry, .. } = &e.config
             && !telemetry
         {
             patchpal_endpoint = None;
         }
+        // The global kill switch wins over the endpoint config, before
+        // anything about the environment is hashed
+        if patchpal_endpoint.is_some() && Self::suppressed(no_telemetry) {
+            logger::status!("Telemetry suppressed, nothing is sent to the patchpal endpoint");
+            patchpal_endpoint = None;
+        }
 
         let mut telemetry = Self {
             data: None,
             endpoint: patchpal_endpoint.cloned(),
         };
         if patchpal_endpoint.is_some() {
-            let data = TelemetryData {
-                endpoints: endpoints
-                    .iter()
-                    .map(|e| match &e.config {
-                        EndpointTypeConfig::OpenAI { .. } => "openai".to_string(),
-                        EndpointTypeConfig::Anthropic { .. } => "anthropic".to_string(),
-                        EndpointTypeConfig::Patchpal { .. } => "patchpal".to_string(),
-                    })
-                    .collect(),
-                version: concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"))
-                    .to_string(),
-                nr_conflicts: conflicts.len(),
-                nr_resolved_conflicts: resolved_conflicts
-                    .iter()
-                    .map(|c| c.deduplicated_conflicts.len().max(1))
-                    .sum(),
-                nr_deduplicated_resolved_conflicts: resolved_conflicts.len(),
-                duration: resolved_conflicts.iter().map(|c| c.duration).sum(),
-                id: Self::create_environment_uuid(),
-            };
-            telemetry.data = Some(data);
+            telemetry.data = Some(Self::telemetry_data(config, conflicts, resolved_conflicts));
         }
         telemetry
     }
 
+    fn telemetry_data(
+        config: &Config,
+        conflicts: &[Conflict],
+        resolved_conflicts: &[ResolvedConflict],
+    ) -> TelemetryData {
+        let endpoints = config.get_all_endpoints();
+        let telemetry_id = endpoints
+            .iter()
+            .find_map(|e| match &e.config {
+                EndpointTypeConfig::Patchpal { telemetry_id, .. } => Some(*telemetry_id),
+                _ => None,
+            })
+            .unwrap_or_default();
+        TelemetryData {
+            endpoints: endpoints
+                .iter()
+                .map(|e| match &e.config {
+                    EndpointTypeConfig::OpenAI { .. } => "openai".to_string(),
+                    EndpointTypeConfig::Anthropic { .. } => "anthropic".to_string(),
+                    EndpointTypeConfig::Ollama { .. } => "ollama".to_string(),
+                    EndpointTypeConfig::Patchpal { .. } => "patchpal".to_string(),
+                })
+                .collect(),
+            version: concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION")).to_string(),
+            nr_conflicts: conflicts.len(),
+            nr_resolved_conflicts: resolved_conflicts
+                .iter()
+                .map(|c| c.deduplicated_conflicts.len().max(1))
+                .sum(),
+            nr_deduplicated_resolved_conflicts: resolved_conflicts.len(),
+            duration: resolved_conflicts.iter().map(|c| c.duration).sum(),
+            id: match telemetry_id {
+                TelemetryId::Random => Some(Uuid::new_v4()),
+                TelemetryId::Stable => Some(Self::create_environment_uuid()),
+                TelemetryId::None => None,
+            },
+        }
+    }
+
+    /// Append the telemetry data of the run and the model and duration
+    /// of each resolution to the local audit log, whether or not any
+    /// telemetry is sent
+    pub fn append_audit_log(
+        config: &Config,
+        conflicts: &[Conflict],
+        resolved_conflicts: &[ResolvedConflict],
+        path: &Path,
+    ) -> Result<()> {
+        let record = AuditRecord {
+            time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+            data: Self::telemetry_data(config, conflicts, resolved_conflicts),
+            resolutions: resolved_conflicts
+                .iter()
+                .map(|c| AuditResolution {
+                    file_path: c.conflict.file_path.clone(),
+                    local_start: c.conflict.local_start,
+                    model: c.model.clone(),
+                    duration: c.duration,
+                })
+                .collect(),
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open the audit log {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        Ok(())
+    }
+
+    /// Telemetry is suppressed by --no-telemetry or by setting
+    /// SYNTHMERGE_NO_TELEMETRY to anything but empty or "0"
+    fn suppressed(no_telemetry: bool) -> bool {
+        no_telemetry
+            || std::env::var("SYNTHMERGE_NO_TELEMETRY")
+                .is_ok_and(|value| !value.is_empty() && value != "0")
+    }
+
     pub async fn submit(&self) -> Result<()> {
         if let (Some(endpoint), Some(data)) = (self.endpoint.clone(), self.data.clone()) {
-            println!("Sending telemetry data to patchpal endpoint");
+            logger::status!("Sending telemetry data to patchpal endpoint");
             log::trace!("Telemetry: {:?}", data);
             self.send_telemetry_patchpal(&endpoint, &data).await?;
         }
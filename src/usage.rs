@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use crate::config::EndpointConfig;
+use crate::conflict_resolver::ResolvedConflict;
+use crate::logger;
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Default)]
+pub struct EndpointUsage {
+    pub name: String,
+    /// Responses queried from the endpoint
+    pub responses: usize,
+    /// Responses taken from the cache, their tokens aren't counted
+    pub cached: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    /// Estimated cost, if the endpoint has a token price configured
+    pub cost: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConflictDuration {
+    pub file_path: String,
+    pub start_line: usize,
+    /// Seconds taken by the slowest model to resolve the conflict
+    pub duration: f64,
+}
+
+/// Token usage and estimated cost of the run, accumulated over the
+/// resolved conflicts of each round of resolution
+#[derive(Serialize, Debug, Default)]
+pub struct UsageStats {
+    pub endpoints: Vec<EndpointUsage>,
+    pub total_tokens: u64,
+    pub cost: Option<f64>,
+    /// The conflicts that took the longest to resolve, slowest first
+    pub slowest_conflicts: Vec<ConflictDuration>,
+    #[serde(skip)]
+    conflict_durations: Vec<ConflictDuration>,
+    #[serde(skip)]
+    prices: Vec<(Option<f64>, Option<f64>)>,
+}
+
+impl UsageStats {
+    const NR_SLOWEST_CONFLICTS: usize = 5;
+
+    pub fn new(endpoints: &[EndpointConfig]) -> Self {
+        Self {
+            endpoints: endpoints
+                .iter()
+                .map(|endpoint| EndpointUsage {
+                    name: endpoint.name.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            prices: endpoints
+                .iter()
+                .map(|endpoint| (endpoint.input_price, endpoint.output_price))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, resolved_conflicts: &[ResolvedConflict]) {
+        self.add_durations(resolved_conflicts);
+        for resolved_conflict in resolved_conflicts {
+            // The additional patched code blocks of a response don't
+            // cost more tokens
+            if resolved_conflict.multi.unwrap_or(0) != 0 {
+                continue;
+            }
+            let Some(usage) = self.endpoints.get_mut(resolved_conflict.endpoint) else {
+                continue;
+            };
+            // Cache hits are parsed with a zero duration
+            if resolved_conflict.duration == 0.0 {
+                usage.cached += 1;
+                continue;
+            }
+            usage.responses += 1;
+            let Some(total_tokens) = resolved_conflict.total_tokens else {
+                continue;
+            };
+            let input_tokens = resolved_conflict
+                .input_tokens
+                .unwrap_or(0)
+                .min(total_tokens);
+            usage.input_tokens += input_tokens;
+            usage.output_tokens += total_tokens - input_tokens;
+            usage.total_tokens += total_tokens;
+        }
+
+        for (usage, (input_price, output_price)) in self.endpoints.iter_mut().zip(&self.prices) {
+            if input_price.is_some() || output_price.is_some() {
+                usage.cost = Some(
+                    usage.input_tokens as f64 / 1000.0 * input_price.unwrap_or(0.0)
+                        + usage.output_tokens as f64 / 1000.0 * output_price.unwrap_or(0.0),
+                );
+            }
+        }
+        self.total_tokens = self.endpoints.iter().map(|usage| usage.total_tokens).sum();
+        self.cost = self
+            .endpoints
+            .iter()
+            .filter_map(|usage| usage.cost)
+            .reduce(|a, b| a + b);
+    }
+
+    fn add_durations(&mut self, resolved_conflicts: &[ResolvedConflict]) {
+        for resolved_conflict in resolved_conflicts {
+            // Cache hits are parsed with a zero duration
+            if resolved_conflict.duration == 0.0 {
+                continue;
+            }
+            let conflict = &resolved_conflict.conflict;
+            match self.conflict_durations.iter_mut().find(|slow| {
+                slow.file_path == conflict.file_path && slow.start_line == conflict.start_line
+            }) {
+                Some(slow) => slow.duration = slow.duration.max(resolved_conflict.duration),
+                None => self.conflict_durations.push(ConflictDuration {
+                    file_path: conflict.file_path.clone(),
+                    start_line: conflict.start_line,
+                    duration: resolved_conflict.duration,
+                }),
+            }
+        }
+        self.conflict_durations
+            .sort_by(|a, b| b.duration.total_cmp(&a.duration));
+        self.slowest_conflicts = self
+            .conflict_durations
+            .iter()
+            .take(Self::NR_SLOWEST_CONFLICTS)
+            .cloned()
+            .collect();
+    }
+
+    pub fn print(&self) {
+        if self
+            .endpoints
+            .iter()
+            .all(|usage| usage.responses == 0 && usage.cached == 0)
+        {
+            return;
+        }
+        logger::status!("Token usage:");
+        for usage in &self.endpoints {
+            if usage.responses == 0 && usage.cached == 0 {
+                continue;
+            }
+            let cached = if usage.cached > 0 {
+                format!(" + {} cached", usage.cached)
+            } else {
+                String::new()
+            };
+            let cost = usage
+                .cost
+                .map(|cost| format!(" ${:.4}", cost))
+                .unwrap_or_default();
+            logger::status!(
+                "  {}: {} responses{}, {} t ({} in, {} out){}",
+                usage.name,
+                usage.responses,
+                cached,
+                usage.total_tokens,
+                usage.input_tokens,
+                usage.output_tokens,
+                cost
+            );
+        }
+        let cost = self
+            .cost
+            .map(|cost| format!(", estimated cost ${:.4}", cost))
+            .unwrap_or_default();
+        logger::status!("  Total: {} t{}", self.total_tokens, cost);
+
+        // A single conflict was already timed when resolved
+        if self.slowest_conflicts.len() > 1 {
+            logger::status!("Slowest conflicts:");
+            for slow in &self.slowest_conflicts {
+                logger::status!(
+                    "  {}:{} {:.1}s",
+                    slow.file_path,
+                    slow.start_line,
+                    slow.duration
+                );
+            }
+        }
+    }
+
+    pub fn write_json(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write the JSON stats to {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conflict_resolver::Conflict;
+
+    #[test]
+    fn test_usage_stats() {
+        let endpoints: Vec<EndpointConfig> = serde_yaml::from_str(
+            r#"
+- name: priced
+  url: http://localhost
+  type: openai
+  input_price: 0.5
+  output_price: 2.0
+- name: free
+  url: http://localhost
+  type: openai
+"#,
+        )
+        .unwrap();
+        let resolved = |endpoint, duration, total_tokens, input_tokens, multi| ResolvedConflict {
+            conflict: Conflict::default(),
+            resolved_version: String::new(),
+            model: String::new(),
+            duration,
+            total_tokens,
+            input_tokens,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint,
+            multi: Some(multi),
+            beam: Some(0),
+        };
+
+        let mut stats = UsageStats::new(&endpoints);
+        stats.add(&[
+            resolved(0, 1.0, Some(3000), Some(2000), 0),
+            // Same response as the previous one
+            resolved(0, 1.0, Some(3000), Some(2000), 1),
+            // Cache hit
+            resolved(0, 0.0, Some(3000), Some(2000), 0),
+            resolved(1, 1.0, Some(100), Some(60), 0),
+        ]);
+        stats.add(&[resolved(0, 1.0, Some(1000), None, 0)]);
+
+        assert_eq!(stats.endpoints[0].responses, 2);
+        assert_eq!(stats.endpoints[0].cached, 1);
+        assert_eq!(stats.endpoints[0].input_tokens, 2000);
+        assert_eq!(stats.endpoints[0].output_tokens, 2000);
+        assert_eq!(stats.endpoints[0].cost, Some(5.0));
+        assert_eq!(stats.endpoints[1].output_tokens, 40);
+        assert_eq!(stats.endpoints[1].cost, None);
+        assert_eq!(stats.total_tokens, 4100);
+        assert_eq!(stats.cost, Some(5.0));
+    }
+
+    #[test]
+    fn test_slowest_conflicts() {
+        let resolved = |start_line, duration| ResolvedConflict {
+            conflict: Conflict {
+                file_path: "a.c".to_string(),
+                start_line,
+                ..Default::default()
+            },
+            resolved_version: String::new(),
+            model: String::new(),
+            duration,
+            total_tokens: None,
+            input_tokens: None,
+            logprob: None,
+            deduplicated_conflicts: Vec::new(),
+            endpoint: 0,
+            multi: Some(0),
+            beam: Some(0),
+        };
+        let mut stats = UsageStats::new(&[]);
+        stats.add(
+            &(1..=7)
+                .map(|start_line| resolved(start_line, start_line as f64))
+                .collect::<Vec<_>>(),
+        );
+        // The slowest model of the conflict counts, not the cache hits
+        stats.add(&[resolved(1, 10.0), resolved(2, 0.0)]);
+        assert_eq!(
+            stats
+                .slowest_conflicts
+                .iter()
+                .map(|slow| (slow.start_line, slow.duration))
+                .collect::<Vec<_>>(),
+            [(1, 10.0), (7, 7.0), (6, 6.0), (5, 5.0), (4, 4.0)]
+        );
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
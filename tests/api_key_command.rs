@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, f_txt, init_repo, mock_endpoint};
+use synthmerge::{ApplyOptions, Config, ContextLines, GitUtils, ResolutionMode};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_api_key_command_runs_once() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |side| {
+        std::fs::write(dir.join("f.txt"), f_txt(side)).unwrap();
+        std::fs::write(dir.join("g.txt"), f_txt(side)).unwrap();
+    });
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 2);
+
+    // The command counts its runs
+    let config_dir = tempfile::tempdir().unwrap();
+    let runs = config_dir.path().join("runs");
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 1\n    api_key_command: \"echo run >> {}; echo sk-test\"\n",
+            mock_endpoint().await.url,
+            runs.display()
+        ),
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+    let resolved_conflicts = synthmerge::resolve_conflicts(&config, &conflicts)
+        .await
+        .unwrap();
+    assert_eq!(resolved_conflicts.len(), 2);
+    assert_eq!(std::fs::read_to_string(&runs).unwrap(), "run\n");
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
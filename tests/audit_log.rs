@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+#![cfg(feature = "telemetry")]
+
+mod common;
+
+use common::{conflicted_repo, mock_endpoint};
+use std::process::Command;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_audit_log_line_per_run() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    conflicted_repo(dir);
+
+    let config = tempfile::tempdir().unwrap();
+    let config = config.path().join("synthmerge.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 1\n",
+            mock_endpoint().await.url
+        ),
+    )
+    .unwrap();
+    let audit_log = dir.join(".git/synthmerge/runs.jsonl");
+    // The second run adds another block of resolutions
+    for (run, force) in [(1, None), (2, Some("--force"))] {
+        let output = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+            .current_dir(dir)
+            .arg("--config")
+            .arg(&config)
+            .args(["--no-cache", "--no-telemetry", "--audit-log"])
+            .args(force)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&audit_log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), run);
+        let record = lines.last().unwrap();
+        assert_eq!(record["endpoints"], serde_json::json!(["openai"]));
+        assert_eq!(record["nr_conflicts"], 1);
+        assert_eq!(record["resolutions"][0]["file_path"], "f.txt");
+        assert_eq!(record["resolutions"][0]["model"], "mock");
+        assert!(record["time"].as_u64().unwrap() > 0);
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
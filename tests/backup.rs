@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, git, git_stdout, resolved_conflict};
+use std::path::Path;
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+fn git_utils(repo: &Path) -> GitUtils {
+    GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(repo.to_string_lossy().into_owned()),
+            backup: true,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn test_backup_and_restore() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    conflicted_repo(dir);
+    let content = || std::fs::read_to_string(dir.join("f.txt")).unwrap();
+    let stages = || git_stdout(dir, &["ls-files", "--stage", "--", "f.txt"]);
+    let (conflicted, unmerged) = (content(), stages());
+    assert_eq!(unmerged.lines().count(), 3);
+
+    let mut backup = git_utils(dir);
+    let conflicts = backup.find_conflicts(200000, &[]).unwrap();
+    backup
+        .apply_resolved_conflicts(&[resolved_conflict(&conflicts[0], "d-merged\n")])
+        .unwrap();
+    assert_ne!(content(), conflicted);
+    // Staged like a manual resolution would
+    assert!(git(dir, &["add", "f.txt"]));
+    assert_eq!(stages().lines().count(), 1);
+
+    git_utils(dir).restore_backups().unwrap();
+    assert_eq!(content(), conflicted);
+    assert_eq!(stages(), unmerged);
+    assert!(!dir.join(".git/synthmerge/backups/worktree/f.txt").exists());
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{git, git_stdout, init_repo, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_rebase_message_comment_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path();
+    init_repo(repo);
+    let write = |content: &str| std::fs::write(repo.join("f.txt"), content).unwrap();
+    write("a\nb\nc\nd\ne\nf\ng\n");
+    assert!(git(repo, &["add", "f.txt"]));
+    assert!(git(repo, &["commit", "-q", "-m", "base"]));
+    assert!(git(repo, &["checkout", "-q", "-b", "topic"]));
+    write("a\nb\nc\nd-topic\ne\nf\ng\n");
+    let message = "topic\n\n#include <stdio.h> is needed\n# twice\n";
+    assert!(git(
+        repo,
+        &["commit", "-q", "-a", "--cleanup=verbatim", "-m", message]
+    ));
+    assert!(git(repo, &["checkout", "-q", "main"]));
+    write("a\nb\nc\nd-main\ne\nf\ng\n");
+    assert!(git(repo, &["commit", "-q", "-am", "main"]));
+    assert!(!git(repo, &["rebase", "-q", "main", "topic"]));
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(repo.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    git_utils.find_commit_hashes().unwrap();
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    let resolved_conflicts = vec![resolved_conflict(&conflicts[0], "d-merged\n")];
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+    git_utils
+        .continue_operation(&ContextLines::default())
+        .unwrap();
+
+    // Without --edit-message the message isn't stripped of the lines
+    // starting with #
+    assert!(!repo.join(".git/rebase-merge").exists());
+    let body = git_stdout(repo, &["log", "-1", "--format=%B"]);
+    assert!(
+        body.starts_with("topic\n\n#include <stdio.h> is needed\n# twice\n"),
+        "{}",
+        body
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
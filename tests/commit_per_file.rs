@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, f_txt, git_stdout, init_repo, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_commit_per_file() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |suffix| {
+        std::fs::write(dir.join("f.txt"), f_txt(suffix)).unwrap();
+        std::fs::write(dir.join("g.txt"), f_txt(suffix)).unwrap();
+    });
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            commit_per_file: true,
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 2);
+    let resolved_conflicts: Vec<_> = conflicts
+        .iter()
+        .map(|conflict| resolved_conflict(conflict, "d-merged\n"))
+        .collect();
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+    assert!(
+        !git_utils
+            .continue_operation(&ContextLines::default())
+            .unwrap()
+    );
+
+    // The cherry-pick is committed with the local side, then each
+    // resolved file gets its own commit
+    assert!(!dir.join(".git/CHERRY_PICK_HEAD").exists());
+    assert_eq!(
+        git_stdout(dir, &["log", "--format=%s"]),
+        "synthmerge: resolve conflicts in g.txt\n\
+         synthmerge: resolve conflicts in f.txt\n\
+         topic\nmain\nbase"
+    );
+    assert_eq!(
+        git_stdout(dir, &["diff", "--name-only", "HEAD~3", "HEAD~2"]),
+        ""
+    );
+    for (rev, file) in [("HEAD~1", "f.txt"), ("HEAD", "g.txt")] {
+        assert_eq!(
+            git_stdout(dir, &["diff", "--name-only", &format!("{}~", rev), rev]),
+            file
+        );
+        assert_eq!(
+            git_stdout(
+                dir,
+                &["log", "-1", "--format=%(trailers:key=Assisted-by)", rev]
+            ),
+            "Assisted-by: synthmerge"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join(file)).unwrap(),
+            f_txt("-merged")
+        );
+    }
+    // The commit of the cherry-pick itself has no Assisted-by trailer
+    assert_eq!(
+        git_stdout(
+            dir,
+            &[
+                "log",
+                "-1",
+                "--format=%(trailers:key=Assisted-by)",
+                "HEAD~2"
+            ]
+        ),
+        ""
+    );
+    assert_eq!(git_stdout(dir, &["status", "--porcelain"]), "");
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
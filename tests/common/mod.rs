@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+//! The fixtures shared by the integration tests, each test binary only
+//! uses some of them
+
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use synthmerge::{Conflict, ResolvedConflict};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// The content of f.txt in the repositories of [`conflicted_repo`], with
+/// the suffix of each side on its fourth line
+pub fn f_txt(side: &str) -> String {
+    format!("a\nb\nc\nd{}\ne\nf\ng\n", side)
+}
+
+pub fn git(dir: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .expect("run git")
+        .status
+        .success()
+}
+
+/// The trimmed stdout of a git command that must succeed
+pub fn git_stdout(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .expect("run git");
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// An empty repository on the main branch with diff3 conflicts
+pub fn init_repo(dir: &Path) {
+    assert!(git(dir, &["init", "-q", "-b", "main"]));
+    for (key, value) in [
+        ("user.name", "test"),
+        ("user.email", "test@example.com"),
+        ("merge.conflictStyle", "diff3"),
+        ("commit.gpgsign", "false"),
+    ] {
+        assert!(git(dir, &["config", key, value]));
+    }
+}
+
+/// Commit the files written by write("") as the base, the ones of
+/// write("-topic") on the topic branch and the ones of write("-main")
+/// on main, then cherry-pick topic, which stops on the conflicts
+pub fn cherry_pick_conflicts(dir: &Path, write: impl Fn(&str)) {
+    write("");
+    assert!(git(dir, &["add", "."]));
+    assert!(git(dir, &["commit", "-q", "-m", "base"]));
+    assert!(git(dir, &["checkout", "-q", "-b", "topic"]));
+    write("-topic");
+    assert!(git(dir, &["commit", "-q", "-am", "topic"]));
+    assert!(git(dir, &["checkout", "-q", "main"]));
+    write("-main");
+    assert!(git(dir, &["commit", "-q", "-am", "main"]));
+    assert!(!git(dir, &["cherry-pick", "topic"]));
+}
+
+/// A repository with a cherry-pick stopped on a conflict in f.txt
+pub fn conflicted_repo(dir: &Path) {
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |side| {
+        std::fs::write(dir.join("f.txt"), f_txt(side)).unwrap()
+    });
+}
+
+/// The resolution of a conflict by the model "test"
+pub fn resolved_conflict(conflict: &Conflict, resolved_version: &str) -> ResolvedConflict {
+    ResolvedConflict {
+        conflict: conflict.clone(),
+        resolved_version: resolved_version.to_string(),
+        model: "test".to_string(),
+        duration: 0.0,
+        total_tokens: None,
+        input_tokens: None,
+        logprob: None,
+        deduplicated_conflicts: Vec::new(),
+        endpoint: 0,
+        multi: Some(0),
+        beam: Some(0),
+    }
+}
+
+/// An OpenAI compatible mock endpoint
+pub struct MockEndpoint {
+    pub url: String,
+    /// The requests received so far
+    pub requests: Arc<AtomicUsize>,
+}
+
+/// An OpenAI compatible endpoint answering with the code of the prompt,
+/// with "-main" replaced by "-merged", and with 404 to the requests for
+/// the model "missing"
+pub async fn mock_endpoint() -> MockEndpoint {
+    mock_endpoint_delayed(|_| 0).await
+}
+
+/// Like [`mock_endpoint`], answering each connection in its own task
+/// after delay(n) milliseconds, n counting the requests from 0
+pub async fn mock_endpoint_delayed(delay: fn(usize) -> u64) -> MockEndpoint {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!(
+        "http://{}/v1/chat/completions",
+        listener.local_addr().unwrap()
+    );
+    let requests = Arc::new(AtomicUsize::new(0));
+    let code = regex::Regex::new(r"(?s)<\|code\|>\n(.*?)<\|/code\|>").unwrap();
+    tokio::spawn({
+        let requests = requests.clone();
+        async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let delay = delay(requests.fetch_add(1, Ordering::Relaxed));
+                let code = code.clone();
+                tokio::spawn(async move {
+                    let body = read_body(&mut socket).await;
+                    tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                    let request: serde_json::Value = serde_json::from_str(&body).unwrap();
+                    if request["model"] == "missing" {
+                        let response = r#"{"error":{"message":"model not found"}}"#;
+                        let reply = format!(
+                            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            response.len(),
+                            response
+                        );
+                        socket.write_all(reply.as_bytes()).await.unwrap();
+                        return;
+                    }
+                    let messages = request["messages"].as_array().unwrap();
+                    let prompt = messages.last().unwrap()["content"].as_str().unwrap();
+                    let content = code
+                        .captures_iter(prompt)
+                        .map(|c| {
+                            format!(
+                                "<|patched_code|>\n{}<|/patched_code|>",
+                                c[1].replace("-main", "-merged")
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let response = serde_json::json!({
+                        "choices": [{ "message": { "content": content }, "finish_reason": "stop" }],
+                        "usage": { "total_tokens": 42, "prompt_tokens": 30 },
+                    })
+                    .to_string();
+                    let reply = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        response.len(),
+                        response
+                    );
+                    // The client may have given up on the request
+                    let _ = socket.write_all(reply.as_bytes()).await;
+                });
+            }
+        }
+    });
+    MockEndpoint { url, requests }
+}
+
+/// Read the body of an HTTP request
+async fn read_body(socket: &mut tokio::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 65536];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+            let length = headers
+                .lines()
+                .find_map(|line| {
+                    line.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|length| length.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            if body.len() >= length {
+                return body.to_string();
+            }
+        }
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
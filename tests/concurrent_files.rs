@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, init_repo};
+use std::path::Path;
+use synthmerge::{ApplyOptions, Config, ContextLines, GitUtils, ResolutionMode};
+
+const FILES: [&str; 3] = ["a.txt", "b.txt", "c.txt"];
+
+/// Resolve and apply the conflicts of a fresh repository, returns the
+/// resolutions in the order they were returned and the resolved files
+async fn resolve_and_apply(config: &Path) -> (Vec<(String, usize, String, String)>, Vec<String>) {
+    let repo = tempfile::tempdir().unwrap();
+    // Two conflicts in each file
+    init_repo(repo.path());
+    cherry_pick_conflicts(repo.path(), |side| {
+        for file in FILES {
+            let content = format!("a\nb{}\nc\nd\ne\nf\ng\nh\ni\nj{}\nk\n", side, side);
+            std::fs::write(repo.path().join(file), content).unwrap();
+        }
+    });
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(repo.path().to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 6);
+
+    // The resolution of the files runs in a task of its own
+    let resolving = tokio::spawn({
+        let (config, conflicts) = (Config::load(config).unwrap(), conflicts.clone());
+        async move { synthmerge::resolve_conflicts(&config, &conflicts).await }
+    });
+    let resolved_conflicts = resolving.await.unwrap().unwrap();
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+
+    let resolutions = resolved_conflicts
+        .iter()
+        .map(|resolved| {
+            (
+                resolved.conflict.file_path.clone(),
+                resolved.conflict.local_start,
+                resolved.model.clone(),
+                resolved.resolved_version.clone(),
+            )
+        })
+        .collect();
+    let files = FILES
+        .iter()
+        .map(|file| std::fs::read_to_string(repo.path().join(file)).unwrap())
+        .collect();
+    (resolutions, files)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_files_deterministic() {
+    // The delay changes with every request, so the requests complete in
+    // a different order in each run
+    let url = common::mock_endpoint_delayed(|request| (request * 7 % 5 * 20) as u64)
+        .await
+        .url;
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "max_concurrent_files: 3\nendpoints:\n  - name: first\n    url: {}\n    type: openai\n  - name: second\n    url: {}\n    type: openai\n",
+            url, url
+        ),
+    )
+    .unwrap();
+
+    let (resolutions, files) = resolve_and_apply(&path).await;
+    // In the order of the conflicts, then of the endpoints
+    let order: Vec<_> = resolutions
+        .iter()
+        .map(|(file, _, model, _)| format!("{}:{}", file, model))
+        .collect();
+    assert_eq!(
+        order,
+        FILES
+            .iter()
+            .flat_map(|file| [file; 2])
+            .flat_map(|file| [format!("{}:first", file), format!("{}:second", file)])
+            .collect::<Vec<_>>()
+    );
+    for file in &files {
+        assert_eq!(file, "a\nb-merged\nc\nd\ne\nf\ng\nh\ni\nj-merged\nk\n");
+    }
+
+    for _ in 0..3 {
+        assert_eq!(
+            resolve_and_apply(&path).await,
+            (resolutions.clone(), files.clone())
+        );
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
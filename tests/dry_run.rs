@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, git_stdout, mock_endpoint};
+use std::process::Command;
+
+/// Run synthmerge with --dry-run and the extra arguments against the
+/// mock endpoint, check the repository is untouched and return stdout
+async fn dry_run(args: &[&str]) -> String {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    conflicted_repo(dir);
+    let status = git_stdout(dir, &["status", "--porcelain"]);
+    let index = git_stdout(dir, &["ls-files", "--stage"]);
+    let content = || std::fs::read_to_string(dir.join("f.txt")).unwrap();
+    let conflicted = content();
+
+    let config = tempfile::tempdir().unwrap();
+    let config = config.path().join("synthmerge.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 1\n",
+            mock_endpoint().await.url
+        ),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+        .current_dir(dir)
+        .arg("--config")
+        .arg(&config)
+        .args(["--no-cache", "--dry-run"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The working tree, the index and the cherry-pick are untouched
+    assert_eq!(content(), conflicted);
+    assert_eq!(git_stdout(dir, &["status", "--porcelain"]), status);
+    assert_eq!(git_stdout(dir, &["ls-files", "--stage"]), index);
+    assert!(dir.join(".git/CHERRY_PICK_HEAD").exists());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dry_run_vibe() {
+    // The diff goes from the conflict markers to the resolution
+    let stdout = dry_run(&["--vibe"]).await;
+    assert!(stdout.contains("--- a/f.txt\n+++ b/f.txt\n"), "{}", stdout);
+    assert!(stdout.contains("\n-<<<<<<< HEAD\n-d-main\n"), "{}", stdout);
+    assert!(stdout.contains("\n->>>>>>> "), "{}", stdout);
+    assert!(stdout.contains("\n+d-merged\n"), "{}", stdout);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dry_run_markers() {
+    // The diff only adds the block of the resolution to the markers
+    let stdout = dry_run(&[]).await;
+    assert!(stdout.contains("--- a/f.txt\n+++ b/f.txt\n"), "{}", stdout);
+    assert!(stdout.contains("\n+&&&&&&& synthmerge: mock"), "{}", stdout);
+    assert!(stdout.contains("\n+d-merged\n"), "{}", stdout);
+    assert!(!stdout.contains("\n-<<<<<<< HEAD\n"), "{}", stdout);
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use synthmerge::{
+    ApplyOptions, Config, Conflict, ConflictResolver, ContextLines, GitUtils, ResolutionMode,
+    SynthmergeError,
+};
+
+#[test]
+fn test_not_a_git_repo() {
+    let dir = tempfile::tempdir().unwrap();
+    // SAFETY: the only test of this binary changing the environment
+    unsafe { std::env::set_var("GIT_CEILING_DIRECTORIES", dir.path()) };
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(dir.path().to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    assert!(matches!(
+        git_utils.find_conflicts(200000, &[]),
+        Err(SynthmergeError::NotAGitRepo)
+    ));
+}
+
+#[tokio::test]
+async fn test_api_error() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        "endpoints:\n  - name: dead\n    url: http://127.0.0.1:1/v1/chat/completions\n    type: openai\n    retries: 1\n    delay: 0\n",
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+    let conflict = Conflict {
+        file_path: "f.txt".to_string(),
+        conflict_code: "a\n".to_string(),
+        ..Default::default()
+    };
+    match synthmerge::resolve_conflicts(&config, &[conflict]).await {
+        Err(SynthmergeError::Api {
+            endpoint, status, ..
+        }) => {
+            assert_eq!(endpoint, "dead");
+            assert_eq!(status, None);
+        }
+        result => panic!("unexpected result {:?}", result.map(|r| r.len())),
+    }
+}
+
+#[tokio::test]
+async fn test_check_endpoint() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        "endpoints:\n  - name: dead\n    url: http://127.0.0.1:1/v1/chat/completions\n    type: openai\n    retries: 1\n    delay: 0\n  - name: nokey\n    url: https://api.anthropic.com/v1/messages\n    type: anthropic\n    x_api_key_file: /nonexistent/key\n",
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+    let endpoints = config.get_all_endpoints();
+    // Without ping the dead endpoint isn't contacted
+    ConflictResolver::check_endpoint(&endpoints[0], false)
+        .await
+        .unwrap();
+    assert!(
+        ConflictResolver::check_endpoint(&endpoints[0], true)
+            .await
+            .is_err()
+    );
+    assert!(
+        ConflictResolver::check_endpoint(&endpoints[1], false)
+            .await
+            .is_err()
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
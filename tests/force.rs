@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, resolved_conflict};
+use std::path::Path;
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+/// Find the conflicts of the repository and apply the resolution
+/// "d-merged" next to the conflict markers, like a run without --vibe
+fn run(repo: &Path, force: bool) -> GitUtils {
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(repo.to_string_lossy().into_owned()),
+            force,
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    let resolved_conflicts: Vec<_> = conflicts
+        .iter()
+        .map(|conflict| resolved_conflict(conflict, "d-merged\n"))
+        .collect();
+    git_utils
+        .apply_resolved_conflicts(&resolved_conflicts)
+        .unwrap();
+    git_utils
+}
+
+#[test]
+fn test_second_run_skips_the_resolved_conflicts() {
+    let repo = tempfile::tempdir().unwrap();
+    conflicted_repo(repo.path());
+    let content = || std::fs::read_to_string(repo.path().join("f.txt")).unwrap();
+    let block = "&&&&&&& synthmerge: test\nd-merged\n";
+
+    assert_eq!(run(repo.path(), false).already_resolved(), 0);
+    let resolved = content();
+    assert_eq!(resolved.matches(block).count(), 1);
+
+    // The resolutions of the previous run are left alone
+    assert_eq!(run(repo.path(), false).already_resolved(), 1);
+    assert_eq!(content(), resolved);
+
+    // Unless forced to add another block
+    assert_eq!(run(repo.path(), true).already_resolved(), 0);
+    assert_eq!(content().matches(block).count(), 2);
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
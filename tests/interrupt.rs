@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::conflicted_repo;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Wait for the condition for up to 10 seconds
+fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while !condition() {
+        if start.elapsed() > Duration::from_secs(10) {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    true
+}
+
+/// Whether the process is gone, or a zombie waiting to be reaped
+fn exited(pid: &str) -> bool {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+    stat.is_empty() || stat.rsplit_once(") ").unwrap().1.starts_with('Z')
+}
+
+/// Whether the fake git wrote its whole pid
+fn pid_file_ready(pid_file: &Path) -> bool {
+    std::fs::read_to_string(pid_file).is_ok_and(|pid| pid.ends_with('\n'))
+}
+
+#[test]
+fn test_interrupt_kills_git() {
+    let repo = tempfile::tempdir().unwrap();
+    conflicted_repo(repo.path());
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("pid");
+
+    // A git hanging on the status of the conflicts
+    let output = Command::new("sh")
+        .args(["-c", "command -v git"])
+        .output()
+        .unwrap();
+    let real_git = String::from_utf8(output.stdout).unwrap();
+    let bin = dir.path().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let fake_git = bin.join("git");
+    std::fs::write(
+        &fake_git,
+        format!(
+            "#!/bin/sh\ncase \" $* \" in *\" status \"*) echo $$ > {}; exec sleep 60;; esac\nexec {} \"$@\"\n",
+            pid_file.display(),
+            real_git.trim()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&fake_git, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let config = dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &config,
+        "endpoints:\n  - name: dead\n    url: http://127.0.0.1:1/v1/chat/completions\n    type: openai\n",
+    )
+    .unwrap();
+
+    // The foreground process group of a terminal, like after a Ctrl-C
+    let path = format!(
+        "{}:{}",
+        bin.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+        .current_dir(repo.path())
+        .env("PATH", path)
+        .arg("--config")
+        .arg(&config)
+        .arg("--no-cache")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .unwrap();
+    assert!(wait_for(|| pid_file_ready(&pid_file)));
+    let git_pid = std::fs::read_to_string(&pid_file).unwrap();
+    let git_pid = git_pid.trim();
+    assert!(
+        Command::new("kill")
+            .args(["-INT", "--", &format!("-{}", child.id())])
+            .status()
+            .unwrap()
+            .success()
+    );
+
+    let interrupted = wait_for(|| child.try_wait().unwrap().is_some());
+    if !interrupted {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+    let git_exited = wait_for(|| exited(git_pid));
+    if !git_exited {
+        let _ = Command::new("kill").args(["-KILL", git_pid]).status();
+    }
+    assert!(interrupted);
+    assert!(git_exited, "git {} outlived synthmerge", git_pid);
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,315 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, f_txt, git, init_repo, mock_endpoint};
+use std::path::Path;
+use synthmerge::{ApplyOptions, Config, ContextLines, GitUtils, ResolutionMode};
+
+/// A repository with a git stash apply stopped on a conflict in f.txt
+fn stash_conflicted_repo(dir: &Path) {
+    init_repo(dir);
+    let write = |side: &str| std::fs::write(dir.join("f.txt"), f_txt(side)).unwrap();
+    write("");
+    assert!(git(dir, &["add", "f.txt"]));
+    assert!(git(dir, &["commit", "-q", "-m", "base"]));
+    write("-topic");
+    assert!(git(dir, &["stash", "-q"]));
+    write("-main");
+    assert!(git(dir, &["commit", "-q", "-am", "main"]));
+    assert!(!git(dir, &["stash", "apply", "-q"]));
+}
+
+fn load_config(dir: &Path, url: &str) -> Config {
+    let path = dir.join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 1\n",
+            url
+        ),
+    )
+    .unwrap();
+    Config::load(&path).unwrap()
+}
+
+#[tokio::test]
+async fn test_find_and_resolve_conflicts() {
+    let repo = tempfile::tempdir().unwrap();
+    conflicted_repo(repo.path());
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(repo.path().to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_path, "f.txt");
+    assert_eq!(conflicts[0].conflict_code, "d-main\n");
+
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = load_config(config_dir.path(), &mock_endpoint().await.url);
+    let resolved_conflicts = synthmerge::resolve_conflicts(&config, &conflicts)
+        .await
+        .unwrap();
+    assert_eq!(resolved_conflicts.len(), 1);
+    assert_eq!(resolved_conflicts[0].resolved_version, "d-merged\n");
+    assert_eq!(resolved_conflicts[0].model, "mock");
+    assert_eq!(resolved_conflicts[0].total_tokens, Some(42));
+
+    git_utils
+        .apply_resolved_conflicts(&resolved_conflicts)
+        .unwrap();
+    let content = std::fs::read_to_string(repo.path().join("f.txt")).unwrap();
+    assert!(content.contains("&&&&&&& synthmerge: mock\nd-merged\n>>>>>>> "));
+}
+
+#[tokio::test]
+async fn test_stash_apply_conflicts() {
+    let repo = tempfile::tempdir().unwrap();
+    stash_conflicted_repo(repo.path());
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(repo.path().to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    // No commit to extract the diff from
+    assert!(git_utils.find_commit_hashes().unwrap().is_empty());
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].conflict_code, "d-main\n");
+    assert!(conflicts[0].remote.contains("d-topic\n"));
+
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = load_config(config_dir.path(), &mock_endpoint().await.url);
+    let resolved_conflicts = synthmerge::resolve_conflicts(&config, &conflicts)
+        .await
+        .unwrap();
+    assert_eq!(resolved_conflicts.len(), 1);
+    assert_eq!(resolved_conflicts[0].resolved_version, "d-merged\n");
+}
+
+#[tokio::test]
+async fn test_since() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    init_repo(dir);
+    let write = |file: &str, content: &str| std::fs::write(dir.join(file), content).unwrap();
+    write("f.txt", "a\nb\nc\n");
+    write("g.txt", "1\n2\n3\n");
+    assert!(git(dir, &["add", "f.txt", "g.txt"]));
+    assert!(git(dir, &["commit", "-q", "-m", "base"]));
+    assert!(git(dir, &["checkout", "-q", "-b", "topic"]));
+    write("g.txt", "1\n2-topic\n3\n");
+    assert!(git(dir, &["commit", "-q", "-am", "topic g"]));
+    assert!(git(dir, &["checkout", "-q", "main"]));
+    write("g.txt", "1\n2-main\n3\n");
+    assert!(git(dir, &["commit", "-q", "-am", "main g"]));
+    // The last attempt already saw both sides of g.txt
+    assert!(git(dir, &["checkout", "-q", "-b", "attempt"]));
+    assert!(git(dir, &["merge", "-q", "-s", "ours", "topic"]));
+    assert!(git(dir, &["checkout", "-q", "topic"]));
+    write("f.txt", "a\nb-topic\nc\n");
+    assert!(git(dir, &["commit", "-q", "-am", "topic f"]));
+    assert!(git(dir, &["checkout", "-q", "main"]));
+    write("f.txt", "a\nb-main\nc\n");
+    assert!(git(dir, &["commit", "-q", "-am", "main f"]));
+    assert!(!git(dir, &["merge", "-q", "topic"]));
+
+    let new_git_utils = || {
+        GitUtils::new(
+            ContextLines::default(),
+            None,
+            false,
+            ResolutionMode::Interactive,
+            0,
+            ApplyOptions {
+                repo: Some(dir.to_string_lossy().into_owned()),
+                ..Default::default()
+            },
+        )
+    };
+    let files = |git_utils: &mut GitUtils| {
+        let mut files: Vec<_> = git_utils
+            .find_conflicts(200000, &[])
+            .unwrap()
+            .into_iter()
+            .map(|conflict| conflict.file_path)
+            .collect();
+        files.dedup();
+        files
+    };
+    assert_eq!(files(&mut new_git_utils()), ["f.txt", "g.txt"]);
+    let mut git_utils = new_git_utils();
+    git_utils.set_since("attempt").unwrap();
+    assert_eq!(files(&mut git_utils), ["f.txt"]);
+}
+
+#[tokio::test]
+async fn test_fallback_endpoint() {
+    let git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions::default(),
+    );
+    let conflicts = git_utils
+        .parse_file_conflicts(
+            "f.txt",
+            "a\nb\nc\n<<<<<<< HEAD\nd-main\n||||||| base\nd\n=======\nd-topic\n>>>>>>> topic\ne\nf\ng\n",
+            7,
+        )
+        .unwrap();
+    let url = mock_endpoint().await.url;
+    let config_dir = tempfile::tempdir().unwrap();
+    let load_config = |first_url: &str| {
+        let path = config_dir.path().join("synthmerge.yaml");
+        std::fs::write(
+            &path,
+            format!(
+                "fallback: [spare]\nendpoints:\n  - name: first\n    url: {}\n    type: openai\n    retries: 1\n    delay: 0\n  - name: spare\n    url: {}\n    type: openai\n    retries: 1\n",
+                first_url, url
+            ),
+        )
+        .unwrap();
+        Config::load(&path).unwrap()
+    };
+
+    // The fallback isn't queried for the conflicts already resolved
+    let config = load_config(&url);
+    let resolved_conflicts = synthmerge::resolve_conflicts(&config, &conflicts)
+        .await
+        .unwrap();
+    assert_eq!(resolved_conflicts.len(), 1);
+    assert_eq!(resolved_conflicts[0].model, "first");
+
+    let config = load_config("http://127.0.0.1:1/v1/chat/completions");
+    let resolved_conflicts = synthmerge::resolve_conflicts(&config, &conflicts)
+        .await
+        .unwrap();
+    assert_eq!(resolved_conflicts.len(), 1);
+    assert_eq!(resolved_conflicts[0].model, "spare");
+    assert_eq!(resolved_conflicts[0].resolved_version, "d-merged\n");
+}
+
+#[tokio::test]
+async fn test_model_failover() {
+    let git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions::default(),
+    );
+    let conflicts = git_utils
+        .parse_file_conflicts(
+            "f.txt",
+            "a\nb\nc\n<<<<<<< HEAD\nd-main\n||||||| base\nd\n=======\nd-topic\n>>>>>>> topic\ne\nf\ng\n",
+            7,
+        )
+        .unwrap();
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 1\n    models: [missing, small]\n",
+            mock_endpoint().await.url
+        ),
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+    let resolved_conflicts = synthmerge::resolve_conflicts(&config, &conflicts)
+        .await
+        .unwrap();
+    assert_eq!(resolved_conflicts.len(), 1);
+    assert_eq!(resolved_conflicts[0].model, "mock [small]");
+    assert_eq!(resolved_conflicts[0].resolved_version, "d-merged\n");
+}
+
+#[tokio::test]
+async fn test_resolve_conflicts_streaming() {
+    let git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions::default(),
+    );
+    let conflicts = git_utils
+        .parse_file_conflicts(
+            "f.txt",
+            "a\nb\nc\n<<<<<<< HEAD\nd-main\n||||||| base\nd\n=======\nd-topic\n>>>>>>> topic\ne\nf\ng\n",
+            7,
+        )
+        .unwrap();
+    let url = mock_endpoint().await.url;
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "endpoints:\n  - name: first\n    url: {}\n    type: openai\n  - name: second\n    url: {}\n    type: openai\n",
+            url, url
+        ),
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let resolving = synthmerge::resolve_conflicts_streaming(&config, &conflicts, sender);
+    let receiving = async {
+        let mut received = Vec::new();
+        while let Some(resolved) = receiver.recv().await {
+            received.push(resolved);
+        }
+        received
+    };
+    let (result, mut received) = tokio::join!(resolving, receiving);
+    result.unwrap();
+    received.sort_by_key(|resolved| resolved.endpoint);
+    assert_eq!(
+        received
+            .iter()
+            .map(|resolved| (resolved.endpoint, resolved.model.as_str()))
+            .collect::<Vec<_>>(),
+        [(0, "first"), (1, "second")]
+    );
+    assert!(
+        received
+            .iter()
+            .all(|resolved| resolved.conflict == conflicts[0]
+                && resolved.resolved_version == "d-merged\n")
+    );
+}
+
+#[tokio::test]
+async fn test_resolve_no_conflicts() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = load_config(config_dir.path(), "http://127.0.0.1:1/v1/chat/completions");
+    let resolved_conflicts = synthmerge::resolve_conflicts(&config, &[]).await.unwrap();
+    assert!(resolved_conflicts.is_empty());
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
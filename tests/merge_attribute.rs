@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, f_txt, git_stdout, init_repo, resolved_conflict};
+use std::path::Path;
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+/// Resolve the conflict of f.txt with the model and check g.txt, left
+/// to its merge gitattribute, ends up with the content of the side
+fn resolve_with_merge_attribute(side: &str, expected: &str) {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |suffix| {
+        let attributes = format!("g.txt merge={}\n", side);
+        std::fs::write(dir.join(".gitattributes"), attributes).unwrap();
+        std::fs::write(dir.join("f.txt"), f_txt(suffix)).unwrap();
+        std::fs::write(dir.join("g.txt"), f_txt(suffix)).unwrap();
+    });
+    let unmerged = || git_stdout(dir, &["ls-files", "--unmerged", "--", "g.txt"]);
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_path, "f.txt");
+    // Finding the conflicts doesn't touch g.txt
+    assert!(!unmerged().is_empty());
+
+    let resolved_conflicts = [resolved_conflict(&conflicts[0], "d-merged\n")];
+    git_utils
+        .apply_resolved_conflicts(&resolved_conflicts)
+        .unwrap();
+    assert!(unmerged().is_empty());
+    assert_eq!(read(dir, "g.txt"), f_txt(expected));
+    assert!(read(dir, "f.txt").contains("synthmerge: test\nd-merged\n"));
+}
+
+fn read(dir: &Path, file: &str) -> String {
+    std::fs::read_to_string(dir.join(file)).unwrap()
+}
+
+#[test]
+fn test_merge_ours() {
+    // main was committed last, it's HEAD of the cherry-pick
+    resolve_with_merge_attribute("ours", "-main");
+}
+
+#[test]
+fn test_merge_theirs() {
+    resolve_with_merge_attribute("theirs", "-topic");
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
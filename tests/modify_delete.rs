@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, f_txt, git_stdout, init_repo};
+use std::path::Path;
+use std::process::Command;
+use synthmerge::git_utils::ModifyDelete;
+use synthmerge::{ApplyOptions, ConflictKind, ContextLines, GitUtils, ResolutionMode};
+
+/// Stop a cherry-pick on h.txt, deleted by us, and k.txt, deleted by
+/// them, and run resolve_modify_delete with the policy. Returns the
+/// number of unresolved conflicts and the files left unmerged.
+fn modify_delete(dir: &Path, policy: Option<ModifyDelete>) -> (usize, String) {
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |suffix| {
+        let (modified, deleted) = match suffix {
+            "" => {
+                std::fs::write(dir.join("k.txt"), f_txt(suffix)).unwrap();
+                ("h.txt", None)
+            }
+            "-topic" => ("h.txt", Some("k.txt")),
+            _ => ("k.txt", Some("h.txt")),
+        };
+        std::fs::write(dir.join(modified), f_txt(suffix)).unwrap();
+        if let Some(deleted) = deleted {
+            std::fs::remove_file(dir.join(deleted)).unwrap();
+        }
+    });
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            modify_delete: policy,
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    let kinds: Vec<_> = conflicts
+        .iter()
+        .map(|conflict| (conflict.file_path.as_str(), conflict.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("h.txt", ConflictKind::DeletedByUs),
+            ("k.txt", ConflictKind::DeletedByThem)
+        ]
+    );
+    let unresolved = git_utils.resolve_modify_delete(&conflicts).unwrap();
+    let unmerged = git_stdout(dir, &["diff", "--name-only", "--diff-filter=U"]);
+    (unresolved, unmerged)
+}
+
+#[test]
+fn test_modify_delete_unresolved() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    assert_eq!(modify_delete(dir, None), (2, "h.txt\nk.txt".to_string()));
+
+    // The binary reports them without contacting any endpoint
+    let config = tempfile::tempdir().unwrap();
+    let config = config.path().join("synthmerge.yaml");
+    std::fs::write(
+        &config,
+        "endpoints:\n  - name: dead\n    url: http://127.0.0.1:1/v1/chat/completions\n    type: openai\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+        .current_dir(dir)
+        .arg("--config")
+        .arg(&config)
+        .arg("--no-cache")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Modify/delete conflict in h.txt: deleted by us\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Modify/delete conflict in k.txt: deleted by them\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("2 modify/delete conflicts left unresolved"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_modify_delete_keep() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    assert_eq!(
+        modify_delete(dir, Some(ModifyDelete::Keep)),
+        (0, String::new())
+    );
+    // The modified side of each file is kept, k.txt as in HEAD
+    assert_eq!(git_stdout(dir, &["status", "--porcelain"]), "A  h.txt");
+    let read = |file: &str| std::fs::read_to_string(dir.join(file)).unwrap();
+    assert_eq!(read("h.txt"), f_txt("-topic"));
+    assert_eq!(read("k.txt"), f_txt("-main"));
+}
+
+#[test]
+fn test_modify_delete_delete() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    assert_eq!(
+        modify_delete(dir, Some(ModifyDelete::Delete)),
+        (0, String::new())
+    );
+    assert_eq!(git_stdout(dir, &["status", "--porcelain"]), "D  k.txt");
+    assert!(!dir.join("h.txt").exists());
+    assert!(!dir.join("k.txt").exists());
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
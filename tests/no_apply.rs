@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, f_txt, git_stdout, init_repo, mock_endpoint};
+use std::process::Command;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_no_apply_leaves_the_repo_alone() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |side| {
+        std::fs::write(dir.join(".gitattributes"), "g.txt merge=ours\n").unwrap();
+        std::fs::write(dir.join("f.txt"), f_txt(side)).unwrap();
+        std::fs::write(dir.join("g.txt"), f_txt(side)).unwrap();
+    });
+    let status = git_stdout(dir, &["status", "--porcelain"]);
+    let contents = |file: &str| std::fs::read_to_string(dir.join(file)).unwrap();
+    let (f, g) = (contents("f.txt"), contents("g.txt"));
+
+    let config = tempfile::tempdir().unwrap();
+    let config = config.path().join("synthmerge.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 1\n",
+            mock_endpoint().await.url
+        ),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+        .current_dir(dir)
+        .arg("--config")
+        .arg(&config)
+        .args(["--no-cache", "--no-apply"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Neither the conflict nor the file of the merge driver are resolved
+    assert_eq!(git_stdout(dir, &["status", "--porcelain"]), status);
+    assert_eq!(contents("f.txt"), f);
+    assert_eq!(contents("g.txt"), g);
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
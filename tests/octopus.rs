@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{git, git_stdout, init_repo};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_octopus_merge_head() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path();
+    init_repo(repo);
+    std::fs::write(repo.join("f.txt"), "base\n").unwrap();
+    assert!(git(repo, &["add", "f.txt"]));
+    assert!(git(repo, &["commit", "-q", "-m", "base"]));
+    let mut heads = Vec::new();
+    for branch in ["one", "two"] {
+        assert!(git(repo, &["checkout", "-q", "-b", branch, "main"]));
+        let file = format!("{}.txt", branch);
+        std::fs::write(repo.join(&file), format!("{}\n", branch)).unwrap();
+        assert!(git(repo, &["add", &file]));
+        assert!(git(repo, &["commit", "-q", "-m", branch]));
+        heads.push(git_stdout(repo, &["rev-parse", "HEAD"]));
+    }
+    assert!(git(repo, &["checkout", "-q", "main"]));
+
+    // The MERGE_HEAD of an octopus merge has one head per line
+    std::fs::write(repo.join(".git/MERGE_HEAD"), heads.join("\n") + "\n").unwrap();
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(repo.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    let commit_hashes = git_utils.find_commit_hashes().unwrap();
+    assert_eq!(commit_hashes, heads);
+
+    let diff = git_utils
+        .extract_diff(&commit_hashes, 200000)
+        .unwrap()
+        .unwrap();
+    assert!(diff.contains("+++ b/one.txt"));
+    assert!(diff.contains("+++ b/two.txt"));
+    assert_eq!(git_utils.extract_diff(&commit_hashes, 10).unwrap(), None);
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, f_txt, git_stdout, init_repo, resolved_conflict};
+use std::path::Path;
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+/// A cherry-pick stopped on the conflicts of f.txt and g[1].txt, with
+/// the clean h.txt, and the GitUtils of the only_file
+fn only_file(dir: &Path, only_file: &str) -> GitUtils {
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |suffix| {
+        std::fs::write(dir.join("f.txt"), f_txt(suffix)).unwrap();
+        std::fs::write(dir.join("g[1].txt"), f_txt(suffix)).unwrap();
+        if suffix.is_empty() {
+            std::fs::write(dir.join("h.txt"), f_txt(suffix)).unwrap();
+        }
+    });
+    GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            only_file: Some(only_file.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn test_only_file() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    // The file name is taken literally, not as a glob
+    let mut git_utils = only_file(dir, "g[1].txt");
+    git_utils.check_only_file().unwrap();
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_path, "g[1].txt");
+    let resolved_conflicts = vec![resolved_conflict(&conflicts[0], "d-merged\n")];
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+    assert_eq!(
+        git_stdout(dir, &["diff", "--name-only", "--diff-filter=U"]),
+        "f.txt"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("g[1].txt")).unwrap(),
+        f_txt("-merged")
+    );
+}
+
+#[test]
+fn test_only_file_not_unmerged() {
+    // A clean file and a file that doesn't exist
+    for file in ["h.txt", "missing.txt"] {
+        let repo = tempfile::tempdir().unwrap();
+        let error = only_file(repo.path(), file).check_only_file().unwrap_err();
+        assert_eq!(error.to_string(), format!("No conflicts found in {}", file));
+    }
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
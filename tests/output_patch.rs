@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, conflicted_repo, f_txt, git, init_repo, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_output_patch_applies() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path().join("repo");
+    std::fs::create_dir(&repo).unwrap();
+    conflicted_repo(&repo);
+    let conflicted = std::fs::read(repo.join("f.txt")).unwrap();
+
+    let output_patch = dir.path().join("resolutions.patch");
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(repo.to_string_lossy().into_owned()),
+            dry_run: true,
+            output_patch: Some(output_patch.clone()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    let resolved_conflicts = vec![resolved_conflict(&conflicts[0], "d-merged\n")];
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+
+    // The working tree is left alone and the patch applies to it
+    assert_eq!(std::fs::read(repo.join("f.txt")).unwrap(), conflicted);
+    let output_patch = output_patch.to_str().unwrap();
+    assert!(git(&repo, &["apply", output_patch]));
+    assert_eq!(
+        std::fs::read_to_string(repo.join("f.txt")).unwrap(),
+        f_txt("-merged")
+    );
+}
+
+#[test]
+fn test_output_patch_crlf_and_no_eof_newline() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path().join("repo");
+    std::fs::create_dir(&repo).unwrap();
+    init_repo(&repo);
+    assert!(git(&repo, &["config", "core.autocrlf", "false"]));
+    // Two conflicts in a CRLF file without a newline at the end, and a
+    // conflict on the last line of a file without a newline at the end
+    let write = |side: &str| {
+        let crlf = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"]
+            .map(|line| match line {
+                "b" | "h" => format!("{}{}", line, side),
+                _ => line.to_string(),
+            })
+            .join("\r\n");
+        std::fs::write(repo.join("crlf.txt"), crlf).unwrap();
+        std::fs::write(repo.join("eof.txt"), format!("a\nb\nc{}", side)).unwrap();
+    };
+    cherry_pick_conflicts(&repo, write);
+    let conflicted = |file: &str| std::fs::read(repo.join(file)).unwrap();
+    let (crlf, eof) = (conflicted("crlf.txt"), conflicted("eof.txt"));
+
+    let output_patch = dir.path().join("resolutions.patch");
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(repo.to_string_lossy().into_owned()),
+            dry_run: true,
+            output_patch: Some(output_patch.clone()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 3);
+    let resolved_conflicts: Vec<_> = conflicts
+        .iter()
+        .map(|conflict| {
+            resolved_conflict(
+                conflict,
+                &conflict.conflict_code.replace("-main", "-merged"),
+            )
+        })
+        .collect();
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+
+    // The working tree is left alone and the patch applies to it
+    assert_eq!(conflicted("crlf.txt"), crlf);
+    assert_eq!(conflicted("eof.txt"), eof);
+    let output_patch = output_patch.to_str().unwrap();
+    assert!(git(&repo, &["apply", "--check", output_patch]));
+    assert!(git(&repo, &["apply", output_patch]));
+    assert_eq!(
+        std::fs::read_to_string(repo.join("crlf.txt")).unwrap(),
+        "a\r\nb-merged\r\nc\r\nd\r\ne\r\nf\r\ng\r\nh-merged\r\ni\r\nj"
+    );
+    assert_eq!(
+        std::fs::read_to_string(repo.join("eof.txt")).unwrap(),
+        "a\nb\nc-merged"
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
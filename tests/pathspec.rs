@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, f_txt, git_stdout, init_repo, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_pathspec_in_subdirectory() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    init_repo(dir);
+    std::fs::create_dir(dir.join("sub")).unwrap();
+    cherry_pick_conflicts(dir, |suffix| {
+        std::fs::write(dir.join("sub/f.txt"), f_txt(suffix)).unwrap();
+        std::fs::write(dir.join("sub/g.txt"), f_txt(suffix)).unwrap();
+    });
+    let unmerged = || git_stdout(dir, &["diff", "--name-only", "--diff-filter=U"]);
+    assert_eq!(unmerged(), "sub/f.txt\nsub/g.txt");
+
+    // The pathspec is relative to the repo directory, like for git
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(dir.join("sub").to_string_lossy().into_owned()),
+            pathspecs: vec!["g.*".to_string()],
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_path, "sub/g.txt");
+    let resolved_conflicts = vec![resolved_conflict(&conflicts[0], "d-merged\n")];
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+
+    // Only the matching file is resolved
+    assert_eq!(unmerged(), "sub/f.txt");
+    assert_eq!(
+        std::fs::read_to_string(dir.join("sub/g.txt")).unwrap(),
+        f_txt("-merged")
+    );
+    assert!(
+        std::fs::read_to_string(dir.join("sub/f.txt"))
+            .unwrap()
+            .contains("<<<<<<< ")
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
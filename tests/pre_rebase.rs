@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{git, git_stdout, init_repo};
+use std::process::Command;
+
+#[test]
+fn test_pre_rebase() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    init_repo(dir);
+    let write = |file: &str, content: &str| std::fs::write(dir.join(file), content).unwrap();
+    write("f.txt", "a\nb\nc\n");
+    assert!(git(dir, &["add", "f.txt"]));
+    assert!(git(dir, &["commit", "-q", "-m", "base"]));
+    assert!(git(dir, &["checkout", "-q", "-b", "topic"]));
+    write("f.txt", "a\nb-topic\nc\n");
+    assert!(git(dir, &["commit", "-q", "-am", "conflicting"]));
+    let conflicting = git_stdout(dir, &["rev-parse", "HEAD"]);
+    write("g.txt", "g\n");
+    assert!(git(dir, &["add", "g.txt"]));
+    assert!(git(dir, &["commit", "-q", "-m", "clean"]));
+    assert!(git(dir, &["checkout", "-q", "main"]));
+    write("f.txt", "a\nb-main\nc\n");
+    assert!(git(dir, &["commit", "-q", "-am", "main"]));
+    let main = git_stdout(dir, &["rev-parse", "HEAD"]);
+
+    let config = tempfile::tempdir().unwrap();
+    let config = config.path().join("synthmerge.yaml");
+    std::fs::write(
+        &config,
+        "endpoints:\n  - name: dead\n    url: http://127.0.0.1:1/v1/chat/completions\n    type: openai\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+        .current_dir(dir)
+        .arg("--config")
+        .arg(&config)
+        .args(["--no-cache", "--pre-rebase", "topic", "--onto", "main"])
+        .args(["--report", "report.json"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("1 of 2 commits conflict, with 1 conflicts"),
+        "{}",
+        stdout
+    );
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    assert_eq!(report["nr_conflicts"], 1);
+    assert_eq!(report["conflicts"][0]["file_path"], "f.txt");
+    assert_eq!(report["conflicts"][0]["commit"], conflicting.as_str());
+
+    // The checkout is untouched and the worktree is gone
+    assert_eq!(git_stdout(dir, &["rev-parse", "HEAD"]), main);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("f.txt")).unwrap(),
+        "a\nb-main\nc\n"
+    );
+    assert_eq!(
+        git_stdout(dir, &["status", "--porcelain"]),
+        "?? report.json"
+    );
+    assert_eq!(
+        git_stdout(dir, &["worktree", "list", "--porcelain"])
+            .lines()
+            .filter(|line| line.starts_with("worktree "))
+            .count(),
+        1
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
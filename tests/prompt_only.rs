@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const CONFLICT: &str =
+    "a\nb\nc\n<<<<<<< HEAD\nd-main\n||||||| base\nd\n=======\nd-topic\n>>>>>>> topic\ne\nf\ng\n";
+
+/// Run synthmerge with --print-prompt --prompt-only and an endpoint
+/// that can't be reached, the prompts are printed without querying it
+fn prompt_only(dir: &Path, args: &[&str], stdin: &str) -> Output {
+    let config = dir.join("synthmerge.yaml");
+    std::fs::write(
+        &config,
+        "endpoints:\n  - name: dead\n    url: http://127.0.0.1:1/v1/chat/completions\n    type: openai\n    retries: 1\n    delay: 0\n",
+    )
+    .unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+        .current_dir(dir)
+        .env("GIT_CEILING_DIRECTORIES", dir)
+        .arg("--config")
+        .arg(&config)
+        .args(["--no-cache", "--print-prompt", "--prompt-only"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_prompt_only_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = prompt_only(dir.path(), &["--stdin"], CONFLICT);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    // The prompts go to stderr with the progress, nothing is resolved
+    assert!(stderr.contains("=== Prompt of dead ==="), "{}", stderr);
+    assert!(stderr.contains("d-topic"), "{}", stderr);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_prompt_only_mergetool() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |file: &str, side: &str| {
+        std::fs::write(
+            dir.path().join(file),
+            format!("a\nb\nc\nd{}\ne\nf\ng\n", side),
+        )
+        .unwrap()
+    };
+    write("local", "-main");
+    write("base", "");
+    write("remote", "-topic");
+    write("merged", "-merged");
+    let output = prompt_only(
+        dir.path(),
+        &["--mergetool", "local", "base", "remote", "merged"],
+        "",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("=== Prompt of dead ==="), "{}", stdout);
+    // git must not take MERGED as resolved
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("merged")).unwrap(),
+        "a\nb\nc\nd-merged\ne\nf\ng\n"
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
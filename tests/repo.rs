@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, f_txt, mock_endpoint};
+use std::process::Command;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_repo_outside_current_dir() {
+    let repo = tempfile::tempdir().unwrap();
+    conflicted_repo(repo.path());
+    let url = mock_endpoint().await.url;
+    let cwd = tempfile::tempdir().unwrap();
+    std::fs::write(
+        cwd.path().join("synthmerge.yaml"),
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 1\n",
+            url
+        ),
+    )
+    .unwrap();
+
+    // The files of the options are relative to the current directory,
+    // which isn't in any repository
+    let output = Command::new(env!("CARGO_BIN_EXE_synthmerge"))
+        .current_dir(cwd.path())
+        .env("GIT_CEILING_DIRECTORIES", cwd.path())
+        .arg("--repo")
+        .arg(repo.path())
+        .args(["--config", "synthmerge.yaml", "--report", "report.json"])
+        .args(["--no-cache", "--vibe", "--yes"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        std::fs::read_to_string(repo.path().join("f.txt")).unwrap(),
+        f_txt("-merged")
+    );
+    assert!(cwd.path().join("report.json").exists());
+    assert!(!repo.path().join("report.json").exists());
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, f_txt, git, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_rerere_replays_the_resolution() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    conflicted_repo(dir);
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            rerere: true,
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    let resolved_conflicts = vec![resolved_conflict(&conflicts[0], "d-merged\n")];
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+
+    // The cherry-pick stops on the same conflict again and git rerere
+    // resolves it like synthmerge did
+    assert!(git(dir, &["cherry-pick", "--abort"]));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("f.txt")).unwrap(),
+        f_txt("-main")
+    );
+    assert!(!git(
+        dir,
+        &["-c", "rerere.enabled=true", "cherry-pick", "topic"]
+    ));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("f.txt")).unwrap(),
+        f_txt("-merged")
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{git, git_stdout, init_repo, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_skip_empty_rebase_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path();
+    init_repo(repo);
+    let write = |file: &str, content: &str| std::fs::write(repo.join(file), content).unwrap();
+    write("f.txt", "a\nb\nc\nd\ne\nf\ng\n");
+    assert!(git(repo, &["add", "f.txt"]));
+    assert!(git(repo, &["commit", "-q", "-m", "base"]));
+    assert!(git(repo, &["checkout", "-q", "-b", "topic"]));
+    write("f.txt", "a\nb\nc\nd-topic\ne\nf\ng\n");
+    assert!(git(repo, &["commit", "-q", "-am", "conflicting"]));
+    write("g.txt", "g\n");
+    assert!(git(repo, &["add", "g.txt"]));
+    assert!(git(repo, &["commit", "-q", "-m", "clean"]));
+    assert!(git(repo, &["checkout", "-q", "main"]));
+    write("f.txt", "a\nb\nc\nd-main\ne\nf\ng\n");
+    assert!(git(repo, &["commit", "-q", "-am", "main"]));
+    assert!(!git(repo, &["rebase", "-q", "main", "topic"]));
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(repo.to_string_lossy().into_owned()),
+            skip_empty: true,
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+
+    // Resolved to the local side, the commit has nothing left to apply
+    let resolved_conflicts = vec![resolved_conflict(
+        &conflicts[0],
+        &conflicts[0].conflict_code,
+    )];
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+    assert!(
+        !git_utils
+            .continue_operation(&ContextLines::default())
+            .unwrap()
+    );
+
+    // The empty commit is skipped and the rebase went on to the end
+    assert!(!repo.join(".git/rebase-merge").exists());
+    assert_eq!(
+        git_stdout(repo, &["log", "--format=%s"]),
+        "clean\nmain\nbase"
+    );
+    assert_eq!(
+        std::fs::read_to_string(repo.join("f.txt")).unwrap(),
+        "a\nb\nc\nd-main\ne\nf\ng\n"
+    );
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
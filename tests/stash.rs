@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, f_txt, git_stdout, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_stash_resolution() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    conflicted_repo(dir);
+    let content = || std::fs::read_to_string(dir.join("f.txt")).unwrap();
+    let index = || git_stdout(dir, &["ls-files", "--stage"]);
+    let (conflicted, conflicted_index) = (content(), index());
+
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::VibeWithMarkers,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            stash: true,
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    let resolved_conflicts = vec![resolved_conflict(&conflicts[0], "d-merged\n")];
+    assert!(
+        git_utils
+            .apply_vibe_resolution(&conflicts, &resolved_conflicts, &Default::default())
+            .unwrap()
+    );
+
+    // The stash entry holds the resolution on top of HEAD
+    assert_eq!(
+        git_stdout(dir, &["stash", "list", "--format=%s"]),
+        "On main: synthmerge vibe resolution of f.txt"
+    );
+    assert_eq!(
+        git_stdout(dir, &["show", "stash@{0}:f.txt"]),
+        f_txt("-merged").trim()
+    );
+    let head = git_stdout(dir, &["rev-parse", "HEAD"]);
+    assert_eq!(git_stdout(dir, &["rev-parse", "stash@{0}^1"]), head);
+
+    // The working tree, the index and the cherry-pick are untouched
+    assert_eq!(content(), conflicted);
+    assert_eq!(index(), conflicted_index);
+    assert!(dir.join(".git/CHERRY_PICK_HEAD").exists());
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
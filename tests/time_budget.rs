@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{cherry_pick_conflicts, init_repo, mock_endpoint_delayed};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use synthmerge::conflict_resolver::Budget;
+use synthmerge::{ApplyOptions, Config, ConflictResolver, ContextLines, GitUtils, ResolutionMode};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_time_budget_cancels_the_requests_in_flight() {
+    let repo = tempfile::tempdir().unwrap();
+    let dir = repo.path();
+    // Three conflicts in the same file, resolved one at a time
+    init_repo(dir);
+    cherry_pick_conflicts(dir, |side| {
+        let content: String = ('a'..='u')
+            .map(|line| match line {
+                'b' | 'j' | 'r' => format!("{}{}\n", line, side),
+                _ => format!("{}\n", line),
+            })
+            .collect();
+        std::fs::write(dir.join("f.txt"), content).unwrap();
+    });
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 3);
+
+    // The first request is answered at once, the second one only long
+    // after the deadline
+    let mock = mock_endpoint_delayed(|request| if request == 0 { 0 } else { 60000 }).await;
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n",
+            mock.url
+        ),
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+    let start = Instant::now();
+    let mut resolver = ConflictResolver::new(&config, false, None, false);
+    resolver.set_budget(Budget::new(Some(start + Duration::from_secs(2)), None));
+    let (resolved_conflicts, resolver_errors) =
+        resolver.resolve_conflicts(&conflicts, &[]).await.unwrap();
+
+    // The second request is cancelled at the deadline and the third
+    // conflict is never sent
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(mock.requests.load(Ordering::Relaxed), 2);
+    assert_eq!(resolved_conflicts.len(), 1);
+    assert_eq!(resolved_conflicts[0].conflict, conflicts[0]);
+    assert_eq!(resolved_conflicts[0].resolved_version, "b-merged\n");
+    assert_eq!(resolver_errors.errors.get("mock"), Some(&1));
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{conflicted_repo, mock_endpoint};
+use std::sync::atomic::Ordering;
+use synthmerge::conflict_resolver::Budget;
+use synthmerge::{ApplyOptions, Config, ConflictResolver, ContextLines, GitUtils, ResolutionMode};
+
+#[tokio::test]
+async fn test_no_request_after_the_token_budget() {
+    let repo = tempfile::tempdir().unwrap();
+    conflicted_repo(repo.path());
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(repo.path().to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+
+    // The variants are queried one after the other by the same client,
+    // the mock uses 42 tokens per response
+    let mock = mock_endpoint().await;
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("synthmerge.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "endpoints:\n  - name: mock\n    url: {}\n    type: openai\n    retries: 3\n    variants:\n      - name: a\n      - name: b\n",
+            mock.url
+        ),
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+    let budget = Budget::new(None, Some(40));
+    let mut resolver = ConflictResolver::new(&config, false, None, false);
+    resolver.set_budget(budget.clone());
+    let (resolved_conflicts, _) = resolver.resolve_conflicts(&conflicts, &[]).await.unwrap();
+
+    assert_eq!(mock.requests.load(Ordering::Relaxed), 1);
+    assert_eq!(budget.tokens(), 42);
+    assert_eq!(resolved_conflicts.len(), 1);
+    assert_eq!(resolved_conflicts[0].resolved_version, "d-merged\n");
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End:
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: GPL-3.0-or-later OR AGPL-3.0-or-later
+// Copyright (C) 2026  Red Hat, Inc.
+
+mod common;
+
+use common::{git, init_repo, resolved_conflict};
+use synthmerge::{ApplyOptions, ContextLines, GitUtils, ResolutionMode};
+
+#[test]
+fn test_rebase_in_linked_worktree() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path().join("repo");
+    let worktree = dir.path().join("worktree");
+    std::fs::create_dir(&repo).unwrap();
+    init_repo(&repo);
+    let write = |content: &str| std::fs::write(repo.join("f.txt"), content).unwrap();
+    write("a\nb\nc\nd\ne\nf\ng\n");
+    assert!(git(&repo, &["add", "f.txt"]));
+    assert!(git(&repo, &["commit", "-q", "-m", "base"]));
+    assert!(git(&repo, &["branch", "topic"]));
+    write("a\nb\nc\nd-main\ne\nf\ng\n");
+    assert!(git(&repo, &["commit", "-q", "-am", "main"]));
+
+    let worktree_arg = worktree.to_str().unwrap();
+    assert!(git(
+        &repo,
+        &["worktree", "add", "-q", worktree_arg, "topic"]
+    ));
+    std::fs::write(worktree.join("f.txt"), "a\nb\nc\nd-topic\ne\nf\ng\n").unwrap();
+    assert!(git(&worktree, &["commit", "-q", "-am", "topic"]));
+    assert!(!git(&worktree, &["rebase", "-q", "main"]));
+
+    // Run from a subdirectory of the worktree, the rebase state is in
+    // the worktree's own git directory
+    let subdir = worktree.join("subdir");
+    std::fs::create_dir(&subdir).unwrap();
+    let mut git_utils = GitUtils::new(
+        ContextLines::default(),
+        None,
+        false,
+        ResolutionMode::Interactive,
+        0,
+        ApplyOptions {
+            repo: Some(subdir.to_string_lossy().into_owned()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(git_utils.find_commit_hashes().unwrap().len(), 1);
+    let conflicts = git_utils.find_conflicts(200000, &[]).unwrap();
+    assert_eq!(conflicts.len(), 1);
+
+    let resolved_conflicts = vec![resolved_conflict(&conflicts[0], "d-merged\n")];
+    git_utils
+        .apply_resolved_conflicts(&resolved_conflicts)
+        .unwrap();
+
+    let message =
+        std::fs::read_to_string(repo.join(".git/worktrees/worktree/rebase-merge/message")).unwrap();
+    assert!(message.contains("Assisted-by: synthmerge"));
+    assert!(!repo.join(".git/rebase-merge").exists());
+}
+
+// Local Variables:
+// rust-format-on-save: t
+// End: